@@ -0,0 +1,386 @@
+use std::fmt;
+use std::collections::HashSet;
+
+use crate::ast::Program;
+use crate::ast::stmt::{Statement, BlockStatement, Pattern};
+use crate::ast::expr::Expression;
+use crate::ast::ident::Identifier;
+use crate::ast::lit::{self, Literal};
+use crate::ast::visit::{self, Visitor};
+use crate::evaluator::macro_expansion;
+use crate::intern::Name;
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+  /// The identifier is used before it is bound, or is not bound anywhere.
+  Unbound {
+    name: Name,
+    span: Span,
+  },
+  /// A binding hides one of an enclosing function, block, `for` loop or `catch`, which starts at `shadowed`.
+  Shadowed {
+    name: Name,
+    span: Span,
+    shadowed: Span,
+  },
+  /// A `let` or `fn` binding is never read. Assigning to it does not count as a use.
+  Unused {
+    name: Name,
+    span: Span,
+  },
+}
+
+impl Warning {
+  /// Returns the span of the identifier the warning is about.
+  pub fn span(&self) -> Span {
+    match self {
+      Warning::Unbound { span, .. }
+      | Warning::Shadowed { span, .. }
+      | Warning::Unused { span, .. } => *span,
+    }
+  }
+}
+
+impl fmt::Display for Warning {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Warning::Unbound { name, .. } =>
+        write!(f, "`{}` is not defined.", name),
+      Warning::Shadowed { name, shadowed, .. } =>
+        write!(f, "`{}` shadows the binding at {}.", name, shadowed),
+      Warning::Unused { name, .. } =>
+        write!(f, "`{}` is never used.", name),
+    }
+  }
+}
+
+/// Checks `program` without running it, and returns the warnings in the order of their spans.
+/// `globals` are the names the program can use without binding them, e.g. the builtin functions
+/// and the values registered by the host.
+///
+/// Blocks make a scope like in the evaluator, and binding a name again in the same scope is not reported.
+/// The body of a function is checked after the rest of its scope, since it can use the names bound after it
+/// by the time it is called. `let` bindings whose names start with `_` are not reported as unused.
+pub fn analyze(program: &Program, globals: &[Name]) -> Vec<Warning> {
+  let mut analyzer = Analyzer {
+    globals: globals.iter().copied().collect(),
+    scopes: vec![Scope::default()],
+    warnings: vec![],
+  };
+  analyzer.visit_program(program);
+  analyzer.pop_scope();
+
+  let mut warnings = analyzer.warnings;
+  warnings.sort_by_key(|warning| warning.span().start.offset);
+  warnings
+}
+
+struct Binding {
+  name: Name,
+  span: Span,
+  // let と fn で束縛したものだけ、使われていなければ報告する
+  is_let: bool,
+  used: bool,
+}
+
+// 関数、ブロック、for、catch ごとのスコープ
+#[derive(Default)]
+struct Scope<'a> {
+  bindings: Vec<Binding>,
+  functions: Vec<&'a lit::Func>,
+}
+
+struct Analyzer<'a> {
+  globals: HashSet<Name>,
+  scopes: Vec<Scope<'a>>,
+  warnings: Vec<Warning>,
+}
+
+impl<'a> Analyzer<'a> {
+  fn push_scope(&mut self) {
+    self.scopes.push(Scope::default());
+  }
+
+  // スコープに後から束縛された名前も見えるように、閉じる前に関数の本体を調べる
+  fn pop_scope(&mut self) {
+    let functions = match self.scopes.last_mut() {
+      Some(scope) => std::mem::take(&mut scope.functions),
+      None => return,
+    };
+    for func in functions {
+      self.check_function(func);
+    }
+
+    if let Some(scope) = self.scopes.pop() {
+      for binding in scope.bindings {
+        if binding.is_let && !binding.used && !binding.name.as_str().starts_with('_') {
+          self.warnings.push(Warning::Unused { name: binding.name, span: binding.span });
+        }
+      }
+    }
+  }
+
+  fn check_function(&mut self, func: &'a lit::Func) {
+    self.push_scope();
+    // デフォルト値はそれより前の引数が束縛されたスコープで評価される
+    for (param, default) in func.args.iter().zip(func.defaults.iter()) {
+      if let Some(default) = default {
+        self.visit_expression(default);
+      }
+      self.declare(param, false);
+    }
+    self.visit_block(&func.body);
+    self.pop_scope();
+  }
+
+  fn visit_scoped_block(&mut self, block: &'a BlockStatement) {
+    self.push_scope();
+    self.visit_block(block);
+    self.pop_scope();
+  }
+
+  fn declare(&mut self, ident: &Identifier, is_let: bool) {
+    let (current, outer) = match self.scopes.split_last_mut() {
+      Some(scopes) => scopes,
+      None => return,
+    };
+    // 同じスコープで束縛し直すのは上書きなので報告しない
+    if current.bindings.iter().any(|binding| binding.name == ident.value) {
+      return;
+    }
+    let shadowed = outer.iter().rev()
+      .find_map(|scope| scope.bindings.iter().find(|binding| binding.name == ident.value));
+    if let Some(shadowed) = shadowed {
+      self.warnings.push(Warning::Shadowed { name: ident.value, span: ident.span, shadowed: shadowed.span });
+    }
+    current.bindings.push(Binding { name: ident.value, span: ident.span, is_let, used: false });
+  }
+
+  fn declare_pattern(&mut self, pattern: &Pattern, is_let: bool) {
+    match pattern {
+      Pattern::Ident(ident) => self.declare(ident, is_let),
+      Pattern::Array(arr) => {
+        for elm in &arr.elements {
+          self.declare_pattern(elm, is_let);
+        }
+        if let Some(rest) = &arr.rest {
+          self.declare(rest, is_let);
+        }
+      },
+      Pattern::Hash(hash) => {
+        for (_, pattern) in &hash.pairs {
+          self.declare_pattern(pattern, is_let);
+        }
+      },
+      // struct の名前は束縛ではなく、値と比べるために使われる
+      Pattern::Struct(pat) => {
+        self.resolve(&pat.name, true);
+        for (_, pattern) in &pat.fields {
+          self.declare_pattern(pattern, is_let);
+        }
+      },
+      Pattern::Literal(_) => (),
+    }
+  }
+
+  // 代入先の名前は、束縛されているかだけを調べて使ったことにはしない
+  fn resolve(&mut self, ident: &Identifier, is_use: bool) {
+    let binding = self.scopes.iter_mut().rev()
+      .find_map(|scope| scope.bindings.iter_mut().find(|binding| binding.name == ident.value));
+    match binding {
+      Some(binding) => binding.used |= is_use,
+      None if self.globals.contains(&ident.value) => (),
+      None => self.warnings.push(Warning::Unbound { name: ident.value, span: ident.span }),
+    }
+  }
+}
+
+impl<'a> Visitor<'a> for Analyzer<'a> {
+  fn visit_statement(&mut self, stmt: &'a Statement) {
+    match stmt {
+      // 右辺はまだ束縛されていないスコープで評価される
+      Statement::Let(let_stmt) => {
+        self.visit_expression(&let_stmt.value);
+        self.declare_pattern(&let_stmt.pattern, true);
+      },
+      // 本体はスコープを閉じる前に調べるので、自分自身の名前が見える
+      Statement::Func(func_stmt) => {
+        self.declare(&func_stmt.ident, true);
+        if let Some(scope) = self.scopes.last_mut() {
+          scope.functions.push(&func_stmt.func);
+        }
+      },
+      Statement::Struct(struct_stmt) => self.declare(&struct_stmt.ident, true),
+      Statement::For(for_stmt) => {
+        self.visit_expression(&for_stmt.iterable);
+        self.push_scope();
+        self.declare(&for_stmt.ident, false);
+        self.visit_block(&for_stmt.body);
+        self.pop_scope();
+      },
+      Statement::While(while_stmt) => {
+        self.visit_expression(&while_stmt.condition);
+        self.visit_scoped_block(&while_stmt.body);
+      },
+      Statement::Block(block) => self.visit_scoped_block(block),
+      stmt => visit::walk_statement(self, stmt),
+    }
+  }
+
+  fn visit_expression(&mut self, expr: &'a Expression) {
+    match expr {
+      Expression::Assign(assign) => {
+        match assign.target.as_ref() {
+          Expression::Identifier(ident) => self.resolve(ident, false),
+          target => self.visit_expression(target),
+        }
+        self.visit_expression(&assign.value);
+      },
+      Expression::Update(update) => match update.target.as_ref() {
+        Expression::Identifier(ident) => self.resolve(ident, false),
+        target => self.visit_expression(target),
+      },
+      Expression::If(if_expr) => {
+        self.visit_expression(&if_expr.condition);
+        self.visit_scoped_block(&if_expr.consequence);
+        if let Some(alt) = &if_expr.alternative {
+          self.visit_scoped_block(alt);
+        }
+      },
+      Expression::Try(try_expr) => {
+        self.visit_scoped_block(&try_expr.block);
+        self.push_scope();
+        self.declare(&try_expr.param, false);
+        self.visit_block(&try_expr.handler);
+        self.pop_scope();
+      },
+      // 腕ごとのスコープで、パターンの変数は for の変数と同じく使わなくても報告しない
+      Expression::Match(match_expr) => {
+        self.visit_expression(&match_expr.subject);
+        for arm in &match_expr.arms {
+          self.push_scope();
+          self.declare_pattern(&arm.pattern, false);
+          self.visit_expression(&arm.body);
+          self.pop_scope();
+        }
+      },
+      Expression::Call(call) if macro_expansion::is_quote_call(call) => {
+        let mut unquoted = Unquoted { exprs: vec![] };
+        for arg in &call.args {
+          unquoted.visit_expression(arg);
+        }
+        for expr in unquoted.exprs {
+          self.visit_expression(expr);
+        }
+      },
+      expr => visit::walk_expression(self, expr),
+    }
+  }
+
+  fn visit_literal(&mut self, lit: &'a Literal) {
+    match lit {
+      Literal::Func(func) => {
+        if let Some(scope) = self.scopes.last_mut() {
+          scope.functions.push(func);
+        }
+      },
+      // マクロの引数には、呼び出しの引数の構文木が束縛される
+      Literal::Macro(mac) => {
+        self.push_scope();
+        for arg in &mac.args {
+          self.declare(arg, false);
+        }
+        self.visit_block(&mac.body);
+        self.pop_scope();
+      },
+      lit => visit::walk_literal(self, lit),
+    }
+  }
+
+  fn visit_identifier(&mut self, ident: &'a Identifier) {
+    self.resolve(ident, true);
+  }
+}
+
+// quote の引数のうち、評価されるのは unquote の引数だけ
+struct Unquoted<'a> {
+  exprs: Vec<&'a Expression>,
+}
+
+impl<'a> Visitor<'a> for Unquoted<'a> {
+  fn visit_expression(&mut self, expr: &'a Expression) {
+    match expr {
+      Expression::Call(call) if macro_expansion::is_unquote_call(call) => self.exprs.extend(&call.args),
+      expr => visit::walk_expression(self, expr),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn test_analyze(input: &str) -> Vec<String> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    analyze(&program, &[Name::new("len"), Name::new("puts")])
+      .iter()
+      .map(|warning| format!("{}: {}", warning.span(), warning))
+      .collect()
+  }
+
+  #[test]
+  fn test_unbound() {
+    let tests: Vec<(&str, Vec<&str>)> = vec![
+      ("let x = 1; puts(x)", vec![]),
+      ("puts(x); let x = 1; x", vec!["1:6: `x` is not defined."]),
+      ("let x = x + 1;", vec!["1:5: `x` is never used.", "1:9: `x` is not defined."]),
+      ("y = 1; z++", vec!["1:1: `y` is not defined.", "1:8: `z` is not defined."]),
+      // 関数の本体は、後から束縛された名前も使える
+      ("let f = fn() { g() }; let g = fn() { len([]) }; f()", vec![]),
+      ("let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(10)", vec![]),
+      ("fn fib(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } } fib(10)", vec![]),
+      ("fn f() { g() } fn g() { 1 } f()", vec![]),
+      ("let f = fn(a, b = a + c) { b }; f(1)", vec!["1:23: `c` is not defined."]),
+      // for と catch の変数はその中だけで見える
+      ("for (i in [1]) { puts(i) }; puts(i)", vec!["1:34: `i` is not defined."]),
+      ("try { puts(1) } catch (e) { puts(e) }; e", vec!["1:40: `e` is not defined."]),
+      // quote の中で評価されるのは unquote の引数だけ
+      ("let m = macro(a) { quote(b + unquote(a) + unquote(c)) }; m(1)", vec!["1:51: `c` is not defined."]),
+      // match の腕のパターンの変数はその腕の中だけで見える
+      ("match ([1]) { [x, ...xs] => puts(x, xs), {y: [z]} => z, n => 0 }; x", vec!["1:67: `x` is not defined."]),
+      ("struct P {x} match (P {x: 1}) { P {x} => x, Q {y} => y }", vec!["1:45: `Q` is not defined."]),
+      // ブロックの中の let はその中だけで見える
+      ("if (true) { let y = 1; puts(y) } else { let y = 2; puts(y) }; puts(y)", vec!["1:68: `y` is not defined."]),
+      ("let i = 0; while (i < 1) { let j = i; i += 1 }; puts(j)", vec!["1:32: `j` is never used.", "1:54: `j` is not defined."]),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(test_analyze(input), expected, "{}", input);
+    }
+  }
+
+  #[test]
+  fn test_shadowed_and_unused() {
+    let tests: Vec<(&str, Vec<&str>)> = vec![
+      ("let x = 1; let f = fn(x) { x }; f(x)", vec!["1:23: `x` shadows the binding at 1:5."]),
+      ("let x = 1; let x = 2; puts(x)", vec![]),
+      ("let x = 1; if (x) { let x = 2; puts(x) }", vec!["1:25: `x` shadows the binding at 1:5."]),
+      ("for (i in [1]) { for (i in [2]) { puts(i) } }", vec!["1:23: `i` shadows the binding at 1:6."]),
+      ("let f = fn() { let [a, {b}] = [1, {\"b\": 2}]; a }; f()", vec!["1:25: `b` is never used."]),
+      ("let count = 0; count += 1; let _ignored = 2;", vec!["1:5: `count` is never used."]),
+      ("let f = fn(unused) { 1 }; f(1)", vec![]),
+      ("fn f(x) { x } fn g() { 1 } f(1)", vec!["1:18: `g` is never used."]),
+      ("struct P {x, y} let f = fn() { struct Q {x} let P {x, y: _y} = P {x: 1, y: 2}; x }; f()", vec!["1:39: `Q` is never used."]),
+      ("let x = 1; fn f() { fn x() { 2 } x() } f(x)", vec!["1:24: `x` shadows the binding at 1:5."]),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(test_analyze(input), expected, "{}", input);
+    }
+  }
+}
@@ -1,20 +1,56 @@
 use std::fmt;
 
 use crate::utils;
+use crate::span::Span;
 use super::ident::Identifier;
 use super::lit::{Literal};
-use super::operator::{Prefix, Infix};
-use super::stmt::{BlockStatement};
+use super::operator::{Prefix, Infix, Update};
+use super::stmt::{BlockStatement, Pattern};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expression {
   Identifier(Identifier),
   Literal(Literal),
   Prefix(PrefixExpression),
   Infix(InfixExpression),
   If(IfExpression),
+  Try(TryExpression),
+  Match(MatchExpression),
   Call(CallExpression),
+  MethodCall(MethodCallExpression),
+  Member(MemberExpression),
   Index(IndexExpression),
+  Slice(SliceExpression),
+  Assign(AssignExpression),
+  Update(UpdateExpression),
+  Ternary(TernaryExpression),
+  Range(RangeExpression),
+  Spread(SpreadExpression),
+}
+
+impl Expression {
+  pub fn span(&self) -> Span {
+    match self {
+      Expression::Identifier(ident) => ident.span,
+      Expression::Literal(lit) => lit.span(),
+      Expression::Prefix(pre) => pre.span,
+      Expression::Infix(inf) => inf.span,
+      Expression::If(if_expr) => if_expr.span,
+      Expression::Try(try_expr) => try_expr.span,
+      Expression::Match(match_expr) => match_expr.span,
+      Expression::Call(call_expr) => call_expr.span,
+      Expression::MethodCall(method) => method.span,
+      Expression::Member(member) => member.span,
+      Expression::Index(index) => index.span,
+      Expression::Slice(slice) => slice.span,
+      Expression::Assign(assign) => assign.span,
+      Expression::Update(update) => update.span,
+      Expression::Ternary(ternary) => ternary.span,
+      Expression::Range(range) => range.span,
+      Expression::Spread(spread) => spread.span,
+    }
+  }
 }
 
 impl fmt::Display for Expression {
@@ -25,21 +61,33 @@ impl fmt::Display for Expression {
       Expression::Prefix(pre) => write!(f, "{}", pre),
       Expression::Infix(inf) => write!(f, "{}", inf),
       Expression::If(if_expr) => write!(f, "{}", if_expr),
+      Expression::Try(try_expr) => write!(f, "{}", try_expr),
+      Expression::Match(match_expr) => write!(f, "{}", match_expr),
       Expression::Call(call_expr) => write!(f, "{}", call_expr),
+      Expression::MethodCall(method) => write!(f, "{}", method),
+      Expression::Member(member) => write!(f, "{}", member),
       Expression::Index(index) => write!(f, "{}", index),
+      Expression::Slice(slice) => write!(f, "{}", slice),
+      Expression::Assign(assign) => write!(f, "{}", assign),
+      Expression::Update(update) => write!(f, "{}", update),
+      Expression::Ternary(ternary) => write!(f, "{}", ternary),
+      Expression::Range(range) => write!(f, "{}", range),
+      Expression::Spread(spread) => write!(f, "{}", spread),
     }
   }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PrefixExpression {
   pub operator: Prefix,
   pub right: Box<Expression>,
+  pub span: Span,
 }
 
 impl PrefixExpression {
-  pub fn new(operator: Prefix, right: Box<Expression>) -> PrefixExpression {
-    PrefixExpression { operator, right }
+  pub fn new(operator: Prefix, right: Box<Expression>, span: Span) -> PrefixExpression {
+    PrefixExpression { operator, right, span }
   }
 }
 
@@ -50,15 +98,31 @@ impl fmt::Display for PrefixExpression {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InfixExpression {
   pub left: Box<Expression>,
   pub operator: Infix,
   pub right: Box<Expression>,
+  pub span: Span,
 }
 
 impl InfixExpression {
-  pub fn new(left: Box<Expression>, operator: Infix, right: Box<Expression>) -> InfixExpression {
-    InfixExpression { left, operator, right }
+  pub fn new(left: Box<Expression>, operator: Infix, right: Box<Expression>, span: Span) -> InfixExpression {
+    InfixExpression { left, operator, right, span }
+  }
+
+  /// Splits a chain like `a + b - c`, which nests to the left, into its leftmost operand and each operator
+  /// with its right operand and span, from left to right. It loops instead of recursing, so a pass can go
+  /// through a long chain without using the host stack for every operator.
+  pub fn unchain(self) -> (Expression, Vec<(Infix, Box<Expression>, Span)>) {
+    let mut operands = vec![];
+    let mut left = Expression::Infix(self);
+    while let Expression::Infix(inf) = left {
+      operands.push((inf.operator, inf.right, inf.span));
+      left = *inf.left;
+    }
+    operands.reverse();
+    (left, operands)
   }
 }
 
@@ -69,15 +133,17 @@ impl fmt::Display for InfixExpression {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IfExpression {
   pub condition: Box<Expression>,
   pub consequence: BlockStatement,
   pub alternative: Option<BlockStatement>,
+  pub span: Span,
 }
 
 impl IfExpression {
-  pub fn new(condition: Box<Expression>, consequence: BlockStatement, alternative: Option<BlockStatement>) -> IfExpression {
-    IfExpression { condition, consequence, alternative }
+  pub fn new(condition: Box<Expression>, consequence: BlockStatement, alternative: Option<BlockStatement>, span: Span) -> IfExpression {
+    IfExpression { condition, consequence, alternative, span }
   }
 }
 
@@ -91,15 +157,148 @@ impl fmt::Display for IfExpression {
   }
 }
 
+/// `try { block } catch (param) { handler }`. When the block fails with a runtime error,
+/// the handler is evaluated with `param` bound to a hash describing the error.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TryExpression {
+  pub block: BlockStatement,
+  pub param: Identifier,
+  pub handler: BlockStatement,
+  pub span: Span,
+}
+
+impl TryExpression {
+  pub fn new(block: BlockStatement, param: Identifier, handler: BlockStatement, span: Span) -> TryExpression {
+    TryExpression { block, param, handler, span }
+  }
+}
+
+impl fmt::Display for TryExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "try {} catch ({}) {}", self.block, self.param, self.handler)
+  }
+}
+
+/// `match (subject) { pattern => body, ... }`. The arms are tried in order, and the body of the first one
+/// whose pattern matches the subject is evaluated with the names the pattern binds. It is null when none matches.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchExpression {
+  pub subject: Box<Expression>,
+  pub arms: Vec<MatchArm>,
+  pub span: Span,
+}
+
+impl MatchExpression {
+  pub fn new(subject: Box<Expression>, arms: Vec<MatchArm>, span: Span) -> MatchExpression {
+    MatchExpression { subject, arms, span }
+  }
+}
+
+impl fmt::Display for MatchExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "match ({}) {{ ", self.subject)?;
+    utils::write_object_list(&self.arms, f)?;
+    write!(f, " }}")
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MatchArm {
+  pub pattern: Pattern,
+  pub body: Expression,
+  pub span: Span,
+}
+
+impl MatchArm {
+  pub fn new(pattern: Pattern, body: Expression, span: Span) -> MatchArm {
+    MatchArm { pattern, body, span }
+  }
+}
+
+impl fmt::Display for MatchArm {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} => {}", self.pattern, self.body)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TernaryExpression {
+  pub condition: Box<Expression>,
+  pub consequence: Box<Expression>,
+  pub alternative: Box<Expression>,
+  pub span: Span,
+}
+
+impl TernaryExpression {
+  pub fn new(condition: Box<Expression>, consequence: Box<Expression>, alternative: Box<Expression>, span: Span) -> TernaryExpression {
+    TernaryExpression { condition, consequence, alternative, span }
+  }
+}
+
+impl fmt::Display for TernaryExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "({} ? {} : {})", &self.condition, &self.consequence, &self.alternative)
+  }
+}
+
+/// `start..end`, or `start..=end` when `inclusive` is true.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeExpression {
+  pub start: Box<Expression>,
+  pub end: Box<Expression>,
+  pub inclusive: bool,
+  pub span: Span,
+}
+
+impl RangeExpression {
+  pub fn new(start: Box<Expression>, end: Box<Expression>, inclusive: bool, span: Span) -> RangeExpression {
+    RangeExpression { start, end, inclusive, span }
+  }
+}
+
+impl fmt::Display for RangeExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let operator = if self.inclusive { "..=" } else { ".." };
+    write!(f, "({}{}{})", &self.start, operator, &self.end)
+  }
+}
+
+/// `...value`; only parsed as an element of an array literal or a call argument.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpreadExpression {
+  pub value: Box<Expression>,
+  pub span: Span,
+}
+
+impl SpreadExpression {
+  pub fn new(value: Box<Expression>, span: Span) -> SpreadExpression {
+    SpreadExpression { value, span }
+  }
+}
+
+impl fmt::Display for SpreadExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "...{}", &self.value)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CallExpression {
   pub func: Box<Expression>, // Identifier or Func literal
   pub args: Vec<Expression>,
+  pub span: Span,
 }
 
 impl CallExpression {
-  pub fn new(func: Box<Expression>, args: Vec<Expression>) -> CallExpression {
-    CallExpression { func, args }
+  pub fn new(func: Box<Expression>, args: Vec<Expression>, span: Span) -> CallExpression {
+    CallExpression { func, args, span }
   }
 }
 
@@ -113,15 +312,65 @@ impl fmt::Display for CallExpression {
   }
 }
 
+/// `receiver.method(args)` calls the builtin function `method` with `receiver` as its first argument,
+/// when `method` is one of the methods of the type of the receiver.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodCallExpression {
+  pub receiver: Box<Expression>,
+  pub method: Identifier,
+  pub args: Vec<Expression>,
+  pub span: Span,
+}
+
+impl MethodCallExpression {
+  pub fn new(receiver: Box<Expression>, method: Identifier, args: Vec<Expression>, span: Span) -> MethodCallExpression {
+    MethodCallExpression { receiver, method, args, span }
+  }
+}
+
+impl fmt::Display for MethodCallExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}(", self.receiver, self.method)?;
+
+    utils::write_object_list(&self.args, f)?;
+
+    write!(f, ")")
+  }
+}
+
+/// `object.property` is the same as `object["property"]`, so it reads and writes the value of a hash.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemberExpression {
+  pub object: Box<Expression>,
+  pub property: Identifier,
+  pub span: Span,
+}
+
+impl MemberExpression {
+  pub fn new(object: Box<Expression>, property: Identifier, span: Span) -> MemberExpression {
+    MemberExpression { object, property, span }
+  }
+}
+
+impl fmt::Display for MemberExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}", self.object, self.property)
+  }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IndexExpression {
   pub left: Box<Expression>, // Identifier or Func literal
   pub index: Box<Expression>,
+  pub span: Span,
 }
 
 impl IndexExpression {
-  pub fn new(left: Box<Expression>, index: Box<Expression>) -> IndexExpression {
-    IndexExpression { left, index }
+  pub fn new(left: Box<Expression>, index: Box<Expression>, span: Span) -> IndexExpression {
+    IndexExpression { left, index, span }
   }
 }
 
@@ -130,3 +379,84 @@ impl fmt::Display for IndexExpression {
     write!(f, "({}[{}])", self.left, self.index)
   }
 }
+
+/// `left[start:end]`; either bound may be omitted.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SliceExpression {
+  pub left: Box<Expression>,
+  pub start: Option<Box<Expression>>,
+  pub end: Option<Box<Expression>>,
+  pub span: Span,
+}
+
+impl SliceExpression {
+  pub fn new(left: Box<Expression>, start: Option<Box<Expression>>, end: Option<Box<Expression>>, span: Span) -> SliceExpression {
+    SliceExpression { left, start, end, span }
+  }
+}
+
+impl fmt::Display for SliceExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "({}[", self.left)?;
+    if let Some(start) = &self.start {
+      write!(f, "{}", start)?;
+    }
+    write!(f, ":")?;
+    if let Some(end) = &self.end {
+      write!(f, "{}", end)?;
+    }
+    write!(f, "])")
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AssignExpression {
+  pub target: Box<Expression>, // Identifier or Index
+  /// The operator of a compound assignment, e.g. `Plus` for `+=`.
+  pub operator: Option<Infix>,
+  pub value: Box<Expression>,
+  pub span: Span,
+}
+
+impl AssignExpression {
+  pub fn new(target: Box<Expression>, operator: Option<Infix>, value: Box<Expression>, span: Span) -> AssignExpression {
+    AssignExpression { target, operator, value, span }
+  }
+}
+
+impl fmt::Display for AssignExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.operator {
+      Some(operator) => write!(f, "({} {}= {})", &self.target, operator, &self.value),
+      None => write!(f, "({} = {})", &self.target, &self.value),
+    }
+  }
+}
+
+/// `++x` and `x++`. The prefix form evaluates to the updated value and the postfix form to the original one.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UpdateExpression {
+  pub target: Box<Expression>, // Identifier or Index
+  pub operator: Update,
+  pub prefix: bool,
+  pub span: Span,
+}
+
+impl UpdateExpression {
+  pub fn new(target: Box<Expression>, operator: Update, prefix: bool, span: Span) -> UpdateExpression {
+    UpdateExpression { target, operator, prefix, span }
+  }
+}
+
+impl fmt::Display for UpdateExpression {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.prefix {
+      write!(f, "({}{})", &self.operator, &self.target)
+    } else {
+      write!(f, "({}{})", &self.target, &self.operator)
+    }
+  }
+}
@@ -0,0 +1,266 @@
+use super::Program;
+use super::stmt::{Statement, BlockStatement, Pattern};
+use super::expr::{Expression, MatchArm};
+use super::ident::Identifier;
+use super::lit::{Literal, Func};
+
+/// Rebuilds a syntax tree, e.g. to rewrite expressions in an optimizer. Each `fold_*` method takes a node
+/// and returns the node which replaces it. By default it folds the children of the node with the matching
+/// `walk_*` function and keeps the node itself, so a transformation only overrides the methods it needs.
+///
+/// The spans of the nodes are kept as they are, even if a node becomes larger or smaller than its source.
+pub trait Fold {
+  fn fold_program(&mut self, program: Program) -> Program {
+    walk_program(self, program)
+  }
+
+  fn fold_statement(&mut self, stmt: Statement) -> Statement {
+    walk_statement(self, stmt)
+  }
+
+  fn fold_block(&mut self, block: BlockStatement) -> BlockStatement {
+    walk_block(self, block)
+  }
+
+  fn fold_pattern(&mut self, pattern: Pattern) -> Pattern {
+    walk_pattern(self, pattern)
+  }
+
+  fn fold_expression(&mut self, expr: Expression) -> Expression {
+    walk_expression(self, expr)
+  }
+
+  fn fold_literal(&mut self, lit: Literal) -> Literal {
+    walk_literal(self, lit)
+  }
+
+  fn fold_identifier(&mut self, ident: Identifier) -> Identifier {
+    ident
+  }
+}
+
+fn fold_boxed<F: Fold + ?Sized>(f: &mut F, expr: Box<Expression>) -> Box<Expression> {
+  Box::new(f.fold_expression(*expr))
+}
+
+// 関数のリテラルと fn 文の関数
+fn fold_func<F: Fold + ?Sized>(f: &mut F, mut func: Func) -> Func {
+  func.args = func.args.into_iter().map(|arg| f.fold_identifier(arg)).collect();
+  func.defaults = func.defaults.into_iter().map(|default| default.map(|default| f.fold_expression(default))).collect();
+  func.body = f.fold_block(func.body);
+  func
+}
+
+pub fn walk_program<F: Fold + ?Sized>(f: &mut F, mut program: Program) -> Program {
+  program.statements = program.statements.into_iter().map(|stmt| f.fold_statement(stmt)).collect();
+  program
+}
+
+pub fn walk_statement<F: Fold + ?Sized>(f: &mut F, stmt: Statement) -> Statement {
+  match stmt {
+    Statement::Let(mut let_stmt) => {
+      let_stmt.pattern = f.fold_pattern(let_stmt.pattern);
+      let_stmt.value = f.fold_expression(let_stmt.value);
+      Statement::Let(let_stmt)
+    },
+    Statement::Func(mut func_stmt) => {
+      func_stmt.ident = f.fold_identifier(func_stmt.ident);
+      func_stmt.func = fold_func(f, func_stmt.func);
+      Statement::Func(func_stmt)
+    },
+    Statement::Struct(mut struct_stmt) => {
+      struct_stmt.ident = f.fold_identifier(struct_stmt.ident);
+      Statement::Struct(struct_stmt)
+    },
+    Statement::Return(mut rtn) => {
+      rtn.value = f.fold_expression(rtn.value);
+      Statement::Return(rtn)
+    },
+    Statement::Expr(mut expr) => {
+      expr.value = f.fold_expression(expr.value);
+      Statement::Expr(expr)
+    },
+    Statement::Block(block) => Statement::Block(f.fold_block(block)),
+    Statement::While(mut while_stmt) => {
+      while_stmt.condition = f.fold_expression(while_stmt.condition);
+      while_stmt.body = f.fold_block(while_stmt.body);
+      Statement::While(while_stmt)
+    },
+    Statement::For(mut for_stmt) => {
+      for_stmt.ident = f.fold_identifier(for_stmt.ident);
+      for_stmt.iterable = f.fold_expression(for_stmt.iterable);
+      for_stmt.body = f.fold_block(for_stmt.body);
+      Statement::For(for_stmt)
+    },
+    Statement::Comment(comment) => Statement::Comment(comment),
+  }
+}
+
+pub fn walk_block<F: Fold + ?Sized>(f: &mut F, mut block: BlockStatement) -> BlockStatement {
+  block.statements = block.statements.into_iter().map(|stmt| f.fold_statement(stmt)).collect();
+  block
+}
+
+pub fn walk_pattern<F: Fold + ?Sized>(f: &mut F, pattern: Pattern) -> Pattern {
+  match pattern {
+    Pattern::Ident(ident) => Pattern::Ident(f.fold_identifier(ident)),
+    Pattern::Array(mut arr) => {
+      arr.elements = arr.elements.into_iter().map(|elm| f.fold_pattern(elm)).collect();
+      arr.rest = arr.rest.map(|rest| f.fold_identifier(rest));
+      Pattern::Array(arr)
+    },
+    Pattern::Hash(mut hash) => {
+      hash.pairs = hash.pairs.into_iter().map(|(key, pattern)| (key, f.fold_pattern(pattern))).collect();
+      Pattern::Hash(hash)
+    },
+    Pattern::Struct(mut pat) => {
+      pat.name = f.fold_identifier(pat.name);
+      pat.fields = pat.fields.into_iter().map(|(key, pattern)| (key, f.fold_pattern(pattern))).collect();
+      Pattern::Struct(pat)
+    },
+    Pattern::Literal(lit) => Pattern::Literal(f.fold_literal(lit)),
+  }
+}
+
+pub fn walk_expression<F: Fold + ?Sized>(f: &mut F, expr: Expression) -> Expression {
+  match expr {
+    Expression::Identifier(ident) => Expression::Identifier(f.fold_identifier(ident)),
+    Expression::Literal(lit) => Expression::Literal(f.fold_literal(lit)),
+    Expression::Prefix(mut pre) => {
+      pre.right = fold_boxed(f, pre.right);
+      Expression::Prefix(pre)
+    },
+    Expression::Infix(mut inf) => {
+      inf.left = fold_boxed(f, inf.left);
+      inf.right = fold_boxed(f, inf.right);
+      Expression::Infix(inf)
+    },
+    Expression::If(mut if_expr) => {
+      if_expr.condition = fold_boxed(f, if_expr.condition);
+      if_expr.consequence = f.fold_block(if_expr.consequence);
+      if_expr.alternative = if_expr.alternative.map(|alt| f.fold_block(alt));
+      Expression::If(if_expr)
+    },
+    Expression::Try(mut try_expr) => {
+      try_expr.block = f.fold_block(try_expr.block);
+      try_expr.param = f.fold_identifier(try_expr.param);
+      try_expr.handler = f.fold_block(try_expr.handler);
+      Expression::Try(try_expr)
+    },
+    Expression::Match(mut match_expr) => {
+      match_expr.subject = fold_boxed(f, match_expr.subject);
+      match_expr.arms = match_expr.arms.into_iter()
+        .map(|arm| MatchArm::new(f.fold_pattern(arm.pattern), f.fold_expression(arm.body), arm.span))
+        .collect();
+      Expression::Match(match_expr)
+    },
+    Expression::Call(mut call) => {
+      call.func = fold_boxed(f, call.func);
+      call.args = call.args.into_iter().map(|arg| f.fold_expression(arg)).collect();
+      Expression::Call(call)
+    },
+    Expression::MethodCall(mut method) => {
+      method.receiver = fold_boxed(f, method.receiver);
+      method.args = method.args.into_iter().map(|arg| f.fold_expression(arg)).collect();
+      Expression::MethodCall(method)
+    },
+    Expression::Member(mut member) => {
+      member.object = fold_boxed(f, member.object);
+      Expression::Member(member)
+    },
+    Expression::Index(mut idx) => {
+      idx.left = fold_boxed(f, idx.left);
+      idx.index = fold_boxed(f, idx.index);
+      Expression::Index(idx)
+    },
+    Expression::Slice(mut slice) => {
+      slice.left = fold_boxed(f, slice.left);
+      slice.start = slice.start.map(|start| fold_boxed(f, start));
+      slice.end = slice.end.map(|end| fold_boxed(f, end));
+      Expression::Slice(slice)
+    },
+    Expression::Assign(mut assign) => {
+      assign.target = fold_boxed(f, assign.target);
+      assign.value = fold_boxed(f, assign.value);
+      Expression::Assign(assign)
+    },
+    Expression::Update(mut update) => {
+      update.target = fold_boxed(f, update.target);
+      Expression::Update(update)
+    },
+    Expression::Ternary(mut ternary) => {
+      ternary.condition = fold_boxed(f, ternary.condition);
+      ternary.consequence = fold_boxed(f, ternary.consequence);
+      ternary.alternative = fold_boxed(f, ternary.alternative);
+      Expression::Ternary(ternary)
+    },
+    Expression::Range(mut range) => {
+      range.start = fold_boxed(f, range.start);
+      range.end = fold_boxed(f, range.end);
+      Expression::Range(range)
+    },
+    Expression::Spread(mut spread) => {
+      spread.value = fold_boxed(f, spread.value);
+      Expression::Spread(spread)
+    },
+  }
+}
+
+pub fn walk_literal<F: Fold + ?Sized>(f: &mut F, lit: Literal) -> Literal {
+  match lit {
+    Literal::Array(mut arr) => {
+      arr.elements = arr.elements.into_iter().map(|elm| f.fold_expression(elm)).collect();
+      Literal::Array(arr)
+    },
+    Literal::Hash(mut hash) => {
+      hash.pairs = hash.pairs.into_iter().map(|(key, val)| (f.fold_expression(key), f.fold_expression(val))).collect();
+      Literal::Hash(hash)
+    },
+    Literal::Struct(mut lit) => {
+      lit.name = f.fold_identifier(lit.name);
+      lit.fields = lit.fields.into_iter().map(|(key, val)| (key, f.fold_expression(val))).collect();
+      Literal::Struct(lit)
+    },
+    Literal::Func(func) => Literal::Func(fold_func(f, func)),
+    Literal::Macro(mut mac) => {
+      mac.args = mac.args.into_iter().map(|arg| f.fold_identifier(arg)).collect();
+      mac.body = f.fold_block(mac.body);
+      Literal::Macro(mac)
+    },
+    lit => lit,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use crate::ast::expr::PrefixExpression;
+  use crate::ast::lit::Integer;
+  use crate::ast::operator::Prefix;
+
+  #[test]
+  fn test_fold() {
+    // 整数リテラルを符号を反転した式に置き換える
+    struct Negate;
+
+    impl Fold for Negate {
+      fn fold_expression(&mut self, expr: Expression) -> Expression {
+        match expr {
+          Expression::Literal(Literal::Integer(int)) => {
+            let span = int.span;
+            let right = Expression::Literal(Literal::Integer(Integer::new(int.value, span)));
+            Expression::Prefix(PrefixExpression::new(Prefix::Minus, Box::new(right), span))
+          },
+          expr => walk_expression(self, expr),
+        }
+      }
+    }
+
+    let mut p = Parser::new(Lexer::new("let f = fn(x = 1) { [x, 2][:3] }; f() + 4".to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    assert_eq!(Negate.fold_program(program).to_string(), "let f = fn(x = (-1)) { ([x, (-2)][:(-3)]) };(f() + (-4))");
+  }
+}
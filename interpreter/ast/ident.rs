@@ -1,14 +1,19 @@
 use std::fmt;
 use std::cmp::PartialEq;
 
+use crate::intern::Name;
+use crate::span::Span;
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Identifier {
-  pub value: String,
+  pub value: Name,
+  pub span: Span,
 }
 
 impl Identifier {
-  pub fn new(value: String) -> Identifier {
-    Identifier { value }
+  pub fn new(value: Name, span: Span) -> Identifier {
+    Identifier { value, span }
   }
 }
 
@@ -1,41 +1,70 @@
 use std::fmt;
 
 use crate::utils;
+use crate::span::Span;
 use super::ident::Identifier;
 use super::stmt::BlockStatement;
 use super::expr::Expression;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
   Integer(Integer),
+  Float(Float),
   Boolean(Boolean),
+  Null(Null),
   Str(Str),
   Array(Array),
   Hash(Hash),
+  Struct(Struct),
   Func(Func),
+  Macro(Macro),
+}
+
+impl Literal {
+  pub fn span(&self) -> Span {
+    match self {
+      Literal::Integer(int) => int.span,
+      Literal::Float(v) => v.span,
+      Literal::Boolean(v) => v.span,
+      Literal::Null(v) => v.span,
+      Literal::Str(v) => v.span,
+      Literal::Array(v) => v.span,
+      Literal::Hash(v) => v.span,
+      Literal::Struct(v) => v.span,
+      Literal::Func(func) => func.span,
+      Literal::Macro(mac) => mac.span,
+    }
+  }
 }
 
 impl fmt::Display for Literal {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Literal::Integer(int) => write!(f, "{}", int),
+      Literal::Float(v) => write!(f, "{}", v),
       Literal::Boolean(v) => write!(f, "{}", v),
+      Literal::Null(v) => write!(f, "{}", v),
       Literal::Str(v) => write!(f, "{}", v),
       Literal::Array(v) => write!(f, "{}", v),
       Literal::Hash(v) => write!(f, "{}", v),
+      Literal::Struct(v) => write!(f, "{}", v),
       Literal::Func(func) => write!(f, "{}", func),
+      Literal::Macro(mac) => write!(f, "{}", mac),
     }
   }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Integer {
   pub value: i64,
+  pub span: Span,
 }
 
 impl Integer {
-  pub fn new(value: i64) -> Integer {
-    Integer { value }
+  pub fn new(value: i64, span: Span) -> Integer {
+    Integer { value, span }
   }
 }
 
@@ -46,13 +75,34 @@ impl fmt::Display for Integer {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Float {
+  pub value: f64,
+  pub span: Span,
+}
+
+impl Float {
+  pub fn new(value: f64, span: Span) -> Float {
+    Float { value, span }
+  }
+}
+
+impl fmt::Display for Float {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", &self.value)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Boolean {
   pub value: bool,
+  pub span: Span,
 }
 
 impl Boolean {
-  pub fn new(value: bool) -> Boolean {
-    Boolean { value }
+  pub fn new(value: bool, span: Span) -> Boolean {
+    Boolean { value, span }
   }
 }
 
@@ -63,13 +113,33 @@ impl fmt::Display for Boolean {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Null {
+  pub span: Span,
+}
+
+impl Null {
+  pub fn new(span: Span) -> Null {
+    Null { span }
+  }
+}
+
+impl fmt::Display for Null {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "null")
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Str {
   pub value: String,
+  pub span: Span,
 }
 
 impl Str {
-  pub fn new(value: String) -> Str {
-    Str { value }
+  pub fn new(value: String, span: Span) -> Str {
+    Str { value, span }
   }
 }
 
@@ -80,13 +150,15 @@ impl fmt::Display for Str {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Array {
   pub elements: Vec<Expression>,
+  pub span: Span,
 }
 
 impl Array {
-  pub fn new(elements: Vec<Expression>) -> Array {
-    Array { elements }
+  pub fn new(elements: Vec<Expression>, span: Span) -> Array {
+    Array { elements, span }
   }
 }
 
@@ -99,13 +171,15 @@ impl fmt::Display for Array {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Hash {
   pub pairs: Vec<(Expression, Expression)>,
+  pub span: Span,
 }
 
 impl Hash {
-  pub fn new(pairs: Vec<(Expression, Expression)>) -> Hash {
-    Hash { pairs }
+  pub fn new(pairs: Vec<(Expression, Expression)>, span: Span) -> Hash {
+    Hash { pairs, span }
   }
 }
 
@@ -121,15 +195,42 @@ impl fmt::Display for Hash {
   }
 }
 
+/// `Point {x: 1, y: 2}` makes a record of the struct bound to `name`, with a value for each of its fields.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Struct {
+  pub name: Identifier,
+  pub fields: Vec<(Identifier, Expression)>,
+  pub span: Span,
+}
+
+impl Struct {
+  pub fn new(name: Identifier, fields: Vec<(Identifier, Expression)>, span: Span) -> Struct {
+    Struct { name, fields, span }
+  }
+}
+
+impl fmt::Display for Struct {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {{", self.name)?;
+    let fields: Vec<String> = self.fields.iter().map(|(key, val)| format!("{}: {}", key, val)).collect();
+    utils::write_object_list(&fields, f)?;
+    write!(f, "}}")
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Func {
   pub args: Vec<Identifier>,
+  pub defaults: Vec<Option<Expression>>, // args と同じ長さ
   pub body: BlockStatement,
+  pub span: Span,
 }
 
 impl Func {
-  pub fn new(args: Vec<Identifier>, body: BlockStatement) -> Func {
-    Func { args, body }
+  pub fn new(args: Vec<Identifier>, defaults: Vec<Option<Expression>>, body: BlockStatement, span: Span) -> Func {
+    Func { args, defaults, body, span }
   }
 }
 
@@ -137,9 +238,33 @@ impl fmt::Display for Func {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "fn(")?;
 
-    utils::write_object_list(&self.args, f)?;
+    utils::write_param_list(&self.args, &self.defaults, f)?;
 
     write!(f, ") {}", &self.body)?;
     Ok(())
   }
 }
+
+/// A macro is called like a function at macro expansion time, before the program is evaluated.
+/// Its arguments are bound to the quoted argument expressions, and the quote it returns replaces the call.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Macro {
+  pub args: Vec<Identifier>,
+  pub body: BlockStatement,
+  pub span: Span,
+}
+
+impl Macro {
+  pub fn new(args: Vec<Identifier>, body: BlockStatement, span: Span) -> Macro {
+    Macro { args, body, span }
+  }
+}
+
+impl fmt::Display for Macro {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "macro(")?;
+    utils::write_object_list(&self.args, f)?;
+    write!(f, ") {}", &self.body)
+  }
+}
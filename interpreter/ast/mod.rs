@@ -7,14 +7,40 @@ pub mod expr;
 pub mod ident;
 pub mod lit;
 pub mod operator;
+pub mod tree;
+pub mod visit;
+pub mod fold;
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
   pub statements: Vec<Statement>,
 }
 
+impl Default for Program {
+  fn default() -> Program {
+    Program::new()
+  }
+}
+
 impl Program {
   pub fn new() -> Program {
-    return Program { statements: vec![] };
+    Program { statements: vec![] }
+  }
+
+  /// Folds the constant expressions of the program. See `optimizer::optimize`.
+  pub fn optimize(self) -> Program {
+    crate::optimizer::optimize(self)
+  }
+
+  /// Formats the program as an indented tree of its nodes with their spans. See `tree::to_tree_string`.
+  pub fn to_tree_string(&self) -> String {
+    tree::to_tree_string(self)
+  }
+
+  /// Formats the program as canonical source code. See `formatter::format`.
+  pub fn format(&self) -> String {
+    crate::formatter::format(self)
   }
 }
 
@@ -26,3 +52,31 @@ impl fmt::Display for Program {
     Ok(())
   }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use super::*;
+
+  #[test]
+  fn test_serde_round_trip() {
+    let input = "
+let {a, b} = {\"a\": 1.5, \"b\": [true, null]};
+let f = fn(x, y = -1) { return x ?? y; };
+for (i in 0..=3) { i++; f(...[i])[0] }
+try { a += b[1:] } catch (e) { e[\"kind\"] } // comment";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+
+    let json = serde_json::to_string(&program).unwrap();
+    let decoded: Program = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded.to_tree_string(), program.to_tree_string());
+    assert_eq!(format!("{}", decoded), format!("{}", program));
+
+    let json = serde_json::to_value(Parser::new(Lexer::new("x".to_string())).parse_program()).unwrap();
+    assert_eq!(json["statements"][0]["Expr"]["value"]["Identifier"]["value"], "x");
+    assert_eq!(json["statements"][0]["Expr"]["value"]["Identifier"]["span"]["end"]["column"], 2);
+  }
+}
@@ -2,11 +2,14 @@ use std::cmp::{PartialEq, PartialOrd};
 use std::fmt;
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Prefix {
   /// `-`
   Minus,
   /// `!`
   Bang,
+  /// `~`
+  BitNot,
 }
 
 impl fmt::Display for Prefix {
@@ -14,11 +17,31 @@ impl fmt::Display for Prefix {
     match self {
       Prefix::Minus => write!(f, "-"),
       Prefix::Bang => write!(f, "!"),
+      Prefix::BitNot => write!(f, "~"),
     }
   }
 }
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Update {
+  /// `++`
+  Increment,
+  /// `--`
+  Decrement,
+}
+
+impl fmt::Display for Update {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Update::Increment => write!(f, "++"),
+      Update::Decrement => write!(f, "--"),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Infix {
   /// `+`
   Plus,
@@ -28,18 +51,63 @@ pub enum Infix {
   Slash,
   /// `*`
   Asterisk,
+  /// `%`
+  Percent,
+  /// `**`
+  Power,
+  /// `&`
+  BitAnd,
+  /// `|`
+  BitOr,
+  /// `^`
+  BitXor,
+  /// `<<`
+  ShiftLeft,
+  /// `>>`
+  ShiftRight,
   /// `>`
   Gt,
   /// `<`
   Lt,
+  /// `>=`
+  GtEq,
+  /// `<=`
+  LtEq,
   /// `==`
   Equal,
   /// `!=`
   NotEq,
+  /// `&&`
+  And,
+  /// `||`
+  Or,
+  /// `??`
+  Coalesce,
   /// `(`
   Call,
 }
 
+impl Infix {
+  /// Returns the precedence the parser gives to the operator.
+  pub fn precedence(&self) -> BinaryOperator {
+    match self {
+      Infix::Coalesce => BinaryOperator::Coalesce,
+      Infix::Or => BinaryOperator::LogicalOr,
+      Infix::And => BinaryOperator::LogicalAnd,
+      Infix::Equal | Infix::NotEq => BinaryOperator::Equals,
+      Infix::Gt | Infix::Lt | Infix::GtEq | Infix::LtEq => BinaryOperator::LtGt,
+      Infix::BitOr => BinaryOperator::BitOr,
+      Infix::BitXor => BinaryOperator::BitXor,
+      Infix::BitAnd => BinaryOperator::BitAnd,
+      Infix::ShiftLeft | Infix::ShiftRight => BinaryOperator::Shift,
+      Infix::Plus | Infix::Minus => BinaryOperator::Sum,
+      Infix::Asterisk | Infix::Slash | Infix::Percent => BinaryOperator::Product,
+      Infix::Power => BinaryOperator::Power,
+      Infix::Call => BinaryOperator::Call,
+    }
+  }
+}
+
 impl fmt::Display for Infix {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
@@ -47,10 +115,22 @@ impl fmt::Display for Infix {
       Infix::Minus => write!(f, "-"),
       Infix::Asterisk => write!(f, "*"),
       Infix::Slash => write!(f, "/"),
+      Infix::Percent => write!(f, "%"),
+      Infix::Power => write!(f, "**"),
+      Infix::BitAnd => write!(f, "&"),
+      Infix::BitOr => write!(f, "|"),
+      Infix::BitXor => write!(f, "^"),
+      Infix::ShiftLeft => write!(f, "<<"),
+      Infix::ShiftRight => write!(f, ">>"),
       Infix::Gt => write!(f, ">"),
       Infix::Lt => write!(f, "<"),
+      Infix::GtEq => write!(f, ">="),
+      Infix::LtEq => write!(f, "<="),
       Infix::Equal => write!(f, "=="),
       Infix::NotEq => write!(f, "!="),
+      Infix::And => write!(f, "&&"),
+      Infix::Or => write!(f, "||"),
+      Infix::Coalesce => write!(f, "??"),
       Infix::Call => write!(f, "("),
     }
   }
@@ -59,11 +139,30 @@ impl fmt::Display for Infix {
 #[derive(PartialEq, PartialOrd)]
 pub enum BinaryOperator {
   Lowest,
+  Assign,
+  Ternary,
+  Coalesce,
+  LogicalOr,
+  LogicalAnd,
   Equals,
   LtGt,
+  Range,
+  BitOr,
+  BitXor,
+  BitAnd,
+  Shift,
   Sum,
   Product,
   Prefix,
+  Power,
+  Postfix,
   Call,
   Index,
 }
+
+impl BinaryOperator {
+  /// Returns true if operators of this precedence group from the right, e.g. `a ** b ** c` is `a ** (b ** c)`.
+  pub fn is_right_assoc(&self) -> bool {
+    matches!(self, BinaryOperator::Assign | BinaryOperator::Ternary | BinaryOperator::Power)
+  }
+}
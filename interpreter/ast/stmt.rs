@@ -1,59 +1,275 @@
 use std::fmt;
 
+use crate::utils;
+use crate::span::Span;
 use super::ident::Identifier;
 use super::expr::Expression;
+use super::lit::{Literal, Func};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Statement {
   Let(LetStatement),
+  Func(FuncStatement),
+  Struct(StructStatement),
   Return(ReturnStatement),
   Expr(ExpressionStatement),
   Block(BlockStatement),
+  While(WhileStatement),
+  For(ForStatement),
   Comment(CommentStatement),
 }
 
+impl Statement {
+  pub fn span(&self) -> Span {
+    match self {
+      Statement::Let(let_stmt) => let_stmt.span,
+      Statement::Func(func_stmt) => func_stmt.span,
+      Statement::Struct(struct_stmt) => struct_stmt.span,
+      Statement::Return(return_stmt) => return_stmt.span,
+      Statement::Expr(expr_stmt) => expr_stmt.span,
+      Statement::Block(block) => block.span,
+      Statement::While(while_stmt) => while_stmt.span,
+      Statement::For(for_stmt) => for_stmt.span,
+      Statement::Comment(comment) => comment.span,
+    }
+  }
+}
+
 impl fmt::Display for Statement {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Statement::Let(let_stmt) => write!(f, "{}", let_stmt),
+      Statement::Func(func_stmt) => write!(f, "{}", func_stmt),
+      Statement::Struct(struct_stmt) => write!(f, "{}", struct_stmt),
       Statement::Return(return_stmt) => write!(f, "{}", return_stmt),
       Statement::Expr(expr_stmt) => write!(f, "{}", expr_stmt),
       Statement::Block(block) => write!(f, "{}", block),
+      Statement::While(while_stmt) => write!(f, "{}", while_stmt),
+      Statement::For(for_stmt) => write!(f, "{}", for_stmt),
       Statement::Comment(comment) => write!(f, "{}", comment),
     }
   }
 }
 
+/// The left side of `let`, which binds one or more names.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Pattern {
+  /// `let x = ...`
+  Ident(Identifier),
+  /// `let [a, b] = ...`
+  Array(ArrayPattern),
+  /// `let {x, y} = ...`
+  Hash(HashPattern),
+  /// `let Point {x, y} = ...`
+  Struct(StructPattern),
+  /// `0 => ...` in a `match` arm, which only matches a value equal to the integer, float, string, boolean or null.
+  Literal(Literal),
+}
+
+impl Pattern {
+  pub fn span(&self) -> Span {
+    match self {
+      Pattern::Ident(ident) => ident.span,
+      Pattern::Array(arr) => arr.span,
+      Pattern::Hash(hash) => hash.span,
+      Pattern::Struct(pat) => pat.span,
+      Pattern::Literal(lit) => lit.span(),
+    }
+  }
+}
+
+impl fmt::Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Pattern::Ident(ident) => write!(f, "{}", ident),
+      Pattern::Array(arr) => write!(f, "{}", arr),
+      Pattern::Hash(hash) => write!(f, "{}", hash),
+      Pattern::Struct(pat) => write!(f, "{}", pat),
+      Pattern::Literal(lit) => write!(f, "{}", lit),
+    }
+  }
+}
+
+/// `rest` is bound to an array of the elements after the ones matched by `elements`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArrayPattern {
+  pub elements: Vec<Pattern>,
+  pub rest: Option<Identifier>,
+  pub span: Span,
+}
+
+impl ArrayPattern {
+  pub fn new(elements: Vec<Pattern>, rest: Option<Identifier>, span: Span) -> ArrayPattern {
+    ArrayPattern { elements, rest, span }
+  }
+}
+
+impl fmt::Display for ArrayPattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "[")?;
+    utils::write_object_list(&self.elements, f)?;
+    if let Some(rest) = &self.rest {
+      if !self.elements.is_empty() {
+        write!(f, ", ")?;
+      }
+      write!(f, "...{}", rest)?;
+    }
+    write!(f, "]")
+  }
+}
+
+/// Each pattern is matched against the value of the hash at the string of its key.
+/// `{x}` is short for `{x: x}`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashPattern {
+  pub pairs: Vec<(Identifier, Pattern)>,
+  pub span: Span,
+}
+
+impl HashPattern {
+  pub fn new(pairs: Vec<(Identifier, Pattern)>, span: Span) -> HashPattern {
+    HashPattern { pairs, span }
+  }
+}
+
+impl fmt::Display for HashPattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{{")?;
+    write_field_patterns(&self.pairs, f)?;
+    write!(f, "}}")
+  }
+}
+
+/// Matches a record of the struct bound to `name`, and each pattern against the value of its field.
+/// `Point {x}` is short for `Point {x: x}`, and the fields which are not written are not matched.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructPattern {
+  pub name: Identifier,
+  pub fields: Vec<(Identifier, Pattern)>,
+  pub span: Span,
+}
+
+impl StructPattern {
+  pub fn new(name: Identifier, fields: Vec<(Identifier, Pattern)>, span: Span) -> StructPattern {
+    StructPattern { name, fields, span }
+  }
+}
+
+impl fmt::Display for StructPattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {{", self.name)?;
+    write_field_patterns(&self.fields, f)?;
+    write!(f, "}}")
+  }
+}
+
+fn write_field_patterns(fields: &[(Identifier, Pattern)], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  for (i, (key, pattern)) in fields.iter().enumerate() {
+    if i > 0 {
+      write!(f, ", ")?;
+    }
+    match pattern {
+      Pattern::Ident(ident) if ident.value == key.value => write!(f, "{}", key)?,
+      pattern => write!(f, "{}: {}", key, pattern)?,
+    }
+  }
+  Ok(())
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LetStatement {
-  pub ident: Identifier,
+  pub pattern: Pattern,
   pub value: Expression,
+  /// Whether it was written with `const`, so the names it binds can not be assigned to.
+  pub constant: bool,
+  pub span: Span,
 }
 
 impl LetStatement {
-  pub fn new(ident: Identifier, value: Expression) -> LetStatement {
-    LetStatement { ident, value }
+  pub fn new(pattern: Pattern, value: Expression, constant: bool, span: Span) -> LetStatement {
+    LetStatement { pattern, value, constant, span }
+  }
+
+  /// Returns the keyword the statement starts with, `let` or `const`.
+  pub fn keyword(&self) -> &'static str {
+    if self.constant { "const" } else { "let" }
   }
 }
 
 impl fmt::Display for LetStatement {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "let ")?;
-    write!(f, "{}", self.ident)?;
+    write!(f, "{} ", self.keyword())?;
+    write!(f, "{}", self.pattern)?;
     write!(f, " = ")?;
     write!(f, "{}", self.value)?;
     write!(f, ";")
   }
 }
 
+/// `fn add(x, y) { x + y }` binds the function to `add` like `let add = fn(x, y) { x + y };`.
+/// The name is bound before the body runs, so the function can call itself.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FuncStatement {
+  pub ident: Identifier,
+  pub func: Func,
+  pub span: Span,
+}
+
+impl FuncStatement {
+  pub fn new(ident: Identifier, func: Func, span: Span) -> FuncStatement {
+    FuncStatement { ident, func, span }
+  }
+}
+
+impl fmt::Display for FuncStatement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "fn {}(", self.ident)?;
+    utils::write_param_list(&self.func.args, &self.func.defaults, f)?;
+    write!(f, ") {}", self.func.body)
+  }
+}
+
+/// `struct Point {x, y}` binds a struct with the fields `x` and `y` to `Point`,
+/// and `Point {x: 1, y: 2}` makes a record of it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StructStatement {
+  pub ident: Identifier,
+  pub fields: Vec<Identifier>,
+  pub span: Span,
+}
+
+impl StructStatement {
+  pub fn new(ident: Identifier, fields: Vec<Identifier>, span: Span) -> StructStatement {
+    StructStatement { ident, fields, span }
+  }
+}
+
+impl fmt::Display for StructStatement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "struct {} {{", self.ident)?;
+    utils::write_object_list(&self.fields, f)?;
+    write!(f, "}}")
+  }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ReturnStatement {
   pub value: Expression,
+  pub span: Span,
 }
 
 impl ReturnStatement {
-  pub fn new(value: Expression) -> ReturnStatement {
-    ReturnStatement { value }
+  pub fn new(value: Expression, span: Span) -> ReturnStatement {
+    ReturnStatement { value, span }
   }
 }
 
@@ -66,13 +282,15 @@ impl fmt::Display for ReturnStatement {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExpressionStatement {
   pub value: Expression,
+  pub span: Span,
 }
 
 impl ExpressionStatement {
-  pub fn new(value: Expression) -> ExpressionStatement {
-    ExpressionStatement { value }
+  pub fn new(value: Expression, span: Span) -> ExpressionStatement {
+    ExpressionStatement { value, span }
   }
 }
 
@@ -83,13 +301,15 @@ impl fmt::Display for ExpressionStatement {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BlockStatement {
   pub statements: Vec<Statement>,
+  pub span: Span,
 }
 
 impl BlockStatement {
-  pub fn new(statements: Vec<Statement>) -> BlockStatement {
-    return BlockStatement { statements }
+  pub fn new(statements: Vec<Statement>, span: Span) -> BlockStatement {
+    BlockStatement { statements, span }
   }
 }
 
@@ -103,13 +323,56 @@ impl fmt::Display for BlockStatement {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WhileStatement {
+  pub condition: Expression,
+  pub body: BlockStatement,
+  pub span: Span,
+}
+
+impl WhileStatement {
+  pub fn new(condition: Expression, body: BlockStatement, span: Span) -> WhileStatement {
+    WhileStatement { condition, body, span }
+  }
+}
+
+impl fmt::Display for WhileStatement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "while({}) {}", self.condition, self.body)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ForStatement {
+  pub ident: Identifier,
+  pub iterable: Expression,
+  pub body: BlockStatement,
+  pub span: Span,
+}
+
+impl ForStatement {
+  pub fn new(ident: Identifier, iterable: Expression, body: BlockStatement, span: Span) -> ForStatement {
+    ForStatement { ident, iterable, body, span }
+  }
+}
+
+impl fmt::Display for ForStatement {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "for({} in {}) {}", self.ident, self.iterable, self.body)
+  }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CommentStatement {
   pub value: String,
+  pub span: Span,
 }
 
 impl CommentStatement {
-  pub fn new(value: String) -> CommentStatement {
-    CommentStatement { value }
+  pub fn new(value: String, span: Span) -> CommentStatement {
+    CommentStatement { value, span }
   }
 }
 
@@ -0,0 +1,310 @@
+use crate::span::Span;
+use super::Program;
+use super::stmt::{Statement, BlockStatement, Pattern};
+use super::expr::Expression;
+use super::ident::Identifier;
+use super::lit::{Literal, Func};
+
+/// Formats `program` as an indented tree with one node per line, followed by the span of the node.
+/// Children are indented by two spaces, and prefixed with their role in the parent when it has several kinds of children.
+pub fn to_tree_string(program: &Program) -> String {
+  let mut tree = Tree { out: String::new() };
+  tree.line(0, "", "Program", None);
+  for stmt in &program.statements {
+    tree.statement(1, "", stmt);
+  }
+  tree.out
+}
+
+struct Tree {
+  out: String,
+}
+
+impl Tree {
+  fn line(&mut self, depth: usize, label: &str, node: &str, span: Option<Span>) {
+    self.out.push_str(&"  ".repeat(depth));
+    if !label.is_empty() {
+      self.out.push_str(label);
+      self.out.push_str(": ");
+    }
+    self.out.push_str(node);
+    if let Some(span) = span {
+      self.out.push_str(&format!(" {}-{}", span.start, span.end));
+    }
+    self.out.push('\n');
+  }
+
+  fn statement(&mut self, depth: usize, label: &str, stmt: &Statement) {
+    match stmt {
+      Statement::Let(let_stmt) => {
+        self.line(depth, label, if let_stmt.constant { "Const" } else { "Let" }, Some(let_stmt.span));
+        self.pattern(depth + 1, "pattern", &let_stmt.pattern);
+        self.expression(depth + 1, "value", &let_stmt.value);
+      },
+      Statement::Func(func_stmt) => {
+        self.line(depth, label, "Fn", Some(func_stmt.span));
+        self.identifier(depth + 1, "name", &func_stmt.ident);
+        self.func(depth + 1, &func_stmt.func);
+      },
+      Statement::Struct(struct_stmt) => {
+        self.line(depth, label, "Struct", Some(struct_stmt.span));
+        self.identifier(depth + 1, "name", &struct_stmt.ident);
+        for field in &struct_stmt.fields {
+          self.identifier(depth + 1, "field", field);
+        }
+      },
+      Statement::Return(rtn) => {
+        self.line(depth, label, "Return", Some(rtn.span));
+        self.expression(depth + 1, "", &rtn.value);
+      },
+      Statement::Expr(expr) => {
+        self.line(depth, label, "ExpressionStatement", Some(expr.span));
+        self.expression(depth + 1, "", &expr.value);
+      },
+      Statement::Block(block) => self.block(depth, label, block),
+      Statement::While(while_stmt) => {
+        self.line(depth, label, "While", Some(while_stmt.span));
+        self.expression(depth + 1, "condition", &while_stmt.condition);
+        self.block(depth + 1, "body", &while_stmt.body);
+      },
+      Statement::For(for_stmt) => {
+        self.line(depth, label, "For", Some(for_stmt.span));
+        self.identifier(depth + 1, "ident", &for_stmt.ident);
+        self.expression(depth + 1, "iterable", &for_stmt.iterable);
+        self.block(depth + 1, "body", &for_stmt.body);
+      },
+      Statement::Comment(comment) => {
+        self.line(depth, label, &format!("Comment {:?}", comment.value), Some(comment.span));
+      },
+    }
+  }
+
+  fn block(&mut self, depth: usize, label: &str, block: &BlockStatement) {
+    self.line(depth, label, "Block", Some(block.span));
+    for stmt in &block.statements {
+      self.statement(depth + 1, "", stmt);
+    }
+  }
+
+  fn pattern(&mut self, depth: usize, label: &str, pattern: &Pattern) {
+    match pattern {
+      Pattern::Ident(ident) => self.identifier(depth, label, ident),
+      Pattern::Array(arr) => {
+        self.line(depth, label, "ArrayPattern", Some(arr.span));
+        for elm in &arr.elements {
+          self.pattern(depth + 1, "", elm);
+        }
+        if let Some(rest) = &arr.rest {
+          self.identifier(depth + 1, "rest", rest);
+        }
+      },
+      Pattern::Hash(hash) => {
+        self.line(depth, label, "HashPattern", Some(hash.span));
+        for (key, pattern) in &hash.pairs {
+          self.pattern(depth + 1, key.value.as_str(), pattern);
+        }
+      },
+      Pattern::Struct(pat) => {
+        self.line(depth, label, &format!("StructPattern {}", pat.name.value), Some(pat.span));
+        for (key, pattern) in &pat.fields {
+          self.pattern(depth + 1, key.value.as_str(), pattern);
+        }
+      },
+      Pattern::Literal(lit) => self.literal(depth, label, lit),
+    }
+  }
+
+  fn identifier(&mut self, depth: usize, label: &str, ident: &Identifier) {
+    self.line(depth, label, &format!("Identifier {}", ident.value), Some(ident.span));
+  }
+
+  fn expression(&mut self, depth: usize, label: &str, expr: &Expression) {
+    match expr {
+      Expression::Identifier(ident) => self.identifier(depth, label, ident),
+      Expression::Literal(lit) => self.literal(depth, label, lit),
+      Expression::Prefix(pre) => {
+        self.line(depth, label, &format!("Prefix {}", pre.operator), Some(pre.span));
+        self.expression(depth + 1, "", &pre.right);
+      },
+      Expression::Infix(inf) => {
+        self.line(depth, label, &format!("Infix {}", inf.operator), Some(inf.span));
+        self.expression(depth + 1, "left", &inf.left);
+        self.expression(depth + 1, "right", &inf.right);
+      },
+      Expression::If(if_expr) => {
+        self.line(depth, label, "If", Some(if_expr.span));
+        self.expression(depth + 1, "condition", &if_expr.condition);
+        self.block(depth + 1, "consequence", &if_expr.consequence);
+        if let Some(alt) = &if_expr.alternative {
+          self.block(depth + 1, "alternative", alt);
+        }
+      },
+      Expression::Try(try_expr) => {
+        self.line(depth, label, "Try", Some(try_expr.span));
+        self.block(depth + 1, "block", &try_expr.block);
+        self.identifier(depth + 1, "param", &try_expr.param);
+        self.block(depth + 1, "handler", &try_expr.handler);
+      },
+      Expression::Match(match_expr) => {
+        self.line(depth, label, "Match", Some(match_expr.span));
+        self.expression(depth + 1, "subject", &match_expr.subject);
+        for arm in &match_expr.arms {
+          self.line(depth + 1, "arm", "MatchArm", Some(arm.span));
+          self.pattern(depth + 2, "pattern", &arm.pattern);
+          self.expression(depth + 2, "body", &arm.body);
+        }
+      },
+      Expression::Call(call) => {
+        self.line(depth, label, "Call", Some(call.span));
+        self.expression(depth + 1, "func", &call.func);
+        for arg in &call.args {
+          self.expression(depth + 1, "arg", arg);
+        }
+      },
+      Expression::MethodCall(method) => {
+        self.line(depth, label, &format!("MethodCall {}", method.method.value), Some(method.span));
+        self.expression(depth + 1, "receiver", &method.receiver);
+        for arg in &method.args {
+          self.expression(depth + 1, "arg", arg);
+        }
+      },
+      Expression::Member(member) => {
+        self.line(depth, label, &format!("Member {}", member.property.value), Some(member.span));
+        self.expression(depth + 1, "object", &member.object);
+      },
+      Expression::Index(idx) => {
+        self.line(depth, label, "Index", Some(idx.span));
+        self.expression(depth + 1, "left", &idx.left);
+        self.expression(depth + 1, "index", &idx.index);
+      },
+      Expression::Slice(slice) => {
+        self.line(depth, label, "Slice", Some(slice.span));
+        self.expression(depth + 1, "left", &slice.left);
+        if let Some(start) = &slice.start {
+          self.expression(depth + 1, "start", start);
+        }
+        if let Some(end) = &slice.end {
+          self.expression(depth + 1, "end", end);
+        }
+      },
+      Expression::Assign(assign) => {
+        let operator = match &assign.operator {
+          Some(operator) => format!("{}=", operator),
+          None => "=".to_string(),
+        };
+        self.line(depth, label, &format!("Assign {}", operator), Some(assign.span));
+        self.expression(depth + 1, "target", &assign.target);
+        self.expression(depth + 1, "value", &assign.value);
+      },
+      Expression::Update(update) => {
+        let fixity = if update.prefix { "prefix" } else { "postfix" };
+        self.line(depth, label, &format!("Update {} ({})", update.operator, fixity), Some(update.span));
+        self.expression(depth + 1, "", &update.target);
+      },
+      Expression::Ternary(ternary) => {
+        self.line(depth, label, "Ternary", Some(ternary.span));
+        self.expression(depth + 1, "condition", &ternary.condition);
+        self.expression(depth + 1, "consequence", &ternary.consequence);
+        self.expression(depth + 1, "alternative", &ternary.alternative);
+      },
+      Expression::Range(range) => {
+        let operator = if range.inclusive { "..=" } else { ".." };
+        self.line(depth, label, &format!("Range {}", operator), Some(range.span));
+        self.expression(depth + 1, "start", &range.start);
+        self.expression(depth + 1, "end", &range.end);
+      },
+      Expression::Spread(spread) => {
+        self.line(depth, label, "Spread", Some(spread.span));
+        self.expression(depth + 1, "", &spread.value);
+      },
+    }
+  }
+
+  fn literal(&mut self, depth: usize, label: &str, lit: &Literal) {
+    match lit {
+      Literal::Integer(int) => self.line(depth, label, &format!("Integer {}", int.value), Some(int.span)),
+      Literal::Float(float) => self.line(depth, label, &format!("Float {:?}", float.value), Some(float.span)),
+      Literal::Boolean(val) => self.line(depth, label, &format!("Boolean {}", val.value), Some(val.span)),
+      Literal::Null(null) => self.line(depth, label, "Null", Some(null.span)),
+      Literal::Str(s) => self.line(depth, label, &format!("String {:?}", s.value), Some(s.span)),
+      Literal::Array(arr) => {
+        self.line(depth, label, "Array", Some(arr.span));
+        for elm in &arr.elements {
+          self.expression(depth + 1, "", elm);
+        }
+      },
+      Literal::Hash(hash) => {
+        self.line(depth, label, "Hash", Some(hash.span));
+        for (key, val) in &hash.pairs {
+          self.expression(depth + 1, "key", key);
+          self.expression(depth + 1, "value", val);
+        }
+      },
+      Literal::Struct(lit) => {
+        self.line(depth, label, &format!("Record {}", lit.name.value), Some(lit.span));
+        for (key, val) in &lit.fields {
+          self.expression(depth + 1, key.value.as_str(), val);
+        }
+      },
+      Literal::Func(func) => {
+        self.line(depth, label, "Function", Some(func.span));
+        self.func(depth + 1, func);
+      },
+      Literal::Macro(mac) => {
+        self.line(depth, label, "Macro", Some(mac.span));
+        for arg in &mac.args {
+          self.identifier(depth + 1, "param", arg);
+        }
+        self.block(depth + 1, "body", &mac.body);
+      },
+    }
+  }
+
+  fn func(&mut self, depth: usize, func: &Func) {
+    for (arg, default) in func.args.iter().zip(&func.defaults) {
+      self.identifier(depth, "param", arg);
+      if let Some(default) = default {
+        self.expression(depth + 1, "default", default);
+      }
+    }
+    self.block(depth, "body", &func.body);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  #[test]
+  fn test_to_tree_string() {
+    let input = "let add = fn(x, y = 1) { x + y };
+add(2)[0:] ?? [\"a\"];";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+
+    assert_eq!(program.to_tree_string(), "Program
+  Let 1:1-1:34
+    pattern: Identifier add 1:5-1:8
+    value: Function 1:11-1:33
+      param: Identifier x 1:14-1:15
+      param: Identifier y 1:17-1:18
+        default: Integer 1 1:21-1:22
+      body: Block 1:24-1:33
+        ExpressionStatement 1:26-1:31
+          Infix + 1:26-1:31
+            left: Identifier x 1:26-1:27
+            right: Identifier y 1:30-1:31
+  ExpressionStatement 2:1-2:21
+    Infix ?? 2:1-2:20
+      left: Slice 2:1-2:11
+        left: Call 2:1-2:7
+          func: Identifier add 2:1-2:4
+          arg: Integer 2 2:5-2:6
+        start: Integer 0 2:8-2:9
+      right: Array 2:15-2:20
+        String \"a\" 2:16-2:19
+");
+  }
+}
@@ -0,0 +1,474 @@
+use super::Program;
+use super::stmt::{Statement, BlockStatement, Pattern};
+use super::expr::Expression;
+use super::ident::Identifier;
+use super::lit::{Literal, Func};
+
+/// Walks a syntax tree by reference. Each `visit_*` method visits the children of its node with the matching
+/// `walk_*` function by default, so an analysis only overrides the methods for the nodes it looks at,
+/// and calls `walk_*` from them to keep going into the children.
+///
+/// The nodes are borrowed for `'a`, so a visitor can keep references to them.
+/// Every identifier is passed to `visit_identifier`, both where it is bound, e.g. by `let` or a parameter,
+/// and where it is used. The names after a dot, as in `x.len()` or `x.name`,
+/// and the field names of structs are not variables, so they are not visited.
+pub trait Visitor<'a> {
+  fn visit_program(&mut self, program: &'a Program) {
+    walk_program(self, program);
+  }
+
+  fn visit_statement(&mut self, stmt: &'a Statement) {
+    walk_statement(self, stmt);
+  }
+
+  fn visit_block(&mut self, block: &'a BlockStatement) {
+    walk_block(self, block);
+  }
+
+  fn visit_pattern(&mut self, pattern: &'a Pattern) {
+    walk_pattern(self, pattern);
+  }
+
+  fn visit_expression(&mut self, expr: &'a Expression) {
+    walk_expression(self, expr);
+  }
+
+  fn visit_literal(&mut self, lit: &'a Literal) {
+    walk_literal(self, lit);
+  }
+
+  fn visit_identifier(&mut self, _ident: &'a Identifier) {}
+}
+
+pub fn walk_program<'a, V: Visitor<'a> + ?Sized>(v: &mut V, program: &'a Program) {
+  for stmt in &program.statements {
+    v.visit_statement(stmt);
+  }
+}
+
+pub fn walk_statement<'a, V: Visitor<'a> + ?Sized>(v: &mut V, stmt: &'a Statement) {
+  match stmt {
+    Statement::Let(let_stmt) => {
+      v.visit_pattern(&let_stmt.pattern);
+      v.visit_expression(&let_stmt.value);
+    },
+    Statement::Func(func_stmt) => {
+      v.visit_identifier(&func_stmt.ident);
+      walk_func(v, &func_stmt.func);
+    },
+    // フィールドは名前ではないので辿らない
+    Statement::Struct(struct_stmt) => v.visit_identifier(&struct_stmt.ident),
+    Statement::Return(rtn) => v.visit_expression(&rtn.value),
+    Statement::Expr(expr) => v.visit_expression(&expr.value),
+    Statement::Block(block) => v.visit_block(block),
+    Statement::While(while_stmt) => {
+      v.visit_expression(&while_stmt.condition);
+      v.visit_block(&while_stmt.body);
+    },
+    Statement::For(for_stmt) => {
+      v.visit_identifier(&for_stmt.ident);
+      v.visit_expression(&for_stmt.iterable);
+      v.visit_block(&for_stmt.body);
+    },
+    Statement::Comment(_) => (),
+  }
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(v: &mut V, block: &'a BlockStatement) {
+  for stmt in &block.statements {
+    v.visit_statement(stmt);
+  }
+}
+
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(v: &mut V, pattern: &'a Pattern) {
+  match pattern {
+    Pattern::Ident(ident) => v.visit_identifier(ident),
+    Pattern::Array(arr) => {
+      for elm in &arr.elements {
+        v.visit_pattern(elm);
+      }
+      if let Some(rest) = &arr.rest {
+        v.visit_identifier(rest);
+      }
+    },
+    // キーは名前ではなく文字列なので辿らない
+    Pattern::Hash(hash) => {
+      for (_, pattern) in &hash.pairs {
+        v.visit_pattern(pattern);
+      }
+    },
+    Pattern::Struct(pat) => {
+      v.visit_identifier(&pat.name);
+      for (_, pattern) in &pat.fields {
+        v.visit_pattern(pattern);
+      }
+    },
+    Pattern::Literal(lit) => v.visit_literal(lit),
+  }
+}
+
+pub fn walk_expression<'a, V: Visitor<'a> + ?Sized>(v: &mut V, expr: &'a Expression) {
+  match expr {
+    Expression::Identifier(ident) => v.visit_identifier(ident),
+    Expression::Literal(lit) => v.visit_literal(lit),
+    Expression::Prefix(pre) => v.visit_expression(&pre.right),
+    Expression::Infix(inf) => {
+      v.visit_expression(&inf.left);
+      v.visit_expression(&inf.right);
+    },
+    Expression::If(if_expr) => {
+      v.visit_expression(&if_expr.condition);
+      v.visit_block(&if_expr.consequence);
+      if let Some(alt) = &if_expr.alternative {
+        v.visit_block(alt);
+      }
+    },
+    Expression::Try(try_expr) => {
+      v.visit_block(&try_expr.block);
+      v.visit_identifier(&try_expr.param);
+      v.visit_block(&try_expr.handler);
+    },
+    Expression::Match(match_expr) => {
+      v.visit_expression(&match_expr.subject);
+      for arm in &match_expr.arms {
+        v.visit_pattern(&arm.pattern);
+        v.visit_expression(&arm.body);
+      }
+    },
+    Expression::Call(call) => {
+      v.visit_expression(&call.func);
+      for arg in &call.args {
+        v.visit_expression(arg);
+      }
+    },
+    Expression::MethodCall(method) => {
+      v.visit_expression(&method.receiver);
+      for arg in &method.args {
+        v.visit_expression(arg);
+      }
+    },
+    Expression::Member(member) => v.visit_expression(&member.object),
+    Expression::Index(idx) => {
+      v.visit_expression(&idx.left);
+      v.visit_expression(&idx.index);
+    },
+    Expression::Slice(slice) => {
+      v.visit_expression(&slice.left);
+      if let Some(start) = &slice.start {
+        v.visit_expression(start);
+      }
+      if let Some(end) = &slice.end {
+        v.visit_expression(end);
+      }
+    },
+    Expression::Assign(assign) => {
+      v.visit_expression(&assign.target);
+      v.visit_expression(&assign.value);
+    },
+    Expression::Update(update) => v.visit_expression(&update.target),
+    Expression::Ternary(ternary) => {
+      v.visit_expression(&ternary.condition);
+      v.visit_expression(&ternary.consequence);
+      v.visit_expression(&ternary.alternative);
+    },
+    Expression::Range(range) => {
+      v.visit_expression(&range.start);
+      v.visit_expression(&range.end);
+    },
+    Expression::Spread(spread) => v.visit_expression(&spread.value),
+  }
+}
+
+pub fn walk_literal<'a, V: Visitor<'a> + ?Sized>(v: &mut V, lit: &'a Literal) {
+  match lit {
+    Literal::Integer(_) | Literal::Float(_) | Literal::Boolean(_) | Literal::Null(_) | Literal::Str(_) => (),
+    Literal::Array(arr) => {
+      for elm in &arr.elements {
+        v.visit_expression(elm);
+      }
+    },
+    Literal::Hash(hash) => {
+      for (key, val) in &hash.pairs {
+        v.visit_expression(key);
+        v.visit_expression(val);
+      }
+    },
+    Literal::Struct(lit) => {
+      v.visit_identifier(&lit.name);
+      for (_, val) in &lit.fields {
+        v.visit_expression(val);
+      }
+    },
+    Literal::Func(func) => walk_func(v, func),
+    Literal::Macro(mac) => {
+      for arg in &mac.args {
+        v.visit_identifier(arg);
+      }
+      v.visit_block(&mac.body);
+    },
+  }
+}
+
+// 関数のリテラルと fn 文の関数
+fn walk_func<'a, V: Visitor<'a> + ?Sized>(v: &mut V, func: &'a Func) {
+  for (arg, default) in func.args.iter().zip(&func.defaults) {
+    v.visit_identifier(arg);
+    if let Some(default) = default {
+      v.visit_expression(default);
+    }
+  }
+  v.visit_block(&func.body);
+}
+
+/// Walks a syntax tree like `Visitor`, but by mutable reference so that the nodes can be changed in place.
+/// The methods and the `walk_*_mut` functions are named after the ones of `Visitor` with a `_mut` suffix.
+pub trait VisitorMut {
+  fn visit_program_mut(&mut self, program: &mut Program) {
+    walk_program_mut(self, program);
+  }
+
+  fn visit_statement_mut(&mut self, stmt: &mut Statement) {
+    walk_statement_mut(self, stmt);
+  }
+
+  fn visit_block_mut(&mut self, block: &mut BlockStatement) {
+    walk_block_mut(self, block);
+  }
+
+  fn visit_pattern_mut(&mut self, pattern: &mut Pattern) {
+    walk_pattern_mut(self, pattern);
+  }
+
+  fn visit_expression_mut(&mut self, expr: &mut Expression) {
+    walk_expression_mut(self, expr);
+  }
+
+  fn visit_literal_mut(&mut self, lit: &mut Literal) {
+    walk_literal_mut(self, lit);
+  }
+
+  fn visit_identifier_mut(&mut self, _ident: &mut Identifier) {}
+}
+
+pub fn walk_program_mut<V: VisitorMut + ?Sized>(v: &mut V, program: &mut Program) {
+  for stmt in &mut program.statements {
+    v.visit_statement_mut(stmt);
+  }
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Statement) {
+  match stmt {
+    Statement::Let(let_stmt) => {
+      v.visit_pattern_mut(&mut let_stmt.pattern);
+      v.visit_expression_mut(&mut let_stmt.value);
+    },
+    Statement::Func(func_stmt) => {
+      v.visit_identifier_mut(&mut func_stmt.ident);
+      walk_func_mut(v, &mut func_stmt.func);
+    },
+    Statement::Struct(struct_stmt) => v.visit_identifier_mut(&mut struct_stmt.ident),
+    Statement::Return(rtn) => v.visit_expression_mut(&mut rtn.value),
+    Statement::Expr(expr) => v.visit_expression_mut(&mut expr.value),
+    Statement::Block(block) => v.visit_block_mut(block),
+    Statement::While(while_stmt) => {
+      v.visit_expression_mut(&mut while_stmt.condition);
+      v.visit_block_mut(&mut while_stmt.body);
+    },
+    Statement::For(for_stmt) => {
+      v.visit_identifier_mut(&mut for_stmt.ident);
+      v.visit_expression_mut(&mut for_stmt.iterable);
+      v.visit_block_mut(&mut for_stmt.body);
+    },
+    Statement::Comment(_) => (),
+  }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut BlockStatement) {
+  for stmt in &mut block.statements {
+    v.visit_statement_mut(stmt);
+  }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(v: &mut V, pattern: &mut Pattern) {
+  match pattern {
+    Pattern::Ident(ident) => v.visit_identifier_mut(ident),
+    Pattern::Array(arr) => {
+      for elm in &mut arr.elements {
+        v.visit_pattern_mut(elm);
+      }
+      if let Some(rest) = &mut arr.rest {
+        v.visit_identifier_mut(rest);
+      }
+    },
+    Pattern::Hash(hash) => {
+      for (_, pattern) in &mut hash.pairs {
+        v.visit_pattern_mut(pattern);
+      }
+    },
+    Pattern::Struct(pat) => {
+      v.visit_identifier_mut(&mut pat.name);
+      for (_, pattern) in &mut pat.fields {
+        v.visit_pattern_mut(pattern);
+      }
+    },
+    Pattern::Literal(lit) => v.visit_literal_mut(lit),
+  }
+}
+
+pub fn walk_expression_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expression) {
+  match expr {
+    Expression::Identifier(ident) => v.visit_identifier_mut(ident),
+    Expression::Literal(lit) => v.visit_literal_mut(lit),
+    Expression::Prefix(pre) => v.visit_expression_mut(&mut pre.right),
+    Expression::Infix(inf) => {
+      v.visit_expression_mut(&mut inf.left);
+      v.visit_expression_mut(&mut inf.right);
+    },
+    Expression::If(if_expr) => {
+      v.visit_expression_mut(&mut if_expr.condition);
+      v.visit_block_mut(&mut if_expr.consequence);
+      if let Some(alt) = &mut if_expr.alternative {
+        v.visit_block_mut(alt);
+      }
+    },
+    Expression::Try(try_expr) => {
+      v.visit_block_mut(&mut try_expr.block);
+      v.visit_identifier_mut(&mut try_expr.param);
+      v.visit_block_mut(&mut try_expr.handler);
+    },
+    Expression::Match(match_expr) => {
+      v.visit_expression_mut(&mut match_expr.subject);
+      for arm in &mut match_expr.arms {
+        v.visit_pattern_mut(&mut arm.pattern);
+        v.visit_expression_mut(&mut arm.body);
+      }
+    },
+    Expression::Call(call) => {
+      v.visit_expression_mut(&mut call.func);
+      for arg in &mut call.args {
+        v.visit_expression_mut(arg);
+      }
+    },
+    Expression::MethodCall(method) => {
+      v.visit_expression_mut(&mut method.receiver);
+      for arg in &mut method.args {
+        v.visit_expression_mut(arg);
+      }
+    },
+    Expression::Member(member) => v.visit_expression_mut(&mut member.object),
+    Expression::Index(idx) => {
+      v.visit_expression_mut(&mut idx.left);
+      v.visit_expression_mut(&mut idx.index);
+    },
+    Expression::Slice(slice) => {
+      v.visit_expression_mut(&mut slice.left);
+      if let Some(start) = &mut slice.start {
+        v.visit_expression_mut(start);
+      }
+      if let Some(end) = &mut slice.end {
+        v.visit_expression_mut(end);
+      }
+    },
+    Expression::Assign(assign) => {
+      v.visit_expression_mut(&mut assign.target);
+      v.visit_expression_mut(&mut assign.value);
+    },
+    Expression::Update(update) => v.visit_expression_mut(&mut update.target),
+    Expression::Ternary(ternary) => {
+      v.visit_expression_mut(&mut ternary.condition);
+      v.visit_expression_mut(&mut ternary.consequence);
+      v.visit_expression_mut(&mut ternary.alternative);
+    },
+    Expression::Range(range) => {
+      v.visit_expression_mut(&mut range.start);
+      v.visit_expression_mut(&mut range.end);
+    },
+    Expression::Spread(spread) => v.visit_expression_mut(&mut spread.value),
+  }
+}
+
+pub fn walk_literal_mut<V: VisitorMut + ?Sized>(v: &mut V, lit: &mut Literal) {
+  match lit {
+    Literal::Integer(_) | Literal::Float(_) | Literal::Boolean(_) | Literal::Null(_) | Literal::Str(_) => (),
+    Literal::Array(arr) => {
+      for elm in &mut arr.elements {
+        v.visit_expression_mut(elm);
+      }
+    },
+    Literal::Hash(hash) => {
+      for (key, val) in &mut hash.pairs {
+        v.visit_expression_mut(key);
+        v.visit_expression_mut(val);
+      }
+    },
+    Literal::Struct(lit) => {
+      v.visit_identifier_mut(&mut lit.name);
+      for (_, val) in &mut lit.fields {
+        v.visit_expression_mut(val);
+      }
+    },
+    Literal::Func(func) => walk_func_mut(v, func),
+    Literal::Macro(mac) => {
+      for arg in &mut mac.args {
+        v.visit_identifier_mut(arg);
+      }
+      v.visit_block_mut(&mut mac.body);
+    },
+  }
+}
+
+fn walk_func_mut<V: VisitorMut + ?Sized>(v: &mut V, func: &mut Func) {
+  for (arg, default) in func.args.iter_mut().zip(&mut func.defaults) {
+    v.visit_identifier_mut(arg);
+    if let Some(default) = default {
+      v.visit_expression_mut(default);
+    }
+  }
+  v.visit_block_mut(&mut func.body);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::intern::Name;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    program
+  }
+
+  #[test]
+  fn test_visitor() {
+    // 識別子を出てきた順に集める
+    struct Names<'a> {
+      names: Vec<&'a str>,
+    }
+
+    impl<'a> Visitor<'a> for Names<'a> {
+      fn visit_identifier(&mut self, ident: &'a Identifier) {
+        self.names.push(ident.value.as_str());
+      }
+    }
+
+    // 識別子の名前を大文字にする
+    struct Upper;
+
+    impl VisitorMut for Upper {
+      fn visit_identifier_mut(&mut self, ident: &mut Identifier) {
+        ident.value = Name::new(&ident.value.as_str().to_uppercase());
+      }
+    }
+
+    let mut program = parse("let [a, {b}] = f(1); for (x in a) { try { fn(y = b) { x + y }[0] } catch (e) { e } }");
+    let mut names = Names { names: vec![] };
+    names.visit_program(&program);
+    assert_eq!(names.names, vec!["a", "b", "f", "x", "a", "y", "b", "x", "y", "e", "e"]);
+
+    Upper.visit_program_mut(&mut program);
+    assert_eq!(program.to_string(), "let [A, {b: B}] = F(1);for(X in A) { try { (fn(Y = B) { (X + Y) }[0]) } catch (E) { E } }");
+  }
+}
@@ -0,0 +1,95 @@
+// 字句解析、構文解析、評価の時間を代表的なプログラムで測る。
+// cargo bench -p interpreter -- fib/eval のように、名前にフィルタを含むものだけを測ることもできる
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use interpreter::{evaluator, lexer, parser};
+use interpreter::ast::Program;
+use evaluator::builtins;
+use evaluator::environment::Environment;
+use evaluator::object::Object;
+
+// 再帰呼び出しと環境の参照
+const FIB: &str = "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } };
+fib(20)";
+
+// 文字列の連結と比較
+const STRINGS: &str = "let s = \"\";
+let words = [\"monkey\", \"lexer\", \"parser\", \"evaluator\"];
+for (i in 0..500) {
+  let word = words[i % len(words)];
+  if (word != \"parser\") {
+    s = s + word + \" \";
+  }
+}
+len(s)";
+
+// 配列の生成、添字アクセス、畳み込み
+const ARRAYS: &str = "let transform = fn(arr, f) {
+  let out = [];
+  for (x in arr) { out = push(out, f(x)) }
+  out
+};
+let sum = fn(arr) {
+  let total = 0;
+  let i = 0;
+  while (i < len(arr)) { total += arr[i]; i += 1 }
+  total
+};
+let xs = transform(0..300, fn(x) { x * x });
+sum(transform(xs, fn(x) { x % 7 }))";
+
+const PROGRAMS: &[(&str, &str)] = &[("fib", FIB), ("strings", STRINGS), ("arrays", ARRAYS)];
+
+// 字句解析と構文解析は短いので、ソースを繰り返して測る
+const REPEAT: usize = 50;
+
+/// How long each benchmark is run for after it has been warmed up.
+const MEASURE: Duration = Duration::from_secs(1);
+
+fn main() {
+  // cargo bench は --bench を渡すので、フラグではない最初の引数をフィルタにする
+  let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--")).unwrap_or_default();
+
+  for (name, source) in PROGRAMS {
+    let repeated = vec![*source; REPEAT].join(";\n");
+    let program = parse(source);
+    let result = evaluator::eval(program.clone(), &Environment::new(builtins::new_builtins()));
+    assert!(!matches!(result, Object::Error(_)), "{}: {}", name, result);
+
+    bench(&filter, &format!("{}/lex", name), || lexer::Lexer::new(repeated.clone()).count());
+    bench(&filter, &format!("{}/parse", name), || parse(&repeated).statements.len());
+    bench(&filter, &format!("{}/eval", name), || {
+      let env = Environment::new(builtins::new_builtins());
+      evaluator::eval(program.clone(), &env)
+    });
+  }
+}
+
+fn parse(source: &str) -> Program {
+  let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+  let program = p.parse_program();
+  assert!(p.errors.is_empty(), "{:?}", p.errors);
+  program
+}
+
+// 1 回あたりの平均時間を表示する
+fn bench<T>(filter: &str, name: &str, mut f: impl FnMut() -> T) {
+  if !name.contains(filter) {
+    return;
+  }
+  let warm_up = Instant::now();
+  while warm_up.elapsed() < MEASURE / 10 {
+    black_box(f());
+  }
+
+  let mut iterations = 0u32;
+  let start = Instant::now();
+  while start.elapsed() < MEASURE {
+    black_box(f());
+    iterations += 1;
+  }
+  let per_iteration = start.elapsed() / iterations;
+  println!("{:<16} {:>12?}/iter ({} iterations)", name, per_iteration, iterations);
+}
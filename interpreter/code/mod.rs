@@ -0,0 +1,285 @@
+use std::fmt;
+
+/// An instruction for the VM. Operands follow the opcode byte in big endian,
+/// with the widths given by `Opcode::operand_widths`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[repr(u8)]
+pub enum Opcode {
+  Constant,
+  Pop,
+  True,
+  False,
+  Null,
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Mod,
+  Pow,
+  BitAnd,
+  BitOr,
+  BitXor,
+  ShiftLeft,
+  ShiftRight,
+  Equal,
+  NotEqual,
+  GreaterThan,
+  LessThan,
+  GreaterEqual,
+  LessEqual,
+  Minus,
+  Bang,
+  BitNot,
+  Jump,
+  JumpNotTruthy,
+  JumpTruthy,
+  JumpNotNull,
+  GetName,
+  DefineName,
+  DefineConstName,
+  AssignName,
+  GetLocal,
+  DefineLocal,
+  AssignLocal,
+  ResetLocal,
+  GetFree,
+  AssignFree,
+  Array,
+  Hash,
+  Index,
+  Slice,
+  Range,
+  Call,
+  CallMethod,
+  ReturnValue,
+  Closure,
+}
+
+const OPCODES: [Opcode; 48] = [
+  Opcode::Constant,
+  Opcode::Pop,
+  Opcode::True,
+  Opcode::False,
+  Opcode::Null,
+  Opcode::Add,
+  Opcode::Sub,
+  Opcode::Mul,
+  Opcode::Div,
+  Opcode::Mod,
+  Opcode::Pow,
+  Opcode::BitAnd,
+  Opcode::BitOr,
+  Opcode::BitXor,
+  Opcode::ShiftLeft,
+  Opcode::ShiftRight,
+  Opcode::Equal,
+  Opcode::NotEqual,
+  Opcode::GreaterThan,
+  Opcode::LessThan,
+  Opcode::GreaterEqual,
+  Opcode::LessEqual,
+  Opcode::Minus,
+  Opcode::Bang,
+  Opcode::BitNot,
+  Opcode::Jump,
+  Opcode::JumpNotTruthy,
+  Opcode::JumpTruthy,
+  Opcode::JumpNotNull,
+  Opcode::GetName,
+  Opcode::DefineName,
+  Opcode::DefineConstName,
+  Opcode::AssignName,
+  Opcode::GetLocal,
+  Opcode::DefineLocal,
+  Opcode::AssignLocal,
+  Opcode::ResetLocal,
+  Opcode::GetFree,
+  Opcode::AssignFree,
+  Opcode::Array,
+  Opcode::Hash,
+  Opcode::Index,
+  Opcode::Slice,
+  Opcode::Range,
+  Opcode::Call,
+  Opcode::CallMethod,
+  Opcode::ReturnValue,
+  Opcode::Closure,
+];
+
+impl Opcode {
+  pub fn from_byte(byte: u8) -> Option<Opcode> {
+    OPCODES.get(byte as usize).copied()
+  }
+
+  /// Returns the number of bytes of each operand.
+  pub fn operand_widths(&self) -> &'static [usize] {
+    match self {
+      Opcode::Constant
+      | Opcode::Jump
+      | Opcode::JumpNotTruthy
+      | Opcode::JumpTruthy
+      | Opcode::JumpNotNull
+      | Opcode::GetName
+      | Opcode::DefineName
+      | Opcode::DefineConstName
+      | Opcode::AssignName
+      | Opcode::GetLocal
+      | Opcode::DefineLocal
+      | Opcode::AssignLocal
+      | Opcode::ResetLocal
+      | Opcode::GetFree
+      | Opcode::AssignFree
+      | Opcode::Array
+      | Opcode::Hash
+      | Opcode::Closure => &[2],
+      Opcode::Range
+      | Opcode::Call => &[1],
+      Opcode::CallMethod => &[2, 1],
+      _ => &[],
+    }
+  }
+}
+
+impl fmt::Display for Opcode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Op{:?}", self)
+  }
+}
+
+/// Encodes `op` and its operands into bytes.
+pub fn make(op: Opcode, operands: &[usize]) -> Vec<u8> {
+  let mut instruction = vec![op as u8];
+  for (width, operand) in op.operand_widths().iter().zip(operands) {
+    match width {
+      2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+      1 => instruction.push(*operand as u8),
+      _ => unreachable!(),
+    }
+  }
+  instruction
+}
+
+/// Decodes the operands of `op` at the start of `ins`, returning them with the number of bytes read.
+pub fn read_operands(op: Opcode, ins: &[u8]) -> (Vec<usize>, usize) {
+  let mut operands = vec![];
+  let mut offset = 0;
+  for width in op.operand_widths() {
+    match width {
+      2 => operands.push(read_u16(&ins[offset..]) as usize),
+      1 => operands.push(ins[offset] as usize),
+      _ => unreachable!(),
+    }
+    offset += width;
+  }
+  (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+  u16::from_be_bytes([ins[0], ins[1]])
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Instructions(pub Vec<u8>);
+
+impl Instructions {
+  pub fn new() -> Instructions {
+    Instructions(vec![])
+  }
+
+  pub fn len(&self) -> usize {
+    self.0.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// Disassembles the instructions, one per line with its offset.
+impl fmt::Display for Instructions {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut i = 0;
+    while i < self.0.len() {
+      let op = match Opcode::from_byte(self.0[i]) {
+        Some(op) => op,
+        None => {
+          writeln!(f, "{:04} ERROR: undefined opcode {}", i, self.0[i])?;
+          i += 1;
+          continue;
+        },
+      };
+
+      let (operands, read) = read_operands(op, &self.0[i + 1..]);
+      write!(f, "{:04} {}", i, op)?;
+      for operand in operands {
+        write!(f, " {}", operand)?;
+      }
+      writeln!(f)?;
+
+      i += 1 + read;
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_make() {
+    let tests = vec![
+      (Opcode::Constant, vec![65534], vec![Opcode::Constant as u8, 255, 254]),
+      (Opcode::Call, vec![255], vec![Opcode::Call as u8, 255]),
+      (Opcode::CallMethod, vec![65534, 3], vec![Opcode::CallMethod as u8, 255, 254, 3]),
+      (Opcode::Add, vec![], vec![Opcode::Add as u8]),
+    ];
+
+    for (op, operands, expected) in tests.into_iter() {
+      assert_eq!(make(op, &operands), expected);
+    }
+  }
+
+  #[test]
+  fn test_read_operands() {
+    let tests = vec![
+      (Opcode::Constant, vec![65535], 2),
+      (Opcode::Range, vec![1], 1),
+      (Opcode::Pop, vec![], 0),
+    ];
+
+    for (op, operands, bytes_read) in tests.into_iter() {
+      let instruction = make(op, &operands);
+      assert_eq!(Opcode::from_byte(instruction[0]), Some(op));
+
+      let (read, n) = read_operands(op, &instruction[1..]);
+      assert_eq!(n, bytes_read);
+      assert_eq!(read, operands);
+    }
+  }
+
+  #[test]
+  fn test_instructions_string() {
+    let instructions = Instructions([
+      make(Opcode::Add, &[]),
+      make(Opcode::Constant, &[2]),
+      make(Opcode::Constant, &[65535]),
+      make(Opcode::Call, &[1]),
+    ].concat());
+
+    let expected = "0000 OpAdd
+0001 OpConstant 2
+0004 OpConstant 65535
+0007 OpCall 1
+";
+    assert_eq!(format!("{}", instructions), expected);
+  }
+
+  #[test]
+  fn test_opcode_from_byte() {
+    for (i, op) in OPCODES.iter().enumerate() {
+      assert_eq!(*op as usize, i);
+    }
+    assert_eq!(Opcode::from_byte(OPCODES.len() as u8), None);
+  }
+}
@@ -0,0 +1,45 @@
+use std::fmt;
+
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum CompileError {
+  /// The VM can not run the syntax yet, although the evaluator can.
+  Unsupported {
+    node: String,
+    span: Span,
+  },
+  /// A local bound with `const` is assigned to.
+  ConstantAssignment {
+    name: String,
+    span: Span,
+  },
+  /// The program needs more constants, names or instructions than an operand can address.
+  TooLarge {
+    span: Span,
+  },
+}
+
+impl CompileError {
+  /// Returns the span of the node which could not be compiled.
+  pub fn span(&self) -> Span {
+    match self {
+      CompileError::Unsupported { span, .. }
+      | CompileError::ConstantAssignment { span, .. }
+      | CompileError::TooLarge { span } => *span,
+    }
+  }
+}
+
+impl fmt::Display for CompileError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CompileError::Unsupported { node, .. } =>
+        write!(f, "{} is not supported by the compiler yet.", node),
+      CompileError::ConstantAssignment { name, .. } =>
+        write!(f, "assignment to constant: {}.", name),
+      CompileError::TooLarge { .. } =>
+        write!(f, "program is too large to compile."),
+    }
+  }
+}
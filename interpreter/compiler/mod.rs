@@ -0,0 +1,782 @@
+use std::rc::Rc;
+use std::collections::HashMap;
+
+use crate::ast::Program;
+use crate::ast::stmt::{Statement, BlockStatement, Pattern};
+use crate::ast::expr::Expression;
+use crate::ast::lit::{self, Literal};
+use crate::ast::ident::Identifier;
+use crate::ast::visit::{self, Visitor};
+use crate::ast::operator::{Prefix, Infix};
+use crate::code::{self, Opcode, Instructions};
+use crate::evaluator::macro_expansion;
+use crate::evaluator::object::{self, Object, CompiledFunction};
+use crate::intern::Name;
+use crate::span::Span;
+
+pub mod error;
+pub mod symbol_table;
+
+use error::CompileError;
+use symbol_table::{SymbolTable, SymbolScope, Symbol};
+
+/// The compiled program which `vm::run` executes.
+#[derive(Debug)]
+pub struct Bytecode {
+  pub instructions: Instructions,
+  pub constants: Vec<Object>,
+  pub names: Vec<Name>,
+  pub functions: Vec<Rc<CompiledFunction>>,
+  /// The names of the local slots of the program, which are bound with `let` in the blocks at the top level.
+  pub locals: Vec<Name>,
+}
+
+/// Lowers `program` into bytecode.
+/// Syntax which the VM does not support yet is reported as `CompileError::Unsupported`.
+pub fn compile(program: &Program) -> Result<Bytecode, CompileError> {
+  let mut compiler = Compiler::new();
+  compiler.compile_statements(&program.statements)?;
+
+  let scope = compiler.scopes.pop().unwrap();
+  Ok(Bytecode {
+    instructions: scope.instructions,
+    constants: scope.constants,
+    names: scope.names,
+    functions: scope.functions,
+    locals: compiler.symbols.locals,
+  })
+}
+
+// 飛び先が決まるまで仮に入れておくオペランド
+const PLACEHOLDER: usize = 9999;
+
+/// The program or a function literal being compiled. Each has its own pools
+/// so that compiled functions do not depend on the program which defined them.
+#[derive(Default)]
+struct Scope {
+  instructions: Instructions,
+  constants: Vec<Object>,
+  names: Vec<Name>,
+  name_indexes: HashMap<Name, usize>,
+  functions: Vec<Rc<CompiledFunction>>,
+}
+
+struct Compiler {
+  // 末尾がコンパイル中のスコープ
+  scopes: Vec<Scope>,
+  symbols: SymbolTable,
+}
+
+impl Compiler {
+  fn new() -> Compiler {
+    Compiler { scopes: vec![Scope::default()], symbols: SymbolTable::new() }
+  }
+
+  fn scope(&mut self) -> &mut Scope {
+    self.scopes.last_mut().unwrap()
+  }
+
+  fn instructions(&mut self) -> &mut Instructions {
+    &mut self.scope().instructions
+  }
+
+  fn emit(&mut self, op: Opcode, operands: &[usize]) -> usize {
+    let ins = code::make(op, operands);
+    let instructions = self.instructions();
+    let pos = instructions.len();
+    instructions.0.extend(ins);
+    pos
+  }
+
+  // 2 バイトのオペランドに収まらないものはコンパイルできない
+  fn emit_u16(&mut self, op: Opcode, operand: usize, span: Span) -> Result<usize, CompileError> {
+    if operand > u16::MAX as usize {
+      return Err(CompileError::TooLarge { span });
+    }
+    Ok(self.emit(op, &[operand]))
+  }
+
+  // pos にあるジャンプ命令の飛び先を現在の位置に書き換える
+  fn patch_jump(&mut self, pos: usize, span: Span) -> Result<(), CompileError> {
+    let target = self.instructions().len();
+    if target > u16::MAX as usize {
+      return Err(CompileError::TooLarge { span });
+    }
+    let operand = (target as u16).to_be_bytes();
+    let instructions = self.instructions();
+    instructions.0[pos + 1] = operand[0];
+    instructions.0[pos + 2] = operand[1];
+    Ok(())
+  }
+
+  fn add_constant(&mut self, obj: Object, span: Span) -> Result<usize, CompileError> {
+    let constants = &mut self.scope().constants;
+    constants.push(obj);
+    let idx = constants.len() - 1;
+    self.emit_u16(Opcode::Constant, idx, span)
+  }
+
+  fn name_index(&mut self, name: Name) -> usize {
+    let scope = self.scope();
+    if let Some(idx) = scope.name_indexes.get(&name) {
+      return *idx;
+    }
+    scope.names.push(name);
+    scope.name_indexes.insert(name, scope.names.len() - 1);
+    scope.names.len() - 1
+  }
+
+  fn emit_name(&mut self, op: Opcode, name: Name, span: Span) -> Result<usize, CompileError> {
+    let idx = self.name_index(name);
+    self.emit_u16(op, idx, span)
+  }
+
+  fn define(&mut self, name: Name, constant: bool) -> Symbol {
+    if constant {
+      self.symbols.define_constant(name)
+    } else {
+      self.symbols.define(name)
+    }
+  }
+
+  fn emit_define(&mut self, symbol: Symbol, name: Name, constant: bool, span: Span) -> Result<(), CompileError> {
+    match symbol.scope {
+      SymbolScope::Local => self.emit_u16(Opcode::DefineLocal, symbol.index, span)?,
+      _ if constant => self.emit_name(Opcode::DefineConstName, name, span)?,
+      _ => self.emit_name(Opcode::DefineName, name, span)?,
+    };
+    Ok(())
+  }
+
+  fn emit_get(&mut self, name: Name, span: Span) -> Result<usize, CompileError> {
+    let symbol = self.symbols.resolve(name);
+    match symbol.scope {
+      SymbolScope::Global => self.emit_name(Opcode::GetName, name, span),
+      SymbolScope::Local => self.emit_u16(Opcode::GetLocal, symbol.index, span),
+      SymbolScope::Free => self.emit_u16(Opcode::GetFree, symbol.index, span),
+    }
+  }
+
+  /// Every statement leaves exactly one value on the stack, so a block leaves the value of its last statement,
+  /// or null when it is empty.
+  fn compile_statements(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+    let statements: Vec<&Statement> = statements.iter()
+      .filter(|stmt| !matches!(stmt, Statement::Comment(_)))
+      .collect();
+
+    if statements.is_empty() {
+      self.emit(Opcode::Null, &[]);
+      return Ok(());
+    }
+
+    for (i, stmt) in statements.into_iter().enumerate() {
+      if i > 0 {
+        self.emit(Opcode::Pop, &[]);
+      }
+      self.compile_statement(stmt)?;
+    }
+    Ok(())
+  }
+
+  // ブロックを出るときに、その中で束縛したローカル変数のスロットを新しくする。
+  // ループで次に入ったときに、前のイテレーションのクロージャとスロットを共有しないようにするため
+  fn compile_block(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+    self.symbols.enter_block();
+    let compiled = self.compile_statements(&block.statements);
+    let slots = self.symbols.leave_block();
+    compiled?;
+    for slot in slots {
+      self.emit_u16(Opcode::ResetLocal, slot, block.span)?;
+    }
+    Ok(())
+  }
+
+  fn compile_statement(&mut self, stmt: &Statement) -> Result<(), CompileError> {
+    match stmt {
+      Statement::Expr(expr) => self.compile_expression(&expr.value),
+      Statement::Return(rtn) => {
+        self.compile_expression(&rtn.value)?;
+        self.emit(Opcode::ReturnValue, &[]);
+        Ok(())
+      },
+      Statement::Let(let_stmt) => {
+        let ident = match &let_stmt.pattern {
+          Pattern::Ident(ident) => ident,
+          _ => return Err(unsupported("destructuring let", let_stmt.span)),
+        };
+        // 関数は自分自身を呼べるように、値より先に名前を束縛しておく
+        let symbol = if is_called_from_closure(ident.value, &let_stmt.value) {
+          Some(self.define(ident.value, let_stmt.constant))
+        } else {
+          None
+        };
+        self.compile_expression(&let_stmt.value)?;
+        let symbol = symbol.unwrap_or_else(|| self.define(ident.value, let_stmt.constant));
+        self.emit_define(symbol, ident.value, let_stmt.constant, let_stmt.span)
+      },
+      Statement::Func(func_stmt) => {
+        let symbol = self.define(func_stmt.ident.value, false);
+        self.compile_func(&func_stmt.func)?;
+        self.emit_define(symbol, func_stmt.ident.value, false, func_stmt.span)
+      },
+      Statement::While(while_stmt) => {
+        let loop_start = self.instructions().len();
+        self.compile_expression(&while_stmt.condition)?;
+        let exit = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER]);
+
+        self.compile_block(&while_stmt.body)?;
+        self.emit(Opcode::Pop, &[]);
+        self.emit_u16(Opcode::Jump, loop_start, while_stmt.span)?;
+
+        self.patch_jump(exit, while_stmt.span)?;
+        self.emit(Opcode::Null, &[]);
+        Ok(())
+      },
+      Statement::For(for_stmt) => Err(unsupported("for statement", for_stmt.span)),
+      Statement::Struct(struct_stmt) => Err(unsupported("struct statement", struct_stmt.span)),
+      Statement::Block(block) => self.compile_block(block),
+      Statement::Comment(_) => {
+        self.emit(Opcode::Null, &[]);
+        Ok(())
+      },
+    }
+  }
+
+  fn compile_expression(&mut self, expr: &Expression) -> Result<(), CompileError> {
+    match expr {
+      Expression::Identifier(ident) => {
+        self.emit_get(ident.value, ident.span)?;
+      },
+      Expression::Literal(lit) => self.compile_literal(lit, expr.span())?,
+      Expression::Prefix(pre) => {
+        self.compile_expression(&pre.right)?;
+        match pre.operator {
+          Prefix::Minus => self.emit(Opcode::Minus, &[]),
+          Prefix::Bang => self.emit(Opcode::Bang, &[]),
+          Prefix::BitNot => self.emit(Opcode::BitNot, &[]),
+        };
+      },
+      Expression::Infix(inf) => {
+        self.compile_expression(&inf.left)?;
+        match inf.operator {
+          Infix::And | Infix::Or => return self.compile_logical(&inf.operator, &inf.right, inf.span),
+          Infix::Coalesce => {
+            let end = self.emit(Opcode::JumpNotNull, &[PLACEHOLDER]);
+            self.compile_expression(&inf.right)?;
+            return self.patch_jump(end, inf.span);
+          },
+          _ => (),
+        }
+
+        self.compile_expression(&inf.right)?;
+        let op = match to_opcode(&inf.operator) {
+          Some(op) => op,
+          None => return Err(unsupported(&format!("{} operator", inf.operator), inf.span)),
+        };
+        self.emit(op, &[]);
+      },
+      Expression::If(if_expr) => {
+        self.compile_expression(&if_expr.condition)?;
+        let alternative = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER]);
+
+        self.compile_block(&if_expr.consequence)?;
+        let end = self.emit(Opcode::Jump, &[PLACEHOLDER]);
+
+        self.patch_jump(alternative, if_expr.span)?;
+        match &if_expr.alternative {
+          Some(block) => self.compile_block(block)?,
+          None => {
+            self.emit(Opcode::Null, &[]);
+          },
+        }
+        self.patch_jump(end, if_expr.span)?;
+      },
+      Expression::Try(try_expr) => return Err(unsupported("try expression", try_expr.span)),
+      Expression::Match(match_expr) => return Err(unsupported("match expression", match_expr.span)),
+      Expression::Ternary(ternary) => {
+        self.compile_expression(&ternary.condition)?;
+        let alternative = self.emit(Opcode::JumpNotTruthy, &[PLACEHOLDER]);
+
+        self.compile_expression(&ternary.consequence)?;
+        let end = self.emit(Opcode::Jump, &[PLACEHOLDER]);
+
+        self.patch_jump(alternative, ternary.span)?;
+        self.compile_expression(&ternary.alternative)?;
+        self.patch_jump(end, ternary.span)?;
+      },
+      Expression::Call(call) if macro_expansion::is_quote_call(call) => return Err(unsupported("quote", call.span)),
+      Expression::Call(call) => {
+        self.compile_expression(&call.func)?;
+        for arg in &call.args {
+          if let Expression::Spread(spread) = arg {
+            return Err(unsupported("spread argument", spread.span));
+          }
+          self.compile_expression(arg)?;
+        }
+        if call.args.len() > u8::MAX as usize {
+          return Err(CompileError::TooLarge { span: call.span });
+        }
+        self.emit(Opcode::Call, &[call.args.len()]);
+      },
+      Expression::MethodCall(method) => {
+        self.compile_expression(&method.receiver)?;
+        for arg in &method.args {
+          if let Expression::Spread(spread) = arg {
+            return Err(unsupported("spread argument", spread.span));
+          }
+          self.compile_expression(arg)?;
+        }
+        // 受け取る値を最初の引数にするかは、呼ぶときにメソッドを引くまで分からない
+        let idx = self.name_index(method.method.value);
+        if idx > u16::MAX as usize || method.args.len() > u8::MAX as usize {
+          return Err(CompileError::TooLarge { span: method.span });
+        }
+        self.emit(Opcode::CallMethod, &[idx, method.args.len()]);
+      },
+      Expression::Member(member) => {
+        self.compile_expression(&member.object)?;
+        let key = Object::Str(object::Str::new(member.property.value.to_string()));
+        self.add_constant(key, member.property.span)?;
+        self.emit(Opcode::Index, &[]);
+      },
+      Expression::Index(idx) => {
+        self.compile_expression(&idx.left)?;
+        self.compile_expression(&idx.index)?;
+        self.emit(Opcode::Index, &[]);
+      },
+      Expression::Slice(slice) => {
+        self.compile_expression(&slice.left)?;
+        for bound in [&slice.start, &slice.end].iter() {
+          match bound {
+            Some(expr) => self.compile_expression(expr)?,
+            None => {
+              self.emit(Opcode::Null, &[]);
+            },
+          }
+        }
+        self.emit(Opcode::Slice, &[]);
+      },
+      Expression::Range(range) => {
+        self.compile_expression(&range.start)?;
+        self.compile_expression(&range.end)?;
+        self.emit(Opcode::Range, &[range.inclusive as usize]);
+      },
+      Expression::Assign(assign) => {
+        let ident = match &*assign.target {
+          Expression::Identifier(ident) => ident,
+          _ => return Err(unsupported("assignment to an index", assign.span)),
+        };
+
+        // 複合代入では右辺より先に現在の値を読む
+        if let Some(operator) = &assign.operator {
+          self.emit_get(ident.value, ident.span)?;
+          self.compile_expression(&assign.value)?;
+          let op = match to_opcode(operator) {
+            Some(op) => op,
+            None => return Err(unsupported(&format!("{}= operator", operator), assign.span)),
+          };
+          self.emit(op, &[]);
+        } else {
+          self.compile_expression(&assign.value)?;
+        }
+        let symbol = self.symbols.resolve(ident.value);
+        if symbol.constant {
+          return Err(CompileError::ConstantAssignment { name: ident.value.to_string(), span: assign.span });
+        }
+        match symbol.scope {
+          SymbolScope::Global => self.emit_name(Opcode::AssignName, ident.value, assign.span)?,
+          SymbolScope::Local => self.emit_u16(Opcode::AssignLocal, symbol.index, assign.span)?,
+          SymbolScope::Free => self.emit_u16(Opcode::AssignFree, symbol.index, assign.span)?,
+        };
+      },
+      Expression::Update(update) => return Err(unsupported(&format!("{} operator", update.operator), update.span)),
+      Expression::Spread(spread) => return Err(unsupported("spread", spread.span)),
+    }
+    Ok(())
+  }
+
+  // 結果は evaluator と同じく真偽値になる
+  fn compile_logical(&mut self, operator: &Infix, right: &Expression, span: Span) -> Result<(), CompileError> {
+    // && は偽、|| は真になった時点で残りを飛ばす
+    let (jump, evaluated, short_circuit) = match operator {
+      Infix::And => (Opcode::JumpNotTruthy, Opcode::True, Opcode::False),
+      _ => (Opcode::JumpTruthy, Opcode::False, Opcode::True),
+    };
+
+    let left_jump = self.emit(jump, &[PLACEHOLDER]);
+    self.compile_expression(right)?;
+    let right_jump = self.emit(jump, &[PLACEHOLDER]);
+
+    self.emit(evaluated, &[]);
+    let end = self.emit(Opcode::Jump, &[PLACEHOLDER]);
+
+    self.patch_jump(left_jump, span)?;
+    self.patch_jump(right_jump, span)?;
+    self.emit(short_circuit, &[]);
+    self.patch_jump(end, span)
+  }
+
+  fn compile_literal(&mut self, lit: &Literal, span: Span) -> Result<(), CompileError> {
+    match lit {
+      Literal::Integer(int) => {
+        self.add_constant(Object::Integer(object::Integer::new(int.value)), span)?;
+      },
+      Literal::Float(float) => {
+        self.add_constant(Object::Float(object::Float::new(float.value)), span)?;
+      },
+      Literal::Str(s) => {
+        self.add_constant(Object::Str(object::Str::new(s.value.clone())), span)?;
+      },
+      Literal::Boolean(b) => {
+        self.emit(if b.value { Opcode::True } else { Opcode::False }, &[]);
+      },
+      Literal::Null(_) => {
+        self.emit(Opcode::Null, &[]);
+      },
+      Literal::Array(arr) => {
+        for elm in &arr.elements {
+          if let Expression::Spread(spread) = elm {
+            return Err(unsupported("spread element", spread.span));
+          }
+          self.compile_expression(elm)?;
+        }
+        self.emit_u16(Opcode::Array, arr.elements.len(), span)?;
+      },
+      Literal::Hash(hash) => {
+        for (key, val) in &hash.pairs {
+          self.compile_expression(key)?;
+          self.compile_expression(val)?;
+        }
+        self.emit_u16(Opcode::Hash, hash.pairs.len(), span)?;
+      },
+      Literal::Struct(lit) => return Err(unsupported("struct literal", lit.span)),
+      Literal::Func(func) => self.compile_func(func)?,
+      // マクロは実行する前に展開されるので、残っているのは let で定義されていないものだけ
+      Literal::Macro(mac) => return Err(unsupported("macro literal", mac.span)),
+    }
+    Ok(())
+  }
+
+  fn compile_func(&mut self, func: &lit::Func) -> Result<(), CompileError> {
+    if func.defaults.iter().any(|default| default.is_some()) {
+      return Err(unsupported("default parameter", func.span));
+    }
+
+    self.scopes.push(Scope::default());
+    self.symbols = SymbolTable::new_enclosed(std::mem::take(&mut self.symbols));
+    for arg in &func.args {
+      self.symbols.define_param(arg.value);
+    }
+
+    self.compile_statements(&func.body.statements)?;
+    self.emit(Opcode::ReturnValue, &[]);
+    let scope = self.scopes.pop().unwrap();
+    let mut symbols = std::mem::take(&mut self.symbols);
+    let locals = std::mem::take(&mut symbols.locals);
+    let free = std::mem::take(&mut symbols.free_symbols);
+    self.symbols = symbols.into_outer().unwrap();
+
+    let compiled = CompiledFunction {
+      instructions: scope.instructions,
+      constants: scope.constants,
+      names: scope.names,
+      functions: scope.functions,
+      locals,
+      num_params: func.args.len(),
+      free,
+      source: format!("{}", func),
+    };
+    let functions = &mut self.scope().functions;
+    functions.push(Rc::new(compiled));
+    let idx = functions.len() - 1;
+    self.emit_u16(Opcode::Closure, idx, func.span)?;
+    Ok(())
+  }
+}
+
+fn unsupported(node: &str, span: Span) -> CompileError {
+  CompileError::Unsupported { node: node.to_string(), span }
+}
+
+// `let f = memoize(fn(n) { f(n - 1) })` のように、値の中の関数だけが name を使うか。
+// 関数の外や `fn() { c }()` のようにその場で呼ぶ関数の中で使うときは、evaluator と同じく
+// 束縛する前の name を読むので先に束縛できない
+fn is_called_from_closure(name: Name, value: &Expression) -> bool {
+  let mut refs = References { name, depth: 0, called: false, in_func: false, outside: false };
+  refs.visit_expression(value);
+  refs.in_func && !refs.outside
+}
+
+struct References {
+  name: Name,
+  // 入れ子になった関数のリテラルの深さ
+  depth: usize,
+  // 次に訪れる関数のリテラルがその場で呼ばれるか
+  called: bool,
+  in_func: bool,
+  outside: bool,
+}
+
+impl<'a> Visitor<'a> for References {
+  fn visit_expression(&mut self, expr: &'a Expression) {
+    if let Expression::Call(call) = expr {
+      self.called = matches!(&*call.func, Expression::Literal(Literal::Func(_)));
+    }
+    visit::walk_expression(self, expr);
+  }
+
+  fn visit_literal(&mut self, lit: &'a Literal) {
+    let is_func = matches!(lit, Literal::Func(_)) && !std::mem::take(&mut self.called);
+    if is_func {
+      self.depth += 1;
+    }
+    visit::walk_literal(self, lit);
+    if is_func {
+      self.depth -= 1;
+    }
+  }
+
+  fn visit_identifier(&mut self, ident: &'a Identifier) {
+    if ident.value != self.name {
+      return;
+    }
+    if self.depth > 0 {
+      self.in_func = true;
+    } else {
+      self.outside = true;
+    }
+  }
+}
+
+fn to_opcode(operator: &Infix) -> Option<Opcode> {
+  match operator {
+    Infix::Plus => Some(Opcode::Add),
+    Infix::Minus => Some(Opcode::Sub),
+    Infix::Asterisk => Some(Opcode::Mul),
+    Infix::Slash => Some(Opcode::Div),
+    Infix::Percent => Some(Opcode::Mod),
+    Infix::Power => Some(Opcode::Pow),
+    Infix::BitAnd => Some(Opcode::BitAnd),
+    Infix::BitOr => Some(Opcode::BitOr),
+    Infix::BitXor => Some(Opcode::BitXor),
+    Infix::ShiftLeft => Some(Opcode::ShiftLeft),
+    Infix::ShiftRight => Some(Opcode::ShiftRight),
+    Infix::Equal => Some(Opcode::Equal),
+    Infix::NotEq => Some(Opcode::NotEqual),
+    Infix::Gt => Some(Opcode::GreaterThan),
+    Infix::Lt => Some(Opcode::LessThan),
+    Infix::GtEq => Some(Opcode::GreaterEqual),
+    Infix::LtEq => Some(Opcode::LessEqual),
+    _ => None,
+  }
+}
+
+/// Maps an arithmetic or comparison opcode back to its operator so the VM can share the evaluator's semantics.
+pub fn to_infix(op: Opcode) -> Option<Infix> {
+  match op {
+    Opcode::Add => Some(Infix::Plus),
+    Opcode::Sub => Some(Infix::Minus),
+    Opcode::Mul => Some(Infix::Asterisk),
+    Opcode::Div => Some(Infix::Slash),
+    Opcode::Mod => Some(Infix::Percent),
+    Opcode::Pow => Some(Infix::Power),
+    Opcode::BitAnd => Some(Infix::BitAnd),
+    Opcode::BitOr => Some(Infix::BitOr),
+    Opcode::BitXor => Some(Infix::BitXor),
+    Opcode::ShiftLeft => Some(Infix::ShiftLeft),
+    Opcode::ShiftRight => Some(Infix::ShiftRight),
+    Opcode::Equal => Some(Infix::Equal),
+    Opcode::NotEqual => Some(Infix::NotEq),
+    Opcode::GreaterThan => Some(Infix::Gt),
+    Opcode::LessThan => Some(Infix::Lt),
+    Opcode::GreaterEqual => Some(Infix::GtEq),
+    Opcode::LessEqual => Some(Infix::LtEq),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn parse(input: &str) -> Program {
+    let l = Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+    program
+  }
+
+  #[test]
+  fn test_compile_instructions() {
+    let tests = vec![
+      ("1 + 2; 3", "0000 OpConstant 0
+0003 OpConstant 1
+0006 OpAdd
+0007 OpPop
+0008 OpConstant 2
+"),
+      ("if (true) { 10 }; 20", "0000 OpTrue
+0001 OpJumpNotTruthy 10
+0004 OpConstant 0
+0007 OpJump 11
+0010 OpNull
+0011 OpPop
+0012 OpConstant 1
+"),
+      ("let x = 1; x = x + 1;", "0000 OpConstant 0
+0003 OpDefineName 0
+0006 OpPop
+0007 OpGetName 0
+0010 OpConstant 1
+0013 OpAdd
+0014 OpAssignName 0
+"),
+      ("a && b", "0000 OpGetName 0
+0003 OpJumpNotTruthy 16
+0006 OpGetName 1
+0009 OpJumpNotTruthy 16
+0012 OpTrue
+0013 OpJump 17
+0016 OpFalse
+"),
+      ("while (x) { y }", "0000 OpGetName 0
+0003 OpJumpNotTruthy 13
+0006 OpGetName 1
+0009 OpPop
+0010 OpJump 0
+0013 OpNull
+"),
+      ("if (true) { let x = 1; x }", "0000 OpTrue
+0001 OpJumpNotTruthy 20
+0004 OpConstant 0
+0007 OpDefineLocal 0
+0010 OpPop
+0011 OpGetLocal 0
+0014 OpResetLocal 0
+0017 OpJump 21
+0020 OpNull
+"),
+      ("", "0000 OpNull\n"),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let bytecode = compile(&parse(input)).unwrap();
+      assert_eq!(format!("{}", bytecode.instructions), expected, "input={}", input);
+    }
+  }
+
+  #[test]
+  fn test_compile_functions() {
+    let bytecode = compile(&parse("let f = fn(x) { return x * 2; }; f(1)")).unwrap();
+    assert_eq!(format!("{}", bytecode.instructions), "0000 OpClosure 0
+0003 OpDefineName 0
+0006 OpPop
+0007 OpGetName 0
+0010 OpConstant 0
+0013 OpCall 1
+");
+
+    // 関数は自分の定数を持ち、引数はスロットで参照する
+    let func = &bytecode.functions[0];
+    assert_eq!(func.locals, vec![Name::new("x")]);
+    assert_eq!(func.num_params, 1);
+    assert!(func.names.is_empty());
+    assert_eq!(func.constants, vec![Object::Integer(object::Integer::new(2))]);
+    assert_eq!(format!("{}", func.instructions), "0000 OpGetLocal 0
+0003 OpConstant 0
+0006 OpMul
+0007 OpReturnValue
+0008 OpReturnValue
+");
+  }
+
+  #[test]
+  fn test_compile_closures() {
+    let bytecode = compile(&parse("fn(a) { let b = 1; fn(c) { a = b + c + d } }")).unwrap();
+    let outer = &bytecode.functions[0];
+    assert_eq!(outer.locals, vec![Name::new("a"), Name::new("b")]);
+    assert!(outer.free.is_empty());
+
+    let inner = &outer.functions[0];
+    assert_eq!(inner.locals, vec![Name::new("c")]);
+    let free: Vec<(&str, SymbolScope, usize)> = inner.free.iter()
+      .map(|symbol| (symbol.name.as_str(), symbol.scope, symbol.index))
+      .collect();
+    assert_eq!(free, vec![("b", SymbolScope::Local, 1), ("a", SymbolScope::Local, 0)]);
+    // 外側の関数にないものは名前で引く
+    assert_eq!(inner.names, vec![Name::new("d")]);
+    assert_eq!(format!("{}", inner.instructions), "0000 OpGetFree 0
+0003 OpGetLocal 0
+0006 OpAdd
+0007 OpGetName 0
+0010 OpAdd
+0011 OpAssignFree 1
+0014 OpReturnValue
+");
+
+    // let で束縛する関数は自分自身をローカル変数として参照できる
+    let bytecode = compile(&parse("fn() { let f = fn() { f() }; f }")).unwrap();
+    let outer = &bytecode.functions[0];
+    assert_eq!(format!("{}", outer.instructions), "0000 OpClosure 0
+0003 OpDefineLocal 0
+0006 OpPop
+0007 OpGetLocal 0
+0010 OpReturnValue
+");
+    assert_eq!(outer.functions[0].free[0].scope, SymbolScope::Local);
+
+    // 値の中の関数から呼ぶときも先に束縛するが、関数の外で使う名前は束縛する前のものを指す
+    let bytecode = compile(&parse("fn(g) { let f = wrap(fn() { f() }); let g = wrap(g, fn() { g() }); f }")).unwrap();
+    let outer = &bytecode.functions[0];
+    assert_eq!(outer.functions[0].free[0].name, Name::new("f"));
+    assert_eq!(outer.functions[0].free[0].scope, SymbolScope::Local);
+    assert_eq!(outer.functions[1].free[0].name, Name::new("g"));
+    assert_eq!(outer.functions[1].free[0].index, 0);
+  }
+
+  #[test]
+  fn test_constant_assignment() {
+    let tests = vec![
+      "let f = fn() { const x = 1; x = 2 }",
+      "let f = fn() { const x = 1; fn() { x += 1 } }",
+    ];
+
+    for input in tests.into_iter() {
+      match compile(&parse(input)) {
+        Err(err @ CompileError::ConstantAssignment { .. }) => assert_eq!(err.to_string(), "assignment to constant: x.", "input={}", input),
+        _ => panic!("{} should not be compiled", input),
+      }
+    }
+  }
+
+  #[test]
+  fn test_unsupported() {
+    let tests = vec![
+      ("for (x in xs) { x }", "for statement"),
+      ("struct P {x}", "struct statement"),
+      ("P {x: 1}", "struct literal"),
+      ("let [a, b] = xs;", "destructuring let"),
+      ("a[0] = 1", "assignment to an index"),
+      ("x++", "++ operator"),
+      ("f(...xs)", "spread argument"),
+      ("[...xs]", "spread element"),
+      ("fn(x = 1) { x }", "default parameter"),
+      ("try { 1 } catch (e) { 2 }", "try expression"),
+      ("match (x) { 1 => 2 }", "match expression"),
+      ("quote(1 + 2)", "quote"),
+      ("let f = fn() { macro(x) { x } }", "macro literal"),
+    ];
+
+    for (input, node) in tests.into_iter() {
+      match compile(&parse(input)) {
+        Err(CompileError::Unsupported { node: actual, .. }) => assert_eq!(actual, node, "input={}", input),
+        _ => panic!("{} should not be supported", input),
+      }
+    }
+  }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::intern::Name;
+
+/// Where the value of an identifier is kept at runtime.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SymbolScope {
+  /// Looked up by name in the environment the program runs in, like the evaluator does.
+  /// Top-level bindings and builtins are shared with the evaluator and the following REPL lines,
+  /// and they can be defined after a function which refers to them.
+  Global,
+  /// A parameter or a `let` binding of the function being compiled, stored in the slot `index` of its frame.
+  Local,
+  /// A local of an enclosing function captured by the closure, stored in the slot `index` of the closure.
+  Free,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+  pub name: Name,
+  pub scope: SymbolScope,
+  pub index: usize,
+  /// True if the binding was made with `const`. Global ones are checked by the VM instead, since they can change at runtime.
+  pub constant: bool,
+}
+
+impl Symbol {
+  fn global(name: Name) -> Symbol {
+    Symbol { name, scope: SymbolScope::Global, index: 0, constant: false }
+  }
+}
+
+/// Resolves identifiers while compiling. The outermost table is for the top level,
+/// and each function literal is compiled with a table enclosed by the one of the code around it.
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+  outer: Option<Box<SymbolTable>>,
+  store: HashMap<Name, Symbol>,
+  /// The names of the local slots; the parameters come first.
+  pub locals: Vec<Name>,
+  /// The symbols of the enclosing table which the closure captures, in the order of their free indexes.
+  pub free_symbols: Vec<Symbol>,
+  // コンパイル中のブロックごとに、束縛した名前とそれが隠したシンボル
+  blocks: Vec<Vec<(Name, Option<Symbol>)>>,
+}
+
+impl SymbolTable {
+  pub fn new() -> SymbolTable {
+    SymbolTable::default()
+  }
+
+  pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+    SymbolTable { outer: Some(Box::new(outer)), ..SymbolTable::default() }
+  }
+
+  /// Returns the table this one is enclosed by, or `None` for the outermost one.
+  pub fn into_outer(self) -> Option<SymbolTable> {
+    self.outer.map(|outer| *outer)
+  }
+
+  /// Binds `name` in this table. Binding a local again in the same function or block reuses its slot,
+  /// as `let` in the evaluator overwrites it, while a block hides the bindings outside of it with new slots.
+  pub fn define(&mut self, name: Name) -> Symbol {
+    self.define_binding(name, false)
+  }
+
+  /// Binds `name` like `define`, but the local can not be assigned to.
+  pub fn define_constant(&mut self, name: Name) -> Symbol {
+    self.define_binding(name, true)
+  }
+
+  fn define_binding(&mut self, name: Name, constant: bool) -> Symbol {
+    if let Some(block) = self.blocks.last_mut() {
+      // ブロックの外の束縛は新しいスロットで隠す
+      if block.iter().all(|(bound, _)| *bound != name) {
+        block.push((name, self.store.get(&name).cloned()));
+        return self.define_local(name, constant);
+      }
+    } else if self.outer.is_none() {
+      return Symbol::global(name);
+    }
+    if let Some(symbol) = self.store.get_mut(&name) {
+      if symbol.scope == SymbolScope::Local {
+        symbol.constant = constant;
+        return symbol.clone();
+      }
+    }
+
+    self.define_local(name, constant)
+  }
+
+  fn define_local(&mut self, name: Name, constant: bool) -> Symbol {
+    let mut symbol = self.define_param(name);
+    symbol.constant = constant;
+    self.store.insert(name, symbol.clone());
+    symbol
+  }
+
+  /// Binds a parameter of the function to a new slot. When a name is repeated, the last one is seen from the body.
+  pub fn define_param(&mut self, name: Name) -> Symbol {
+    let symbol = Symbol { name, scope: SymbolScope::Local, index: self.locals.len(), constant: false };
+    self.locals.push(name);
+    self.store.insert(name, symbol.clone());
+    symbol
+  }
+
+  /// Starts a block, in which `define` binds names to new locals even in the outermost table.
+  pub fn enter_block(&mut self) {
+    self.blocks.push(vec![]);
+  }
+
+  /// Ends the block started last, so that the names bound in it refer to what they did before it.
+  /// Returns the slots of the locals bound in the block.
+  pub fn leave_block(&mut self) -> Vec<usize> {
+    let bound = self.blocks.pop().unwrap_or_default();
+    let mut slots = vec![];
+    for (name, hidden) in bound.into_iter().rev() {
+      if let Some(symbol) = self.store.remove(&name) {
+        slots.push(symbol.index);
+      }
+      if let Some(hidden) = hidden {
+        self.store.insert(name, hidden);
+      }
+    }
+    slots
+  }
+
+  /// Finds the binding `name` refers to at this point of the program.
+  /// Names which no enclosing function binds are global.
+  pub fn resolve(&mut self, name: Name) -> Symbol {
+    if let Some(symbol) = self.store.get(&name) {
+      return symbol.clone();
+    }
+
+    let outer = match &mut self.outer {
+      Some(outer) => outer.resolve(name),
+      None => return Symbol::global(name),
+    };
+    if outer.scope == SymbolScope::Global {
+      return outer;
+    }
+
+    // 外側の関数のローカル変数は、クロージャの自由変数として取り込む
+    let constant = outer.constant;
+    self.free_symbols.push(outer);
+    let symbol = Symbol { name, scope: SymbolScope::Free, index: self.free_symbols.len() - 1, constant };
+    self.store.insert(name, symbol.clone());
+    symbol
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+    Symbol { name: Name::new(name), scope, index, constant: false }
+  }
+
+  fn name(name: &str) -> Name {
+    Name::new(name)
+  }
+
+  #[test]
+  fn test_resolve() {
+    let mut global = SymbolTable::new();
+    assert_eq!(global.define(name("a")), symbol("a", SymbolScope::Global, 0));
+
+    let mut outer = SymbolTable::new_enclosed(global);
+    assert_eq!(outer.define(name("b")), symbol("b", SymbolScope::Local, 0));
+    assert_eq!(outer.define(name("c")), symbol("c", SymbolScope::Local, 1));
+    assert_eq!(outer.define(name("b")), symbol("b", SymbolScope::Local, 0));
+
+    let mut inner = SymbolTable::new_enclosed(outer);
+    assert_eq!(inner.define(name("d")), symbol("d", SymbolScope::Local, 0));
+
+    let tests = vec![
+      ("a", symbol("a", SymbolScope::Global, 0)),
+      ("len", symbol("len", SymbolScope::Global, 0)),
+      ("d", symbol("d", SymbolScope::Local, 0)),
+      ("c", symbol("c", SymbolScope::Free, 0)),
+      ("b", symbol("b", SymbolScope::Free, 1)),
+      ("c", symbol("c", SymbolScope::Free, 0)),
+    ];
+    for (name, expected) in tests.into_iter() {
+      assert_eq!(inner.resolve(Name::new(name)), expected, "name={}", name);
+    }
+    assert_eq!(inner.locals, vec![name("d")]);
+    assert_eq!(inner.free_symbols, vec![symbol("c", SymbolScope::Local, 1), symbol("b", SymbolScope::Local, 0)]);
+  }
+
+  #[test]
+  fn test_resolve_nested_free() {
+    let mut outer = SymbolTable::new_enclosed(SymbolTable::new());
+    outer.define(name("x"));
+    let middle = SymbolTable::new_enclosed(outer);
+    let mut inner = SymbolTable::new_enclosed(middle);
+
+    assert_eq!(inner.resolve(name("x")), symbol("x", SymbolScope::Free, 0));
+    assert_eq!(inner.free_symbols, vec![symbol("x", SymbolScope::Free, 0)]);
+
+    // 自由変数を後から同じ名前のローカル変数で隠せる
+    assert_eq!(inner.define(name("x")), symbol("x", SymbolScope::Local, 0));
+    assert_eq!(inner.resolve(name("x")), symbol("x", SymbolScope::Local, 0));
+
+    let middle = inner.into_outer().unwrap();
+    assert_eq!(middle.free_symbols, vec![symbol("x", SymbolScope::Local, 0)]);
+  }
+
+  #[test]
+  fn test_block() {
+    let mut global = SymbolTable::new();
+    global.enter_block();
+    assert_eq!(global.define(name("a")), symbol("a", SymbolScope::Local, 0));
+    assert_eq!(global.define(name("a")), symbol("a", SymbolScope::Local, 0));
+    global.enter_block();
+    assert_eq!(global.define(name("a")), symbol("a", SymbolScope::Local, 1));
+    assert_eq!(global.leave_block(), vec![1]);
+    assert_eq!(global.resolve(name("a")), symbol("a", SymbolScope::Local, 0));
+    assert_eq!(global.leave_block(), vec![0]);
+    assert_eq!(global.resolve(name("a")), symbol("a", SymbolScope::Global, 0));
+    assert_eq!(global.locals, vec![name("a"), name("a")]);
+
+    // ブロックの中で隠した自由変数は、ブロックを出ると元に戻る
+    let mut outer = SymbolTable::new_enclosed(SymbolTable::new());
+    outer.define(name("x"));
+    let mut inner = SymbolTable::new_enclosed(outer);
+    assert_eq!(inner.resolve(name("x")), symbol("x", SymbolScope::Free, 0));
+    inner.enter_block();
+    assert_eq!(inner.define(name("x")), symbol("x", SymbolScope::Local, 0));
+    inner.leave_block();
+    assert_eq!(inner.resolve(name("x")), symbol("x", SymbolScope::Free, 0));
+  }
+
+  #[test]
+  fn test_define_constant() {
+    let mut global = SymbolTable::new();
+    assert!(!global.define_constant(name("a")).constant);
+
+    let mut outer = SymbolTable::new_enclosed(global);
+    assert!(outer.define_constant(name("b")).constant);
+    let mut inner = SymbolTable::new_enclosed(outer);
+    assert!(inner.resolve(name("b")).constant);
+
+    // let で束縛し直すと代入できるようになる
+    let mut outer = inner.into_outer().unwrap();
+    assert_eq!(outer.define(name("b")), symbol("b", SymbolScope::Local, 0));
+  }
+}
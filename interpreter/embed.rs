@@ -0,0 +1,451 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fmt;
+use std::io::Write;
+
+use crate::evaluator::{self, builtins, macro_expansion, prelude};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::limits::{Limits, CancelToken, Capabilities};
+use crate::evaluator::object::{self, BuiltinFunc, Object};
+use crate::intern::Name;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::parser::error::ParseError;
+
+/// Why `Interpreter::eval_str` failed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum EvalError {
+  /// The source does not parse. Nothing was evaluated.
+  Parse(Vec<ParseError>),
+  /// An error was raised while evaluating, and not caught with `try`.
+  Runtime(object::Error),
+}
+
+impl fmt::Display for EvalError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EvalError::Parse(errors) => {
+        for (i, err) in errors.iter().enumerate() {
+          if i > 0 {
+            writeln!(f)?;
+          }
+          write!(f, "{}: {}", err.span(), err)?;
+        }
+        Ok(())
+      },
+      EvalError::Runtime(err) => write!(f, "{}", err.report(None)),
+    }
+  }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Runs Monkey code from a Rust program. Bindings made by one call of `eval_str` are kept for the next,
+/// like the lines of the REPL, and the host can read and write them with `get` and `set`.
+///
+/// ```
+/// use interpreter::Interpreter;
+///
+/// let mut monkey = Interpreter::new();
+/// monkey.set("x", 20.into());
+/// monkey.eval_str("let double = fn(n) { n * 2 };").unwrap();
+/// let result = monkey.eval_str("double(x) + 2").unwrap();
+/// assert_eq!(result.to_string(), "42");
+/// ```
+pub struct Interpreter {
+  env: Rc<RefCell<Environment>>,
+}
+
+impl Interpreter {
+  /// Creates an interpreter with the standard builtins and the functions of the prelude, such as `sum`, and no other bindings.
+  /// The scripts can not access the filesystem, even to `import` a module, until it is granted with `set_capabilities`.
+  pub fn new() -> Interpreter {
+    let interpreter = Interpreter::without_prelude();
+    // prelude は同梱したソースなので失敗しない
+    let _ = prelude::load(&interpreter.env);
+    interpreter
+  }
+
+  /// Creates an interpreter with only the builtins written in Rust, without the functions of `evaluator::prelude`.
+  pub fn without_prelude() -> Interpreter {
+    Interpreter { env: Environment::new(builtins::new_builtins()) }
+  }
+
+  /// Creates an interpreter like `new`, whose integer arithmetic goes on with a `BigInt` instead of failing
+  /// when the result does not fit in an `i64`. See `Environment::set_bigint`.
+  ///
+  /// ```
+  /// use interpreter::Interpreter;
+  ///
+  /// let mut monkey = Interpreter::with_bigint();
+  /// let result = monkey.eval_str("let fact = fn(n) { n < 2 ? 1 : n * fact(n - 1) }; fact(25)").unwrap();
+  /// assert_eq!(result.to_string(), "15511210043330985984000000");
+  /// ```
+  #[cfg(feature = "bigint")]
+  pub fn with_bigint() -> Interpreter {
+    let interpreter = Interpreter::new();
+    interpreter.env.borrow_mut().set_bigint(true);
+    interpreter
+  }
+
+  /// Parses and evaluates `source`, and returns the value of its last statement.
+  /// Macros are expanded and constant expressions are folded first, as when a file is run.
+  /// The macros stay defined for the later calls. The `Limits` apply to each call separately.
+  pub fn eval_str(&mut self, source: &str) -> Result<Object, EvalError> {
+    self.env.borrow().budget().reset();
+    let mut p = Parser::new(Lexer::new(source.to_string()));
+    let mut program = p.parse_program();
+    if !p.errors.is_empty() {
+      return Err(EvalError::Parse(p.errors));
+    }
+
+    macro_expansion::define_macros(&mut program, &self.env);
+    let result = match macro_expansion::expand_macros(program, &self.env) {
+      Ok(program) => evaluator::eval(program.optimize(), &self.env),
+      Err(err) => err,
+    };
+    match result {
+      Object::Error(err) => Err(EvalError::Runtime(*err)),
+      evaluated => Ok(evaluated),
+    }
+  }
+
+  /// Returns the value bound to `name` at the top level, or the builtin of the name.
+  pub fn get(&self, name: &str) -> Option<Object> {
+    let env = self.env.borrow();
+    let name = Name::new(name);
+    env.get(name).or_else(|| env.get_builtin(name))
+  }
+
+  /// Binds `name` to `value` at the top level, as `let` does.
+  pub fn set(&mut self, name: &str, value: Object) {
+    self.env.borrow_mut().set(Name::new(name), value);
+  }
+
+  /// Binds `name` to a function which calls `func` with the evaluated arguments.
+  /// Unlike a builtin, `func` can capture state of the host.
+  pub fn set_function<F: FnMut(Vec<Object>) -> Object + 'static>(&mut self, name: &str, func: F) {
+    let external = object::External::new(Rc::new(RefCell::new(func)));
+    self.set(name, Object::External(external));
+  }
+
+  /// Registers a Rust function or closure as a builtin which can be called as `name` from Monkey code.
+  /// `func` receives the evaluated arguments, and an `Err` is raised in the script like the errors of the
+  /// other builtins, so it can be caught with `try`. Like the standard builtins, registered functions
+  /// are kept by `Environment::clear` and are shadowed by a `let` of the same name.
+  ///
+  /// ```
+  /// use interpreter::Interpreter;
+  /// use interpreter::evaluator::object::{Error, ErrorKind, Integer, Object};
+  ///
+  /// let mut monkey = Interpreter::new();
+  /// monkey.register_fn("square", |args: &[Object]| match args {
+  ///   [Object::Integer(n)] => Ok(Object::Integer(Integer::new(n.value * n.value))),
+  ///   _ => Err(Error::new(ErrorKind::Argument, "square takes one integer.".to_string())),
+  /// });
+  /// assert_eq!(monkey.eval_str("square(7)").unwrap().to_string(), "49");
+  /// assert!(monkey.eval_str("square(\"7\")").is_err());
+  /// ```
+  pub fn register_fn<F>(&mut self, name: &str, mut func: F)
+  where
+    F: FnMut(&[Object]) -> Result<Object, object::Error> + 'static,
+  {
+    let external = move |args: Vec<Object>| match func(&args) {
+      Ok(obj) => obj,
+      Err(err) => Object::Error(Box::new(err)),
+    };
+    self.env.borrow_mut().register_external(name, Rc::new(RefCell::new(external)));
+  }
+
+  /// Registers `func` as a builtin function. See `Environment::register_builtin`.
+  pub fn register_builtin(&mut self, name: &str, func: BuiltinFunc) {
+    self.env.borrow_mut().register_builtin(name, func);
+  }
+
+  /// Returns the top-level bindings sorted by name, without the builtins.
+  pub fn bindings(&self) -> Vec<(Name, Object)> {
+    self.env.borrow().bindings()
+  }
+
+  /// Limits the steps, the call depth, the objects, the memory and the running time of each `eval_str`, so that scripts
+  /// which are not trusted can not run forever or use up the memory. Going over a limit is a `LimitExceeded` error, which the script can not catch.
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.env.borrow_mut().set_limits(limits);
+  }
+
+  /// Grants the scripts the access outside the interpreter in `capabilities`, e.g. to the filesystem for `read_file`.
+  /// Everything is denied by default.
+  pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+    self.env.borrow_mut().set_capabilities(capabilities);
+  }
+
+  /// Sets the token with which another thread can stop a running `eval_str` with a `Cancelled` error.
+  /// A cancelled token stops every later call too, until it is reset.
+  pub fn set_cancel_token(&mut self, token: CancelToken) {
+    self.env.borrow_mut().set_cancel_token(token);
+  }
+
+  /// Replaces the sink which `puts` writes to, which is stdout by default.
+  pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+    self.env.borrow_mut().set_output(output);
+  }
+
+  /// Returns the environment the code runs in, for the settings which the interpreter does not expose.
+  pub fn env(&self) -> &Rc<RefCell<Environment>> {
+    &self.env
+  }
+}
+
+impl Default for Interpreter {
+  fn default() -> Interpreter {
+    Interpreter::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::evaluator::object::{ErrorKind, Integer};
+
+  #[test]
+  fn test_eval_str() {
+    let mut monkey = Interpreter::new();
+    assert!(monkey.eval_str("let a = 1; let f = fn(x) { x + a };").is_ok());
+    assert_eq!(monkey.eval_str("f(2)").unwrap().to_string(), "3");
+
+    monkey.set("a", Object::Integer(Integer::new(10)));
+    assert_eq!(monkey.eval_str("f(2)").unwrap().to_string(), "12");
+    assert_eq!(monkey.get("a").map(|a| a.to_string()), Some("10".to_string()));
+    assert!(monkey.get("len").is_some());
+    assert!(monkey.get("b").is_none());
+    assert_eq!(monkey.eval_str("sum([min(1, 2), max(3.5, 2), abs(-1)])").unwrap().to_string(), "5.5");
+
+    let names: Vec<String> = monkey.bindings().iter().map(|(name, _)| name.to_string()).collect();
+    assert_eq!(names, vec!["a", "f"]);
+
+    // マクロは後の呼び出しでも展開される
+    assert!(monkey.eval_str("let twice = macro(x) { quote(unquote(x) + unquote(x)) };").is_ok());
+    assert_eq!(monkey.eval_str("twice(f(1))").unwrap().to_string(), "22");
+
+    // min などは Rust で書いた組み込み関数なので、prelude がなくても使える
+    let mut monkey = Interpreter::without_prelude();
+    assert_eq!(monkey.eval_str("[min(1, 2), max(1, 2), abs(-1)]").unwrap().to_string(), "[1, 2, 1]");
+    assert!(monkey.get("sum").is_none());
+  }
+
+  #[test]
+  fn test_eval_str_errors() {
+    let mut monkey = Interpreter::new();
+    match monkey.eval_str("let = 1;") {
+      Err(EvalError::Parse(errors)) => assert!(!errors.is_empty()),
+      result => panic!("expected parse errors: {:?}", result),
+    }
+    match monkey.eval_str("let x = 1;\nx + y") {
+      Err(EvalError::Runtime(err)) => {
+        assert_eq!(err.kind, ErrorKind::Name);
+        assert_eq!(EvalError::Runtime(err).to_string(), "2:5: NameError: identifier not found: y.");
+      },
+      result => panic!("expected a runtime error: {:?}", result),
+    }
+    // エラーの前に束縛したものは残る
+    assert_eq!(monkey.eval_str("x").unwrap().to_string(), "1");
+  }
+
+  #[test]
+  fn test_set_limits() {
+    let mut monkey = Interpreter::new();
+    monkey.set_limits(Limits { max_steps: Some(100), ..Default::default() });
+    match monkey.eval_str("let n = 0; while (true) { n += 1 }") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::LimitExceeded),
+      result => panic!("expected a runtime error: {:?}", result),
+    }
+    // 上限は呼び出しごと
+    for _ in 0..3 {
+      assert!(monkey.eval_str("n = 0; while (n < 10) { n += 1 }; n").is_ok());
+    }
+  }
+
+  #[test]
+  fn test_set_capabilities() {
+    let path = std::env::temp_dir().join(format!("rsmonkey-fs-{}.txt", std::process::id()));
+    let mut monkey = Interpreter::new();
+    monkey.set("path", path.display().to_string().into());
+
+    // 許可するまではファイルに触れない。import も読めないファイルの中身をエラーで漏らさない
+    for source in ["write_file(path, \"\")", "read_file(path)", "read_lines(path)", "import(\"/etc/host.conf\")", "import(path)"] {
+      match monkey.eval_str(source) {
+        Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::Permission),
+        result => panic!("expected a permission error: {:?}", result),
+      }
+    }
+
+    monkey.set_capabilities(Capabilities { filesystem: true, ..Default::default() });
+    assert_eq!(monkey.eval_str("write_file(path, \"a\\nb\\u{d}\\n\\nc\")"), Ok(Object::Null));
+    assert_eq!(monkey.eval_str("read_file(path)").unwrap().to_string(), "\"a\nb\r\n\nc\"");
+    assert_eq!(monkey.eval_str("read_lines(path)").unwrap().to_string(), "[\"a\", \"b\", \"\", \"c\"]");
+    assert_eq!(monkey.eval_str("try { read_file(path + \".missing\") } catch (e) { e[\"kind\"] }").unwrap().to_string(), "\"IOError\"");
+    assert_eq!(monkey.eval_str("try { import(path) } catch (e) { e[\"message\"] }").unwrap().to_string(), "\"identifier not found: a.\"");
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_clock() {
+    let mut monkey = Interpreter::new();
+    for source in ["now_ms()", "clock()", "sleep(0)"] {
+      match monkey.eval_str(source) {
+        Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::Permission),
+        result => panic!("expected a permission error: {:?}", result),
+      }
+    }
+
+    monkey.set_capabilities(Capabilities { clock: true, ..Default::default() });
+    let source = "let start = clock(); let ms = now_ms(); sleep(20); [clock() - start >= 0.02, now_ms() - ms >= 20, ms > 0]";
+    assert_eq!(monkey.eval_str(source).unwrap().to_string(), "[true, true, true]");
+    assert!(monkey.eval_str("sleep(-1)").is_err());
+
+    // タイムアウトを過ぎたら待つのをやめる
+    monkey.set_limits(Limits { timeout: Some(std::time::Duration::from_millis(20)), ..Default::default() });
+    let start = std::time::Instant::now();
+    match monkey.eval_str("sleep(10000)") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::LimitExceeded),
+      result => panic!("expected a runtime error: {:?}", result),
+    }
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+  }
+
+  #[test]
+  fn test_getenv() {
+    let mut monkey = Interpreter::new();
+    match monkey.eval_str("getenv(\"PATH\")") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::Permission),
+      result => panic!("expected a permission error: {:?}", result),
+    }
+
+    monkey.set_capabilities(Capabilities { env: true, ..Default::default() });
+    let path = std::env::var("PATH").unwrap_or_default();
+    assert_eq!(monkey.eval_str("getenv(\"PATH\") ?? \"\"").unwrap(), Object::from(path));
+    assert_eq!(monkey.eval_str("getenv(\"RSMONKEY_SURELY_UNSET_VARIABLE\")").unwrap(), Object::Null);
+  }
+
+  #[test]
+  fn test_eval() {
+    let mut monkey = Interpreter::new();
+    match monkey.eval_str("eval(\"1\")") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::Permission),
+      result => panic!("expected a permission error: {:?}", result),
+    }
+
+    monkey.set_capabilities(Capabilities { eval: true, ..Default::default() });
+    assert_eq!(monkey.eval_str("let x = 1; eval(\"x + 1\")").unwrap().to_string(), "2");
+    assert_eq!(monkey.eval_str("eval(\"let y = x * 10\"); y").unwrap().to_string(), "10");
+    assert_eq!(monkey.eval_str("let f = fn(n) { eval(\"n * 2\") }; f(21)").unwrap().to_string(), "42");
+    assert_eq!(monkey.eval_str("eval(\"let m = macro(a) { quote(unquote(a) + 1) }; m(2)\")").unwrap().to_string(), "3");
+
+    // 新しい環境では呼び出したスコープの名前は見えず、束縛も外に漏れない
+    assert_eq!(monkey.eval_str("eval(\"let z = 3; len(\\\"abc\\\") + z\", true)").unwrap().to_string(), "6");
+    assert_eq!(monkey.eval_str("try { eval(\"x\", true) } catch (e) { e[\"kind\"] }").unwrap().to_string(), "\"NameError\"");
+    assert_eq!(monkey.eval_str("try { z } catch (e) { e[\"kind\"] }").unwrap().to_string(), "\"NameError\"");
+
+    assert_eq!(monkey.eval_str("try { eval(\"let = 1\") } catch (e) { e[\"kind\"] }").unwrap().to_string(), "\"SyntaxError\"");
+    match monkey.eval_str("\neval(\"1;\\n1 / 0\")") {
+      Err(EvalError::Runtime(err)) => {
+        assert_eq!(err.kind, ErrorKind::Arithmetic);
+        assert_eq!(err.message, "in eval at 2:1: division by zero: 1 / 0.");
+        assert_eq!(err.span.map(|span| span.start.line), Some(2));
+      },
+      result => panic!("expected a runtime error: {:?}", result),
+    }
+    assert!(monkey.eval_str("eval(1)").is_err());
+    assert!(monkey.eval_str("eval(\"1\", 1)").is_err());
+  }
+
+  #[cfg(feature = "bigint")]
+  #[test]
+  fn test_bigint() {
+    let mut monkey = Interpreter::with_bigint();
+    monkey.eval_str("let fact = fn(n) { let acc = 1; for (i in 1..=n) { acc *= i }; acc };").unwrap();
+    assert_eq!(monkey.eval_str("fact(30)").unwrap().to_string(), "265252859812191058636308480000000");
+    assert_eq!(monkey.eval_str("fact(30) / fact(28)").unwrap(), Object::from(870));
+    assert_eq!(monkey.eval_str("type(fact(30))").unwrap().to_string(), "\"INTEGER\"");
+    assert_eq!(monkey.eval_str("2 ** 64 - 1 > 2 ** 63").unwrap().to_string(), "true");
+    assert_eq!(monkey.eval_str("(2 ** 64 == 2 ** 64) && (2 ** 64 != 2 ** 63)").unwrap().to_string(), "true");
+    assert_eq!(monkey.eval_str("let n = 9223372036854775807; n++; n").unwrap().to_string(), "9223372036854775808");
+    assert_eq!(monkey.eval_str("abs(-9223372036854775807 - 1)").unwrap().to_string(), "9223372036854775808");
+    assert_eq!(monkey.eval_str("[min(2 ** 64, 1), max(2 ** 64, 1.5), abs(-(2 ** 64))]").unwrap().to_string(), "[1, 18446744073709551616, 18446744073709551616]");
+    assert_eq!(monkey.eval_str("let lowest = -9223372036854775807 - 1; -lowest").unwrap().to_string(), "9223372036854775808");
+    assert_eq!(monkey.eval_str("2 ** 64 * 0.5").unwrap().to_string(), "9.223372036854776e18");
+    assert!(monkey.eval_str("2 ** 64 / 0").is_err());
+    assert_eq!(monkey.eval_str("let h = {2 ** 64: \"big\", 1: \"one\"}; [h[2 ** 64], h[2 ** 64 + 1], keys(h)]").unwrap().to_string(), "[\"big\", null, [1, 18446744073709551616]]");
+    // 小数に収まらない大きさの整数は無限大として比べる
+    for (input, expected) in [("10 ** 399", f64::INFINITY), ("-(10 ** 399)", f64::NEG_INFINITY)] {
+      match monkey.eval_str(input).unwrap() {
+        Object::BigInt(big) => {
+          assert_eq!(big.to_string().trim_start_matches('-').len(), 400);
+          assert_eq!(big.to_f64(), expected, "input={}", input);
+        },
+        obj => panic!("expected a big integer, got {}", obj),
+      }
+    }
+    assert_eq!(monkey.eval_str("[10 ** 399 > 1.5, -(10 ** 399) < -1.5, 10 ** 399 == 1.5]").unwrap().to_string(), "[true, true, false]");
+    match monkey.eval_str("0..2 ** 64") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.message, "range bounds must fit in 64 bits: got=0..18446744073709551616"),
+      result => panic!("expected a range error: {:?}", result),
+    }
+    #[cfg(feature = "serde_json")]
+    {
+      assert_eq!(monkey.eval_str("json_stringify({2 ** 64: 1})").unwrap().to_string(), "\"{\"18446744073709551616\":1}\"");
+      match monkey.eval_str("json_stringify([2 ** 64])") {
+        Err(EvalError::Runtime(err)) => assert_eq!(err.message, "cannot convert 18446744073709551616 to JSON: integer is too large."),
+        result => panic!("expected a JSON error: {:?}", result),
+      }
+    }
+
+    // 有効にしなければ、これまでどおりあふれた演算はエラーになる
+    let mut monkey = Interpreter::new();
+    match monkey.eval_str("9223372036854775807 + 1") {
+      Err(EvalError::Runtime(err)) => assert_eq!(err.kind, ErrorKind::Arithmetic),
+      result => panic!("expected an overflow error: {:?}", result),
+    }
+  }
+
+  #[test]
+  fn test_register_fn() {
+    let mut monkey = Interpreter::new();
+    let mut count = 0;
+    monkey.register_fn("next", move |args: &[Object]| {
+      if !args.is_empty() {
+        return Err(object::Error::new(ErrorKind::Argument, "next takes no arguments.".to_string()));
+      }
+      count += 1;
+      Ok(Object::Integer(Integer::new(count)))
+    });
+
+    assert_eq!(monkey.eval_str("next(); next() + next()").unwrap().to_string(), "5");
+    assert_eq!(monkey.eval_str("try { next(1) } catch (e) { e[\"kind\"] }").unwrap().to_string(), "\"ArgumentError\"");
+    match monkey.eval_str("\nnext(1)") {
+      Err(err) => assert_eq!(err.to_string(), "2:1: ArgumentError: next takes no arguments."),
+      result => panic!("expected a runtime error: {:?}", result),
+    }
+    assert!(monkey.bindings().is_empty());
+    monkey.env().borrow_mut().clear();
+    assert_eq!(monkey.eval_str("next()").unwrap().to_string(), "4");
+  }
+
+  #[test]
+  fn test_set_function() {
+    let calls = Rc::new(RefCell::new(vec![]));
+    let output = Rc::new(RefCell::new(vec![]));
+
+    let mut monkey = Interpreter::new();
+    let recorded = Rc::clone(&calls);
+    monkey.set_function("record", move |args| {
+      recorded.borrow_mut().extend(args.iter().map(|arg| arg.to_string()));
+      Object::Null
+    });
+    monkey.set_output(output.clone());
+
+    monkey.eval_str("record(1, \"a\"); puts(\"done\")").unwrap();
+    assert_eq!(*calls.borrow(), vec!["1", "\"a\""]);
+    assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "\"done\"\n");
+  }
+}
@@ -0,0 +1,160 @@
+use num_bigint::BigInt;
+use num_traits::{Signed, ToPrimitive, Zero};
+
+use crate::ast::operator::{Prefix, Infix};
+use super::object::{self, Object, ErrorKind};
+use super::{new_error, native_bool_to_boolean_object};
+
+/// Returns `value` as an `Integer` if it fits in an `i64`, and as a `BigInt` otherwise,
+/// so that an integer has only one representation and `==` can compare them by variant.
+pub fn normalize(value: BigInt) -> Object {
+  match value.to_i64() {
+    Some(int) => Object::Integer(object::Integer::new(int)),
+    None => Object::BigInt(object::BigInt::new(value)),
+  }
+}
+
+fn to_bigint(obj: &Object) -> Option<BigInt> {
+  match obj {
+    Object::Integer(int) => Some(BigInt::from(int.value)),
+    Object::BigInt(big) => Some(big.value.clone()),
+    _ => None,
+  }
+}
+
+/// Evaluates `left operator right` with big integers when either operand is a `BigInt`, or when both are
+/// `Integer`s and the result does not fit in an `i64`. Returns `None` when the `i64` arithmetic is enough
+/// or an operand is not an integer.
+pub(super) fn eval_infix(left: &Object, operator: &Infix, right: &Object) -> Option<Object> {
+  if let (Object::Integer(l), Object::Integer(r)) = (left, right) {
+    if !overflows(l.value, operator, r.value) {
+      return None;
+    }
+  }
+  let (left, right) = (to_bigint(left)?, to_bigint(right)?);
+  Some(eval_bigint_infix(left, operator, right))
+}
+
+// 0 で割る場合や負の指数は、i64 のままエラーにする
+fn overflows(left: i64, operator: &Infix, right: i64) -> bool {
+  match operator {
+    Infix::Plus => left.checked_add(right).is_none(),
+    Infix::Minus => left.checked_sub(right).is_none(),
+    Infix::Asterisk => left.checked_mul(right).is_none(),
+    Infix::Slash => right != 0 && left.checked_div(right).is_none(),
+    Infix::Percent => right != 0 && left.checked_rem(right).is_none(),
+    Infix::Power => right >= 0 && (right > u32::MAX as i64 || left.checked_pow(right as u32).is_none()),
+    _ => false,
+  }
+}
+
+fn eval_bigint_infix(left: BigInt, operator: &Infix, right: BigInt) -> Object {
+  let value = match operator {
+    Infix::Plus => left + right,
+    Infix::Minus => left - right,
+    Infix::Asterisk => left * right,
+    // i64 と同じく、商は 0 に向かって切り捨て、余りは左辺と同じ符号になる
+    Infix::Slash => {
+      if right.is_zero() {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {} / {}.", left, right));
+      }
+      left / right
+    },
+    Infix::Percent => {
+      if right.is_zero() {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {} % {}.", left, right));
+      }
+      left % right
+    },
+    Infix::Power => {
+      if right.is_negative() {
+        return new_error(ErrorKind::Arithmetic, format!("negative exponent: {} ** {}.", left, right));
+      }
+      match right.to_u32() {
+        Some(exp) => left.pow(exp),
+        None => return new_error(ErrorKind::Arithmetic, format!("integer overflow: {} ** {}.", left, right)),
+      }
+    },
+    Infix::BitAnd => left & right,
+    Infix::BitOr => left | right,
+    Infix::BitXor => left ^ right,
+    Infix::ShiftLeft | Infix::ShiftRight => match right.to_u32() {
+      Some(shift) if *operator == Infix::ShiftLeft => left << shift,
+      Some(shift) => left >> shift,
+      None => return new_error(ErrorKind::Arithmetic, format!("invalid shift amount: {} {} {}.", left, operator, right)),
+    },
+    Infix::Equal => return native_bool_to_boolean_object(left == right),
+    Infix::NotEq => return native_bool_to_boolean_object(left != right),
+    Infix::Lt => return native_bool_to_boolean_object(left < right),
+    Infix::Gt => return native_bool_to_boolean_object(left > right),
+    Infix::LtEq => return native_bool_to_boolean_object(left <= right),
+    Infix::GtEq => return native_bool_to_boolean_object(left >= right),
+    _ => return new_error(
+      ErrorKind::Type, format!("unknown operator: {} {} {}.", left, operator, right),
+    ),
+  };
+  normalize(value)
+}
+
+/// Evaluates a prefix operator with big integers when `right` is a `BigInt` or `i64::MIN`, whose negation
+/// does not fit in an `i64`. Returns `None` for any other operand.
+pub(super) fn eval_prefix(operator: &Prefix, right: &Object) -> Option<Object> {
+  match (operator, right) {
+    (Prefix::Minus, Object::Integer(int)) if int.value == i64::MIN => Some(normalize(-BigInt::from(int.value))),
+    (Prefix::Minus, Object::BigInt(big)) => Some(normalize(-&big.value)),
+    (Prefix::BitNot, Object::BigInt(big)) => Some(normalize(!&big.value)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn int(value: i64) -> Object {
+    Object::Integer(object::Integer::new(value))
+  }
+
+  #[test]
+  fn test_normalize() {
+    assert_eq!(normalize(BigInt::from(i64::MAX)), int(i64::MAX));
+    assert_eq!(normalize(BigInt::from(i64::MAX) + 1).to_string(), "9223372036854775808");
+    assert!(matches!(normalize(BigInt::from(i64::MAX) + 1), Object::BigInt(_)));
+  }
+
+  #[test]
+  fn test_eval_infix() {
+    assert_eq!(eval_infix(&int(1), &Infix::Plus, &int(2)), None);
+    assert_eq!(eval_infix(&int(1), &Infix::Slash, &int(0)), None);
+    assert_eq!(eval_infix(&int(1), &Infix::Plus, &Object::Null), None);
+
+    let big = eval_infix(&int(i64::MAX), &Infix::Plus, &int(1)).unwrap();
+    assert_eq!(big.to_string(), "9223372036854775808");
+    let tests = vec![
+      (Infix::Minus, int(1), "9223372036854775807"),
+      (Infix::Asterisk, int(2), "18446744073709551616"),
+      (Infix::Slash, int(2), "4611686018427387904"),
+      (Infix::Percent, int(10), "8"),
+      (Infix::Power, int(2), "85070591730234615865843651857942052864"),
+      (Infix::ShiftLeft, int(1), "18446744073709551616"),
+      (Infix::BitAnd, int(-1), "9223372036854775808"),
+      (Infix::Gt, int(i64::MAX), "true"),
+      (Infix::Equal, int(i64::MAX), "false"),
+      (Infix::Slash, int(0), "ArithmeticError: division by zero: 9223372036854775808 / 0."),
+      (Infix::Power, int(-1), "ArithmeticError: negative exponent: 9223372036854775808 ** -1."),
+    ];
+    for (operator, right, expected) in tests {
+      let result = eval_infix(&big, &operator, &right).unwrap();
+      assert_eq!(result.to_string(), expected, "{} {}", operator, right);
+    }
+  }
+
+  #[test]
+  fn test_eval_prefix() {
+    assert_eq!(eval_prefix(&Prefix::Minus, &int(1)), None);
+    let big = eval_prefix(&Prefix::Minus, &int(i64::MIN)).unwrap();
+    assert_eq!(big.to_string(), "9223372036854775808");
+    assert_eq!(eval_prefix(&Prefix::Minus, &big), Some(int(i64::MIN)));
+    assert_eq!(eval_prefix(&Prefix::BitNot, &big).unwrap().to_string(), "-9223372036854775809");
+  }
+}
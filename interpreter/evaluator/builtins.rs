@@ -1,24 +1,137 @@
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::hash::{BuildHasher, Hasher};
 
+use crate::ast::operator::{Infix, Prefix};
+use crate::intern::Name;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
 use crate::utils::{format_object_list};
 use super::object::*;
+use super::environment::Environment;
+use super::macro_expansion;
 
-pub fn new_builtins() -> HashMap<String, Object> {
+pub fn new_builtins() -> HashMap<Name, Object> {
   let mut hash = HashMap::new();
-  hash.insert("len".into(), Object::Builtin(Builtin::new(len)));
-  hash.insert("first".into(), Object::Builtin(Builtin::new(first)));
-  hash.insert("last".into(), Object::Builtin(Builtin::new(last)));
-  hash.insert("rest".into(), Object::Builtin(Builtin::new(rest)));
-  hash.insert("push".into(), Object::Builtin(Builtin::new(push)));
-  hash.insert("insert".into(), Object::Builtin(Builtin::new(insert)));
-  hash.insert("remove".into(), Object::Builtin(Builtin::new(remove)));
-  hash.insert("puts".into(), Object::Builtin(Builtin::new(puts)));
+  hash.insert(Name::new("len"), Object::Builtin(Builtin::new(len)));
+  hash.insert(Name::new("first"), Object::Builtin(Builtin::new(first)));
+  hash.insert(Name::new("last"), Object::Builtin(Builtin::new(last)));
+  hash.insert(Name::new("rest"), Object::Builtin(Builtin::new(rest)));
+  hash.insert(Name::new("push"), Object::Builtin(Builtin::new(push)));
+  hash.insert(Name::new("insert"), Object::Builtin(Builtin::new(insert)));
+  hash.insert(Name::new("remove"), Object::Builtin(Builtin::new(remove)));
+  hash.insert(Name::new("delete"), Object::Builtin(Builtin::new(delete)));
+  hash.insert(Name::new("merge"), Object::Builtin(Builtin::new(merge)));
+  hash.insert(Name::new("has_key"), Object::Builtin(Builtin::new(has_key)));
+  hash.insert(Name::new("puts"), Object::Builtin(Builtin::new(puts)));
+  hash.insert(Name::new("range"), Object::Builtin(Builtin::new(range)));
+  hash.insert(Name::new("assert"), Object::Builtin(Builtin::new(assert)));
+  hash.insert(Name::new("panic"), Object::Builtin(Builtin::new(panic)));
+  hash.insert(Name::new("import"), Object::Builtin(Builtin::new(import)));
+  hash.insert(Name::new("eval"), Object::Builtin(Builtin::new(eval)));
+  hash.insert(Name::new("read_file"), Object::Builtin(Builtin::new(read_file)));
+  hash.insert(Name::new("read_lines"), Object::Builtin(Builtin::new(read_lines)));
+  hash.insert(Name::new("write_file"), Object::Builtin(Builtin::new(write_file)));
+  hash.insert(Name::new("now_ms"), Object::Builtin(Builtin::new(now_ms)));
+  hash.insert(Name::new("clock"), Object::Builtin(Builtin::new(clock)));
+  hash.insert(Name::new("sleep"), Object::Builtin(Builtin::new(sleep)));
+  hash.insert(Name::new("getenv"), Object::Builtin(Builtin::new(getenv)));
+  hash.insert(Name::new("type"), Object::Builtin(Builtin::new(type_of)));
+  hash.insert(Name::new("keys"), Object::Builtin(Builtin::new(keys)));
+  hash.insert(Name::new("values"), Object::Builtin(Builtin::new(values)));
+  hash.insert(Name::new("arity"), Object::Builtin(Builtin::new(arity)));
+  hash.insert(Name::new("to_string"), Object::Builtin(Builtin::new(to_string)));
+  hash.insert(Name::new("to_int"), Object::Builtin(Builtin::new(to_int)));
+  hash.insert(Name::new("to_bool"), Object::Builtin(Builtin::new(to_bool)));
+  hash.insert(Name::new("map"), Object::Builtin(Builtin::new(map)));
+  hash.insert(Name::new("filter"), Object::Builtin(Builtin::new(filter)));
+  hash.insert(Name::new("reduce"), Object::Builtin(Builtin::new(reduce)));
+  hash.insert(Name::new("sort"), Object::Builtin(Builtin::new(sort)));
+  hash.insert(Name::new("reverse"), Object::Builtin(Builtin::new(reverse)));
+  hash.insert(Name::new("index_of"), Object::Builtin(Builtin::new(index_of)));
+  hash.insert(Name::new("slice"), Object::Builtin(Builtin::new(slice)));
+  hash.insert(Name::new("concat"), Object::Builtin(Builtin::new(concat)));
+  hash.insert(Name::new("split"), Object::Builtin(Builtin::new(split)));
+  hash.insert(Name::new("join"), Object::Builtin(Builtin::new(join)));
+  hash.insert(Name::new("trim"), Object::Builtin(Builtin::new(trim)));
+  hash.insert(Name::new("upper"), Object::Builtin(Builtin::new(upper)));
+  hash.insert(Name::new("lower"), Object::Builtin(Builtin::new(lower)));
+  hash.insert(Name::new("replace"), Object::Builtin(Builtin::new(replace)));
+  hash.insert(Name::new("contains"), Object::Builtin(Builtin::new(contains)));
+  hash.insert(Name::new("starts_with"), Object::Builtin(Builtin::new(starts_with)));
+  hash.insert(Name::new("ends_with"), Object::Builtin(Builtin::new(ends_with)));
+  hash.insert(Name::new("chars"), Object::Builtin(Builtin::new(chars)));
+  hash.insert(Name::new("abs"), Object::Builtin(Builtin::new(abs)));
+  hash.insert(Name::new("min"), Object::Builtin(Builtin::new(min)));
+  hash.insert(Name::new("max"), Object::Builtin(Builtin::new(max)));
+  hash.insert(Name::new("pow"), Object::Builtin(Builtin::new(pow)));
+  hash.insert(Name::new("sqrt"), Object::Builtin(Builtin::new(sqrt)));
+  hash.insert(Name::new("floor"), Object::Builtin(Builtin::new(floor)));
+  hash.insert(Name::new("ceil"), Object::Builtin(Builtin::new(ceil)));
+  hash.insert(Name::new("random"), Object::Builtin(Builtin::new(random)));
+  #[cfg(feature = "serde_json")]
+  {
+    hash.insert(Name::new("json_parse"), Object::Builtin(Builtin::new(super::json::json_parse)));
+    hash.insert(Name::new("json_stringify"), Object::Builtin(Builtin::new(super::json::json_stringify)));
+  }
   hash
 }
 
-fn len(args: Vec<Object>) -> Object {
+// メソッドとして呼べる組み込み関数を型ごとに並べる。どれも受け取る値を最初の引数に取る
+const ARRAY_METHODS: &[&str] = &[
+  "len", "first", "last", "rest", "push", "contains", "index_of", "slice", "concat", "join",
+  "map", "filter", "reduce", "sort", "reverse",
+];
+const STRING_METHODS: &[&str] = &[
+  "len", "split", "trim", "upper", "lower", "replace", "contains", "starts_with", "ends_with", "chars",
+  "slice", "reverse",
+];
+const HASH_METHODS: &[&str] = &["len", "keys", "values", "has_key", "insert", "remove", "delete", "merge"];
+
+/// Returns the function which `receiver.method(args)` calls and the arguments it is called with.
+/// A function stored in a hash or a record as `method`, such as a function of an imported module, is called
+/// with `args` like `(receiver.method)(args)`. Otherwise arrays, strings and hashes each have their own builtin
+/// methods, e.g. `len` and `map` for arrays, which are called with `receiver` as their first argument, and
+/// calling any other method, or a method of any other type, is a TypeError.
+pub fn lookup_method(
+  receiver: Object, method: Name, mut args: Vec<Object>, env: &Rc<RefCell<Environment>>,
+) -> Result<(Object, Vec<Object>), Object> {
+  if let Some(func @ (Object::Func(_) | Object::Closure(_) | Object::Builtin(_) | Object::External(_))) = field(&receiver, method) {
+    return Ok((func.clone(), args));
+  }
+
+  let methods = match receiver {
+    Object::Array(_) => ARRAY_METHODS,
+    Object::Str(_) => STRING_METHODS,
+    Object::Hash(_) => HASH_METHODS,
+    _ => &[],
+  };
+  let builtin = if methods.contains(&method.as_str()) { env.borrow().get_builtin(method) } else { None };
+  match builtin {
+    Some(builtin) => {
+      args.insert(0, receiver);
+      Ok((builtin, args))
+    },
+    None => Err(new_error(ErrorKind::Type, format!("unknown method `{}` for {}.", method, receiver.type_name()))),
+  }
+}
+
+// receiver.method で引ける値。関数ならメソッドより先に呼ぶ
+fn field(receiver: &Object, method: Name) -> Option<&Object> {
+  match receiver {
+    Object::Hash(hash) => hash.pairs.get(&Object::Str(Str::new(method.to_string()))),
+    Object::Record(record) => record.def.field_index(method.as_str()).map(|i| &record.values[i]),
+    _ => None,
+  }
+}
+
+fn len(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 1 {
-    return new_error(format!("wrong number of argument: got={}, want=1.", args.len()));
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
   }
 
   let obj = &args[0];
@@ -26,33 +139,37 @@ fn len(args: Vec<Object>) -> Object {
     Object::Str(s) => Object::Integer(Integer::new(s.value.len() as i64)),
     Object::Array(arr) => Object::Integer(Integer::new(arr.elements.len() as i64)),
     Object::Hash(hash) => Object::Integer(Integer::new(hash.pairs.len() as i64)),
-    _ => new_error(format!("argument to `len` not supported: got={}", obj)),
+    Object::Range(range) => match range.len() {
+      Some(len) => Object::Integer(Integer::new(len)),
+      None => new_error(ErrorKind::Arithmetic, format!("length of {} does not fit in 64 bits.", range)),
+    },
+    _ => new_error(ErrorKind::Type, format!("argument to `len` not supported: got={}", obj)),
   }
 }
 
-fn first(args: Vec<Object>) -> Object {
+fn first(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 1 {
     return new_error(
-      format!("wrong number of argument: got={}, want=1.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len())
     );
   }
 
   let obj = &args[0];
   match obj {
     Object::Array(arr) => {
-      if arr.elements.len() == 0 {
+      if arr.elements.is_empty() {
         return Object::Null;
       }
       arr.elements[0].clone()
     },
-    _ => new_error(format!("argument to `first` must be ARRAY: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `first` must be ARRAY: got={}", obj)),
   }
 }
 
-fn last(args: Vec<Object>) -> Object {
+fn last(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 1 {
     return new_error(
-      format!("wrong number of argument: got={}, want=1.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len())
     );
   }
 
@@ -65,14 +182,14 @@ fn last(args: Vec<Object>) -> Object {
       }
       arr.elements[len - 1].clone()
     },
-    _ => new_error(format!("argument to `last` must be ARRAY: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `last` must be ARRAY: got={}", obj)),
   }
 }
 
-fn rest(args: Vec<Object>) -> Object {
+fn rest(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 1 {
     return new_error(
-      format!("wrong number of argument: got={}, want=1.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len())
     );
   }
 
@@ -85,14 +202,14 @@ fn rest(args: Vec<Object>) -> Object {
       }
       Object::Array(Array::new(arr.elements[1..len].to_vec()))
     },
-    _ => new_error(format!("argument to `rest` must be ARRAY: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `rest` must be ARRAY: got={}", obj)),
   }
 }
 
-fn push(args: Vec<Object>) -> Object {
+fn push(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 2 {
     return new_error(
-      format!("wrong number of argument: got={}, want=2.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len())
     );
   }
 
@@ -103,57 +220,903 @@ fn push(args: Vec<Object>) -> Object {
       new_elements.push(args[1].clone());
       Object::Array(Array::new(new_elements))
     },
-    _ => new_error(format!("argument to `push` must be ARRAY: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `push` must be ARRAY: got={}", obj)),
   }
 }
 
-fn insert(args: Vec<Object>) -> Object {
+fn insert(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 3 {
     return new_error(
-      format!("wrong number of argument: got={}, want=3.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=3.", args.len())
     );
   }
 
   let obj = &args[0];
   let key = &args[1];
   let val = &args[2];
+  if !key.is_hashable() {
+    return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
+  }
   match obj {
     Object::Hash(hash) => {
       let mut new_hash = hash.pairs.clone();
       new_hash.insert(key.clone(), val.clone());
       Object::Hash(Hash::new(new_hash))
     },
-    _ => new_error(format!("argument to `insert` must be HASH: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `insert` must be HASH: got={}", obj)),
   }
 }
 
-fn remove(args: Vec<Object>) -> Object {
+fn remove(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
   if args.len() != 2 {
     return new_error(
-      format!("wrong number of argument: got={}, want=2.", args.len())
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len())
     );
   }
 
   let obj = &args[0];
   let key = &args[1];
+  if !key.is_hashable() {
+    return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
+  }
   match obj {
     Object::Hash(hash) => {
-      if hash.pairs.len() == 0 {
+      if hash.pairs.is_empty() {
         return obj.clone();
       }
       let mut new_hash = hash.pairs.clone();
       new_hash.remove(key);
       Object::Hash(Hash::new(new_hash))
     },
-    _ => new_error(format!("argument to `remove` must be HASH: got={}", obj)),
+    _ => new_error(ErrorKind::Type, format!("argument to `remove` must be HASH: got={}", obj)),
+  }
+}
+
+// delete(hash, key, ...) は remove と違い、いくつものキーを一度に取り除ける
+fn delete(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() < 2 {
+    return new_error(
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=2 or more.", args.len())
+    );
+  }
+
+  let mut args = args.into_iter();
+  let mut pairs = match args.next().unwrap() {
+    Object::Hash(hash) => hash.pairs,
+    obj => return new_error(ErrorKind::Type, format!("argument to `delete` must be HASH: got={}", obj)),
+  };
+  for key in args {
+    if !key.is_hashable() {
+      return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
+    }
+    pairs.remove(&key);
+  }
+  Object::Hash(Hash::new(pairs))
+}
+
+// merge(a, b, ...) はすべてのハッシュの組を集めた新しいハッシュを返す。同じキーは後のものが勝つ
+fn merge(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.is_empty() {
+    return new_error(
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=1 or more.", args.len())
+    );
+  }
+
+  let mut pairs = HashMap::new();
+  for obj in args {
+    match obj {
+      Object::Hash(hash) => pairs.extend(hash.pairs),
+      obj => return new_error(ErrorKind::Type, format!("argument to `merge` must be HASH: got={}", obj)),
+    }
+  }
+  Object::Hash(Hash::new(pairs))
+}
+
+fn has_key(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 2 {
+    return new_error(
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len())
+    );
+  }
+
+  let key = &args[1];
+  if !key.is_hashable() {
+    return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
+  }
+  match &args[0] {
+    Object::Hash(hash) => Object::from(hash.pairs.contains_key(key)),
+    obj => new_error(ErrorKind::Type, format!("argument to `has_key` must be HASH: got={}", obj)),
+  }
+}
+
+fn puts(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let output = env.borrow().output();
+  let mut output = output.borrow_mut();
+  if let Err(err) = writeln!(output, "{}", format_object_list(&args, " ")) {
+    return new_error(ErrorKind::Io, format!("failed to write output: {}", err));
+  }
+  Object::Null
+}
+
+// range(end) または range(start, end)
+fn range(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  let mut bounds = vec![];
+  for arg in &args {
+    match arg {
+      Object::Integer(int) => bounds.push(int.value),
+      _ => return new_error(ErrorKind::Type, format!("argument to `range` must be INTEGER: got={}", arg)),
+    }
+  }
+
+  match bounds[..] {
+    [end] => Object::Range(Range::new(0, end)),
+    [start, end] => Object::Range(Range::new(start, end)),
+    _ => new_error(
+      ErrorKind::Argument, format!("wrong number of argument: got={}, want=1 or 2.", args.len())
+    ),
+  }
+}
+
+// assert(cond) または assert(cond, msg)
+fn assert(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.is_empty() || args.len() > 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1 or 2.", args.len()));
+  }
+
+  let mut args = args.into_iter();
+  let cond = args.next().unwrap();
+  let msg = args.next();
+  if super::is_truthy(cond) {
+    return Object::Null;
+  }
+  match msg {
+    Some(msg) => new_error(ErrorKind::Assertion, message_of(msg)),
+    None => new_error(ErrorKind::Assertion, "assertion failed.".to_string()),
+  }
+}
+
+fn panic(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  new_error(ErrorKind::Panic, message_of(args.into_iter().next().unwrap()))
+}
+
+// map(arr, f) は f(x) を並べた配列を返す
+fn map(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let (elements, mut func) = match callback_args("map", args) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  let mut out = Vec::with_capacity(elements.len());
+  for elm in elements {
+    let result = super::apply_func(&mut func, vec![elm], env);
+    if super::is_error(&result) {
+      return result;
+    }
+    out.push(result);
+  }
+  Object::Array(Array::new(out))
+}
+
+// filter(arr, f) は f(x) が truthy な要素だけの配列を返す
+fn filter(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let (elements, mut func) = match callback_args("filter", args) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  let mut out = vec![];
+  for elm in elements {
+    let result = super::apply_func(&mut func, vec![elm.clone()], env);
+    if super::is_error(&result) {
+      return result;
+    }
+    if super::is_truthy(result) {
+      out.push(elm);
+    }
+  }
+  Object::Array(Array::new(out))
+}
+
+// reduce(arr, initial, f) は initial から順に acc = f(acc, x) を繰り返した値を返す
+fn reduce(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 3 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=3.", args.len()));
+  }
+  let mut args = args.into_iter();
+  let (arr, mut acc) = (args.next().unwrap(), args.next().unwrap());
+  let (elements, mut func) = match callback_args("reduce", vec![arr, args.next().unwrap()]) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  for elm in elements {
+    acc = super::apply_func(&mut func, vec![acc, elm], env);
+    if super::is_error(&acc) {
+      return acc;
+    }
+  }
+  acc
+}
+
+// 配列か範囲の要素と、呼び出せる値の 2 つの引数を取り出す
+fn callback_args(name: &str, args: Vec<Object>) -> Result<(Vec<Object>, Object), Object> {
+  if args.len() != 2 {
+    return Err(new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len())));
+  }
+  let mut args = args.into_iter();
+  let elements = match args.next().unwrap() {
+    Object::Array(arr) => arr.elements,
+    Object::Range(range) => range.iter().map(|i| Object::Integer(Integer::new(i))).collect(),
+    obj => return Err(new_error(ErrorKind::Type, format!("argument to `{}` must be ARRAY: got={}", name, obj))),
+  };
+  match args.next().unwrap() {
+    func @ Object::Func(_) | func @ Object::Builtin(_) | func @ Object::Closure(_) | func @ Object::External(_) => Ok((elements, func)),
+    obj => Err(new_error(ErrorKind::Type, format!("argument to `{}` must be FUNCTION: got={}", name, obj))),
+  }
+}
+
+// sort(arr) は < で、sort(arr, f) は f(a, b) が true か負の整数なら a を b より前にして並べる。順番は安定している
+fn sort(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 && args.len() != 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1 or 2.", args.len()));
+  }
+  let mut args = args.into_iter();
+  let elements = match args.next().unwrap() {
+    Object::Array(arr) => arr.elements,
+    Object::Range(range) => range.iter().map(|i| Object::Integer(Integer::new(i))).collect(),
+    obj => return new_error(ErrorKind::Type, format!("argument to `sort` must be ARRAY: got={}", obj)),
+  };
+
+  let result = match args.next() {
+    None => merge_sort(elements, &mut |a, b| {
+      match super::eval_infix_expression(a.clone(), &Infix::Lt, b.clone()) {
+        Object::Boolean(less) => Ok(less.value),
+        err => Err(err),
+      }
+    }),
+    Some(mut func @ Object::Func(_)) | Some(mut func @ Object::Builtin(_))
+    | Some(mut func @ Object::Closure(_)) | Some(mut func @ Object::External(_)) => merge_sort(elements, &mut |a, b| {
+      match super::apply_func(&mut func, vec![a.clone(), b.clone()], env) {
+        Object::Boolean(less) => Ok(less.value),
+        Object::Integer(order) => Ok(order.value < 0),
+        obj if super::is_error(&obj) => Err(obj),
+        obj => Err(new_error(ErrorKind::Type, format!("comparator of `sort` must return BOOLEAN or INTEGER: got={}", obj))),
+      }
+    }),
+    Some(obj) => return new_error(ErrorKind::Type, format!("argument to `sort` must be FUNCTION: got={}", obj)),
+  };
+  match result {
+    Ok(elements) => Object::Array(Array::new(elements)),
+    Err(err) => err,
+  }
+}
+
+// 比較関数は Monkey で書かれていて全順序とは限らないので、slice::sort_by ではなく自前のマージソートを使う
+fn merge_sort(mut elements: Vec<Object>, less: &mut dyn FnMut(&Object, &Object) -> Result<bool, Object>) -> Result<Vec<Object>, Object> {
+  if elements.len() <= 1 {
+    return Ok(elements);
+  }
+  let right = elements.split_off(elements.len() / 2);
+  let left = merge_sort(elements, less)?;
+  let right = merge_sort(right, less)?;
+
+  let mut merged = Vec::with_capacity(left.len() + right.len());
+  let mut left = left.into_iter().peekable();
+  let mut right = right.into_iter().peekable();
+  while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+    // 等しいときは左を先にして、元の順番を保つ
+    if less(r, l)? {
+      merged.push(right.next().unwrap());
+    } else {
+      merged.push(left.next().unwrap());
+    }
+  }
+  merged.extend(left);
+  merged.extend(right);
+  Ok(merged)
+}
+
+// reverse(x) は配列の要素か文字列の文字を逆に並べる
+fn reverse(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  match args.into_iter().next().unwrap() {
+    Object::Array(arr) => Object::Array(Array::new(arr.elements.into_iter().rev().collect())),
+    Object::Str(s) => Object::from(s.value.chars().rev().collect::<String>()),
+    obj => new_error(ErrorKind::Type, format!("argument to `reverse` must be ARRAY or STRING: got={}", obj)),
   }
 }
 
-fn puts(args: Vec<Object>) -> Object {
-  println!("{}", format_object_list(&args, " "));
-  return Object::Null;
+// index_of(arr, x) は == で x と等しい最初の要素の位置を返す。なければ null を返す
+fn index_of(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len()));
+  }
+  match &args[0] {
+    Object::Array(arr) => Object::from(arr.elements.iter().position(|elm| *elm == args[1]).map(|i| i as i64)),
+    obj => new_error(ErrorKind::Type, format!("argument to `index_of` must be ARRAY: got={}", obj)),
+  }
+}
+
+// slice(x, start, end) は x[start:end] と同じ。end を省くと末尾まで切り出す
+fn slice(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 2 && args.len() != 3 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=2 or 3.", args.len()));
+  }
+  let mut args = args.into_iter();
+  let (obj, start) = (args.next().unwrap(), args.next().unwrap());
+  match obj {
+    Object::Array(_) | Object::Str(_) => super::slice_object(obj, start, args.next().unwrap_or(Object::Null)),
+    obj => new_error(ErrorKind::Type, format!("argument to `slice` must be ARRAY or STRING: got={}", obj)),
+  }
+}
+
+// concat(a, b, ...) はすべての配列をつないだ新しい配列を返す
+fn concat(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  let mut elements = vec![];
+  for obj in args {
+    match obj {
+      Object::Array(arr) => elements.extend(arr.elements),
+      obj => return new_error(ErrorKind::Type, format!("argument to `concat` must be ARRAY: got={}", obj)),
+    }
+  }
+  Object::Array(Array::new(elements))
+}
+
+// split(s, sep) は sep で区切った文字列の配列を返す。sep が空なら 1 文字ずつに分ける
+fn split(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  let args = match string_args("split", args, 2) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  if args[1].is_empty() {
+    return chars_of(&args[0]);
+  }
+  Object::from(args[0].split(args[1].as_str()).collect::<Vec<&str>>())
+}
+
+// join(arr, sep) は文字列の配列を sep でつないだ文字列を返す
+fn join(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len()));
+  }
+
+  let sep = match &args[1] {
+    Object::Str(s) => &s.value,
+    obj => return new_error(ErrorKind::Type, format!("argument to `join` must be STRING: got={}", obj)),
+  };
+  let elements = match &args[0] {
+    Object::Array(arr) => &arr.elements,
+    obj => return new_error(ErrorKind::Type, format!("argument to `join` must be ARRAY: got={}", obj)),
+  };
+  let mut parts = Vec::with_capacity(elements.len());
+  for elm in elements {
+    match elm {
+      Object::Str(s) => parts.push(s.value.as_str()),
+      obj => return new_error(ErrorKind::Type, format!("elements of `join` must be STRING: got={}", obj)),
+    }
+  }
+  Object::from(parts.join(sep))
+}
+
+fn trim(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("trim", args, 1) {
+    Ok(args) => Object::from(args[0].trim()),
+    Err(err) => err,
+  }
+}
+
+fn upper(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("upper", args, 1) {
+    Ok(args) => Object::from(args[0].to_uppercase()),
+    Err(err) => err,
+  }
+}
+
+fn lower(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("lower", args, 1) {
+    Ok(args) => Object::from(args[0].to_lowercase()),
+    Err(err) => err,
+  }
+}
+
+// replace(s, from, to) は s の中の from をすべて to に置き換える
+fn replace(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("replace", args, 3) {
+    Ok(args) => Object::from(args[0].replace(args[1].as_str(), &args[2])),
+    Err(err) => err,
+  }
+}
+
+// contains(s, sub) は部分文字列を、contains(arr, x) は == で等しい要素を探す
+fn contains(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if let [Object::Array(arr), x] = args.as_slice() {
+    return Object::from(arr.elements.contains(x));
+  }
+  match string_args("contains", args, 2) {
+    Ok(args) => Object::from(args[0].contains(args[1].as_str())),
+    Err(err) => err,
+  }
+}
+
+fn starts_with(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("starts_with", args, 2) {
+    Ok(args) => Object::from(args[0].starts_with(args[1].as_str())),
+    Err(err) => err,
+  }
+}
+
+fn ends_with(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("ends_with", args, 2) {
+    Ok(args) => Object::from(args[0].ends_with(args[1].as_str())),
+    Err(err) => err,
+  }
+}
+
+// chars(s) は s を 1 文字ずつの文字列の配列にする。バイトではなく Unicode のスカラー値で分ける
+fn chars(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match string_args("chars", args, 1) {
+    Ok(args) => chars_of(&args[0]),
+    Err(err) => err,
+  }
+}
+
+fn chars_of(s: &str) -> Object {
+  Object::from(s.chars().map(String::from).collect::<Vec<String>>())
+}
+
+// 引数がすべて文字列で、want 個あることを確かめて取り出す
+fn string_args(name: &str, args: Vec<Object>, want: usize) -> Result<Vec<String>, Object> {
+  if args.len() != want {
+    return Err(new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want={}.", args.len(), want)));
+  }
+  args.into_iter()
+    .map(|arg| match arg {
+      Object::Str(s) => Ok(s.value),
+      obj => Err(new_error(ErrorKind::Type, format!("argument to `{}` must be STRING: got={}", name, obj))),
+    })
+    .collect()
+}
+
+// abs(x) は x の絶対値を返す。整数なら整数を返す
+fn abs(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  let x = args.into_iter().next().unwrap();
+  if let Object::Float(f) = &x {
+    return Object::from(f.value.abs());
+  }
+  match number_arg("abs", &x) {
+    // -x と同じく、i64 に収まらなければ --bigint のときだけ BigInt にする
+    Ok(n) if n < 0.0 => match super::eval_prefix_in_env(&Prefix::Minus, x.clone(), env) {
+      Object::Error(_) => new_error(ErrorKind::Arithmetic, format!("integer overflow: abs({}).", x)),
+      obj => obj,
+    },
+    Ok(_) => x,
+    Err(err) => err,
+  }
+}
+
+// min(a, b, ...) は一番小さい数を返す。配列か範囲を一つだけ渡すとその要素から選び、空なら null を返す
+fn min(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  extremum("min", args, Infix::Lt)
+}
+
+fn max(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  extremum("max", args, Infix::Gt)
+}
+
+// 整数と浮動小数点数は < と同じく比べ、同じ大きさなら先の引数を返す
+fn extremum(name: &str, args: Vec<Object>, operator: Infix) -> Object {
+  let values = match args.as_slice() {
+    [] => return new_error(ErrorKind::Argument, "wrong number of argument: got=0, want=1 or more.".to_string()),
+    [Object::Array(arr)] => arr.elements.clone(),
+    [Object::Range(range)] => range.iter().map(Object::from).collect(),
+    _ => args,
+  };
+  let mut best: Option<Object> = None;
+  for val in values {
+    if let Err(err) = number_arg(name, &val) {
+      return err;
+    }
+    best = match best {
+      Some(best) if !super::is_truthy(super::eval_infix_expression(val.clone(), &operator, best.clone())) => Some(best),
+      _ => Some(val),
+    };
+  }
+  best.unwrap_or(Object::Null)
+}
+
+// pow(x, y) は x ** y と同じ。整数どうしなら整数を返す
+fn pow(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=2.", args.len()));
+  }
+  let mut args = args.into_iter();
+  let (x, y) = (args.next().unwrap(), args.next().unwrap());
+  for obj in [&x, &y] {
+    if let Err(err) = number_arg("pow", obj) {
+      return err;
+    }
+  }
+  super::eval_infix_expression(x, &Infix::Power, y)
+}
+
+fn sqrt(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  match number_arg("sqrt", &args[0]) {
+    Ok(n) if n < 0.0 => new_error(ErrorKind::Arithmetic, format!("square root of negative number: {}.", args[0])),
+    Ok(n) => Object::from(n.sqrt()),
+    Err(err) => err,
+  }
+}
+
+fn floor(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  round_to_integer("floor", args, f64::floor)
+}
+
+fn ceil(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  round_to_integer("ceil", args, f64::ceil)
+}
+
+// 浮動小数点数を丸めて整数にする。整数はそのまま返す
+fn round_to_integer(name: &str, args: Vec<Object>, round: fn(f64) -> f64) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  match &args[0] {
+    Object::Integer(_) => args[0].clone(),
+    #[cfg(feature = "bigint")]
+    Object::BigInt(_) => args[0].clone(),
+    Object::Float(f) => {
+      let n = round(f.value);
+      // i64::MAX as f64 は 2^63 に丸められるので、それ未満なら収まる
+      if n.is_nan() || n < i64::MIN as f64 || n >= i64::MAX as f64 {
+        return new_error(ErrorKind::Arithmetic, format!("integer overflow: {}({}).", name, f));
+      }
+      Object::from(n as i64)
+    },
+    obj => new_error(ErrorKind::Type, format!("argument to `{}` must be NUMBER: got={}", name, obj)),
+  }
+}
+
+fn number_arg(name: &str, obj: &Object) -> Result<f64, Object> {
+  match obj {
+    Object::Integer(i) => Ok(i.value as f64),
+    #[cfg(feature = "bigint")]
+    Object::BigInt(big) => Ok(big.to_f64()),
+    Object::Float(f) => Ok(f.value),
+    obj => Err(new_error(ErrorKind::Type, format!("argument to `{}` must be NUMBER: got={}", name, obj))),
+  }
+}
+
+// random() は 0 以上 1 未満の浮動小数点数、random(n) は 0 以上 n 未満の整数を返す
+fn random(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match args.as_slice() {
+    [] => Object::from((next_random() >> 11) as f64 / (1u64 << 53) as f64),
+    [Object::Integer(n)] if n.value > 0 => Object::from((next_random() % n.value as u64) as i64),
+    [obj] => new_error(ErrorKind::Type, format!("argument to `random` must be a positive INTEGER: got={}", obj)),
+    _ => new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=0 or 1.", args.len())),
+  }
+}
+
+thread_local! {
+  // 依存を増やさないように、標準ライブラリの RandomState が持つ乱数で xorshift の種を作る
+  static RANDOM_STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+// xorshift64*
+fn next_random() -> u64 {
+  RANDOM_STATE.with(|state| {
+    let mut x = state.get();
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    state.set(x);
+    x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+  })
+}
+
+// import("path/to/module") はファイルのトップレベルの束縛をハッシュにして返す
+// import はファイルを読むので、read_file と同じく filesystem の許可がいる
+fn import(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if !env.borrow().capabilities().filesystem {
+    return new_error(ErrorKind::Permission, "`import` is not allowed to access the filesystem.".to_string());
+  }
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+
+  let path = match &args[0] {
+    Object::Str(s) => s.value.clone(),
+    obj => return new_error(ErrorKind::Type, format!("argument to `import` must be STRING: got={}", obj)),
+  };
+  let modules = env.borrow().modules();
+  modules.import(&path, env)
+}
+
+// eval(code) は code を呼び出したスコープで評価する。eval(code, true) は import と同じく新しい環境で評価する
+fn eval(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if !env.borrow().capabilities().eval {
+    return new_error(ErrorKind::Permission, "`eval` is not allowed to evaluate code.".to_string());
+  }
+  if args.len() != 1 && args.len() != 2 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1 or 2.", args.len()));
+  }
+
+  let code = match &args[0] {
+    Object::Str(s) => s.value.clone(),
+    obj => return new_error(ErrorKind::Type, format!("argument to `eval` must be STRING: got={}", obj)),
+  };
+  let fresh = match args.get(1) {
+    None => false,
+    Some(Object::Boolean(b)) => b.value,
+    Some(obj) => return new_error(ErrorKind::Type, format!("second argument to `eval` must be BOOLEAN: got={}", obj)),
+  };
+
+  let mut p = Parser::new(Lexer::new(code));
+  let mut program = p.parse_program();
+  if let Some(err) = p.errors.first() {
+    return new_error(ErrorKind::Syntax, format!("invalid syntax: {}: {}", err.span(), err));
+  }
+
+  let target = if fresh { Environment::new_module_env(env, "<eval>") } else { Rc::clone(env) };
+  macro_expansion::define_macros(&mut program, &target);
+  let result = match macro_expansion::expand_macros(program, &target) {
+    Ok(program) => super::eval(program, &target),
+    Err(err) => err,
+  };
+
+  // 位置は文字列の中のものなので、メッセージに移して eval を呼び出した位置を付けさせる
+  match result {
+    Object::Error(mut err) => {
+      if let Some(span) = err.span.take() {
+        err.message = format!("in eval at {}: {}", span, err.message);
+      }
+      err.trace.clear();
+      Object::Error(err)
+    },
+    obj => obj,
+  }
+}
+
+fn read_file(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let args = match file_args("read_file", args, 1, env) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  match fs::read_to_string(&args[0]) {
+    Ok(content) => Object::from(content),
+    Err(err) => new_error(ErrorKind::Io, format!("could not read {}: {}.", args[0], err)),
+  }
+}
+
+// read_lines(path) は改行を取り除いた行の配列を返す
+fn read_lines(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let args = match file_args("read_lines", args, 1, env) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  match fs::read_to_string(&args[0]) {
+    Ok(content) => Object::from(content.lines().collect::<Vec<&str>>()),
+    Err(err) => new_error(ErrorKind::Io, format!("could not read {}: {}.", args[0], err)),
+  }
+}
+
+// write_file(path, content) はファイルを content で置き換える
+fn write_file(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  let args = match file_args("write_file", args, 2, env) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  match fs::write(&args[0], &args[1]) {
+    Ok(()) => Object::Null,
+    Err(err) => new_error(ErrorKind::Io, format!("could not write {}: {}.", args[0], err)),
+  }
+}
+
+// ファイルを扱う組み込み関数は、環境がファイルシステムへのアクセスを許しているときだけ使える
+fn file_args(name: &str, args: Vec<Object>, want: usize, env: &Rc<RefCell<Environment>>) -> Result<Vec<String>, Object> {
+  if !env.borrow().capabilities().filesystem {
+    return Err(new_error(ErrorKind::Permission, format!("`{}` is not allowed to access the filesystem.", name)));
+  }
+  string_args(name, args, want)
+}
+
+// now_ms() は UNIX エポックからのミリ秒を返す
+fn now_ms(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if let Err(err) = clock_args("now_ms", &args, 0, env) {
+    return err;
+  }
+  match SystemTime::now().duration_since(UNIX_EPOCH) {
+    Ok(elapsed) => Object::from(elapsed.as_millis() as i64),
+    Err(err) => new_error(ErrorKind::Io, format!("could not read the clock: {}.", err)),
+  }
+}
+
+// clock() はある時点からの秒数を浮動小数点数で返す。2 回呼んだ差で時間を測る
+fn clock(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if let Err(err) = clock_args("clock", &args, 0, env) {
+    return err;
+  }
+  Object::from(CLOCK_START.with(|start| start.elapsed().as_secs_f64()))
+}
+
+thread_local! {
+  static CLOCK_START: Instant = Instant::now();
+}
+
+// sleep(ms) は ms ミリ秒待つ。途中でタイムアウトやキャンセルがあればすぐに止まる
+fn sleep(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if let Err(err) = clock_args("sleep", &args, 1, env) {
+    return err;
+  }
+  let ms = match &args[0] {
+    Object::Integer(ms) if ms.value >= 0 => ms.value as u64,
+    obj => return new_error(ErrorKind::Type, format!("argument to `sleep` must be a non-negative INTEGER: got={}", obj)),
+  };
+  let budget = env.borrow().budget();
+  match budget.sleep(Duration::from_millis(ms)) {
+    Ok(()) => Object::Null,
+    Err(err) => err,
+  }
+}
+
+// 時計を使う組み込み関数は、環境が許しているときだけ使える
+fn clock_args(name: &str, args: &[Object], want: usize, env: &Rc<RefCell<Environment>>) -> Result<(), Object> {
+  if !env.borrow().capabilities().clock {
+    return Err(new_error(ErrorKind::Permission, format!("`{}` is not allowed to use the clock.", name)));
+  }
+  if super::limits::now().is_none() {
+    return Err(new_error(ErrorKind::Io, "the clock is not available on this platform.".to_string()));
+  }
+  if args.len() != want {
+    return Err(new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want={}.", args.len(), want)));
+  }
+  Ok(())
+}
+
+// getenv(name) は環境変数の値を返す。設定されていなければ null を返す
+fn getenv(args: Vec<Object>, env: &Rc<RefCell<Environment>>) -> Object {
+  if !env.borrow().capabilities().env {
+    return new_error(ErrorKind::Permission, "`getenv` is not allowed to read the environment variables.".to_string());
+  }
+  let args = match string_args("getenv", args, 1) {
+    Ok(args) => args,
+    Err(err) => return err,
+  };
+  match std::env::var(&args[0]) {
+    Ok(value) => Object::from(value),
+    Err(std::env::VarError::NotPresent) => Object::Null,
+    Err(err) => new_error(ErrorKind::Io, format!("could not read {}: {}.", args[0], err)),
+  }
+}
+
+// type(x) はエラーメッセージと同じ大文字の型名を返す
+fn type_of(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  Object::from(args[0].type_name().to_uppercase())
+}
+
+fn keys(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match sorted_pairs("keys", args) {
+    Ok(pairs) => Object::Array(Array::new(pairs.into_iter().map(|(key, _)| key).collect())),
+    Err(err) => err,
+  }
+}
+
+fn values(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  match sorted_pairs("values", args) {
+    Ok(pairs) => Object::Array(Array::new(pairs.into_iter().map(|(_, val)| val).collect())),
+    Err(err) => err,
+  }
+}
+
+fn sorted_pairs(name: &str, args: Vec<Object>) -> Result<Vec<(Object, Object)>, Object> {
+  if args.len() != 1 {
+    return Err(new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len())));
+  }
+  match args.into_iter().next().unwrap() {
+    Object::Hash(hash) => Ok(sort_pairs(hash.pairs)),
+    obj => Err(new_error(ErrorKind::Type, format!("argument to `{}` must be HASH: got={}", name, obj))),
+  }
+}
+
+/// Returns the pairs of a hash sorted by key: integers, then booleans, then strings, each by value.
+/// `keys`, `values` and `for` all go through this, so they see the pairs in the same order on every run.
+pub(crate) fn sort_pairs(pairs: HashMap<Object, Object>) -> Vec<(Object, Object)> {
+  let mut pairs: Vec<(Object, Object)> = pairs.into_iter().collect();
+  pairs.sort_by(|(a, _), (b, _)| match (a, b) {
+    (Object::Integer(a), Object::Integer(b)) => a.value.cmp(&b.value),
+    #[cfg(feature = "bigint")]
+    (Object::BigInt(a), Object::BigInt(b)) => a.value.cmp(&b.value),
+    #[cfg(feature = "bigint")]
+    (Object::Integer(a), Object::BigInt(b)) => num_bigint::BigInt::from(a.value).cmp(&b.value),
+    #[cfg(feature = "bigint")]
+    (Object::BigInt(a), Object::Integer(b)) => a.value.cmp(&num_bigint::BigInt::from(b.value)),
+    (Object::Boolean(a), Object::Boolean(b)) => a.value.cmp(&b.value),
+    (Object::Str(a), Object::Str(b)) => a.value.cmp(&b.value),
+    _ => key_rank(a).cmp(&key_rank(b)),
+  });
+  pairs
+}
+
+fn key_rank(key: &Object) -> u8 {
+  match key {
+    Object::Integer(_) => 0,
+    #[cfg(feature = "bigint")]
+    Object::BigInt(_) => 0,
+    Object::Boolean(_) => 1,
+    Object::Str(_) => 2,
+    _ => 3,
+  }
+}
+
+// arity(f) は引数の数を返す。デフォルト値のある引数も数える。組み込み関数は決まっていないので null を返す
+fn arity(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  match &args[0] {
+    Object::Func(func) => Object::from(func.args.len() as i64),
+    Object::Closure(closure) => Object::from(closure.func.num_params as i64),
+    Object::Builtin(_) | Object::External(_) => Object::Null,
+    obj => new_error(ErrorKind::Type, format!("argument to `arity` must be FUNCTION: got={}", obj)),
+  }
+}
+
+// to_string(x) は文字列ならそのまま、それ以外は表示した形を返す
+fn to_string(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  Object::from(message_of(args.into_iter().next().unwrap()))
+}
+
+// to_int(x) は浮動小数点数を 0 の方へ切り捨て、文字列は前後の空白を除いて 10 進数として読む
+fn to_int(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  let obj = &args[0];
+  match obj {
+    Object::Integer(_) => obj.clone(),
+    #[cfg(feature = "bigint")]
+    Object::BigInt(_) => obj.clone(),
+    Object::Float(f) if f.value.is_finite() && f.value >= i64::MIN as f64 && f.value < i64::MAX as f64 => {
+      Object::from(f.value as i64)
+    },
+    Object::Boolean(b) => Object::from(b.value as i64),
+    Object::Str(s) => match s.value.trim().parse::<i64>() {
+      Ok(i) => Object::from(i),
+      Err(_) => new_error(ErrorKind::Type, format!("cannot convert {} to INTEGER.", obj)),
+    },
+    _ => new_error(ErrorKind::Type, format!("cannot convert {} to INTEGER.", obj)),
+  }
+}
+
+// to_bool(x) は if の条件と同じく、false と null だけを false にする
+fn to_bool(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+  Object::from(super::is_truthy(args.into_iter().next().unwrap()))
+}
+
+// 文字列はそのまま、それ以外は表示した形をメッセージにする
+fn message_of(obj: Object) -> String {
+  match obj {
+    Object::Str(s) => s.value,
+    _ => obj.to_string(),
+  }
 }
 
-fn new_error(msg: String) -> Object {
-  Object::Error(Error::new(msg))
+fn new_error(kind: ErrorKind, msg: String) -> Object {
+  Object::Error(Box::new(Error::new(kind, msg)))
 }
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash;
+
+use super::object::{Object, Integer, Float, Boolean, Str, Array, Hash, Error, ErrorKind};
+
+// 変換に失敗したときの Error は register_fn に渡した関数からそのまま返せる
+fn type_error(want: &str, got: &Object) -> Error {
+  Error::new(ErrorKind::Type, format!("expected {}: got={}.", want, got.type_name()))
+}
+
+impl From<i64> for Object {
+  fn from(value: i64) -> Object {
+    Object::Integer(Integer::new(value))
+  }
+}
+
+impl From<f64> for Object {
+  fn from(value: f64) -> Object {
+    Object::Float(Float::new(value))
+  }
+}
+
+impl From<bool> for Object {
+  fn from(value: bool) -> Object {
+    Object::Boolean(Boolean { value })
+  }
+}
+
+impl From<String> for Object {
+  fn from(value: String) -> Object {
+    Object::Str(Str::new(value))
+  }
+}
+
+impl From<&str> for Object {
+  fn from(value: &str) -> Object {
+    Object::Str(Str::new(value.to_string()))
+  }
+}
+
+/// `None` becomes `null`.
+impl<T: Into<Object>> From<Option<T>> for Object {
+  fn from(value: Option<T>) -> Object {
+    value.map_or(Object::Null, Into::into)
+  }
+}
+
+impl<T: Into<Object>> From<Vec<T>> for Object {
+  fn from(value: Vec<T>) -> Object {
+    Object::Array(Array::new(value.into_iter().map(Into::into).collect()))
+  }
+}
+
+/// Rust types which are converted to objects that can be used as a key of `Hash`.
+pub trait Hashable {}
+
+impl Hashable for i64 {}
+impl Hashable for bool {}
+impl Hashable for String {}
+impl Hashable for &str {}
+
+impl<K: Hashable + Into<Object>, V: Into<Object>> From<HashMap<K, V>> for Object {
+  fn from(value: HashMap<K, V>) -> Object {
+    let pairs = value.into_iter().map(|(key, val)| (key.into(), val.into())).collect();
+    Object::Hash(Hash::new(pairs))
+  }
+}
+
+impl TryFrom<Object> for i64 {
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<i64, Error> {
+    match obj {
+      Object::Integer(int) => Ok(int.value),
+      _ => Err(type_error("integer", &obj)),
+    }
+  }
+}
+
+/// Integers are accepted too, as they are mixed with floats in arithmetic.
+impl TryFrom<Object> for f64 {
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<f64, Error> {
+    match obj {
+      Object::Float(float) => Ok(float.value),
+      Object::Integer(int) => Ok(int.value as f64),
+      _ => Err(type_error("float", &obj)),
+    }
+  }
+}
+
+impl TryFrom<Object> for bool {
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<bool, Error> {
+    match obj {
+      Object::Boolean(val) => Ok(val.value),
+      _ => Err(type_error("boolean", &obj)),
+    }
+  }
+}
+
+impl TryFrom<Object> for String {
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<String, Error> {
+    match obj {
+      Object::Str(s) => Ok(s.value),
+      _ => Err(type_error("string", &obj)),
+    }
+  }
+}
+
+impl<T: TryFrom<Object, Error = Error>> TryFrom<Object> for Vec<T> {
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<Vec<T>, Error> {
+    match obj {
+      Object::Array(arr) => arr.elements.into_iter().map(T::try_from).collect(),
+      _ => Err(type_error("array", &obj)),
+    }
+  }
+}
+
+impl<K, V> TryFrom<Object> for HashMap<K, V>
+where
+  K: TryFrom<Object, Error = Error> + Eq + hash::Hash,
+  V: TryFrom<Object, Error = Error>,
+{
+  type Error = Error;
+
+  fn try_from(obj: Object) -> Result<HashMap<K, V>, Error> {
+    match obj {
+      Object::Hash(hash) => hash.pairs.into_iter().map(|(key, val)| Ok((K::try_from(key)?, V::try_from(val)?))).collect(),
+      _ => Err(type_error("hash", &obj)),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_into_object() {
+    assert_eq!(Object::from(3).to_string(), "3");
+    assert_eq!(Object::from(1.5).to_string(), "1.5");
+    assert_eq!(Object::from(true).to_string(), "true");
+    assert_eq!(Object::from("a").to_string(), "\"a\"");
+    assert_eq!(Object::from(None::<i64>).to_string(), "null");
+    assert_eq!(Object::from(vec![Some(1), None]).to_string(), "[1, null]");
+
+    let mut map = HashMap::new();
+    map.insert("a".to_string(), vec![1, 2]);
+    assert_eq!(Object::from(map).to_string(), "{\"a\": [1, 2]}");
+  }
+
+  #[test]
+  fn test_from_object() {
+    assert_eq!(i64::try_from(Object::from(3)), Ok(3));
+    assert_eq!(f64::try_from(Object::from(3)), Ok(3.0));
+    assert_eq!(String::try_from(Object::from("a")), Ok("a".to_string()));
+    assert_eq!(Vec::<bool>::try_from(Object::from(vec![true, false])), Ok(vec![true, false]));
+
+    let mut map = HashMap::new();
+    map.insert(1, "one".to_string());
+    assert_eq!(HashMap::<i64, String>::try_from(Object::from(map.clone())), Ok(map));
+
+    let err = Vec::<i64>::try_from(Object::from(vec!["a"])).unwrap_err();
+    assert_eq!(err.to_string(), "TypeError: expected integer: got=string.");
+    assert_eq!(bool::try_from(Object::Null).unwrap_err().kind, ErrorKind::Type);
+  }
+}
@@ -0,0 +1,133 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+use crate::ast::Program;
+use crate::ast::stmt::Statement;
+use crate::ast::visit::{self, Visitor};
+use super::observer::EvalObserver;
+
+/// Counts how many times each statement is executed. Install a clone with `Environment::set_observer`,
+/// evaluate the program, and then make a report with `lcov` or `annotate` from the same clone of `Coverage`.
+///
+/// A report lists the statements of the program as it was parsed, so evaluate that program rather than
+/// its `optimize`d one, whose folded branches would be reported as not executed.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+  // 文の開始位置ごとの実行回数
+  hits: Rc<RefCell<HashMap<usize, u64>>>,
+}
+
+impl Coverage {
+  pub fn new() -> Coverage {
+    Coverage::default()
+  }
+
+  /// How many times `stmt` has been executed.
+  pub fn hits(&self, stmt: &Statement) -> u64 {
+    self.hits.borrow().get(&stmt.span().start.offset).copied().unwrap_or(0)
+  }
+
+  /// The lines of `program` which have a statement, with how many times the statements starting on each line
+  /// were executed. When a line has several statements the largest count is used.
+  pub fn lines(&self, program: &Program) -> BTreeMap<usize, u64> {
+    let mut statements = Statements::default();
+    statements.visit_program(program);
+    let mut lines = BTreeMap::new();
+    for stmt in statements.statements {
+      let count = lines.entry(stmt.span().start.line).or_insert(0);
+      *count = self.hits(stmt).max(*count);
+    }
+    lines
+  }
+
+  /// Formats the coverage of `program`, which was read from `path`, as an lcov tracefile.
+  pub fn lcov(&self, program: &Program, path: &str) -> String {
+    let lines = self.lines(program);
+    let mut out = format!("TN:\nSF:{}\n", path);
+    for (line, count) in &lines {
+      let _ = writeln!(out, "DA:{},{}", line, count);
+    }
+    let hit = lines.values().filter(|count| **count > 0).count();
+    let _ = write!(out, "LF:{}\nLH:{}\nend_of_record\n", lines.len(), hit);
+    out
+  }
+
+  /// Prefixes each line of `source`, from which `program` was parsed, with how many times it was executed,
+  /// like gcov: `#####` marks a line which was never executed, and `-` one without statements.
+  pub fn annotate(&self, program: &Program, source: &str) -> String {
+    let lines = self.lines(program);
+    let mut out = String::new();
+    for (i, text) in source.lines().enumerate() {
+      let count = match lines.get(&(i + 1)) {
+        Some(0) => "#####".to_string(),
+        Some(count) => count.to_string(),
+        None => "-".to_string(),
+      };
+      let _ = writeln!(out, "{:>9}:{:>5}: {}", count, i + 1, text);
+    }
+    out
+  }
+}
+
+impl EvalObserver for Coverage {
+  fn enter_statement(&mut self, stmt: &Statement) {
+    *self.hits.borrow_mut().entry(stmt.span().start.offset).or_insert(0) += 1;
+  }
+}
+
+// 関数の本体や if のブロックの中の文も集める
+#[derive(Default)]
+struct Statements<'a> {
+  statements: Vec<&'a Statement>,
+}
+
+impl<'a> Visitor<'a> for Statements<'a> {
+  fn visit_statement(&mut self, stmt: &'a Statement) {
+    if let Statement::Comment(_) = stmt {
+      return;
+    }
+    self.statements.push(stmt);
+    visit::walk_statement(self, stmt);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use crate::evaluator::{self, builtins, environment::Environment};
+
+  #[test]
+  fn test_coverage() {
+    let input = "let magnitude = fn(n) {
+  if (n < 0) {
+    return -n;
+  }
+  n
+};
+// 負の数は渡さない
+for (i in [1, 2]) { magnitude(i) }";
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+
+    let coverage = Coverage::new();
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_observer(Some(Box::new(coverage.clone())));
+    evaluator::eval(program.clone(), &env);
+
+    assert_eq!(coverage.annotate(&program, input), "        1:    1: let magnitude = fn(n) {
+        2:    2:   if (n < 0) {
+    #####:    3:     return -n;
+        -:    4:   }
+        2:    5:   n
+        -:    6: };
+        -:    7: // 負の数は渡さない
+        2:    8: for (i in [1, 2]) { magnitude(i) }
+");
+    assert_eq!(coverage.lcov(&program, "abs.mk"), "TN:\nSF:abs.mk\nDA:1,1\nDA:2,2\nDA:3,0\nDA:5,2\nDA:8,2\nLF:5\nLH:4\nend_of_record\n");
+  }
+}
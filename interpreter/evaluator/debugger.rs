@@ -0,0 +1,27 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::ast::stmt::Statement;
+use super::environment::Environment;
+
+/// What the evaluator does after `Debugger::on_statement` returns.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Action {
+  /// Evaluates the statement and goes on.
+  Continue,
+  /// Stops the program with a `Cancelled` error, which `try` does not catch.
+  Abort,
+}
+
+/// A hook which the evaluator calls before each statement, including the ones in blocks and function bodies.
+/// The program is paused while `on_statement` runs, so a debugger can wait there for the user's commands,
+/// and inspect or change the bindings through `env`.
+///
+/// Code which the debugger itself evaluates in `env`, e.g. to print an expression, is not reported to it again.
+/// The VM does not call the hook.
+pub trait Debugger {
+  fn on_statement(&mut self, stmt: &Statement, env: &Rc<RefCell<Environment>>) -> Action;
+}
+
+/// Where the debugger of an environment is kept. It is shared by every environment created from the same outermost one.
+pub type DebuggerSlot = Rc<RefCell<Option<Box<dyn Debugger>>>>;
@@ -1,62 +1,465 @@
-use std::collections::HashMap;
-use std::rc::{Rc, Weak};
-use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::fmt;
 
-use super::object::Object;
+use crate::intern::Name;
+use crate::utils;
+use super::object::{Object, Builtin, BuiltinFunc, External, ExternalFunc};
+use super::gc::Heap;
+use super::limits::{Budget, Limits, CancelToken, Capabilities};
+use super::debugger::{Debugger, DebuggerSlot};
+use super::observer::{EvalObserver, ObserverSlot};
+use super::module::Modules;
 
-#[derive(Debug, Clone)]
+/// The default for how deeply function calls may be nested.
+pub const DEFAULT_MAX_DEPTH: usize = 10_000;
+
+/// Counts how deeply function calls are nested, and measures how much of the host stack the evaluator uses,
+/// so that a deep recursion or a deeply nested expression stops with an error before the stack overflows.
+/// It is shared by every environment created from the same outermost one.
+#[derive(Debug)]
+pub struct Depth {
+  calls: Cell<usize>,
+  max: Cell<usize>,
+  // 入れ子になった評価の数と、一番外側の評価を始めたときのスタックの位置
+  nested: Cell<usize>,
+  base: Cell<usize>,
+  max_stack: Cell<usize>,
+}
+
+impl Depth {
+  fn new() -> Depth {
+    Depth {
+      calls: Cell::new(0),
+      max: Cell::new(DEFAULT_MAX_DEPTH),
+      nested: Cell::new(0),
+      base: Cell::new(0),
+      max_stack: Cell::new(utils::DEFAULT_MAX_STACK),
+    }
+  }
+
+  /// Enters a nested evaluation. Returns false without entering if the evaluations it is nested in
+  /// already use more than `max_stack` bytes of the host stack.
+  pub fn enter(&self) -> bool {
+    let here = utils::stack_address();
+    if self.nested.get() == 0 {
+      self.base.set(here);
+    } else if here.abs_diff(self.base.get()) > self.max_stack.get() {
+      return false;
+    }
+    self.nested.set(self.nested.get() + 1);
+    true
+  }
+
+  pub fn leave(&self) {
+    self.nested.set(self.nested.get() - 1);
+  }
+
+  /// Enters a function call. Returns false without entering if `max` calls are already nested.
+  pub fn enter_call(&self) -> bool {
+    if self.calls.get() >= self.max.get() {
+      return false;
+    }
+    self.calls.set(self.calls.get() + 1);
+    true
+  }
+
+  pub fn leave_call(&self) {
+    self.calls.set(self.calls.get() - 1);
+  }
+
+  /// Returns how deeply function calls may be nested.
+  pub fn max(&self) -> usize {
+    self.max.get()
+  }
+
+  /// Returns how many bytes of the host stack the evaluator may use.
+  pub fn max_stack(&self) -> usize {
+    self.max_stack.get()
+  }
+}
+
+fn for_each_env(obj: &Object, f: &mut dyn FnMut(&Rc<RefCell<Environment>>)) {
+  match obj {
+    Object::Func(func) => f(&func.env),
+    Object::Closure(closure) => f(&closure.env),
+    Object::Macro(mac) => f(&mac.env),
+    Object::Array(arr) => arr.elements.iter().for_each(|elm| for_each_env(elm, f)),
+    Object::Hash(hash) => hash.pairs.iter().for_each(|(key, val)| {
+      for_each_env(key, f);
+      for_each_env(val, f);
+    }),
+    Object::Record(record) => record.values.iter().for_each(|val| for_each_env(val, f)),
+    Object::Return(rtn) => for_each_env(&rtn.value, f),
+    _ => (),
+  }
+}
+
+#[derive(Clone)]
 pub struct Environment {
-  store: HashMap<String, Object>,
-  outer: Option<Weak<RefCell<Environment>>>,
-  pub builtins: Option<HashMap<String, Object>>,
+  store: HashMap<Name, Object>,
+  // const で束縛した名前。代入できない
+  constants: HashSet<Name>,
+  // Function objects keep their defining environment alive through this chain,
+  // so it must be a strong reference.
+  outer: Option<Rc<RefCell<Environment>>>,
+  pub builtins: Option<HashMap<Name, Object>>,
+  output: Option<Rc<RefCell<dyn Write>>>,
+  depth: Rc<Depth>,
+  budget: Rc<Budget>,
+  capabilities: Rc<Cell<Capabilities>>,
+  #[cfg(feature = "bigint")]
+  bigint: Rc<Cell<bool>>,
+  debugger: DebuggerSlot,
+  observer: ObserverSlot,
+  modules: Rc<Modules>,
+  heap: Rc<Heap>,
+  // この環境で評価するコードのファイル。実行しているプログラムなら None
+  file: Option<Name>,
+}
+
+impl fmt::Debug for Environment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Environment")
+      .field("store", &self.store)
+      .field("outer", &self.outer)
+      .field("builtins", &self.builtins)
+      .finish()
+  }
 }
 
 impl Environment {
-  pub fn new(builtins: HashMap<String, Object>) -> Rc<RefCell<Environment>> {
-    Rc::new(RefCell::new(
+  pub fn new(builtins: HashMap<Name, Object>) -> Rc<RefCell<Environment>> {
+    let heap = Rc::new(Heap::new());
+    let env = Rc::new(RefCell::new(
       Environment {
         store: HashMap::new(),
+        constants: HashSet::new(),
         outer: None,
         builtins: Some(builtins),
+        output: Some(Rc::new(RefCell::new(io::stdout()))),
+        depth: Rc::new(Depth::new()),
+        budget: Rc::new(Budget::default()),
+        capabilities: Rc::new(Cell::new(Capabilities::default())),
+        #[cfg(feature = "bigint")]
+        bigint: Rc::new(Cell::new(false)),
+        debugger: Rc::new(RefCell::new(None)),
+        observer: Rc::new(RefCell::new(None)),
+        modules: Rc::new(Modules::default()),
+        heap: Rc::clone(&heap),
+        file: None,
       }
-    ))
+    ));
+    heap.track(&env);
+    env
   }
 
-  pub fn new_enclosed_env(outer: Weak<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
-    Rc::new(RefCell::new(
+  pub fn new_enclosed_env(outer: Rc<RefCell<Environment>>) -> Rc<RefCell<Environment>> {
+    let depth = Rc::clone(&outer.borrow().depth);
+    let budget = Rc::clone(&outer.borrow().budget);
+    let capabilities = Rc::clone(&outer.borrow().capabilities);
+    #[cfg(feature = "bigint")]
+    let bigint = Rc::clone(&outer.borrow().bigint);
+    let debugger = Rc::clone(&outer.borrow().debugger);
+    let observer = Rc::clone(&outer.borrow().observer);
+    let modules = Rc::clone(&outer.borrow().modules);
+    let heap = Rc::clone(&outer.borrow().heap);
+    let file = outer.borrow().file;
+    let env = Rc::new(RefCell::new(
       Environment {
         store: HashMap::new(),
+        constants: HashSet::new(),
         outer: Some(outer),
         builtins: None,
+        output: None,
+        depth,
+        budget,
+        capabilities,
+        #[cfg(feature = "bigint")]
+        bigint,
+        debugger,
+        observer,
+        modules,
+        heap: Rc::clone(&heap),
+        file,
       }
-    ))
+    ));
+    heap.track(&env);
+    env
   }
 
-  pub fn get(&self, key: &str) -> Option<Object> {
-    match self.store.get(key) {
+  // import したファイルは、呼び出し元の変数が見えない新しい最も外側の環境で評価する。組み込み関数や出力先、上限は共有する。
+  // file はエラーの位置を報告するときに使う、そのコードの名前
+  pub(super) fn new_module_env(importer: &Rc<RefCell<Environment>>, file: &str) -> Rc<RefCell<Environment>> {
+    let importer = importer.borrow();
+    let heap = Rc::clone(&importer.heap);
+    let env = Rc::new(RefCell::new(
+      Environment {
+        store: HashMap::new(),
+        constants: HashSet::new(),
+        outer: None,
+        builtins: Some(importer.root_builtins()),
+        output: Some(importer.output()),
+        depth: Rc::clone(&importer.depth),
+        budget: Rc::clone(&importer.budget),
+        capabilities: Rc::clone(&importer.capabilities),
+        #[cfg(feature = "bigint")]
+        bigint: Rc::clone(&importer.bigint),
+        debugger: Rc::clone(&importer.debugger),
+        observer: Rc::clone(&importer.observer),
+        modules: Rc::clone(&importer.modules),
+        heap: Rc::clone(&heap),
+        file: Some(Name::new(file)),
+      }
+    ));
+    heap.track(&env);
+    env
+  }
+
+  fn root_builtins(&self) -> HashMap<Name, Object> {
+    match (&self.builtins, &self.outer) {
+      (Some(builtins), _) => builtins.clone(),
+      (None, Some(env)) => env.borrow().root_builtins(),
+      (None, None) => HashMap::new(),
+    }
+  }
+
+  pub fn get(&self, key: Name) -> Option<Object> {
+    match self.store.get(&key) {
       Some(val) => Some(val.clone()),
       None => match &self.outer {
-        Some(env) => env.upgrade().expect("env is already exposed").borrow().get(key),
+        Some(env) => env.borrow().get(key),
         None => None,
       } 
     }
   }
 
-  pub fn get_builtin(&self, key: &str) -> Option<Object> {
+  pub fn get_builtin(&self, key: Name) -> Option<Object> {
     match &self.builtins {
-      Some(builtins) => match builtins.get(key) {
-        Some(val) => Some(val.clone()),
-        None => None,
-      },
+      Some(builtins) => builtins.get(&key).cloned(),
       None => match &self.outer {
-        Some(env) => env.upgrade().expect("env is already exposed").borrow().get_builtin(key),
+        Some(env) => env.borrow().get_builtin(key),
         None => None,
       } 
     }
   }
 
-  pub fn set(&mut self, key: &str, val: Object) {
-    // TODO: to be immutable
-    self.store.insert(key.to_string(), val);
+  /// Registers `func` as a builtin function which can be called as `name` from Monkey code.
+  /// Builtins always live in the outermost environment.
+  pub fn register_builtin(&mut self, name: &str, func: BuiltinFunc) {
+    self.insert_builtin(Name::new(name), Object::Builtin(Builtin::new(func)));
+  }
+
+  /// Registers a closure of the host as a builtin function. Unlike `register_builtin`, `func` can capture state.
+  pub fn register_external(&mut self, name: &str, func: ExternalFunc) {
+    self.insert_builtin(Name::new(name), Object::External(External::new(func)));
+  }
+
+  pub(super) fn insert_builtin(&mut self, name: Name, builtin: Object) {
+    match &mut self.builtins {
+      Some(builtins) => {
+        builtins.insert(name, builtin);
+      },
+      None => {
+        if let Some(env) = &self.outer {
+          env.borrow_mut().insert_builtin(name, builtin);
+        }
+      },
+    }
+  }
+
+  /// Returns the sink which `puts` writes to. It is stdout unless replaced with `set_output`.
+  pub fn output(&self) -> Rc<RefCell<dyn Write>> {
+    match &self.output {
+      Some(output) => Rc::clone(output),
+      None => match &self.outer {
+        Some(env) => env.borrow().output(),
+        None => Rc::new(RefCell::new(io::stdout())),
+      },
+    }
+  }
+
+  /// Replaces the output sink of the outermost environment.
+  pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+    match &self.outer {
+      Some(env) => env.borrow_mut().set_output(output),
+      None => self.output = Some(output),
+    }
+  }
+
+  pub fn depth(&self) -> Rc<Depth> {
+    Rc::clone(&self.depth)
+  }
+
+  pub fn budget(&self) -> Rc<Budget> {
+    Rc::clone(&self.budget)
+  }
+
+  pub fn debugger(&self) -> DebuggerSlot {
+    Rc::clone(&self.debugger)
+  }
+
+  /// Sets the debugger which is called before each statement evaluated in this environment or an enclosed one,
+  /// or removes it with `None`.
+  pub fn set_debugger(&mut self, debugger: Option<Box<dyn Debugger>>) {
+    *self.debugger.borrow_mut() = debugger;
+  }
+
+  pub fn observer(&self) -> ObserverSlot {
+    Rc::clone(&self.observer)
+  }
+
+  /// Sets the observer which is told about evaluation in this environment or an enclosed one,
+  /// or removes it with `None`.
+  pub fn set_observer(&mut self, observer: Option<Box<dyn EvalObserver>>) {
+    *self.observer.borrow_mut() = observer;
+  }
+
+  /// Returns the loader of the files imported from this environment or an enclosed one.
+  pub fn modules(&self) -> Rc<Modules> {
+    Rc::clone(&self.modules)
+  }
+
+  /// Returns the name of the file the code evaluated in this environment comes from: the path of an imported module,
+  /// `<prelude>` or `<eval>`. `None` for the program being run.
+  pub fn file(&self) -> Option<Name> {
+    self.file
+  }
+
+  /// Returns the heap which tracks this environment and every one enclosed by the same outermost one.
+  pub fn heap(&self) -> Rc<Heap> {
+    Rc::clone(&self.heap)
+  }
+
+  /// Calls `f` with each environment which this one keeps alive.
+  /// Variables captured by compiled closures are not followed, so the environments they refer to are never freed by `Heap`.
+  pub(super) fn for_each_reference(&self, f: &mut dyn FnMut(&Rc<RefCell<Environment>>)) {
+    if let Some(outer) = &self.outer {
+      f(outer);
+    }
+    for val in self.store.values() {
+      for_each_env(val, f);
+    }
+  }
+
+  // 循環を断つために束縛を取り出す。取り出した値は借用を返してから捨てる
+  pub(super) fn take_bindings(&mut self) -> (HashMap<Name, Object>, Option<Rc<RefCell<Environment>>>) {
+    (std::mem::take(&mut self.store), self.outer.take())
+  }
+
+  /// Sets how deeply function calls may be nested before `eval` returns a `RecursionError`, which is
+  /// `DEFAULT_MAX_DEPTH` by default. The limit is shared with every environment enclosed by the outermost one.
+  pub fn set_max_depth(&mut self, max: usize) {
+    self.depth.max.set(max);
+  }
+
+  /// Sets how many bytes of the host stack `eval` may use before it returns a `RecursionError` instead of
+  /// overflowing the stack, which is `utils::DEFAULT_MAX_STACK` by default. A Monkey function call takes about
+  /// 14KiB of it in a debug build and 5KiB in a release build, so a host which runs deep recursions should
+  /// evaluate on a thread with a larger stack and raise this to a little less than its size.
+  pub fn set_max_stack(&mut self, bytes: usize) {
+    self.depth.max_stack.set(bytes);
+  }
+
+  /// Sets the limits on the steps, calls and objects of the programs evaluated in this environment,
+  /// and starts counting them from zero. Like the recursion depth, they are shared with every enclosed environment.
+  pub fn set_limits(&mut self, limits: Limits) {
+    self.budget.set_limits(limits);
+  }
+
+  pub fn capabilities(&self) -> Capabilities {
+    self.capabilities.get()
+  }
+
+  /// Grants the programs evaluated in this environment the access outside the interpreter in `capabilities`,
+  /// and denies the rest. They are shared with every enclosed environment and the modules it imports.
+  pub fn set_capabilities(&mut self, capabilities: Capabilities) {
+    self.capabilities.set(capabilities);
+  }
+
+  #[cfg(feature = "bigint")]
+  pub fn bigint(&self) -> bool {
+    self.bigint.get()
+  }
+
+  /// Makes the integer arithmetic of the programs evaluated in this environment go on with a `BigInt`
+  /// when the result does not fit in an `i64`, instead of failing with an overflow error.
+  /// Like the capabilities, it is shared with every enclosed environment and the modules it imports.
+  #[cfg(feature = "bigint")]
+  pub fn set_bigint(&mut self, enabled: bool) {
+    self.bigint.set(enabled);
+  }
+
+  /// Sets the token with which another thread can stop the programs evaluated in this environment.
+  pub fn set_cancel_token(&mut self, token: CancelToken) {
+    self.budget.set_cancel_token(Some(token));
+  }
+
+  /// Updates the existing binding of `key` in the nearest environment which has it.
+  /// Returns false if `key` is not bound anywhere.
+  pub fn assign(&mut self, key: Name, val: Object) -> bool {
+    if let Some(slot) = self.store.get_mut(&key) {
+      *slot = val;
+      return true;
+    }
+    match &self.outer {
+      Some(env) => env.borrow_mut().assign(key, val),
+      None => false,
+    }
+  }
+
+  /// Returns the bindings of this environment, without the ones of the outer environments and the builtins, sorted by name.
+  pub fn bindings(&self) -> Vec<(Name, Object)> {
+    let mut bindings: Vec<(Name, Object)> = self.store.iter().map(|(key, val)| (*key, val.clone())).collect();
+    bindings.sort_by_key(|(key, _)| key.as_str());
+    bindings
+  }
+
+  /// Returns the bindings visible from this environment, including the ones of the outer environments
+  /// which are not shadowed, sorted by name. The builtins are not included.
+  pub fn visible_bindings(&self) -> Vec<(Name, Object)> {
+    let mut bindings = self.bindings();
+    if let Some(outer) = &self.outer {
+      for (name, val) in outer.borrow().visible_bindings() {
+        if !self.store.contains_key(&name) {
+          bindings.push((name, val));
+        }
+      }
+    }
+    bindings.sort_by_key(|(key, _)| key.as_str());
+    bindings
+  }
+
+  /// Removes the bindings of this environment. The builtins and the settings of the outermost environment are kept.
+  pub fn clear(&mut self) {
+    self.store.clear();
+    self.constants.clear();
+  }
+
+  /// Binds `key` in this environment. A constant binding of `key` in it is replaced with a mutable one.
+  pub fn set(&mut self, key: Name, val: Object) {
+    if !self.constants.is_empty() {
+      self.constants.remove(&key);
+    }
+    self.store.insert(key, val);
+  }
+
+  /// Binds `key` in this environment like `set`, but the binding can not be assigned to.
+  pub fn set_constant(&mut self, key: Name, val: Object) {
+    self.constants.insert(key);
+    self.store.insert(key, val);
+  }
+
+  /// Returns true if the nearest binding of `key` is a constant one.
+  pub fn is_constant(&self, key: Name) -> bool {
+    if self.store.contains_key(&key) {
+      return self.constants.contains(&key);
+    }
+    match &self.outer {
+      Some(env) => env.borrow().is_constant(key),
+      None => false,
+    }
   }
 }
@@ -0,0 +1,178 @@
+use std::rc::{Rc, Weak};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use super::environment::Environment;
+
+/// How many environments are tracked before the first collection.
+pub const DEFAULT_GC_THRESHOLD: usize = 1024;
+
+type EnvRef = Rc<RefCell<Environment>>;
+
+/// Tracks every environment created from the same outermost one, and frees the ones
+/// which are only kept alive by reference cycles, such as a function stored in the environment it closes over.
+///
+/// Environments are never freed while something outside of the tracked environments refers to them,
+/// so a collection is safe at any point of evaluation: an environment held by the evaluator's Rust stack,
+/// a host closure or a VM slot has a strong count which the tracked environments do not explain.
+#[derive(Debug)]
+pub struct Heap {
+  envs: RefCell<Vec<Weak<RefCell<Environment>>>>,
+  threshold: Cell<usize>,
+  min_threshold: Cell<usize>,
+}
+
+impl Default for Heap {
+  fn default() -> Heap {
+    Heap::new()
+  }
+}
+
+impl Heap {
+  pub fn new() -> Heap {
+    Heap {
+      envs: RefCell::new(vec![]),
+      threshold: Cell::new(DEFAULT_GC_THRESHOLD),
+      min_threshold: Cell::new(DEFAULT_GC_THRESHOLD),
+    }
+  }
+
+  /// Starts tracking `env`, collecting first if enough environments were created since the last collection.
+  pub(super) fn track(&self, env: &EnvRef) {
+    if self.envs.borrow().len() >= self.threshold.get() {
+      self.collect();
+    }
+    self.envs.borrow_mut().push(Rc::downgrade(env));
+  }
+
+  /// Sets how many environments are tracked before a collection runs.
+  pub fn set_threshold(&self, threshold: usize) {
+    self.min_threshold.set(threshold);
+    self.threshold.set(threshold);
+  }
+
+  /// Returns the number of tracked environments which have not been freed yet.
+  pub fn len(&self) -> usize {
+    self.envs.borrow().iter().filter(|env| env.strong_count() > 0).count()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Frees the environments which are unreachable from outside of the tracked ones,
+  /// and returns how many were freed.
+  pub fn collect(&self) -> usize {
+    let envs: Vec<EnvRef> = self.envs.borrow_mut().drain(..).filter_map(|env| env.upgrade()).collect();
+    let index: HashMap<*const RefCell<Environment>, usize> = envs.iter()
+      .enumerate()
+      .map(|(i, env)| (Rc::as_ptr(env), i))
+      .collect();
+
+    // 強参照の数から追跡中の環境どうしの参照を引いて、残りがあれば外から参照されている
+    // (upgrade した分の 1 も引く)
+    let mut external: Vec<isize> = envs.iter().map(|env| Rc::strong_count(env) as isize - 1).collect();
+    let mut borrowed = vec![false; envs.len()];
+    for (i, env) in envs.iter().enumerate() {
+      match env.try_borrow() {
+        Ok(env) => env.for_each_reference(&mut |target| {
+          if let Some(j) = index.get(&Rc::as_ptr(target)) {
+            external[*j] -= 1;
+          }
+        }),
+        // 使用中の環境は辿れないので、それ自身と参照先を生きているものとして扱う
+        Err(_) => borrowed[i] = true,
+      }
+    }
+
+    let mut reachable = vec![false; envs.len()];
+    let mut stack: Vec<usize> = (0..envs.len()).filter(|i| external[*i] > 0 || borrowed[*i]).collect();
+    for i in &stack {
+      reachable[*i] = true;
+    }
+    while let Some(i) = stack.pop() {
+      if let Ok(env) = envs[i].try_borrow() {
+        env.for_each_reference(&mut |target| {
+          if let Some(j) = index.get(&Rc::as_ptr(target)) {
+            if !reachable[*j] {
+              reachable[*j] = true;
+              stack.push(*j);
+            }
+          }
+        });
+      }
+    }
+
+    let mut freed = 0;
+    let mut survivors = vec![];
+    for (env, reachable) in envs.iter().zip(reachable) {
+      if reachable {
+        survivors.push(Rc::downgrade(env));
+        continue;
+      }
+      // 借用を返してから束縛を捨てる。捨てた値が他の環境を解放することがある
+      let bindings = env.borrow_mut().take_bindings();
+      drop(bindings);
+      freed += 1;
+    }
+
+    self.threshold.set((survivors.len() * 2).max(self.min_threshold.get()));
+    self.envs.borrow_mut().extend(survivors);
+    freed
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use crate::evaluator::{self, builtins};
+  use crate::intern::Name;
+
+  fn eval(input: &str, env: &EnvRef) -> String {
+    let l = Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    format!("{}", evaluator::eval(program, env))
+  }
+
+  #[test]
+  fn test_collect_cycles() {
+    let env = Environment::new(builtins::new_builtins());
+    let heap = env.borrow().heap();
+    heap.set_threshold(100000);
+
+    // 呼び出しの環境に束縛した関数は、その環境を参照するので循環する
+    let input = "let f = fn() { let g = fn() { 1 }; g() }; let i = 0; while (i < 300) { f(); i += 1 }; i";
+    assert_eq!(eval(input, &env), "300");
+    assert!(heap.len() >= 300);
+
+    heap.collect();
+    // 外から参照されているトップレベルの環境だけが残る
+    assert_eq!(heap.len(), 1);
+  }
+
+  #[test]
+  fn test_collect_keeps_reachable() {
+    let env = Environment::new(builtins::new_builtins());
+    let heap = env.borrow().heap();
+    heap.set_threshold(4);
+
+    let input = "
+      let counter = fn() { let c = 0; let next = fn() { c += 1; c }; next };
+      let counters = [counter(), counter()];
+      let i = 0;
+      while (i < 50) { counters[0](); counter()(); i += 1 };
+      counters[0]() + counters[1]()";
+    assert_eq!(eval(input, &env), "52");
+
+    // Rust 側で持っている関数の環境は解放されない
+    let mut next = evaluator::eval(Parser::new(Lexer::new("counter()".to_string())).parse_program(), &env);
+    heap.collect();
+    assert_eq!(format!("{}", evaluator::apply_func(&mut next, vec![], &env)), "1");
+    assert_eq!(format!("{}", evaluator::apply_func(&mut next, vec![], &env)), "2");
+    assert_eq!(eval("counters[1]()", &env), "2");
+    assert!(env.borrow().get(Name::new("counter")).is_some());
+  }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use serde_json::{Map, Number, Value};
+
+use super::object::{Object, Integer, Float, Boolean, Str, Array, Hash, Error, ErrorKind};
+use super::environment::Environment;
+
+/// Converts a JSON value to an object. An object becomes a hash whose keys are strings, and a number becomes
+/// an integer if it fits in one, otherwise a float.
+pub fn from_json(value: Value) -> Object {
+  match value {
+    Value::Null => Object::Null,
+    Value::Bool(value) => Object::Boolean(Boolean { value }),
+    Value::Number(n) => match n.as_i64() {
+      Some(i) => Object::Integer(Integer::new(i)),
+      None => Object::Float(Float::new(n.as_f64().unwrap_or(f64::NAN))),
+    },
+    Value::String(s) => Object::Str(Str::new(s)),
+    Value::Array(values) => Object::Array(Array::new(values.into_iter().map(from_json).collect())),
+    Value::Object(map) => {
+      let pairs: HashMap<Object, Object> = map.into_iter()
+        .map(|(key, val)| (Object::Str(Str::new(key)), from_json(val)))
+        .collect();
+      Object::Hash(Hash::new(pairs))
+    },
+  }
+}
+
+/// Converts an object to a JSON value. A range becomes an array, and the integer and boolean keys of a hash
+/// become strings since JSON only has string keys. Functions, floats which are not finite and integers which do not
+/// fit in an `i64` can not be converted.
+pub fn to_json(obj: &Object) -> Result<Value, Error> {
+  match obj {
+    Object::Null => Ok(Value::Null),
+    Object::Boolean(b) => Ok(Value::Bool(b.value)),
+    Object::Integer(i) => Ok(Value::from(i.value)),
+    // serde_json の数は 64 ビットに収まらない整数を正確に表せない
+    #[cfg(feature = "bigint")]
+    Object::BigInt(i) => Err(Error::new(ErrorKind::Type, format!("cannot convert {} to JSON: integer is too large.", i))),
+    Object::Float(f) => match Number::from_f64(f.value) {
+      Some(n) => Ok(Value::Number(n)),
+      None => Err(Error::new(ErrorKind::Type, format!("cannot convert {} to JSON.", f))),
+    },
+    Object::Str(s) => Ok(Value::String(s.value.clone())),
+    Object::Array(arr) => {
+      let mut values = Vec::with_capacity(arr.elements.len());
+      for elm in &arr.elements {
+        values.push(to_json(elm)?);
+      }
+      Ok(Value::Array(values))
+    },
+    Object::Range(range) => Ok(Value::Array(range.iter().map(Value::from).collect())),
+    Object::Hash(hash) => {
+      let mut map = Map::new();
+      for (key, val) in &hash.pairs {
+        let key = match key {
+          Object::Str(s) => s.value.clone(),
+          Object::Integer(_) | Object::Boolean(_) => key.to_string(),
+          #[cfg(feature = "bigint")]
+          Object::BigInt(_) => key.to_string(),
+          key => return Err(Error::new(ErrorKind::Type, format!("cannot convert {} to a JSON key.", key))),
+        };
+        map.insert(key, to_json(val)?);
+      }
+      Ok(Value::Object(map))
+    },
+    // レコードはフィールドの名前をキーにしたオブジェクトになる
+    Object::Record(record) => {
+      let mut map = Map::new();
+      for (name, val) in record.def.fields.iter().zip(&record.values) {
+        map.insert(name.to_string(), to_json(val)?);
+      }
+      Ok(Value::Object(map))
+    },
+    obj => Err(Error::new(ErrorKind::Type, format!("cannot convert {} to JSON.", obj.type_name()))),
+  }
+}
+
+// json_parse(str) は JSON の文字列を値にする
+pub(super) fn json_parse(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+
+  let source = match &args[0] {
+    Object::Str(s) => &s.value,
+    obj => return new_error(ErrorKind::Type, format!("argument to `json_parse` must be STRING: got={}", obj)),
+  };
+  match serde_json::from_str(source) {
+    Ok(value) => from_json(value),
+    Err(err) => new_error(ErrorKind::Json, format!("invalid JSON: {}.", err)),
+  }
+}
+
+// json_stringify(obj) は値を JSON の文字列にする。ハッシュのキーは並べ替えて出力する
+pub(super) fn json_stringify(args: Vec<Object>, _env: &Rc<RefCell<Environment>>) -> Object {
+  if args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", args.len()));
+  }
+
+  match to_json(&args[0]) {
+    Ok(value) => Object::Str(Str::new(value.to_string())),
+    Err(err) => Object::Error(Box::new(err)),
+  }
+}
+
+fn new_error(kind: ErrorKind, msg: String) -> Object {
+  Object::Error(Box::new(Error::new(kind, msg)))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use super::*;
+  use super::super::builtins;
+
+  fn test_eval(input: &str) -> Object {
+    let env = Environment::new(builtins::new_builtins());
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    super::super::eval(program, &env)
+  }
+
+  #[test]
+  fn test_json() {
+    let tests = vec![
+      ("json_parse(\"[1, 2.5, true, null, \\\"a\\\"]\")", "[1, 2.5, true, null, \"a\"]"),
+      ("json_parse(\"{\\\"a\\\": {\\\"b\\\": [1]}}\")[\"a\"][\"b\"][0]", "1"),
+      ("json_parse(\"18446744073709551615\")", "1.8446744073709552e19"),
+      ("json_stringify({\"b\": [1, 2.5], \"a\": null, 1: true})", r#""{"1":true,"a":null,"b":[1,2.5]}""#),
+      ("json_stringify(\"日本\\n\")", r#"""日本\n"""#),
+      ("json_stringify(0..3)", "\"[0,1,2]\""),
+      ("struct P {b, a} json_stringify(P {a: 1, b: [P {a: null, b: 2}]})", r#""{"a":1,"b":[{"a":null,"b":2}]}""#),
+      ("let s = \"{\\\"x\\\":[1,{\\\"y\\\":false}]}\"; json_stringify(json_parse(s)) == s", "true"),
+    ];
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("json_parse(\"[1,\")", ErrorKind::Json),
+      ("json_parse(1)", ErrorKind::Type),
+      ("json_stringify(fn(x) { x })", ErrorKind::Type),
+      ("json_stringify([1e308 * 10.0])", ErrorKind::Type),
+      ("json_stringify(1, 2)", ErrorKind::Argument),
+    ];
+    for (input, expected) in errors {
+      match test_eval(input) {
+        Object::Error(err) => assert_eq!(err.kind, expected, "{}: {}", input, err.message),
+        result => panic!("{}: expected an error, got {}", input, result),
+      }
+    }
+  }
+}
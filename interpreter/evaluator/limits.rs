@@ -0,0 +1,241 @@
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::new_error;
+use super::object::{Object, ErrorKind};
+
+/// Limits on the work a program may do, for running scripts which are not trusted.
+/// `None` means no limit, which is the default.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Limits {
+  /// How many expressions and statements may be evaluated, or instructions run by the VM.
+  pub max_steps: Option<u64>,
+  /// How deeply function calls may be nested.
+  pub max_calls: Option<usize>,
+  /// How many strings, arrays, hashes and functions may be created. Values are copied when they are
+  /// passed around, so every evaluation which produces one counts, together with its elements.
+  pub max_objects: Option<u64>,
+  /// About how many bytes the strings, arrays and hashes may take up. Like `max_objects`, it counts every copy,
+  /// so it bounds what is allocated over the run rather than what is alive at once.
+  pub max_bytes: Option<u64>,
+  /// How long a program may run, from when the limits are set or the budget is reset.
+  /// It is not checked on `wasm32-unknown-unknown`, which has no clock.
+  pub timeout: Option<Duration>,
+}
+
+/// What a program may do outside the interpreter. Everything is denied by default, so a host which embeds the
+/// interpreter opts in to what its scripts need. Calling a builtin which needs a denied capability is a `PermissionError`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Capabilities {
+  /// Whether `read_file`, `read_lines` and `write_file` may access the filesystem, and `import` may load modules.
+  pub filesystem: bool,
+  /// Whether `now_ms`, `clock` and `sleep` may read the clock and wait. Leave it denied for deterministic runs.
+  pub clock: bool,
+  /// Whether `getenv` may read the environment variables of the process.
+  pub env: bool,
+  /// Whether `eval` may parse and evaluate code from strings.
+  pub eval: bool,
+}
+
+// wasm32-unknown-unknown には時計がないので、タイムアウトは確かめない
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(super) fn now() -> Option<Instant> {
+  Some(Instant::now())
+}
+
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(super) fn now() -> Option<Instant> {
+  None
+}
+
+/// How many steps are taken between checks of the timeout and the cancel token, which are slower than counting.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// A flag which another thread sets to stop a running program. The program stops with a `Cancelled` error
+/// at the next check, which is done every `CHECK_INTERVAL` steps, and so do the later ones until the token is reset.
+#[derive(Debug, Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  pub fn new() -> CancelToken {
+    CancelToken::default()
+  }
+
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::Relaxed);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// How long `Budget::sleep` waits between checks of the timeout and the cancel token.
+const SLEEP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Counts the work done by a program against its `Limits`. It is shared by every environment
+/// created from the same outermost one, so the limits cover the whole program.
+#[derive(Debug, Default)]
+pub struct Budget {
+  limits: Cell<Limits>,
+  steps: Cell<u64>,
+  calls: Cell<usize>,
+  objects: Cell<u64>,
+  bytes: Cell<u64>,
+  deadline: Cell<Option<Instant>>,
+  cancel: RefCell<Option<CancelToken>>,
+}
+
+impl Budget {
+  pub fn limits(&self) -> Limits {
+    self.limits.get()
+  }
+
+  /// Replaces the limits and forgets what was counted so far.
+  pub fn set_limits(&self, limits: Limits) {
+    self.limits.set(limits);
+    self.reset();
+  }
+
+  /// Forgets the steps, objects and bytes counted so far and restarts the timeout, e.g. before running the next program in the same environment.
+  pub fn reset(&self) {
+    self.steps.set(0);
+    self.objects.set(0);
+    self.bytes.set(0);
+    self.deadline.set(self.limits.get().timeout.and_then(|timeout| Some(now()? + timeout)));
+  }
+
+  /// Sets the token which stops the program when it is cancelled, or removes it with `None`.
+  pub fn set_cancel_token(&self, token: Option<CancelToken>) {
+    *self.cancel.borrow_mut() = token;
+  }
+
+  /// Returns how many steps were taken since the last reset.
+  pub fn steps(&self) -> u64 {
+    self.steps.get()
+  }
+
+  /// Returns how many objects were created since the last reset. They are only counted while `max_objects` or `max_bytes` is set.
+  pub fn objects(&self) -> u64 {
+    self.objects.get()
+  }
+
+  /// Returns about how many bytes were allocated since the last reset, counted like `objects`.
+  pub fn bytes(&self) -> u64 {
+    self.bytes.get()
+  }
+
+  /// Counts a step, and returns a `LimitExceeded` error if there were already `max_steps` or the timeout has passed,
+  /// or a `Cancelled` error if the cancel token was cancelled.
+  pub fn step(&self) -> Result<(), Object> {
+    let steps = self.steps.get();
+    if let Some(max) = self.limits.get().max_steps {
+      if steps >= max {
+        return Err(new_error(ErrorKind::LimitExceeded, format!("step limit exceeded: {}.", max)));
+      }
+    }
+    if steps.is_multiple_of(CHECK_INTERVAL) {
+      self.check_deadline()?;
+    }
+    self.steps.set(steps + 1);
+    Ok(())
+  }
+
+  fn check_deadline(&self) -> Result<(), Object> {
+    if let Some(token) = &*self.cancel.borrow() {
+      if token.is_cancelled() {
+        return Err(new_error(ErrorKind::Cancelled, "evaluation was cancelled.".to_string()));
+      }
+    }
+    match (self.deadline.get(), self.limits.get().timeout) {
+      (Some(deadline), Some(timeout)) if now().is_some_and(|now| now >= deadline) => {
+        Err(new_error(ErrorKind::LimitExceeded, format!("timeout exceeded: {:?}.", timeout)))
+      },
+      _ => Ok(()),
+    }
+  }
+
+  /// Waits for `duration`, but returns the error of the timeout or the cancel token as soon as one of them stops the program.
+  pub fn sleep(&self, duration: Duration) -> Result<(), Object> {
+    let end = match now() {
+      Some(now) => now + duration,
+      None => return Err(new_error(ErrorKind::Io, "the clock is not available on this platform.".to_string())),
+    };
+    loop {
+      self.check_deadline()?;
+      let left = end.saturating_duration_since(Instant::now());
+      if left.is_zero() {
+        return Ok(());
+      }
+      std::thread::sleep(left.min(SLEEP_INTERVAL));
+    }
+  }
+
+  /// Counts a function call, which must be followed by `leave_call` unless an error is returned.
+  pub fn enter_call(&self) -> Result<(), Object> {
+    self.check_calls(self.calls.get())?;
+    self.calls.set(self.calls.get() + 1);
+    Ok(())
+  }
+
+  pub fn leave_call(&self) {
+    self.calls.set(self.calls.get() - 1);
+  }
+
+  /// Returns a `LimitExceeded` error if `calls` nested calls are already `max_calls`.
+  pub fn check_calls(&self, calls: usize) -> Result<(), Object> {
+    match self.limits.get().max_calls {
+      Some(max) if calls >= max => Err(new_error(ErrorKind::LimitExceeded, format!("call depth limit exceeded: {}.", max))),
+      _ => Ok(()),
+    }
+  }
+
+  /// Counts the objects `obj` is made of and their approximate size in bytes, and returns a `LimitExceeded` error
+  /// if there are more than `max_objects` or `max_bytes` in total.
+  pub fn allocate(&self, obj: &Object) -> Result<(), Object> {
+    let limits = self.limits.get();
+    if limits.max_objects.is_none() && limits.max_bytes.is_none() {
+      return Ok(());
+    }
+    let (objects, bytes) = measure(obj);
+    let objects = self.objects.get().saturating_add(objects);
+    let bytes = self.bytes.get().saturating_add(bytes);
+    self.objects.set(objects);
+    self.bytes.set(bytes);
+    match (limits.max_objects, limits.max_bytes) {
+      (Some(max), _) if objects > max => Err(new_error(ErrorKind::LimitExceeded, format!("object limit exceeded: {}.", max))),
+      (_, Some(max)) if bytes > max => Err(new_error(ErrorKind::LimitExceeded, format!("memory limit exceeded: {} bytes.", max))),
+      _ => Ok(()),
+    }
+  }
+}
+
+const OBJECT_SIZE: u64 = mem::size_of::<Object>() as u64;
+
+// 数値や真偽値はコピーするだけなので数えない。要素は Object の大きさに中身を足す
+fn measure(obj: &Object) -> (u64, u64) {
+  let element = |elm: &Object| {
+    let (objects, bytes) = measure(elm);
+    (objects, bytes + OBJECT_SIZE)
+  };
+  let sum = |(objects, bytes): (u64, u64), (more_objects, more_bytes): (u64, u64)| (objects + more_objects, bytes + more_bytes);
+  match obj {
+    Object::Str(s) => (1, s.value.len() as u64),
+    // 多倍長整数は桁数に比例した領域を確保する
+    #[cfg(feature = "bigint")]
+    Object::BigInt(big) => (1, big.value.bits().div_ceil(8)),
+    Object::Func(_) | Object::Closure(_) | Object::Macro(_) | Object::Quote(_) => (1, OBJECT_SIZE),
+    Object::Array(arr) => arr.elements.iter().map(element).fold((1, 0), sum),
+    Object::Hash(hash) => hash.pairs.iter().map(|(key, val)| sum(element(key), element(val))).fold((1, 0), sum),
+    Object::Record(record) => record.values.iter().map(element).fold((1, 0), sum),
+    Object::Return(rtn) => measure(&rtn.value),
+    _ => (0, 0),
+  }
+}
@@ -0,0 +1,325 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::ast::Program;
+use crate::ast::fold::{self, Fold};
+use crate::ast::stmt::{Statement, Pattern};
+use crate::ast::expr::{Expression, CallExpression, InfixExpression};
+use crate::ast::lit::{self, Literal};
+use crate::span::Span;
+use crate::utils;
+use super::object::{Object, Quote, Macro, ErrorKind};
+use super::environment::Environment;
+use super::{eval_expression, eval_block_statement, bind_new, locate, new_error, unwrap_returned_value};
+
+/// Removes the top-level `let` statements which bind a macro literal from `program`, and binds the macros in `env`
+/// so `expand_macros` can call them. Macro literals anywhere else are left to be evaluated as values.
+pub fn define_macros(program: &mut Program, env: &Rc<RefCell<Environment>>) {
+  program.statements.retain(|stmt| {
+    let (ident, mac) = match stmt {
+      Statement::Let(let_stmt) => match (&let_stmt.pattern, &let_stmt.value) {
+        (Pattern::Ident(ident), Expression::Literal(Literal::Macro(mac))) => (ident, mac),
+        _ => return true,
+      },
+      _ => return true,
+    };
+    let mac = Macro::new(mac.args.clone(), Rc::new(mac.body.clone()), Rc::clone(env));
+    env.borrow_mut().set(ident.value, Object::Macro(mac));
+    false
+  });
+}
+
+/// Replaces the calls to the macros bound in `env` with the quotes they return. The arguments are expanded
+/// before they are passed to the macro, but the expression a macro returns is not expanded again.
+/// Returns the error object if a macro fails or does not return a quote, or a `RecursionError` if the program is
+/// nested deeper than `utils::DEFAULT_MAX_STACK` of the host stack allows.
+pub fn expand_macros(program: Program, env: &Rc<RefCell<Environment>>) -> Result<Program, Object> {
+  let mut expander = Expander { env, error: None, stack_base: utils::stack_address() };
+  let program = expander.fold_program(program);
+  match expander.error {
+    Some(err) => Err(err),
+    None => Ok(program),
+  }
+}
+
+struct Expander<'a> {
+  env: &'a Rc<RefCell<Environment>>,
+  // 最初のエラーだけを返し、その後は展開しない
+  error: Option<Object>,
+  // 展開を始めたときのスタックの位置
+  stack_base: usize,
+}
+
+impl<'a> Expander<'a> {
+  fn lookup_macro(&self, call: &CallExpression) -> Option<Macro> {
+    match &*call.func {
+      Expression::Identifier(ident) => match self.env.borrow().get(ident.value) {
+        Some(Object::Macro(mac)) => Some(mac),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  fn expand(&self, mac: Macro, call: &CallExpression) -> Result<Expression, Object> {
+    if call.args.len() != mac.args.len() {
+      return Err(new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want={}.", call.args.len(), mac.args.len())));
+    }
+    let env = Environment::new_enclosed_env(Rc::clone(&mac.env));
+    for (param, arg) in mac.args.iter().zip(&call.args) {
+      bind_new(param.value, Object::Quote(Quote::new(arg.clone())), &env);
+    }
+    match unwrap_returned_value(eval_block_statement(&mac.body, &env)) {
+      Object::Quote(quote) => Ok((*quote.node).clone()),
+      err @ Object::Error(_) => Err(err),
+      obj => Err(new_error(ErrorKind::Type, format!("macro must return a quote: got={}.", obj.type_name()))),
+    }
+  }
+
+  // 1 + 2 + ... のような長い式は左に深く入れ子になるので、再帰せずに左端から順に展開する
+  fn fold_infix(&mut self, inf: InfixExpression) -> Expression {
+    let (left, operands) = inf.unchain();
+    let mut folded = self.fold_expression(left);
+    for (operator, right, span) in operands {
+      let right = self.fold_expression(*right);
+      folded = Expression::Infix(InfixExpression::new(Box::new(folded), operator, Box::new(right), span));
+    }
+    folded
+  }
+}
+
+impl<'a> Fold for Expander<'a> {
+  fn fold_expression(&mut self, expr: Expression) -> Expression {
+    if self.error.is_some() {
+      return expr;
+    }
+    // ホストのスタックが溢れる前にエラーにする
+    if utils::stack_address().abs_diff(self.stack_base) > utils::DEFAULT_MAX_STACK {
+      let err = new_error(ErrorKind::Recursion, "expression is nested too deeply to expand macros.".to_string());
+      self.error = Some(locate(err, expr.span(), self.env));
+      return expr;
+    }
+    let call = match expr {
+      Expression::Infix(inf) => return self.fold_infix(inf),
+      expr => match fold::walk_expression(self, expr) {
+        Expression::Call(call) => call,
+        expr => return expr,
+      },
+    };
+    let mac = match self.lookup_macro(&call) {
+      Some(mac) => mac,
+      None => return Expression::Call(call),
+    };
+    match self.expand(mac, &call) {
+      Ok(expr) => expr,
+      Err(err) => {
+        self.error = Some(locate(err, call.span, self.env));
+        Expression::Call(call)
+      },
+    }
+  }
+}
+
+// quote(expr) は expr を評価せずに返す。ただし中の unquote(x) は x を評価した値に置き換える
+pub(super) fn eval_quote(call: &CallExpression, env: &Rc<RefCell<Environment>>) -> Object {
+  if call.args.len() != 1 {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", call.args.len()));
+  }
+  let mut unquoter = Unquoter { env, error: None };
+  let node = unquoter.fold_expression(call.args[0].clone());
+  match unquoter.error {
+    Some(err) => err,
+    None => Object::Quote(Quote::new(node)),
+  }
+}
+
+pub(crate) fn is_quote_call(call: &CallExpression) -> bool {
+  is_call_to(call, "quote")
+}
+
+pub(crate) fn is_unquote_call(call: &CallExpression) -> bool {
+  is_call_to(call, "unquote")
+}
+
+fn is_call_to(call: &CallExpression, name: &str) -> bool {
+  matches!(&*call.func, Expression::Identifier(ident) if ident.value.as_str() == name)
+}
+
+struct Unquoter<'a> {
+  env: &'a Rc<RefCell<Environment>>,
+  error: Option<Object>,
+}
+
+impl<'a> Fold for Unquoter<'a> {
+  fn fold_expression(&mut self, expr: Expression) -> Expression {
+    let call = match expr {
+      Expression::Call(call) if is_unquote_call(&call) && self.error.is_none() => call,
+      expr => return fold::walk_expression(self, expr),
+    };
+    if call.args.len() != 1 {
+      self.error = Some(locate(
+        new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want=1.", call.args.len())),
+        call.span,
+        self.env,
+      ));
+      return Expression::Call(call);
+    }
+    let value = eval_expression(&call.args[0], self.env);
+    match object_to_expression(value, call.span) {
+      Ok(expr) => expr,
+      Err(err) => {
+        self.error = Some(locate(err, call.span, self.env));
+        Expression::Call(call)
+      },
+    }
+  }
+}
+
+// unquote した値を、その値に評価される式に戻す
+fn object_to_expression(obj: Object, span: Span) -> Result<Expression, Object> {
+  let lit = match obj {
+    Object::Quote(quote) => return Ok((*quote.node).clone()),
+    Object::Integer(int) => Literal::Integer(lit::Integer::new(int.value, span)),
+    Object::Float(float) => Literal::Float(lit::Float::new(float.value, span)),
+    Object::Boolean(b) => Literal::Boolean(lit::Boolean::new(b.value, span)),
+    Object::Str(s) => Literal::Str(lit::Str::new(s.value, span)),
+    Object::Null => Literal::Null(lit::Null::new(span)),
+    Object::Array(arr) => {
+      let mut elements = Vec::with_capacity(arr.elements.len());
+      for elm in arr.elements {
+        elements.push(object_to_expression(elm, span)?);
+      }
+      Literal::Array(lit::Array::new(elements, span))
+    },
+    err @ Object::Error(_) => return Err(err),
+    obj => return Err(new_error(ErrorKind::Type, format!("cannot unquote {}.", obj.type_name()))),
+  };
+  Ok(Expression::Literal(lit))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use super::*;
+  use super::super::builtins;
+
+  fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    program
+  }
+
+  fn test_eval(input: &str) -> Object {
+    let env = Environment::new(builtins::new_builtins());
+    super::super::eval(parse(input), &env)
+  }
+
+  #[test]
+  fn test_quote_unquote() {
+    let tests = vec![
+      ("quote(5)", "QUOTE(5)"),
+      ("quote(5 + 8)", "QUOTE((5 + 8))"),
+      ("quote(foobar + barfoo)", "QUOTE((foobar + barfoo))"),
+      ("quote(unquote(4 + 4) + 8)", "QUOTE((8 + 8))"),
+      ("let x = 8; quote(unquote(x) * 2)", "QUOTE((8 * 2))"),
+      ("quote(unquote(true == false))", "QUOTE(false)"),
+      ("quote(unquote([1, \"a\", null]))", "QUOTE([1, \"a\", null])"),
+      ("quote(unquote(quote(4 + 4)))", "QUOTE((4 + 4))"),
+      ("let q = quote(4 + 4); quote(unquote(4 + 4) + unquote(q))", "QUOTE((8 + (4 + 4)))"),
+    ];
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("quote(1, 2)", ErrorKind::Argument),
+      ("quote(unquote(fn(x) { x }))", ErrorKind::Type),
+      ("quote(unquote(1 / 0))", ErrorKind::Arithmetic),
+    ];
+    for (input, expected) in errors {
+      match test_eval(input) {
+        Object::Error(err) => assert_eq!(err.kind, expected, "{}: {}", input, err.message),
+        result => panic!("{}: expected an error, got {}", input, result),
+      }
+    }
+  }
+
+  #[test]
+  fn test_define_macros() {
+    let env = Environment::new(builtins::new_builtins());
+    let mut program = parse("let number = 1; let function = fn(x, y) { x + y }; let mymacro = macro(x, y) { x + y; };");
+    define_macros(&mut program, &env);
+
+    assert_eq!(program.statements.len(), 2);
+    assert!(env.borrow().get(crate::intern::Name::new("number")).is_none());
+    let mymacro = env.borrow().get(crate::intern::Name::new("mymacro"));
+    match mymacro {
+      Some(Object::Macro(mac)) => {
+        assert_eq!(mac.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(mac.body.to_string(), "{ (x + y) }");
+      },
+      obj => panic!("mymacro should be a macro, got {:?}", obj),
+    }
+  }
+
+  #[test]
+  fn test_expand_macros() {
+    let tests = vec![
+      ("let infix = macro() { quote(1 + 2) }; infix()", "(1 + 2)"),
+      ("let reverse = macro(a, b) { quote(unquote(b) - unquote(a)) }; reverse(2 + 2, 10 - 5)", "((10 - 5) - (2 + 2))"),
+      ("
+let unless = macro(cond, consequence, alternative) {
+  quote(if (!(unquote(cond))) { unquote(consequence) } else { unquote(alternative) });
+};
+unless(10 > 5, puts(\"not greater\"), puts(\"greater\"));",
+        "if((!(10 > 5))) { puts(\"not greater\") } else { puts(\"greater\") }"),
+      ("let twice = macro(x) { quote(unquote(x) * 2) }; twice(twice(3))", "((3 * 2) * 2)"),
+      ("let f = fn() { let m = macro() { quote(1) }; m }; f()", "let f = fn() { let m = macro() { quote(1) }; }{ m };f()"),
+    ];
+    for (input, expected) in tests {
+      let env = Environment::new(builtins::new_builtins());
+      let mut program = parse(input);
+      define_macros(&mut program, &env);
+      let expanded = expand_macros(program, &env).unwrap();
+      assert_eq!(expanded.to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("let m = macro(x) { x + 1 }; m(1)", "type mismatch: QUOTE(1) + 1."),
+      ("let m = macro() { 1 }; m()", "macro must return a quote: got=integer."),
+      ("let m = macro(a, b) { a }; m(1)", "wrong number of argument: got=1, want=2."),
+    ];
+    for (input, expected) in errors {
+      let env = Environment::new(builtins::new_builtins());
+      let mut program = parse(input);
+      define_macros(&mut program, &env);
+      match expand_macros(program, &env) {
+        Err(Object::Error(err)) => assert_eq!(err.message, expected, "{}", input),
+        result => panic!("{}: expected an error, got {:?}", input, result.map(|program| program.to_string())),
+      }
+    }
+  }
+
+  // 深い式はホストのスタックを溢れさせずにエラーにし、長い式はそのまま展開する
+  #[test]
+  fn test_expand_deep_expressions() {
+    let env = Environment::new(builtins::new_builtins());
+    let mut program = parse(&format!("let one = macro() {{ quote(1) }}; {}", vec!["one()"; 10000].join(" + ")));
+    define_macros(&mut program, &env);
+    let expanded = expand_macros(program, &env).unwrap();
+    assert_eq!(super::super::eval(expanded.optimize(), &env).to_string(), "10000");
+
+    let mut program = parse(&format!("f{}", "()".repeat(10000)));
+    define_macros(&mut program, &env);
+    match expand_macros(program, &env) {
+      Err(Object::Error(err)) => {
+        assert_eq!(err.kind, ErrorKind::Recursion);
+        assert_eq!(err.message, "expression is nested too deeply to expand macros.");
+      },
+      Err(err) => panic!("expected a RecursionError, got {}", err),
+      Ok(_) => panic!("expected a RecursionError"),
+    }
+  }
+}
@@ -3,17 +3,33 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 
 use crate::ast::Program;
-use crate::ast::stmt::{Statement, BlockStatement};
-use crate::ast::expr::{Expression, IfExpression, CallExpression};
+use crate::ast::stmt::{Statement, BlockStatement, WhileStatement, ForStatement, Pattern};
+use crate::ast::expr::{Expression, IfExpression, TryExpression, MatchExpression, TernaryExpression, RangeExpression, SliceExpression, CallExpression, MethodCallExpression, InfixExpression, AssignExpression, UpdateExpression};
 use crate::ast::ident::{Identifier};
 use crate::ast::lit::{self, Literal};
-use crate::ast::operator::{Prefix, Infix};
+use crate::ast::operator::{Prefix, Infix, Update};
+use crate::intern::Name;
+use crate::span::Span;
 
 pub mod object;
 pub mod environment;
 pub mod builtins;
+pub mod gc;
+pub mod convert;
+pub mod limits;
+pub mod debugger;
+pub mod observer;
+pub mod coverage;
+pub mod module;
+pub mod prelude;
+pub mod macro_expansion;
+#[cfg(feature = "bigint")]
+pub mod bigint;
+#[cfg(feature = "serde_json")]
+pub mod json;
 
 use environment::Environment;
+use object::ErrorKind;
 
 const TRUE: object::Object = object::Object::Boolean(object::Boolean { value: true });
 const FALSE: object::Object = object::Object::Boolean(object::Boolean { value: false });
@@ -43,26 +59,50 @@ fn eval_program(node: &Program, env: &Rc<RefCell<Environment>>) -> object::Objec
 }
 
 fn eval_expression(expr: &Expression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if let Err(err) = enter_expression(expr, env) {
+    return err;
+  }
+  let result = eval_nested_expression(expr, env);
+  leave_expression(expr, result, env)
+}
+
+fn enter_expression(expr: &Expression, env: &Rc<RefCell<Environment>>) -> Result<(), object::Object> {
+  let budget = env.borrow().budget();
+  if let Err(err) = budget.step() {
+    return Err(locate(err, expr.span(), env));
+  }
+  // 再帰や入れ子が深すぎるとホストのスタックが溢れるので、その前に Monkey のエラーにする
+  let depth = env.borrow().depth();
+  if !depth.enter() {
+    return Err(new_error(ErrorKind::Recursion, format!("stack limit exceeded: {} bytes.", depth.max_stack())));
+  }
+  let observer = env.borrow().observer();
+  observer::notify(&observer, |o| o.enter_expression(expr));
+  Ok(())
+}
+
+fn leave_expression(expr: &Expression, mut result: object::Object, env: &Rc<RefCell<Environment>>) -> object::Object {
+  env.borrow().depth().leave();
+  if let Err(err) = env.borrow().budget().allocate(&result) {
+    result = err;
+  }
+  let result = locate(result, expr.span(), env);
+  let observer = env.borrow().observer();
+  observer::notify(&observer, |o| o.exit_expression(expr, &result));
+  result
+}
+
+fn eval_nested_expression(expr: &Expression, env: &Rc<RefCell<Environment>>) -> object::Object {
   match expr {
-    Expression::Literal(lit) => eval_literal(&lit, env),
+    Expression::Literal(lit) => eval_literal(lit, env),
     Expression::Prefix(pre) => {
       let right = eval_expression(&pre.right, env);
       if is_error(&right) {
         return right;
       }
-      eval_prefix_expression(&pre.operator, right)
-    },
-    Expression::Infix(inf) => {
-      let left = eval_expression(&inf.left, env);
-      if is_error(&left) {
-        return left;
-      }
-      let right = eval_expression(&inf.right, env);
-      if is_error(&right) {
-        return right;
-      }
-      eval_infix_expression(left, &inf.operator, right)
+      eval_prefix_in_env(&pre.operator, right, env)
     },
+    Expression::Infix(inf) => eval_infix_chain(inf, env),
     Expression::Index(idx) => {
       let left = eval_expression(&idx.left, env);
       if is_error(&left) {
@@ -74,9 +114,26 @@ fn eval_expression(expr: &Expression, env: &Rc<RefCell<Environment>>) -> object:
       }
       eval_index_expression(left, index)
     },
+    Expression::Member(member) => {
+      let object = eval_expression(&member.object, env);
+      if is_error(&object) {
+        return object;
+      }
+      eval_index_expression(object, property_key(&member.property))
+    },
+    Expression::Slice(slice) => eval_slice_expression(slice, env),
     Expression::If(if_expr) => eval_if_expression(if_expr, env),
+    Expression::Try(try_expr) => eval_try_expression(try_expr, env),
+    Expression::Match(match_expr) => eval_match_expression(match_expr, env),
+    Expression::Ternary(ternary) => eval_ternary_expression(ternary, env),
+    Expression::Range(range) => eval_range_expression(range, env),
+    // パーサーは配列リテラルと関数呼び出しの中でしか spread を作らない
+    Expression::Spread(spread) => new_error(ErrorKind::Argument, format!("unexpected spread: {}.", spread)),
     Expression::Identifier(ident) => eval_ident_expression(ident, env),
     Expression::Call(call) => eval_call_expression(call, env),
+    Expression::MethodCall(method) => eval_method_call_expression(method, env),
+    Expression::Assign(assign) => eval_assign_expression(assign, env),
+    Expression::Update(update) => eval_update_expression(update, env),
   }
 }
 
@@ -85,7 +142,11 @@ fn eval_literal(lit: &Literal, env: &Rc<RefCell<Environment>>) -> object::Object
     Literal::Integer(int) => object::Object::Integer(
       object::Integer::new(int.value),
     ),
+    Literal::Float(float) => object::Object::Float(
+      object::Float::new(float.value),
+    ),
     Literal::Boolean(val) => native_bool_to_boolean_object(val.value),
+    Literal::Null(_) => NULL,
     Literal::Str(val) => object::Object::Str(
       object::Str::new(val.value.clone()),
     ),
@@ -99,12 +160,20 @@ fn eval_literal(lit: &Literal, env: &Rc<RefCell<Environment>>) -> object::Object
       )
     },
     Literal::Hash(val) => eval_hash_literal(val, env),
-    Literal::Func(func) => object::Object::Func(
-      object::Func::new(func.args.clone(), func.body.clone(), Rc::clone(env))
+    Literal::Struct(val) => eval_struct_literal(val, env),
+    Literal::Func(func) => eval_func(func, env),
+    Literal::Macro(mac) => object::Object::Macro(
+      object::Macro::new(mac.args.clone(), Rc::new(mac.body.clone()), Rc::clone(env))
     ),
   }
 }
 
+fn eval_func(func: &lit::Func, env: &Rc<RefCell<Environment>>) -> object::Object {
+  object::Object::Func(
+    object::Func::new(func.args.clone(), func.defaults.clone(), Rc::new(func.body.clone()), Rc::clone(env))
+  )
+}
+
 fn eval_hash_literal(hash: &lit::Hash, env: &Rc<RefCell<Environment>>) -> object::Object {
   let mut pairs: HashMap<object::Object, object::Object> = HashMap::new();
   for (key, val) in &hash.pairs {
@@ -113,8 +182,8 @@ fn eval_hash_literal(hash: &lit::Hash, env: &Rc<RefCell<Environment>>) -> object
       return key;
     }
 
-    if !key.is_primitive() {
-      return new_error(format!("unusable as hash key: {}", key));
+    if !key.is_hashable() {
+      return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
     }
 
     let val = eval_expression(val, env);
@@ -128,10 +197,96 @@ fn eval_hash_literal(hash: &lit::Hash, env: &Rc<RefCell<Environment>>) -> object
   object::Object::Hash(object::Hash::new(pairs))
 }
 
-fn eval_prefix_expression(operator: &Prefix, right: object::Object) -> object::Object {
+// フィールドの値は書いた順に評価して、struct の順に並べる
+fn eval_struct_literal(lit: &lit::Struct, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let def = match lookup_struct(&lit.name, env) {
+    Ok(def) => def,
+    Err(err) => return err,
+  };
+  let mut values = vec![None; def.fields.len()];
+  for (key, val) in &lit.fields {
+    let i = match def.field_index(key.value.as_str()) {
+      Some(i) => i,
+      None => return unknown_field(&def, key.value.as_str()),
+    };
+    if values[i].is_some() {
+      return new_error(ErrorKind::Type, format!("field `{}` is given more than once.", key));
+    }
+    let val = eval_expression(val, env);
+    if is_error(&val) {
+      return val;
+    }
+    values[i] = Some(val);
+  }
+
+  let mut fields = Vec::with_capacity(values.len());
+  for (name, val) in def.fields.iter().zip(values) {
+    match val {
+      Some(val) => fields.push(val),
+      None => return new_error(ErrorKind::Type, format!("missing field `{}` for {}.", name, def.name)),
+    }
+  }
+  object::Object::Record(object::Record::new(def, fields))
+}
+
+fn lookup_struct(name: &Identifier, env: &Rc<RefCell<Environment>>) -> Result<object::Struct, object::Object> {
+  match eval_ident_expression(name, env) {
+    object::Object::Struct(def) => Ok(def),
+    err @ object::Object::Error(_) => Err(err),
+    obj => Err(new_error(ErrorKind::Type, format!("`{}` is not a struct: got={}.", name, obj.type_name()))),
+  }
+}
+
+fn unknown_field(def: &object::Struct, field: &str) -> object::Object {
+  new_error(ErrorKind::Type, format!("unknown field `{}` for {}.", field, def.name))
+}
+
+/// Evaluates a prefix operator like `eval_prefix_expression`, and goes on with a `BigInt` when the result
+/// does not fit in an `i64` and `env` allows it with `set_bigint`.
+#[cfg(feature = "bigint")]
+pub(crate) fn eval_prefix_in_env(operator: &Prefix, right: object::Object, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if env.borrow().bigint() {
+    if let Some(obj) = bigint::eval_prefix(operator, &right) {
+      return obj;
+    }
+  }
+  eval_prefix_expression(operator, right)
+}
+
+#[cfg(not(feature = "bigint"))]
+pub(crate) fn eval_prefix_in_env(operator: &Prefix, right: object::Object, _env: &Rc<RefCell<Environment>>) -> object::Object {
+  eval_prefix_expression(operator, right)
+}
+
+/// Evaluates an infix operator like `eval_infix_expression`, and goes on with a `BigInt` when the result
+/// does not fit in an `i64` and `env` allows it with `set_bigint`.
+#[cfg(feature = "bigint")]
+pub(crate) fn eval_infix_in_env(left: object::Object, operator: &Infix, right: object::Object, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if env.borrow().bigint() {
+    if let Some(obj) = bigint::eval_infix(&left, operator, &right) {
+      return obj;
+    }
+  }
+  eval_infix_expression(left, operator, right)
+}
+
+#[cfg(not(feature = "bigint"))]
+pub(crate) fn eval_infix_in_env(left: object::Object, operator: &Infix, right: object::Object, _env: &Rc<RefCell<Environment>>) -> object::Object {
+  eval_infix_expression(left, operator, right)
+}
+
+pub(crate) fn eval_prefix_expression(operator: &Prefix, right: object::Object) -> object::Object {
+  // BigInt は i64 に収まらない値なので、どの環境で計算しても i64 には戻さない
+  #[cfg(feature = "bigint")]
+  if let object::Object::BigInt(_) = right {
+    if let Some(obj) = bigint::eval_prefix(operator, &right) {
+      return obj;
+    }
+  }
   match operator {
     Prefix::Bang => eval_bang_operator_expression(right),
     Prefix::Minus => eval_minus_operator_expression(right),
+    Prefix::BitNot => eval_bit_not_operator_expression(right),
   }
 }
 
@@ -145,66 +300,228 @@ fn eval_bang_operator_expression(right: object::Object) -> object::Object {
 
 fn eval_minus_operator_expression(right: object::Object) -> object::Object {
   match right {
-    object::Object::Integer(int) => 
-      object::Object::Integer(object::Integer::new(-int.value)),
+    object::Object::Integer(int) => match int.value.checked_neg() {
+      Some(value) => object::Object::Integer(object::Integer::new(value)),
+      None => new_error(ErrorKind::Arithmetic, format!("integer overflow: -{}.", int.value)),
+    },
+    object::Object::Float(float) =>
+      object::Object::Float(object::Float::new(-float.value)),
     _ => new_error(
-      format!("unknown operator: -{}.", right),
+      ErrorKind::Type, format!("unknown operator: -{}.", right),
     ),
   }
 }
 
-fn eval_infix_expression(left: object::Object, operator: &Infix, right: object::Object) -> object::Object {
+fn eval_bit_not_operator_expression(right: object::Object) -> object::Object {
+  match right {
+    object::Object::Integer(int) => object::Object::Integer(object::Integer::new(!int.value)),
+    _ => new_error(
+      ErrorKind::Type, format!("unknown operator: ~{}.", right),
+    ),
+  }
+}
+
+pub(crate) fn eval_infix_expression(left: object::Object, operator: &Infix, right: object::Object) -> object::Object {
+  // どんな値どうしでも等しいかどうかは比較できる
+  match operator {
+    Infix::Equal => return native_bool_to_boolean_object(left == right),
+    Infix::NotEq => return native_bool_to_boolean_object(left != right),
+    _ => (),
+  }
+
   if let (object::Object::Integer(_), object::Object::Integer(_)) = (&left, &right) {
     return eval_integer_infix_expression(left, operator, right);
   }
 
+  #[cfg(feature = "bigint")]
+  if let (object::Object::BigInt(_), _) | (_, object::Object::BigInt(_)) = (&left, &right) {
+    if let Some(obj) = bigint::eval_infix(&left, operator, &right) {
+      return obj;
+    }
+  }
+
+  // 整数と小数の演算は小数に揃えて計算する
+  if let (Some(l), Some(r)) = (to_float(&left), to_float(&right)) {
+    return eval_float_infix_expression(l, operator, r);
+  }
+
   if let (object::Object::Str(_), object::Object::Str(_)) = (&left, &right) {
     return eval_string_infix_expression(left, operator, right);
   }
 
-  let is_eq = match (&left, &right) {
-    (object::Object::Boolean(left), object::Object::Boolean(right)) => left.value == right.value,
-    (object::Object::Null, object::Object::Null) => true,
-    _ => return new_error(
-      format!("type mismatch: {} {} {}.", left, operator, right),
-    ),
-  };
+  // 大小を比べられるのは数と文字列だけで、それ以外の型には演算子がない
+  if std::mem::discriminant(&left) == std::mem::discriminant(&right) {
+    return new_error(
+      ErrorKind::Type, format!("unknown operator: {} {} {}.", left, operator, right),
+    );
+  }
+  new_error(
+    ErrorKind::Type, format!("type mismatch: {} {} {}.", left, operator, right),
+  )
+}
 
+// 右辺は左辺だけで結果が決まらない場合にのみ評価する
+// 1 + 2 + 3 + ... は左に深く入れ子になるので、再帰せずに一番内側の中置式から順に評価する。
+// 途中の中置式も eval_expression と同じように数えて observer に知らせる
+fn eval_infix_chain(inf: &InfixExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let mut chain: Vec<(&Expression, &InfixExpression)> = vec![];
+  let mut left = &*inf.left;
+  while let Expression::Infix(inner) = left {
+    if let Err(err) = enter_expression(left, env) {
+      return chain.iter().rev().fold(err, |result, (expr, _)| leave_expression(expr, result, env));
+    }
+    chain.push((left, inner));
+    left = &*inner.left;
+  }
+  let mut result = eval_expression(left, env);
+  for (expr, inner) in chain.iter().rev() {
+    result = eval_infix_operands(result, inner, env);
+    result = leave_expression(expr, result, env);
+  }
+  eval_infix_operands(result, inf, env)
+}
+
+// 評価済みの左辺に右辺を評価して演算する
+fn eval_infix_operands(left: object::Object, inf: &InfixExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if is_error(&left) {
+    return left;
+  }
+  if let Infix::And | Infix::Or | Infix::Coalesce = inf.operator {
+    return eval_logical_expression(left, &inf.operator, &inf.right, env);
+  }
+  let right = eval_expression(&inf.right, env);
+  if is_error(&right) {
+    return right;
+  }
+  eval_infix_in_env(left, &inf.operator, right, env)
+}
+
+fn eval_logical_expression(left: object::Object, operator: &Infix, right: &Expression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if let Infix::Coalesce = operator {
+    return match left {
+      object::Object::Null => eval_expression(right, env),
+      _ => left,
+    };
+  }
+
+  let left = is_truthy(left);
   match operator {
-    Infix::Equal => native_bool_to_boolean_object(is_eq),
-    Infix::NotEq => native_bool_to_boolean_object(!is_eq),
-    _ => new_error(
-      format!("unknown operator: {} {} {}.", left, operator, right),
-    ),
+    Infix::And if !left => return FALSE,
+    Infix::Or if left => return TRUE,
+    _ => (),
   }
+
+  let right = eval_expression(right, env);
+  if is_error(&right) {
+    return right;
+  }
+  native_bool_to_boolean_object(is_truthy(right))
+}
+
+// a.name は a["name"] と同じ
+pub(crate) fn property_key(property: &Identifier) -> object::Object {
+  str_object(property.value.as_str())
 }
 
-fn eval_index_expression(left: object::Object, index: object::Object) -> object::Object {
+pub(crate) fn eval_index_expression(left: object::Object, index: object::Object) -> object::Object {
   match left {
     object::Object::Array(arr) => eval_array_index_expression(arr, index),
     object::Object::Hash(hash) => eval_hash_index_expression(hash, index),
-    _ => new_error(format!("index operator not supported: {}", left)),
+    object::Object::Record(record) => eval_record_index_expression(record, index),
+    object::Object::Range(range) => eval_range_index_expression(range, index),
+    _ => new_error(ErrorKind::Type, format!("index operator not supported: {}", left)),
+  }
+}
+
+fn eval_slice_expression(slice: &SliceExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let left = eval_expression(&slice.left, env);
+  if is_error(&left) {
+    return left;
+  }
+
+  let mut bounds = vec![];
+  for bound in [&slice.start, &slice.end].iter() {
+    let bound = match bound {
+      Some(expr) => eval_expression(expr, env),
+      None => NULL,
+    };
+    if is_error(&bound) {
+      return bound;
+    }
+    bounds.push(bound);
   }
+
+  let end = bounds.pop().unwrap();
+  let start = bounds.pop().unwrap();
+  slice_object(left, start, end)
+}
+
+/// Slices `left` between `start` and `end`, where null stands for an omitted bound.
+pub(crate) fn slice_object(left: object::Object, start: object::Object, end: object::Object) -> object::Object {
+  let mut bounds = vec![];
+  for bound in [start, end].iter() {
+    match bound {
+      object::Object::Integer(i) => bounds.push(Some(i.value)),
+      object::Object::Null => bounds.push(None),
+      _ => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", bound)),
+    }
+  }
+
+  match left {
+    object::Object::Array(arr) => {
+      let (start, end) = slice_bounds(bounds[0], bounds[1], arr.elements.len());
+      object::Object::Array(object::Array::new(arr.elements[start..end].to_vec()))
+    },
+    // 文字列はバイトではなく文字単位で切り出す
+    object::Object::Str(s) => {
+      let chars: Vec<char> = s.value.chars().collect();
+      let (start, end) = slice_bounds(bounds[0], bounds[1], chars.len());
+      object::Object::Str(object::Str::new(chars[start..end].iter().collect()))
+    },
+    _ => new_error(ErrorKind::Type, format!("slice operator not supported: {}", left)),
+  }
+}
+
+// 負の値は末尾から数え、範囲外の値は 0..len に収める
+fn slice_bounds(start: Option<i64>, end: Option<i64>, len: usize) -> (usize, usize) {
+  let len = len as i64;
+  let clamp = |i: i64| {
+    let i = if i < 0 { i + len } else { i };
+    i.max(0).min(len) as usize
+  };
+
+  let start = clamp(start.unwrap_or(0));
+  let end = clamp(end.unwrap_or(len));
+  (start, end.max(start))
 }
 
-#[allow(unused_comparisons)]
 fn eval_array_index_expression(arr: object::Array, idx: object::Object) -> object::Object {
   let idx = match idx {
     object::Object::Integer(i) => i,
-    _ => return new_error(format!("specified index type is not supported: {}", idx)),
+    _ => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", idx)),
   };
 
-  let idx = idx.value as usize;
-  let len = arr.elements.len();
-  if idx < 0 || len == 0 || idx > len - 1 {
+  if idx.value < 0 || idx.value as usize >= arr.elements.len() {
     return NULL;
   }
-  arr.elements[idx].clone()
+  arr.elements[idx.value as usize].clone()
+}
+
+fn eval_range_index_expression(range: object::Range, idx: object::Object) -> object::Object {
+  let idx = match idx {
+    object::Object::Integer(i) => i,
+    _ => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", idx)),
+  };
+
+  match range.get(idx.value) {
+    Some(value) => object::Object::Integer(object::Integer::new(value)),
+    None => NULL,
+  }
 }
 
 fn eval_hash_index_expression(hash: object::Hash, idx: object::Object) -> object::Object {
-  if !idx.is_primitive() {
-    return new_error(format!("unusable as hash key: {}", idx));
+  if !idx.is_hashable() {
+    return new_error(ErrorKind::Type, format!("unusable as hash key: {}", idx));
   }
 
   match hash.pairs.get(&idx) {
@@ -213,6 +530,18 @@ fn eval_hash_index_expression(hash: object::Hash, idx: object::Object) -> object
   }
 }
 
+fn eval_record_index_expression(record: object::Record, idx: object::Object) -> object::Object {
+  let field = match &idx {
+    object::Object::Str(s) => &s.value,
+    _ => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", idx)),
+  };
+
+  match record.get(field) {
+    Some(val) => val.clone(),
+    None => unknown_field(&record.def, field),
+  }
+}
+
 fn eval_integer_infix_expression(left: object::Object, operator: &Infix, right: object::Object) -> object::Object {
   let left = if let object::Object::Integer(int) = left {
     int.value
@@ -225,21 +554,97 @@ fn eval_integer_infix_expression(left: object::Object, operator: &Infix, right:
     return NULL;
   };
 
+  // 結果が i64 に収まらなければ None になり、折り返さずにエラーにする
   let int = match operator {
-    Infix::Plus => object::Integer::new(left + right),
-    Infix::Minus => object::Integer::new(left - right),
-    Infix::Asterisk => object::Integer::new(left * right),
-    Infix::Slash => object::Integer::new(left / right),
+    Infix::Plus => left.checked_add(right),
+    Infix::Minus => left.checked_sub(right),
+    Infix::Asterisk => left.checked_mul(right),
+    Infix::Slash => {
+      if right == 0 {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {} / {}.", left, right));
+      }
+      left.checked_div(right)
+    },
+    Infix::Percent => {
+      if right == 0 {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {} % {}.", left, right));
+      }
+      left.checked_rem(right)
+    },
+    Infix::Power => {
+      if right < 0 {
+        return new_error(ErrorKind::Arithmetic, format!("negative exponent: {} ** {}.", left, right));
+      }
+      if right > u32::MAX as i64 { None } else { left.checked_pow(right as u32) }
+    },
+    Infix::BitAnd => Some(left & right),
+    Infix::BitOr => Some(left | right),
+    Infix::BitXor => Some(left ^ right),
+    // 負の数や 64 以上だけずらすことはできない。>> は符号を保つ
+    Infix::ShiftLeft | Infix::ShiftRight => {
+      let shifted = match operator {
+        _ if right < 0 || right > u32::MAX as i64 => None,
+        Infix::ShiftLeft => left.checked_shl(right as u32),
+        _ => left.checked_shr(right as u32),
+      };
+      if shifted.is_none() {
+        return new_error(ErrorKind::Arithmetic, format!("invalid shift amount: {} {} {}.", left, operator, right));
+      }
+      shifted
+    },
     Infix::Lt => return native_bool_to_boolean_object(left < right),
     Infix::Gt => return native_bool_to_boolean_object(left > right),
-    Infix::Equal => return native_bool_to_boolean_object(left == right),
-    Infix::NotEq => return native_bool_to_boolean_object(left != right),
+    Infix::LtEq => return native_bool_to_boolean_object(left <= right),
+    Infix::GtEq => return native_bool_to_boolean_object(left >= right),
     _ => return new_error(
-      format!("unknown operator: {} {} {}.", left, operator, right),
+      ErrorKind::Type, format!("unknown operator: {} {} {}.", left, operator, right),
     ),
   };
 
-  object::Object::Integer(int)
+  match int {
+    Some(int) => object::Object::Integer(object::Integer::new(int)),
+    None => new_error(ErrorKind::Arithmetic, format!("integer overflow: {} {} {}.", left, operator, right)),
+  }
+}
+
+fn to_float(obj: &object::Object) -> Option<f64> {
+  match obj {
+    object::Object::Integer(int) => Some(int.value as f64),
+    object::Object::Float(float) => Some(float.value),
+    #[cfg(feature = "bigint")]
+    object::Object::BigInt(big) => Some(big.to_f64()),
+    _ => None,
+  }
+}
+
+fn eval_float_infix_expression(left: f64, operator: &Infix, right: f64) -> object::Object {
+  let float = match operator {
+    Infix::Plus => left + right,
+    Infix::Minus => left - right,
+    Infix::Asterisk => left * right,
+    Infix::Slash => {
+      if right == 0.0 {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {:?} / {:?}.", left, right));
+      }
+      left / right
+    },
+    Infix::Percent => {
+      if right == 0.0 {
+        return new_error(ErrorKind::Arithmetic, format!("division by zero: {:?} % {:?}.", left, right));
+      }
+      left % right
+    },
+    Infix::Power => left.powf(right),
+    Infix::Lt => return native_bool_to_boolean_object(left < right),
+    Infix::Gt => return native_bool_to_boolean_object(left > right),
+    Infix::LtEq => return native_bool_to_boolean_object(left <= right),
+    Infix::GtEq => return native_bool_to_boolean_object(left >= right),
+    _ => return new_error(
+      ErrorKind::Type, format!("unknown operator: {:?} {} {:?}.", left, operator, right),
+    ),
+  };
+
+  object::Object::Float(object::Float::new(float))
 }
 
 fn eval_string_infix_expression(left: object::Object, operator: &Infix, right: object::Object) -> object::Object {
@@ -251,99 +656,628 @@ fn eval_string_infix_expression(left: object::Object, operator: &Infix, right: o
 
   let s = match operator {
     Infix::Plus => object::Str::new(format!("{}{}", left, right)),
-    Infix::Equal => return native_bool_to_boolean_object(left == right),
-    Infix::NotEq => return native_bool_to_boolean_object(left != right),
+    // 文字列はコードポイントの辞書順で比べる
+    Infix::Lt => return native_bool_to_boolean_object(left < right),
+    Infix::Gt => return native_bool_to_boolean_object(left > right),
+    Infix::LtEq => return native_bool_to_boolean_object(left <= right),
+    Infix::GtEq => return native_bool_to_boolean_object(left >= right),
     _ => return new_error(
-      format!("unknown operator: \"{}\" {} \"{}\".", left, operator, right),
+      ErrorKind::Type, format!("unknown operator: \"{}\" {} \"{}\".", left, operator, right),
     ),
   };
 
   object::Object::Str(s)
 }
 
+fn eval_try_expression(expr: &TryExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let err = match eval_scoped_block(&expr.block, env) {
+    // 上限を越えたり止められたりしたスクリプトが catch して動き続けないように、それらのエラーは捕まえない
+    object::Object::Error(err) if err.kind != ErrorKind::LimitExceeded && err.kind != ErrorKind::Cancelled => err,
+    result => return result,
+  };
+
+  // catch の引数は、for の変数と同じく handler の中だけで見える
+  let env = Environment::new_enclosed_env(Rc::clone(env));
+  bind_new(expr.param.value, error_to_hash(&err), &env);
+  eval_block_statement(&expr.handler, &env)
+}
+
+/// Converts `err` to the hash a `catch` handler receives, with the keys `kind`, `message`, `line`, `column` and `trace`.
+/// `trace` is an array of hashes with the keys `function`, `line` and `column`, from the innermost call.
+fn error_to_hash(err: &object::Error) -> object::Object {
+  let mut pairs = HashMap::new();
+  pairs.insert(str_object("kind"), str_object(&err.kind.to_string()));
+  pairs.insert(str_object("message"), str_object(&err.message));
+  insert_location(&mut pairs, err.span);
+
+  let trace = err.trace.iter().map(|frame| {
+    let mut pairs = HashMap::new();
+    let function = match frame.function {
+      Some(name) => str_object(name.as_str()),
+      None => NULL,
+    };
+    pairs.insert(str_object("function"), function);
+    insert_location(&mut pairs, Some(frame.span));
+    object::Object::Hash(object::Hash::new(pairs))
+  }).collect();
+  pairs.insert(str_object("trace"), object::Object::Array(object::Array::new(trace)));
+  object::Object::Hash(object::Hash::new(pairs))
+}
+
+fn insert_location(pairs: &mut HashMap<object::Object, object::Object>, span: Option<Span>) {
+  let (line, column) = match span {
+    Some(span) => (
+      object::Object::Integer(object::Integer::new(span.start.line as i64)),
+      object::Object::Integer(object::Integer::new(span.start.column as i64)),
+    ),
+    None => (NULL, NULL),
+  };
+  pairs.insert(str_object("line"), line);
+  pairs.insert(str_object("column"), column);
+}
+
+fn str_object(s: &str) -> object::Object {
+  object::Object::Str(object::Str::new(s.to_string()))
+}
+
 fn eval_if_expression(expr: &IfExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
   let condition = eval_expression(&expr.condition, env);
   if is_error(&condition) {
     return condition;
   }
 
-  if is_truthy(condition) {
-    eval_block_statement(&expr.consequence, env)
-  } else if let Some(alt) = &expr.alternative {
-    eval_block_statement(&alt, env)
-  } else {
-    NULL
+  if is_truthy(condition) {
+    eval_scoped_block(&expr.consequence, env)
+  } else if let Some(alt) = &expr.alternative {
+    eval_scoped_block(alt, env)
+  } else {
+    NULL
+  }
+}
+
+fn eval_ternary_expression(expr: &TernaryExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let condition = eval_expression(&expr.condition, env);
+  if is_error(&condition) {
+    return condition;
+  }
+
+  if is_truthy(condition) {
+    eval_expression(&expr.consequence, env)
+  } else {
+    eval_expression(&expr.alternative, env)
+  }
+}
+
+fn eval_range_expression(range: &RangeExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let start = eval_expression(&range.start, env);
+  if is_error(&start) {
+    return start;
+  }
+  let end = eval_expression(&range.end, env);
+  if is_error(&end) {
+    return end;
+  }
+  new_range(start, end, range.inclusive)
+}
+
+pub(crate) fn new_range(start: object::Object, end: object::Object, inclusive: bool) -> object::Object {
+  let (start, end) = match (&start, &end) {
+    (object::Object::Integer(start), object::Object::Integer(end)) => (start.value, end.value),
+    #[cfg(feature = "bigint")]
+    (object::Object::BigInt(_), object::Object::Integer(_) | object::Object::BigInt(_))
+    | (object::Object::Integer(_), object::Object::BigInt(_)) => {
+      return new_error(ErrorKind::Arithmetic, format!("range bounds must fit in 64 bits: got={}..{}", start, end));
+    },
+    _ => return new_error(ErrorKind::Type, format!("range bounds must be INTEGER: got={}..{}", start, end)),
+  };
+
+  if inclusive {
+    object::Object::Range(object::Range::new_inclusive(start, end))
+  } else {
+    object::Object::Range(object::Range::new(start, end))
+  }
+}
+
+fn eval_block_statement(block: &BlockStatement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let mut result: object::Object = NULL;
+  for stmt in &block.statements {
+    if let Statement::Comment(_) = stmt {
+      continue;
+    }
+    result = eval_statement(stmt, env);
+
+    match result {
+      object::Object::Return(_)
+      | object::Object::Error(_) => return result,
+      _ => (),
+    }
+  }
+  result
+}
+
+// ブロックの中の let、fn と struct はブロックの外から見えない。どれもないブロックは環境を作らずに評価する
+fn eval_scoped_block(block: &BlockStatement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if !block.statements.iter().any(|stmt| matches!(stmt, Statement::Let(_) | Statement::Func(_) | Statement::Struct(_))) {
+    return eval_block_statement(block, env);
+  }
+  let block_env = Environment::new_enclosed_env(Rc::clone(env));
+  eval_block_statement(block, &block_env)
+}
+
+fn eval_while_statement(stmt: &WhileStatement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  loop {
+    let condition = eval_expression(&stmt.condition, env);
+    if is_error(&condition) {
+      return condition;
+    }
+    if !is_truthy(condition) {
+      return NULL;
+    }
+
+    // イテレーションごとに新しいスコープで評価する
+    let result = eval_scoped_block(&stmt.body, env);
+    match result {
+      object::Object::Return(_)
+      | object::Object::Error(_) => return result,
+      _ => (),
+    }
+  }
+}
+
+fn eval_for_statement(stmt: &ForStatement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let iterable = eval_expression(&stmt.iterable, env);
+  if is_error(&iterable) {
+    return iterable;
+  }
+
+  // Hash は keys() と同じ順でキーを回す
+  let items: Box<dyn Iterator<Item = object::Object>> = match iterable {
+    object::Object::Array(arr) => Box::new(arr.elements.into_iter()),
+    object::Object::Hash(hash) => Box::new(builtins::sort_pairs(hash.pairs).into_iter().map(|(key, _)| key)),
+    object::Object::Range(range) => Box::new(
+      range.iter().map(|i| object::Object::Integer(object::Integer::new(i))),
+    ),
+    _ => return new_error(ErrorKind::Type, format!("{} is not iterable.", iterable)),
+  };
+
+  for item in items {
+    // ループ変数はイテレーションごとに新しいスコープに束縛する
+    let loop_env = Environment::new_enclosed_env(Rc::clone(env));
+    bind_new(stmt.ident.value, item, &loop_env);
+
+    let result = eval_block_statement(&stmt.body, &loop_env);
+    match result {
+      object::Object::Return(_)
+      | object::Object::Error(_) => return result,
+      _ => (),
+    }
+  }
+  NULL
+}
+
+pub(crate) fn bind(name: Name, value: object::Object, env: &Rc<RefCell<Environment>>) -> Result<(), object::Object> {
+  check_bindable(name, env)?;
+  bind_new(name, value, env);
+  Ok(())
+}
+
+fn bind_new(name: Name, value: object::Object, env: &Rc<RefCell<Environment>>) {
+  observer::notify(&env.borrow().observer(), |o| o.bind(name, &value));
+  env.borrow_mut().set(name, value);
+}
+
+// const の束縛は代入できない
+pub(crate) fn bind_constant(name: Name, value: object::Object, env: &Rc<RefCell<Environment>>) -> Result<(), object::Object> {
+  check_bindable(name, env)?;
+  observer::notify(&env.borrow().observer(), |o| o.bind(name, &value));
+  env.borrow_mut().set_constant(name, value);
+  Ok(())
+}
+
+// let で組み込み関数の名前は隠せない。Monkey で書かれた prelude の関数は隠せる
+pub(crate) fn check_bindable(name: Name, env: &Rc<RefCell<Environment>>) -> Result<(), object::Object> {
+  if let Some(object::Object::Builtin(_)) | Some(object::Object::External(_)) = env.borrow().get_builtin(name) {
+    return Err(new_error(ErrorKind::Name, format!("`{}` is already used as a builtin function.", name)));
+  }
+  Ok(())
+}
+
+// 足りない要素やキーには null を束縛する
+fn bind_pattern(pattern: &Pattern, value: object::Object, constant: bool, env: &Rc<RefCell<Environment>>) -> Result<(), object::Object> {
+  let mut bindings = vec![];
+  destructure(pattern, value, false, &mut bindings, env)?;
+  for (name, value) in bindings {
+    if constant {
+      bind_constant(name, value, env)?;
+    } else {
+      bind(name, value, env)?;
+    }
+  }
+  Ok(())
+}
+
+// pattern に value を当てはめて、束縛する名前と値を bindings に集める。
+// refutable なら形が合わないときに false を返し、そうでなければ let と同じく足りない分を null にして、形が違えばエラーにする
+fn destructure(
+  pattern: &Pattern,
+  value: object::Object,
+  refutable: bool,
+  bindings: &mut Vec<(Name, object::Object)>,
+  env: &Rc<RefCell<Environment>>,
+) -> Result<bool, object::Object> {
+  match pattern {
+    Pattern::Ident(ident) => bindings.push((ident.value, value)),
+    Pattern::Literal(lit) => {
+      let expected = eval_literal(lit, env);
+      return Ok(is_truthy(eval_infix_expression(value, &Infix::Equal, expected)));
+    },
+    Pattern::Array(pat) => {
+      let elements = match value {
+        object::Object::Array(arr) => arr.elements,
+        _ if refutable => return Ok(false),
+        _ => return Err(new_error(ErrorKind::Type, format!("cannot destructure {} as an array.", value))),
+      };
+      if refutable && (elements.len() < pat.elements.len() || pat.rest.is_none() && elements.len() > pat.elements.len()) {
+        return Ok(false);
+      }
+      let mut elements = elements.into_iter();
+      for elm in &pat.elements {
+        if !destructure(elm, elements.next().unwrap_or(NULL), refutable, bindings, env)? {
+          return Ok(false);
+        }
+      }
+      if let Some(rest) = &pat.rest {
+        bindings.push((rest.value, object::Object::Array(object::Array::new(elements.collect()))));
+      }
+    },
+    Pattern::Hash(pat) => {
+      let hash = match value {
+        object::Object::Hash(hash) => hash,
+        _ if refutable => return Ok(false),
+        _ => return Err(new_error(ErrorKind::Type, format!("cannot destructure {} as a hash.", value))),
+      };
+      for (key, pattern) in &pat.pairs {
+        let val = match hash.pairs.get(&str_object(key.value.as_str())) {
+          Some(val) => val.clone(),
+          None if refutable => return Ok(false),
+          None => NULL,
+        };
+        if !destructure(pattern, val, refutable, bindings, env)? {
+          return Ok(false);
+        }
+      }
+    },
+    Pattern::Struct(pat) => {
+      let def = lookup_struct(&pat.name, env)?;
+      let record = match value {
+        object::Object::Record(record) if record.def.is(&def) => record,
+        _ if refutable => return Ok(false),
+        _ => return Err(new_error(ErrorKind::Type, format!("cannot destructure {} as {}.", value, def.name))),
+      };
+      for (key, pattern) in &pat.fields {
+        let val = match record.get(key.value.as_str()) {
+          Some(val) => val.clone(),
+          None => return Err(unknown_field(&def, key.value.as_str())),
+        };
+        if !destructure(pattern, val, refutable, bindings, env)? {
+          return Ok(false);
+        }
+      }
+    },
+  }
+  Ok(true)
+}
+
+// 最初に当てはまった腕の本体を評価する。どの腕にも当てはまらなければ null を返す
+fn eval_match_expression(expr: &MatchExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let subject = eval_expression(&expr.subject, env);
+  if is_error(&subject) {
+    return subject;
+  }
+  for arm in &expr.arms {
+    let mut bindings = vec![];
+    match destructure(&arm.pattern, subject.clone(), true, &mut bindings, env) {
+      Ok(true) => (),
+      Ok(false) => continue,
+      Err(err) => return err,
+    }
+    if bindings.is_empty() {
+      return eval_expression(&arm.body, env);
+    }
+    // パターンの変数は、catch の引数と同じく腕の本体の中だけで見える
+    let arm_env = Environment::new_enclosed_env(Rc::clone(env));
+    for (name, value) in bindings {
+      if let Err(err) = bind(name, value, &arm_env) {
+        return err;
+      }
+    }
+    return eval_expression(&arm.body, &arm_env);
+  }
+  NULL
+}
+
+fn eval_statement(stmt: &Statement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if let Err(err) = env.borrow().budget().step() {
+    return locate(err, stmt.span(), env);
+  }
+  // デバッガが評価するコードでは、借用済みなのでデバッガを呼ばない
+  let debugger = env.borrow().debugger();
+  if let Ok(mut debugger) = debugger.try_borrow_mut() {
+    if let Some(debugger) = debugger.as_mut() {
+      if debugger.on_statement(stmt, env) == debugger::Action::Abort {
+        return locate(new_error(ErrorKind::Cancelled, "evaluation was stopped by the debugger.".to_string()), stmt.span(), env);
+      }
+    }
+  }
+  let observer = env.borrow().observer();
+  observer::notify(&observer, |o| o.enter_statement(stmt));
+  let result = locate(eval_nested_statement(stmt, env), stmt.span(), env);
+  observer::notify(&observer, |o| o.exit_statement(stmt, &result));
+  result
+}
+
+fn eval_nested_statement(stmt: &Statement, env: &Rc<RefCell<Environment>>) -> object::Object {
+  match stmt {
+    Statement::Expr(expr) => eval_expression(&expr.value, env),
+    Statement::Return(rtn) => {
+      let expr = eval_expression(&rtn.value, env);
+      if is_error(&expr) {
+        return expr;
+      }
+      object::Object::Return(
+        object::Return::new(Box::new(expr)),
+      )
+    },
+    Statement::Let(let_stmt) => {
+      let expr = eval_expression(&let_stmt.value, env);
+      if is_error(&expr) {
+        return expr;
+      }
+      if let Err(err) = bind_pattern(&let_stmt.pattern, expr.clone(), let_stmt.constant, env) {
+        return err;
+      }
+      expr
+    },
+    // 関数は env を閉じ込めるので、後から束縛した名前で自分自身を呼べる
+    Statement::Func(func_stmt) => {
+      let func = eval_func(&func_stmt.func, env);
+      if let Err(err) = bind(func_stmt.ident.value, func.clone(), env) {
+        return err;
+      }
+      func
+    },
+    Statement::Struct(struct_stmt) => {
+      let mut fields = Vec::with_capacity(struct_stmt.fields.len());
+      for field in &struct_stmt.fields {
+        if fields.contains(&field.value) {
+          return new_error(ErrorKind::Name, format!("duplicate field `{}` in struct {}.", field, struct_stmt.ident));
+        }
+        fields.push(field.value);
+      }
+      let def = object::Object::Struct(object::Struct::new(struct_stmt.ident.value, fields));
+      if let Err(err) = bind(struct_stmt.ident.value, def.clone(), env) {
+        return err;
+      }
+      def
+    },
+    Statement::Block(block) => eval_scoped_block(block, env),
+    Statement::While(while_stmt) => eval_while_statement(while_stmt, env),
+    Statement::For(for_stmt) => eval_for_statement(for_stmt, env),
+    _ => NULL,
+  }
+}
+
+/// A location which can be written to.
+/// The collections and indexes on the way are evaluated only once, even when the location is read and then written.
+enum Place<'a> {
+  Ident(&'a Identifier),
+  Index {
+    parent: Box<Place<'a>>,
+    collection: Box<object::Object>,
+    index: Box<object::Object>,
+  },
+}
+
+fn eval_place<'a>(target: &'a Expression, env: &Rc<RefCell<Environment>>) -> Result<Place<'a>, object::Object> {
+  match target {
+    Expression::Identifier(ident) => Ok(Place::Ident(ident)),
+    Expression::Index(idx) => {
+      let parent = eval_place(&idx.left, env)?;
+      let collection = read_place(&parent, env);
+      if is_error(&collection) {
+        return Err(collection);
+      }
+      let index = eval_expression(&idx.index, env);
+      if is_error(&index) {
+        return Err(index);
+      }
+      Ok(Place::Index { parent: Box::new(parent), collection: Box::new(collection), index: Box::new(index) })
+    },
+    Expression::Member(member) => {
+      let parent = eval_place(&member.object, env)?;
+      let collection = read_place(&parent, env);
+      if is_error(&collection) {
+        return Err(collection);
+      }
+      let index = property_key(&member.property);
+      Ok(Place::Index { parent: Box::new(parent), collection: Box::new(collection), index: Box::new(index) })
+    },
+    _ => Err(new_error(ErrorKind::Type, format!("invalid assignment target: {}.", target))),
+  }
+}
+
+fn read_place(place: &Place, env: &Rc<RefCell<Environment>>) -> object::Object {
+  match place {
+    Place::Ident(ident) => eval_ident_expression(ident, env),
+    Place::Index { collection, index, .. } => eval_index_expression(*collection.clone(), *index.clone()),
+  }
+}
+
+// コレクションは値として扱われるので、要素を書き換えたコレクションを親に書き戻す
+fn write_place(place: Place, value: object::Object, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let (parent, collection, index) = match place {
+    Place::Ident(ident) => {
+      if env.borrow().is_constant(ident.value) {
+        return new_error(ErrorKind::Name, format!("assignment to constant: {}.", ident.value));
+      }
+      if !env.borrow_mut().assign(ident.value, value.clone()) {
+        return new_error(ErrorKind::Name, format!("assignment to undeclared identifier: {}.", ident.value));
+      }
+      observer::notify(&env.borrow().observer(), |o| o.assign(ident.value, &value));
+      return value;
+    },
+    Place::Index { parent, collection, index } => (parent, collection, index),
+  };
+
+  let updated = match (*collection, *index) {
+    (object::Object::Array(mut arr), object::Object::Integer(idx)) => {
+      if idx.value < 0 || idx.value as usize >= arr.elements.len() {
+        return new_error(ErrorKind::Index, format!("index out of range: {}.", idx.value));
+      }
+      arr.elements[idx.value as usize] = value.clone();
+      object::Object::Array(arr)
+    },
+    (object::Object::Array(_), idx) => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", idx)),
+    (object::Object::Hash(mut hash), key) => {
+      if !key.is_hashable() {
+        return new_error(ErrorKind::Type, format!("unusable as hash key: {}", key));
+      }
+      hash.pairs.insert(key, value.clone());
+      object::Object::Hash(hash)
+    },
+    // レコードには struct にあるフィールドしか書けない
+    (object::Object::Record(mut record), object::Object::Str(key)) => match record.def.field_index(&key.value) {
+      Some(i) => {
+        record.values[i] = value.clone();
+        object::Object::Record(record)
+      },
+      None => return unknown_field(&record.def, &key.value),
+    },
+    (object::Object::Record(_), idx) => return new_error(ErrorKind::Type, format!("specified index type is not supported: {}", idx)),
+    (collection, _) => return new_error(ErrorKind::Type, format!("index operator not supported: {}", collection)),
+  };
+
+  let result = write_place(*parent, updated, env);
+  if is_error(&result) {
+    return result;
+  }
+  value
+}
+
+fn eval_assign_expression(assign: &AssignExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let place = match eval_place(&assign.target, env) {
+    Ok(place) => place,
+    Err(err) => return err,
+  };
+
+  // 複合代入では右辺より先に現在の値を読む
+  let current = match &assign.operator {
+    Some(_) => {
+      let current = read_place(&place, env);
+      if is_error(&current) {
+        return current;
+      }
+      Some(current)
+    },
+    None => None,
+  };
+
+  let mut value = eval_expression(&assign.value, env);
+  if is_error(&value) {
+    return value;
+  }
+
+  if let (Some(operator), Some(current)) = (&assign.operator, current) {
+    value = eval_infix_in_env(current, operator, value, env);
+    if is_error(&value) {
+      return value;
+    }
   }
+
+  write_place(place, value, env)
 }
 
-fn eval_block_statement(block: &BlockStatement, env: &Rc<RefCell<Environment>>) -> object::Object {
-  let mut result: object::Object = NULL;
-  for stmt in &block.statements {
-    if let Statement::Comment(_) = stmt {
-      continue;
-    }
-    result = eval_statement(stmt, env);
+fn eval_update_expression(update: &UpdateExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let place = match eval_place(&update.target, env) {
+    Ok(place) => place,
+    Err(err) => return err,
+  };
 
-    match result {
-      object::Object::Return(_)
-      | object::Object::Error(_) => return result,
-      _ => (),
-    }
+  let current = read_place(&place, env);
+  if is_error(&current) {
+    return current;
   }
-  result
-}
 
-fn eval_statement(stmt: &Statement, env: &Rc<RefCell<Environment>>) -> object::Object {
-  match stmt {
-    Statement::Expr(expr) => eval_expression(&expr.value, env),
-    Statement::Return(rtn) => {
-      let expr = eval_expression(&rtn.value, env);
-      if is_error(&expr) {
-        return expr;
-      }
-      object::Object::Return(
-        object::Return::new(Box::new(expr)),
-      )
-    },
-    Statement::Let(let_stmt) => {
-      let expr = eval_expression(&let_stmt.value, env);
-      if is_error(&expr) {
-        return expr;
-      }
-      if let Some(_) = env.borrow().get_builtin(&let_stmt.ident.value) {
-        return new_error(format!("`{}` is already used as a builtin function.", &let_stmt.ident.value))
-      }
-      env.borrow_mut().set(&let_stmt.ident.value, expr.clone());
-      expr
+  let delta = match update.operator {
+    Update::Increment => 1,
+    Update::Decrement => -1,
+  };
+  let updated = match &current {
+    object::Object::Integer(int) => match int.value.checked_add(delta) {
+      Some(value) => object::Object::Integer(object::Integer::new(value)),
+      #[cfg(feature = "bigint")]
+      None if env.borrow().bigint() => eval_infix_in_env(current.clone(), &Infix::Plus, object::Object::Integer(object::Integer::new(delta)), env),
+      None if update.prefix => return new_error(ErrorKind::Arithmetic, format!("integer overflow: {}{}.", update.operator, current)),
+      None => return new_error(ErrorKind::Arithmetic, format!("integer overflow: {}{}.", current, update.operator)),
     },
-    _ => NULL,
+    #[cfg(feature = "bigint")]
+    object::Object::BigInt(_) => eval_infix_expression(current.clone(), &Infix::Plus, object::Object::Integer(object::Integer::new(delta))),
+    object::Object::Float(float) => object::Object::Float(object::Float::new(float.value + delta as f64)),
+    _ if update.prefix => return new_error(ErrorKind::Type, format!("unknown operator: {}{}.", update.operator, current)),
+    _ => return new_error(ErrorKind::Type, format!("unknown operator: {}{}.", current, update.operator)),
+  };
+
+  let result = write_place(place, updated.clone(), env);
+  if is_error(&result) {
+    return result;
+  }
+
+  if update.prefix {
+    updated
+  } else {
+    current
   }
 }
 
 fn eval_ident_expression(ident: &Identifier, env: &Rc<RefCell<Environment>>) -> object::Object {
-  match env.borrow().get(&ident.value) {
-    Some(val) => return val.clone(),
-    None => (),
+  lookup(ident.value, env)
+}
+
+pub(crate) fn lookup(name: Name, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if let Some(val) = env.borrow().get(name) {
+    return val;
   }
 
-  match env.borrow().get_builtin(&ident.value) {
+  match env.borrow().get_builtin(name) {
     Some(val) => val.clone(),
-    None => new_error(format!("identifier not found: {}.", ident.value)),
+    None => new_error(ErrorKind::Name, format!("identifier not found: {}.", name)),
   }
 }
 
-fn new_error(msg: String) -> object::Object {
-  object::Object::Error(object::Error::new(msg))
+pub(crate) fn new_error(kind: ErrorKind, msg: String) -> object::Object {
+  object::Object::Error(Box::new(object::Error::new(kind, msg)))
 }
 
-fn is_error(obj: &object::Object) -> bool {
-  match obj {
-    object::Object::Error(_) => true,
-    _ => false,
+// エラーの位置は、それが起きた一番内側の式や文にする
+// span は env で評価したコードの中の位置
+fn locate(mut obj: object::Object, span: Span, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if let object::Object::Error(err) = &mut obj {
+    if err.span.is_none() {
+      err.span = Some(span);
+      err.file = env.borrow().file();
+    }
   }
+  obj
+}
+
+pub(crate) fn is_error(obj: &object::Object) -> bool {
+  matches!(obj, object::Object::Error(_))
 }
 
 fn eval_call_expression(call: &CallExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  if macro_expansion::is_quote_call(call) {
+    return macro_expansion::eval_quote(call, env);
+  }
   let mut func = eval_expression(&call.func, env);
   if is_error(&func) {
     return func;
@@ -354,35 +1288,119 @@ fn eval_call_expression(call: &CallExpression, env: &Rc<RefCell<Environment>>) -
     return args.pop().unwrap();
   }
 
-  return apply_func(&mut func, args);
+  let function = match &*call.func {
+    Expression::Identifier(ident) => Some(ident.value),
+    _ => None,
+  };
+  let observer = env.borrow().observer();
+  observer::notify(&observer, |o| o.enter_call(call, function, &args));
+  let mut result = apply_func(&mut func, args, env);
+  observer::notify(&observer, |o| o.exit_call(call, function, &result));
+  // 関数の中で起きたエラーには位置が付いているので、この呼び出しをトレースに積む。
+  // 引数の数の誤りのように呼び出しそのもののエラーは、呼び出し式の位置になる
+  if let object::Object::Error(err) = &mut result {
+    if err.span.is_some() {
+      err.trace.push(object::TraceFrame { function, span: call.span, file: env.borrow().file() });
+    }
+  }
+  result
+}
+
+// 型ごとのメソッドは組み込み関数なので、let で束縛した関数を呼ぶことはない。
+// ハッシュやレコードが関数を持っていれば、それを呼ぶ
+fn eval_method_call_expression(method: &MethodCallExpression, env: &Rc<RefCell<Environment>>) -> object::Object {
+  let receiver = eval_expression(&method.receiver, env);
+  if is_error(&receiver) {
+    return receiver;
+  }
+
+  let mut args = eval_expressions(&method.args, env);
+  if args.len() == 1 && is_error(&args[0]) {
+    return args.pop().unwrap();
+  }
+  let (mut func, args) = match builtins::lookup_method(receiver, method.method.value, args, env) {
+    Ok(method) => method,
+    Err(err) => return err,
+  };
+
+  let mut result = apply_func(&mut func, args, env);
+  if let object::Object::Error(err) = &mut result {
+    if err.span.is_some() {
+      err.trace.push(object::TraceFrame { function: Some(method.method.value), span: method.span, file: env.borrow().file() });
+    }
+  }
+  result
 }
 
 fn eval_expressions(args: &Vec<Expression>, env: &Rc<RefCell<Environment>>) -> Vec<object::Object> {
   let mut result: Vec<object::Object> = vec![];
   for arg in args {
+    let (arg, is_spread) = match arg {
+      Expression::Spread(spread) => (&*spread.value, true),
+      _ => (arg, false),
+    };
+
     let evaluated = eval_expression(arg, env);
     if is_error(&evaluated) {
       return vec![evaluated];
     }
-    result.push(evaluated);
+
+    if !is_spread {
+      result.push(evaluated);
+      continue;
+    }
+    match evaluated {
+      object::Object::Array(arr) => result.extend(arr.elements),
+      _ => return vec![new_error(ErrorKind::Type, format!("spread operand must be ARRAY: got={}", evaluated))],
+    }
   }
   result
 }
 
-fn apply_func(obj: &mut object::Object, args: Vec<object::Object>) -> object::Object {
+pub(crate) fn apply_func(obj: &mut object::Object, args: Vec<object::Object>, env: &Rc<RefCell<Environment>>) -> object::Object {
   let func = match obj {
     object::Object::Func(func) => func,
-    object::Object::Builtin(builtin) => return (builtin.func)(args),
+    object::Object::Builtin(builtin) => return (builtin.func)(args, env),
+    object::Object::Closure(closure) => return crate::vm::apply_closure(closure, args),
     object::Object::External(external) => return (external.func.borrow_mut())(args),
-    _ => return new_error(format!("not a function: {}.", obj)),
+    _ => return new_error(ErrorKind::Type, format!("not a function: {}.", obj)),
   };
 
-  let env = Environment::new_enclosed_env(Rc::downgrade(&func.env));
-  for (i, arg) in args.into_iter().enumerate() {
-    (&env).borrow_mut().set(&func.args[i].value, arg);
+  if args.len() > func.args.len() {
+    return new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want={}.", args.len(), func.args.len()));
+  }
+
+  let env = Environment::new_enclosed_env(Rc::clone(&func.env));
+  let mut args = args.into_iter();
+  for (param, default) in func.args.iter().zip(func.defaults.iter()) {
+    // 省略された引数のデフォルト値は、それより前の引数が束縛された関数のスコープで評価する
+    let arg = match (args.next(), default) {
+      (Some(arg), _) => arg,
+      (None, Some(default)) => {
+        let evaluated = eval_expression(default, &env);
+        if is_error(&evaluated) {
+          return evaluated;
+        }
+        evaluated
+      },
+      (None, None) => return new_error(ErrorKind::Argument, format!("missing argument: {}.", param.value)),
+    };
+    bind_new(param.value, arg, &env);
   }
 
+  // 入れ子になった式の深さとは別に、関数呼び出しの深さを数える
+  let depth = env.borrow().depth();
+  if !depth.enter_call() {
+    return new_error(ErrorKind::Recursion, format!("maximum recursion depth exceeded: {}.", depth.max()));
+  }
+  let budget = env.borrow().budget();
+  if let Err(err) = budget.enter_call() {
+    depth.leave_call();
+    return err;
+  }
   let evaluated = eval_block_statement(&func.body, &env);
+  budget.leave_call();
+  depth.leave_call();
 
   unwrap_returned_value(evaluated)
 }
@@ -394,7 +1412,7 @@ fn unwrap_returned_value(obj: object::Object) -> object::Object {
   obj
 }
 
-fn is_truthy(obj: object::Object) -> bool {
+pub(crate) fn is_truthy(obj: object::Object) -> bool {
   match obj {
     object::Object::Boolean(val) => val.value,
     object::Object::Null => false,
@@ -402,7 +1420,7 @@ fn is_truthy(obj: object::Object) -> bool {
   }
 }
 
-fn native_bool_to_boolean_object(input: bool) -> object::Object {
+pub(crate) fn native_bool_to_boolean_object(input: bool) -> object::Object {
   if input {
     return TRUE;
   }
@@ -411,6 +1429,7 @@ fn native_bool_to_boolean_object(input: bool) -> object::Object {
 
 #[cfg(test)] 
 mod tests {
+  use std::time::Duration;
   use crate::lexer::Lexer;
   use crate::parser::Parser;
   use super::*;
@@ -433,6 +1452,27 @@ mod tests {
         ("3 * 3 * 3 + 10", 37),
         ("3 * (3 * 3) + 10", 37),
         ("(5 + 10 * 2 + 15 / 3) * 2 + -10", 50),
+        ("10 % 3", 1),
+        ("2 ** 10", 1024),
+        ("2 ** 3 ** 2", 512),
+        ("-2 ** 2", -4),
+        ("(-2) ** 3", -8),
+        ("3 * 2 ** 2", 12),
+        ("5 ** 0", 1),
+        ("-7 % 3", -1),
+        ("2 + 10 % 4 * 3", 8),
+        ("12 & 10", 8),
+        ("12 | 10", 14),
+        ("12 ^ 10", 6),
+        ("~5", -6),
+        ("~-1", 0),
+        ("1 << 10", 1024),
+        ("1024 >> 3", 128),
+        ("-16 >> 2", -4),
+        ("1 << 63", i64::MIN),
+        ("1 | 2 ^ 3 & 6", 1),
+        ("1 << 2 + 1", 8),
+        ("6 & 3 == 2 ? 1 : 0", 1),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -441,6 +1481,26 @@ mod tests {
       }
   }
 
+  #[test]
+  fn test_eval_float_expression() {
+      let tests: Vec<(&str, f64)> = vec![
+        ("1.5", 1.5),
+        ("-2.5", -2.5),
+        ("1.5 + 2.25", 3.75),
+        ("1 + 0.5", 1.5),
+        ("0.5 * 4", 2.0),
+        ("7 / 2.0", 3.5),
+        ("7.5 % 2", 1.5),
+        ("2 ** 0.5 * 2 ** 0.5", 2.0000000000000004),
+        ("1e3 - 1", 999.0),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_float_object(evaluated, expected);
+      }
+  }
+
   #[test]
   fn test_eval_boolean_expression() {
       let tests: Vec<(&str, bool)> = vec![
@@ -450,6 +1510,13 @@ mod tests {
         ("1 > 2", false),
         ("1 < 1", false),
         ("1 > 1", false),
+        ("1 <= 1", true),
+        ("1 <= 2", true),
+        ("2 <= 1", false),
+        ("1 >= 1", true),
+        ("1 >= 2", false),
+        ("2 >= 1", true),
+        ("(1 <= 2) == (3 >= 4)", false),
         ("1 == 1", true),
         ("1 != 1", false),
         ("1 == 2", false),
@@ -465,6 +1532,54 @@ mod tests {
         ("(1 < 2) == false", false),
         ("(1 > 2) != true", true),
         ("(1 > 2) != false", false),
+        ("1.5 < 2", true),
+        ("2.0 == 2", true),
+        ("0.1 + 0.2 == 0.3", false),
+        ("3 >= 3.5", false),
+        ("true && true", true),
+        ("true && false", false),
+        ("false || true", true),
+        ("false || false", false),
+        ("1 < 2 && 2 < 3", true),
+        ("1 && \"a\"", true),
+        ("false && (1 + true)", false),
+        ("true || undefined", true),
+        ("let f = fn() { return 1 / 0; }; false && f()", false),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_boolean_object(evaluated, expected);
+      }
+  }
+
+  #[test]
+  fn test_eval_equality() {
+      let tests: Vec<(&str, bool)> = vec![
+        ("1 == \"1\"", false),
+        ("1 != true", true),
+        ("null == false", false),
+        ("null == null", true),
+        ("[] == []", true),
+        ("[1, [2, \"a\"]] == [1, [2, \"a\"]]", true),
+        ("[1, 2] == [1, 2.0]", true),
+        ("[1, 2] == [2, 1]", false),
+        ("[1] == [1, 2]", false),
+        ("{1: \"a\", true: [2]} == {true: [2], 1: \"a\"}", true),
+        ("{1: \"a\"} == {1: \"b\"}", false),
+        ("{1: \"a\"} == {2: \"a\"}", false),
+        ("{1: \"a\"} != {1: \"a\", 2: \"b\"}", true),
+        ("0..3 == 0..3", true),
+        ("let f = fn(x) { x }; let g = f; f == g", true),
+        ("fn(x) { x } == fn(x) { x }", false),
+        ("let make = fn() { fn() { 1 } }; make() == make()", false),
+        ("len == len", true),
+        ("len == first", false),
+        ("len == fn() {}", false),
+        ("\"a\" < \"b\"", true),
+        ("\"b\" <= \"a\"", false),
+        ("\"ab\" > \"a\"", true),
+        ("\"a\" >= \"a\"", true),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -477,6 +1592,8 @@ mod tests {
   fn test_eval_string_expression() {
       let tests: Vec<(&str, &str)> = vec![
         ("\"Hello World!\"", "Hello World!"),
+        ("let name = \"monkey\"; name;", "monkey"),
+        ("\"\"", ""),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -538,6 +1655,9 @@ mod tests {
         ("[1, 2, 3][3]", None),
         ("[1, 2, 3][-1]", None),
         ("[][0]", None),
+        ("[[1, 2], [3, 4]][1][0]", Some(3)),
+        ("let f = fn() { [1, 2, 3] }; f()[1]", Some(2)),
+        ("let arr = [fn(x) { x * 2 }]; arr[0](4)", Some(8)),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -614,6 +1734,9 @@ let two = \"two\";
         ("{5: 5}[5]", Some(5)),
         ("{true: 5}[true]", Some(5)),
         ("{false: 5}[false]", Some(5)),
+        ("{1: 5, true: 6, \"1\": 7}[1]", Some(5)),
+        ("{1: 5, true: 6, \"1\": 7}[true]", Some(6)),
+        ("{1: 5, true: 6, \"1\": 7}[\"1\"]", Some(7)),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -625,9 +1748,41 @@ let two = \"two\";
       }
   }
 
+  #[test]
+  fn test_member_expression() {
+    let tests = vec![
+      ("{\"foo\": 5}.foo", "5"),
+      ("{\"foo\": 5}.bar", "null"),
+      ("let p = {\"a\": {\"b\": [1, 2]}}; p.a.b[1]", "2"),
+      ("let p = {\"name\": \"x\"}; p.name.len()", "1"),
+      ("let p = {}; p.name = \"x\"; p[\"name\"]", "\"x\""),
+      ("let p = {\"n\": 1}; p.n += 2; p.n++; p.n", "4"),
+      ("let p = {\"a\": {}}; p.a.b = 3; p", "{\"a\": {\"b\": 3}}"),
+      ("let p = {\"f\": fn(x) { x * 2 }}; (p.f)(4)", "8"),
+      ("let p = {\"f\": fn(x) { x * 2 }}; p.f(4) == (p.f)(4)", "true"),
+      ("let p = {\"a\": {\"f\": fn() { 3 }}}; p.a.f()", "3"),
+    ];
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("[1].foo", "specified index type is not supported: \"foo\""),
+      ("5.foo", "index operator not supported: 5"),
+      ("let p = {}; p.a.b = 1", "index operator not supported: null"),
+    ];
+    for (input, expected) in errors {
+      match test_eval(input) {
+        object::Object::Error(err) => assert_eq!(err.message, expected, "{}", input),
+        result => panic!("{}: expected an error, got {}", input, result),
+      }
+    }
+  }
+
   #[test]
   fn test_eval_hash_builtin() {
-      let tests: Vec<(&str, fn() -> HashMap<object::Object, i64>)> = vec![
+      type ExpectedPairs = fn() -> HashMap<object::Object, i64>;
+      let tests: Vec<(&str, ExpectedPairs)> = vec![
         ("insert({}, 1, 2)", || {
           let mut expected: HashMap<object::Object, i64> = HashMap::new();
           expected.insert(
@@ -732,6 +1887,25 @@ let two = \"two\";
       }
   }
 
+  #[test]
+  fn test_ternary_expression() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("true ? 1 : 2", 1),
+        ("false ? 1 : 2", 2),
+        ("null ? 1 : 2", 2),
+        ("0 ? 1 : 2", 1),
+        ("1 > 2 ? 10 : 1 < 2 ? 20 : 30", 20),
+        ("let x = 5; x % 2 == 0 ? x / 2 : x * 3 + 1", 16),
+        ("true ? 1 : 1 + true", 1),
+        ("let x = 0; true ? x = 3 : 0; x", 3),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+  }
+
   #[test]
   fn test_return_expression() {
       let tests: Vec<(&str, i64)> = vec![
@@ -756,16 +1930,326 @@ if(10 > 1) {
   }
 
   #[test]
-  fn test_let_statement() {
-      let tests: Vec<(&str, i64)> = vec![
-        ("let a = 5; a;", 5),
-        ("let a = 5 * 5; a;", 25),
-        ("let a = 5; let b = a; b;", 5),
-        ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+  fn test_while_statement() {
+      let tests: Vec<(&str, Option<i64>)> = vec![
+        ("while (false) { 1 }", None),
+        ("while (1 > 2) { 1 }; 5", Some(5)),
+        ("let f = fn() { while (true) { return 10; } }; f()", Some(10)),
+        ("let f = fn(n) { while (n > 0) { if (n == 3) { return n * 2; } return f(n - 1); } }; f(5)", Some(6)),
+        ("while (true) { return 3; }; 4", Some(3)),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        match expected {
+          Some(expected) => test_integer_object(evaluated, expected),
+          None => test_null_object(evaluated),
+        }
+      }
+  }
+
+  #[test]
+  fn test_for_statement() {
+      let tests: Vec<(&str, &str)> = vec![
+        ("for (x in [1, 2, 3]) { puts(x) }", "1\n2\n3\n"),
+        ("for (x in []) { puts(x) }", ""),
+        ("for (i in range(3)) { puts(i) }", "0\n1\n2\n"),
+        ("for (i in range(2, 4)) { puts(i * 10) }", "20\n30\n"),
+        ("for (i in range(3, 1)) { puts(i) }", ""),
+        ("for (k in {\"a\": 1}) { puts(k) }", "\"a\"\n"),
+        ("for (k in {\"b\": 1, 2: 2, \"a\": 3, true: 4, 1: 5}) { puts(k) }", "1\n2\ntrue\n\"a\"\n\"b\"\n"),
+        ("let x = 1; for (x in [2]) { puts(x) }; puts(x)", "2\n1\n"),
+        ("for (x in [1, 2]) { let y = x; puts(y) }", "1\n2\n"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let (evaluated, output) = test_eval_output(input);
+        test_null_object(evaluated);
+        assert_eq!(output, expected, "input={}", input);
+      }
+
+      test_integer_object(
+        test_eval("let f = fn(xs) { for (x in xs) { if (x > 1) { return x * 10; } } }; f([1, 2, 3])"),
+        20,
+      );
+  }
+
+  #[test]
+  fn test_assign_expression() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let x = 1; x = 10; x", 10),
+        ("let x = 1; x = 10", 10),
+        ("let x = 1; let y = 2; x = y = 3; x + y", 6),
+        ("let x = 1; let f = fn() { x = x + 1; }; f(); f(); x", 3),
+        ("let f = fn() { let x = 1; let g = fn() { x = 5; }; g(); x }; f()", 5),
+        ("let x = 1; let f = fn(x) { x = 100; }; f(2); x", 1),
+        ("let i = 0; let sum = 0; while (i < 5) { i = i + 1; sum = sum + i; }; sum", 15),
+        ("let sum = 0; for (x in [1, 2, 3]) { sum = sum + x; }; sum", 6),
+        ("let x = 10; x += 5; x", 15),
+        ("let x = 10; x -= 5", 5),
+        ("let x = 10; x *= 2 + 1; x", 30),
+        ("let x = 10; x /= 3; x", 3),
+        ("let x = 1; let y = 2; x += y += 3; x * 10 + y", 65),
+        ("let i = 0; let n = 1; while (i < 4) { i += 1; n *= 2; }; n", 16),
+        ("let x = 1; x += (x = 10); x", 11),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+  }
+
+  #[test]
+  fn test_index_assign_expression() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let a = [1, 2, 3]; a[0] = 5; a[0] + a[1]", 7),
+        ("let a = [1, 2, 3]; a[2] = 10", 10),
+        ("let h = {}; h[\"k\"] = 4; h[\"k\"]", 4),
+        ("let h = {\"k\": 1}; h[\"k\"] = 2; len(h) * 10 + h[\"k\"]", 12),
+        ("let h = {}; h[1] = 1; h[true] = 2; h[1] + h[true]", 3),
+        ("let m = [[0, 0], [0, 0]]; m[1][0] = 7; m[1][0] + m[0][0]", 7),
+        ("let h = {\"xs\": [1]}; h[\"xs\"][0] += 9; h[\"xs\"][0]", 10),
+        ("let a = [1, 2]; let b = a; a[0] = 5; b[0]", 1),
+        ("let a = [0, 0, 0]; for (i in range(3)) { a[i] = i * i; }; a[2]", 4),
+        ("let a = [1]; let f = fn() { a[0] = 3; }; f(); a[0]", 3),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+  }
+
+  #[test]
+  fn test_update_expression() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let x = 1; ++x", 2),
+        ("let x = 1; x++", 1),
+        ("let x = 1; x++; x", 2),
+        ("let x = 1; --x", 0),
+        ("let x = 1; x--; x", 0),
+        ("let x = 1; x++ + ++x", 4),
+        ("let a = [1, 2]; a[1]++; a[1]", 3),
+        ("let a = [1, [2, 3]]; ++a[1][0]; a[1][0] * 10 + a[1][1]", 33),
+        ("let h = {\"n\": 1}; h[\"n\"]--; h[\"n\"]", 0),
+        ("let i = 0; let sum = 0; while (i < 4) { sum += i++; }; sum * 10 + i", 64),
+        ("let a = [0]; let i = 0; let f = fn() { i++; 0 }; a[f()]++; i", 1),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+
+      test_float_object(test_eval("let x = 1.5; x++; x"), 2.5);
+  }
+
+  #[test]
+  fn test_null_expression() {
+      test_null_object(test_eval("null"));
+      test_null_object(test_eval("let x = null; x"));
+      test_null_object(test_eval("null ?? null"));
+
+      let tests: Vec<(&str, i64)> = vec![
+        ("null ?? 5", 5),
+        ("1 ?? 5", 1),
+        ("let h = {\"a\": 1}; h[\"b\"] ?? 2", 2),
+        ("[1][3] ?? [2][5] ?? 3", 3),
+        ("let x = 1; x ?? (1 + true)", 1),
+        ("let f = fn() { }; f() ?? 7", 7),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+
+      let tests: Vec<(&str, bool)> = vec![
+        ("null == null", true),
+        ("null != null", false),
+        ("1 == null", false),
+        ("null != \"a\"", true),
+        ("false ?? true", false),
+        ("!null", true),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_boolean_object(evaluated, expected);
+      }
+  }
+
+  #[test]
+  fn test_range_expression() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("len(0..10)", 10),
+        ("len(1..=10)", 10),
+        ("len(5..1)", 0),
+        ("(2..8)[3]", 5),
+        ("let n = 3; (0..=n)[n]", 3),
+        ("let sum = 0; for (i in 1..=4) { sum += i; }; sum", 10),
+        ("let sum = 0; for (i in 0..len([1, 2, 3])) { sum += i; }; sum", 3),
+        ("let [a, b] = [1..3, 1..=3]; len(a) * 10 + len(b)", 23),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+
+      test_null_object(test_eval("(0..3)[3]"));
+      test_null_object(test_eval("(0..3)[-1]"));
+      test_boolean_object(test_eval("0..3 == range(3)"), true);
+      test_boolean_object(test_eval("0..3 == 0..=3"), false);
+      assert_eq!(format!("{}", test_eval("1..=3")), "1..4");
+
+      // i64::MAX で終わる範囲も end + 1 を求めずに回せる
+      let max = i64::MAX;
+      let input = format!("let xs = []; for (i in {}..={}) {{ xs = push(xs, i) }}; xs", max - 1, max);
+      assert_eq!(format!("{}", test_eval(&input)), format!("[{}, {}]", max - 1, max));
+      test_integer_object(test_eval(&format!("len({}..={})", max - 2, max)), 3);
+      test_integer_object(test_eval(&format!("({}..={})[2]", max - 2, max)), max);
+      test_null_object(test_eval(&format!("({}..={})[3]", max - 2, max)));
+      assert_eq!(format!("{}", test_eval(&format!("0..={}", max))), format!("0..={}", max));
+      test_boolean_object(test_eval(&format!("0..={} == 0..={}", max, max)), true);
+      test_null_object(test_eval(&format!("(0..{})[{}]", max, max)));
+
+      // i64 に収まらない長さは丸めずにエラーにする
+      for input in [format!("len(-1..{})", max), format!("len((-{} - 1)..={})", max, max), format!("len(0..={})", max)] {
+        match test_eval(&input) {
+          object::Object::Error(err) => {
+            assert_eq!(err.kind, ErrorKind::Arithmetic, "input={}", input);
+            assert!(err.message.ends_with("does not fit in 64 bits."), "input={}: {}", input, err.message);
+          },
+          obj => panic!("Object should has Error, but got {:?} for {}", obj, input),
+        }
+      }
+      test_integer_object(test_eval(&format!("len(0..{})", max)), max);
+  }
+
+  #[test]
+  fn test_slice_expression() {
+      let tests = vec![
+        ("[1, 2, 3, 4][1:3]", "[2, 3]"),
+        ("[1, 2, 3, 4][2:]", "[3, 4]"),
+        ("[1, 2, 3, 4][:-1]", "[1, 2, 3]"),
+        ("[1, 2, 3, 4][-2:]", "[3, 4]"),
+        ("[1, 2, 3, 4][:]", "[1, 2, 3, 4]"),
+        ("[1, 2, 3, 4][3:1]", "[]"),
+        ("[1, 2, 3, 4][-10:10]", "[1, 2, 3, 4]"),
+        ("let s = \"hello\"; s[2:]", "\"llo\""),
+        ("\"hello\"[1:-1]", "\"ell\""),
+        ("\"héllo\"[:2]", "\"hé\""),
+        ("\"hello\"[5:]", "\"\""),
+        ("let n = 1; [1, 2, 3][n:n + 1][0]", "2"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(format!("{}", evaluated), expected, "input={}", input);
+      }
+  }
+
+  #[test]
+  fn test_let_statement() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let a = 5; a;", 5),
+        ("let a = 5 * 5; a;", 25),
+        ("let a = 5; let b = a; b;", 5),
+        ("let a = 5; let b = a; let c = a + b + 5; c;", 15),
+        ("let 合計 = fn(値, 倍率2) { 値 * 倍率2 }; 合計(5, 3);", 15),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+  }
+
+  #[test]
+  fn test_block_scope() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let x = 1; if (true) { let x = 2; }; x", 1),
+        ("let x = 1; if (true) { x = 2; }; x", 2),
+        ("let x = 1; if (false) { 0 } else { let x = x + 10; x }", 11),
+        ("let x = 1; let f = fn() { x }; if (true) { let x = 2; f() }", 1),
+        ("let f = fn(x) { if (true) { let x = 10; }; x }; f(1)", 1),
+        ("let i = 0; let sum = 0; while (i < 3) { let n = i * 2; sum += n; i += 1 }; sum", 6),
+        ("let fs = []; let i = 0; while (i < 3) { let j = i; fs = push(fs, fn() { j }); i += 1 }; fs[0]() * 10 + fs[2]()", 2),
+        ("let x = 1; try { let x = 2; x } catch (e) { 0 }; x", 1),
+        ("const x = 1; if (true) { let x = 2; x = 3; x }", 3),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+  }
+
+  #[test]
+  fn test_const_statement() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("const a = 5; a", 5),
+        ("const [a, b] = [1, 2]; a * 10 + b", 12),
+        ("const a = 1; let a = 2; a = 3; a", 3),
+        ("let a = 1; const a = 2; a", 2),
+        ("const a = 1; let f = fn() { let a = 2; a = 5; a }; f() + a", 6),
+        ("const f = fn(n) { if (n == 0) { 0 } else { n + f(n - 1) } }; f(4)", 10),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+  }
+
+  #[test]
+  fn test_let_pattern() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let [a, b] = [1, 2]; a * 10 + b", 12),
+        ("let [a, [b, c]] = [1, [2, 3]]; a + b + c", 6),
+        ("let {x, y} = {\"x\": 3, \"y\": 4}; x * y", 12),
+        ("let [{n}, m] = [{\"n\": 5}, 6]; n + m", 11),
+        ("let [a] = [1, 2, 3]; a", 1),
+        ("let swap = fn(p) { let [a, b] = p; [b, a] }; let [x, y] = swap([1, 2]); x * 10 + y", 21),
+        ("let [a, ...others] = [1, 2, 3]; a + len(others) * 10", 21),
+        ("let [a, b, ...others] = [1]; len(others)", 0),
+        ("let {p: [x, y], q: {r}} = {\"p\": [1, 2], \"q\": {\"r\": 3}}; x + y + r", 6),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        test_integer_object(evaluated, expected);
+      }
+
+      test_null_object(test_eval("let [a, b] = [1]; b"));
+      test_null_object(test_eval("let {z} = {\"x\": 1}; z"));
+  }
+
+  #[test]
+  fn test_match_expression() {
+      let tests: Vec<(&str, &str)> = vec![
+        ("match (1) { 0 => \"zero\", 1 => \"one\" }", "\"one\""),
+        ("match (2) { 0 => \"zero\", n => n * 10 }", "20"),
+        ("match (-1) { -1 => true }", "true"),
+        ("match (1.0) { 1 => \"equal\", 1.0 => \"float\" }", "\"equal\""),
+        ("match (1.5) { 1 => 1, 1.5 => 2 }", "2"),
+        ("match (\"a\") { \"a\" => 1 }", "1"),
+        ("match (null) { false => 1, null => 2 }", "2"),
+        ("match (3) { 0 => 1 }", "null"),
+        ("match ([1, 2, 3]) { [] => 0, [x] => x, [head, ...tail] => head + len(tail) * 10 }", "21"),
+        ("match ([1, 2]) { [a, b, c] => 3, [a, b] => 2 }", "2"),
+        ("match ([1, 2]) { [a] => 1, _ => 0 }", "0"),
+        ("match ([1, [2, 3]]) { [1, [x, 3]] => x }", "2"),
+        ("match ({\"name\": \"a\", \"age\": 3}) { {name: n, age} => [n, age] }", "[\"a\", 3]"),
+        ("match ({\"kind\": \"square\"}) { {kind: \"circle\"} => 1, {kind: \"square\"} => 2 }", "2"),
+        ("match ({\"x\": 1}) { {y} => y, {} => \"any hash\" }", "\"any hash\""),
+        ("match (1) { [a] => a, {a} => a, a => a + 1 }", "2"),
+        ("let a = 1; match (5) { a => a }; a", "1"),
+        ("let f = fn(xs) { match (xs) { [] => 0, [x, ...tail] => x + f(tail) } }; f([1, 2, 3, 4])", "10"),
+        ("let f = fn(x) { match (x) { 0 => if (true) { return \"early\" } }; \"late\" }; [f(0), f(1)]", "[\"early\", \"late\"]"),
       ];
 
       for (input, expected) in tests.into_iter() {
-        test_integer_object(test_eval(input), expected);
+        assert_eq!(format!("{}", test_eval(input)), expected, "input={}", input);
       }
   }
 
@@ -781,7 +2265,7 @@ if(10 > 1) {
         panic!("Func should has 1 args, but got {}", func.args.len());
       }
 
-      if &func.args[0].value != "x" {
+      if func.args[0].value.as_str() != "x" {
         panic!("func.args[0] should be 'x', but got {}", func.args[0].value);
       }
 
@@ -800,6 +2284,111 @@ if(10 > 1) {
         ("let add = fn(x, y) { x + y; }; add(5, 5);", 10),
         ("let add = fn(x, y) { x + y; }; add(5 + 5, add(5, 5));", 20),
         ("let add = fn(x, y) { if(x > 10) { x + y } else { add(x + 1, y) }; }; add(0, 9);", 20),
+        ("let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5);", 120),
+        ("let f = fn() { let fact = fn(n) { if (n < 2) { 1 } else { n * fact(n - 1) } }; fact(5) }; f();", 120),
+        ("let wrap = fn(h) { fn(n) { h(n) } }; let sum = wrap(fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }); sum(4);", 10),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+  }
+
+  #[test]
+  fn test_func_statement() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("fn add(x, y) { x + y } add(2, 3);", 5),
+        ("fn fact(n) { if (n == 0) { 1 } else { n * fact(n - 1) } } fact(5);", 120),
+        ("fn even(n) { n == 0 ? 1 : odd(n - 1) } fn odd(n) { n == 0 ? 0 : even(n - 1) } even(10);", 1),
+        ("fn f(x = 2) { x } f();", 2),
+        ("let f = fn() { fn g() { 7 } g() }; f();", 7),
+        ("fn f() { 1 } fn f() { 2 } f();", 2),
+        ("fn(x) { x }(4);", 4),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+
+      assert_eq!(test_eval("fn add(x, y) { x + y }").to_string(), "fn(x, y) { (x + y) }");
+      match test_eval("if (true) { fn g() { 1 } }; g()") {
+        object::Object::Error(err) => assert_eq!(err.kind, ErrorKind::Name),
+        obj => panic!("expected an error, got {}", obj),
+      }
+      match test_eval("fn len(x) { x }") {
+        object::Object::Error(err) => assert_eq!(err.message, "`len` is already used as a builtin function."),
+        obj => panic!("expected an error, got {}", obj),
+      }
+  }
+
+  #[test]
+  fn test_struct_statement() {
+    let tests = vec![
+      ("struct Point {x, y}", "struct Point {x, y}"),
+      ("struct Point {x, y} Point {y: 2, x: 1}", "Point {x: 1, y: 2}"),
+      ("struct Point {x, y} let p = Point {x: 1, y: [2]}; p.x + p[\"y\"][0]", "3"),
+      ("struct Point {x, y} let p = Point {x: 1, y: 2}; p.x = 5; p.y += 1; p", "Point {x: 5, y: 3}"),
+      ("struct Point {x, y} let p = Point {x: 1, y: 2}; let q = p; q.x = 5; p.x", "1"),
+      ("struct Point {x, y} Point {x: 1, y: 2} == Point {y: 2, x: 1}", "true"),
+      // 同じ名前とフィールドでも、別の struct のレコードは等しくない
+      ("struct P {x} let a = P {x: 1}; struct P {x} a == P {x: 1}", "false"),
+      ("struct P {x} let a = P {x: 1}; let b = a; struct P {x} a == b", "true"),
+      ("struct Empty {} [Empty {}, type(Empty {}), type(Empty)]", "[Empty {}, \"RECORD\", \"STRUCT\"]"),
+      ("let f = fn() { struct P {x} P {x: 1} }; f().x", "1"),
+      ("struct Point {x, y} let Point {x, y: b} = Point {x: 1, y: 2}; [x, b]", "[1, 2]"),
+      ("struct Line {from, to} struct Point {x, y} let l = Line {from: Point {x: 0, y: 0}, to: Point {x: 3, y: 4}}; l.to.y", "4"),
+      ("struct Point {x, y}
+let f = fn(v) {
+  match (v) {
+    Point {x: 0, y} => y,
+    Point {x, y} => x * y,
+    {x} => -x,
+    _ => null,
+  }
+};
+[f(Point {x: 0, y: 5}), f(Point {x: 2, y: 3}), f({\"x\": 1}), f(1)]", "[5, 6, -1, null]"),
+      ("struct A {x} struct B {x} match (B {x: 1}) { A {x} => \"a\", B {x} => \"b\" }", "\"b\""),
+    ];
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("struct P {x, x}", ErrorKind::Name, "duplicate field `x` in struct P."),
+      ("struct P {x, y} P {x: 1}", ErrorKind::Type, "missing field `y` for P."),
+      ("struct P {x} P {x: 1, z: 2}", ErrorKind::Type, "unknown field `z` for P."),
+      ("struct P {x} P {x: 1, x: 2}", ErrorKind::Type, "field `x` is given more than once."),
+      ("struct P {x} P {x: 1}.z", ErrorKind::Type, "unknown field `z` for P."),
+      ("struct P {x} P {x: 1}[0]", ErrorKind::Type, "specified index type is not supported: 0"),
+      ("struct P {x} let p = P {x: 1}; p.z = 2", ErrorKind::Type, "unknown field `z` for P."),
+      ("let P = 1; P {x: 1}", ErrorKind::Type, "`P` is not a struct: got=integer."),
+      ("Q {x: 1}", ErrorKind::Name, "identifier not found: Q."),
+      ("struct P {x} let P {x} = {\"x\": 1}", ErrorKind::Type, "cannot destructure {\"x\": 1} as P."),
+      ("struct P {x} match (P {x: 1}) { P {y} => y }", ErrorKind::Type, "unknown field `y` for P."),
+      ("struct len {x}", ErrorKind::Name, "`len` is already used as a builtin function."),
+      ("if (true) { struct P {x} }; P", ErrorKind::Name, "identifier not found: P."),
+    ];
+    for (input, kind, message) in errors {
+      match test_eval(input) {
+        object::Object::Error(err) => {
+          assert_eq!(err.kind, kind, "{}", input);
+          assert_eq!(err.message, message, "{}", input);
+        },
+        obj => panic!("{}: expected an error, got {}", input, obj),
+      }
+    }
+  }
+
+  #[test]
+  fn test_func_default_args() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let add = fn(x, y = 10) { x + y }; add(1);", 11),
+        ("let add = fn(x, y = 10) { x + y }; add(1, 2);", 3),
+        ("let f = fn(x = 1, y = x * 2) { x * 10 + y }; f();", 12),
+        ("let f = fn(x = 1, y = x * 2) { x * 10 + y }; f(3);", 36),
+        ("let n = 5; let f = fn(x = n) { x }; let n = 7; f();", 7),
+        ("let calls = 0; let f = fn(x = calls++) { x }; f(9); f(9); calls;", 0),
+        ("let f = fn(x, y = null) { y ?? x }; f(4);", 4),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -807,6 +2396,241 @@ if(10 > 1) {
       }
   }
 
+  #[test]
+  fn test_spread_expression() {
+      let tests = vec![
+        ("let xs = [2, 3]; [1, ...xs, 9]", "[1, 2, 3, 9]"),
+        ("[...[], ...[1], ...[]]", "[1]"),
+        ("let add = fn(x, y, z) { x + y + z }; let args = [1, 2, 3]; add(...args)", "6"),
+        ("let add = fn(x, y, z) { x + y + z }; add(1, ...[2, 3])", "6"),
+        ("let f = fn(x, y = 10) { x + y }; f(...[1])", "11"),
+        ("len([...[1, 2], ...[3]])", "3"),
+        ("let pair = fn(a, b) { [b, a] }; pair(...[1, 2])", "[2, 1]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(format!("{}", evaluated), expected, "input={}", input);
+      }
+  }
+
+  #[test]
+  fn test_recursion_limit() {
+      let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+      let env = Environment::new(builtins::new_builtins());
+      eval(Parser::new(Lexer::new(input.to_string())).parse_program(), &env);
+
+      let call = |n: i64| eval(Parser::new(Lexer::new(format!("f({})", n))).parse_program(), &env);
+      test_integer_object(call(50), 50);
+
+      env.borrow_mut().set_max_depth(40);
+      match call(50) {
+        object::Object::Error(err) => assert_eq!(err.message, "maximum recursion depth exceeded: 40."),
+        obj => panic!("Object should has Error, but got {:?}", obj),
+      }
+      // エラーで抜けた後も深さは元に戻っている
+      test_integer_object(call(5), 5);
+
+      // 呼び出しの数より先にスタックの上限に届く
+      let evaluated = test_eval("let loop = fn() { loop() }; loop();");
+      match evaluated {
+        object::Object::Error(err) => {
+          assert_eq!(err.kind, ErrorKind::Recursion);
+          assert_eq!(err.message, format!("stack limit exceeded: {} bytes.", crate::utils::DEFAULT_MAX_STACK));
+        },
+        obj => panic!("Object should has Error, but got {:?}", obj),
+      }
+
+      // 長い中置式の連なりは入れ子の深さに数えない
+      let input = vec!["1"; 500].join(" + ");
+      test_integer_object(test_eval(&input), 500);
+      let input = vec!["true"; 500].join(" && ");
+      test_boolean_object(test_eval(&input), true);
+  }
+
+  #[test]
+  fn test_deep_recursion() {
+      // 大きなスタックのスレッドで上限を上げれば何千段も再帰できる
+      const STACK_SIZE: usize = 256 << 20;
+      std::thread::Builder::new().stack_size(STACK_SIZE).spawn(|| {
+        let input = "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } };";
+        let env = Environment::new(builtins::new_builtins());
+        env.borrow_mut().set_max_stack(STACK_SIZE - (16 << 20));
+        eval(Parser::new(Lexer::new(input.to_string())).parse_program(), &env);
+
+        let call = |n: usize| eval(Parser::new(Lexer::new(format!("f({})", n))).parse_program(), &env);
+        test_integer_object(call(5000), 5000);
+        match call(environment::DEFAULT_MAX_DEPTH + 1) {
+          object::Object::Error(err) => assert_eq!(
+            err.message,
+            format!("maximum recursion depth exceeded: {}.", environment::DEFAULT_MAX_DEPTH),
+          ),
+          obj => panic!("Object should has Error, but got {:?}", obj),
+        }
+      }).unwrap().join().unwrap();
+  }
+
+  #[test]
+  fn test_limits() {
+      let env = Environment::new(builtins::new_builtins());
+      let limit_error = |input: &str, limits: limits::Limits| {
+        env.borrow_mut().set_limits(limits);
+        match test_eval_in(input, &env) {
+          object::Object::Error(err) => {
+            assert_eq!(err.kind, ErrorKind::LimitExceeded, "input={}", input);
+            err.message
+          },
+          obj => panic!("Object should has Error, but got {:?} for {}", obj, input),
+        }
+      };
+
+      let steps = limits::Limits { max_steps: Some(1000), ..Default::default() };
+      assert_eq!(limit_error("while (true) {}", steps), "step limit exceeded: 1000.");
+      // try では捕まらない
+      assert_eq!(limit_error("try { while (true) {} } catch (e) { 0 }", steps), "step limit exceeded: 1000.");
+
+      let calls = limits::Limits { max_calls: Some(10), ..Default::default() };
+      assert_eq!(limit_error("let f = fn(n) { f(n + 1) }; f(0)", calls), "call depth limit exceeded: 10.");
+      test_integer_object(test_eval_in("let g = fn(n) { if (n == 0) { 0 } else { g(n - 1) } }; g(9)", &env), 0);
+
+      let objects = limits::Limits { max_objects: Some(100), ..Default::default() };
+      assert_eq!(limit_error("let a = []; while (true) { a = push(a, \"x\") }", objects), "object limit exceeded: 100.");
+
+      let bytes = limits::Limits { max_bytes: Some(1 << 20), ..Default::default() };
+      assert_eq!(limit_error("let s = \"x\"; while (true) { s = s + s }", bytes), "memory limit exceeded: 1048576 bytes.");
+      let budget = env.borrow().budget();
+      assert!(budget.bytes() > 1 << 20 && budget.bytes() < 1 << 23, "bytes={}", budget.bytes());
+
+      let timeout = limits::Limits { timeout: Some(Duration::from_millis(20)), ..Default::default() };
+      assert_eq!(limit_error("while (true) {}", timeout), "timeout exceeded: 20ms.");
+
+      env.borrow_mut().set_limits(steps);
+      test_integer_object(test_eval_in("let n = 0; while (n < 10) { n += 1 }; n", &env), 10);
+      let budget = env.borrow().budget();
+      assert!(budget.steps() > 0 && budget.steps() < 1000);
+  }
+
+  #[test]
+  fn test_debugger() {
+      // 止まった行とそのときの x を記録し、x が 3 になったら止める
+      struct Recorder {
+        stops: Rc<RefCell<Vec<String>>>,
+      }
+
+      impl debugger::Debugger for Recorder {
+        fn on_statement(&mut self, stmt: &Statement, env: &Rc<RefCell<Environment>>) -> debugger::Action {
+          let x = env.borrow().get(Name::new("x")).map_or("-".to_string(), |x| x.to_string());
+          self.stops.borrow_mut().push(format!("{} x={}", stmt.span().start.line, x));
+          if x == "3" {
+            return debugger::Action::Abort;
+          }
+          debugger::Action::Continue
+        }
+      }
+
+      let stops = Rc::new(RefCell::new(vec![]));
+      let env = Environment::new(builtins::new_builtins());
+      env.borrow_mut().set_debugger(Some(Box::new(Recorder { stops: Rc::clone(&stops) })));
+      let input = "let x = 1;
+let f = fn(n) {
+  n + 1
+};
+x = f(x);
+while (true) {
+  x = f(x);
+}";
+      match test_eval_in(input, &env) {
+        object::Object::Error(err) => assert_eq!(err.report(None), "7:3: Cancelled: evaluation was stopped by the debugger."),
+        obj => panic!("Object should has Error, but got {:?}", obj),
+      }
+      assert_eq!(*stops.borrow(), vec!["1 x=-", "2 x=1", "5 x=1", "3 x=1", "6 x=2", "7 x=2", "3 x=2", "7 x=3"]);
+
+      let names: Vec<String> = env.borrow().visible_bindings().iter().map(|(name, _)| name.to_string()).collect();
+      assert_eq!(names, vec!["f", "x"]);
+  }
+
+  #[test]
+  fn test_observer() {
+      // 呼び出しと束縛を記録し、式の数を数える
+      struct Tracer {
+        events: Rc<RefCell<Vec<String>>>,
+        expressions: Rc<RefCell<(usize, usize)>>,
+      }
+
+      impl observer::EvalObserver for Tracer {
+        fn enter_expression(&mut self, _expr: &Expression) {
+          self.expressions.borrow_mut().0 += 1;
+        }
+
+        fn exit_expression(&mut self, _expr: &Expression, _result: &object::Object) {
+          self.expressions.borrow_mut().1 += 1;
+        }
+
+        fn enter_call(&mut self, call: &CallExpression, function: Option<Name>, args: &[object::Object]) {
+          let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+          self.events.borrow_mut().push(format!("{} call {:?}({})", call.span.start, function.map(|f| f.to_string()), args.join(", ")));
+        }
+
+        fn exit_call(&mut self, _call: &CallExpression, function: Option<Name>, result: &object::Object) {
+          self.events.borrow_mut().push(format!("return {:?} {}", function.map(|f| f.to_string()), result));
+        }
+
+        fn bind(&mut self, name: Name, value: &object::Object) {
+          if let object::Object::Integer(_) = value {
+            self.events.borrow_mut().push(format!("bind {}={}", name, value));
+          }
+        }
+
+        fn assign(&mut self, name: Name, value: &object::Object) {
+          self.events.borrow_mut().push(format!("assign {}={}", name, value));
+        }
+      }
+
+      let events = Rc::new(RefCell::new(vec![]));
+      let expressions = Rc::new(RefCell::new((0, 0)));
+      let env = Environment::new(builtins::new_builtins());
+      env.borrow_mut().set_observer(Some(Box::new(Tracer { events: Rc::clone(&events), expressions: Rc::clone(&expressions) })));
+      let input = "let double = fn(n) { n * 2 };
+let x = double(1);
+for (i in [3]) { x += i }
+try { x = double(x, 1) } catch (e) { (fn() { x })() }";
+      test_integer_object(test_eval_in(input, &env), 5);
+      assert_eq!(*events.borrow(), vec![
+        "2:9 call Some(\"double\")(1)",
+        "bind n=1",
+        "return Some(\"double\") 2",
+        "bind x=2",
+        "bind i=3",
+        "assign x=5",
+        "4:11 call Some(\"double\")(5, 1)",
+        "return Some(\"double\") ArgumentError: wrong number of argument: got=2, want=1.",
+        "4:39 call None()",
+        "return None 5",
+      ]);
+      let (entered, exited) = *expressions.borrow();
+      assert!(entered > 10 && entered == exited, "entered={}, exited={}", entered, exited);
+
+      env.borrow_mut().set_observer(None);
+      test_integer_object(test_eval_in("x", &env), 5);
+  }
+
+  #[test]
+  fn test_cancel_token() {
+      let env = Environment::new(builtins::new_builtins());
+      let token = limits::CancelToken::new();
+      env.borrow_mut().set_cancel_token(token.clone());
+
+      let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        token.cancel();
+      });
+      match test_eval_in("try { while (true) {} } catch (e) { 0 }", &env) {
+        object::Object::Error(err) => assert_eq!(err.to_string(), "Cancelled: evaluation was cancelled."),
+        obj => panic!("Object should has Error, but got {:?}", obj),
+      }
+      canceller.join().unwrap();
+  }
+
   #[test]
   fn test_closure() {
       let input = "
@@ -820,6 +2644,31 @@ f(3)
       test_integer_object(test_eval(input), 5);
   }
 
+  #[test]
+  fn test_nested_closure() {
+      let tests: Vec<(&str, i64)> = vec![
+        ("let f = fn(x) { fn(y) { fn(z) { x + y + z } } }; f(1)(2)(3)", 6),
+        ("let f = fn(x) { fn(y) { fn(z) { x + y + z } } }; let g = f(1)(2); g(3) + g(4)", 13),
+        ("
+let adder = fn(x) { fn(y) { x + y } };
+let add_two = adder(2);
+let add_ten = adder(10);
+add_two(1) + add_ten(1)
+", 14),
+        ("
+let counter = fn(x) {
+  let y = x * 2;
+  fn() { y + x }
+};
+counter(3)()
+", 9),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        test_integer_object(test_eval(input), expected);
+      }
+  }
+
   #[test]
   fn test_eval_external() {
       let input = "external()";
@@ -832,7 +2681,7 @@ f(3)
       }
 
       let env = Environment::new(builtins::new_builtins());
-      env.borrow_mut().set("external", object::Object::External(
+      env.borrow_mut().set(Name::new("external"), object::Object::External(
         object::External::new(
           Rc::new(
             RefCell::new(
@@ -841,9 +2690,62 @@ f(3)
           ),
         ),
       );
-      let mut env = Rc::new(env);
+      test_integer_object(eval(program, &env), 100);
+  }
+
+  #[test]
+  fn test_register_builtin() {
+      fn double(args: Vec<object::Object>, _env: &Rc<RefCell<Environment>>) -> object::Object {
+        match args.as_slice() {
+          [object::Object::Integer(int)] => object::Object::Integer(object::Integer::new(int.value * 2)),
+          _ => new_error(ErrorKind::Type, format!("argument to `double` must be INTEGER: got={}", args.len())),
+        }
+      }
+
+      let tests: Vec<(&str, i64)> = vec![
+        ("double(21)", 42),
+        ("let f = fn(x) { double(x) + 1 }; f(2)", 5),
+        ("len([double])", 1),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let l = Lexer::new(input.into());
+        let mut p = Parser::new(l);
+        let program = p.parse_program();
+        if !p.check_parse_errors() {
+          panic!();
+        }
+
+        let env = Environment::new(builtins::new_builtins());
+        env.borrow_mut().register_builtin("double", double);
+
+        test_integer_object(eval(program, &env), expected);
+      }
+
+      let env = Environment::new(builtins::new_builtins());
+      env.borrow_mut().register_builtin("double", double);
+      let l = Lexer::new("let double = 1;".into());
+      let mut p = Parser::new(l);
+      match eval(p.parse_program(), &env) {
+        object::Object::Error(err) => assert_eq!(&err.message, "`double` is already used as a builtin function."),
+        obj => panic!("Object should has Error, but got {:?}", obj),
+      }
+  }
+
+  #[test]
+  fn test_puts_output() {
+      let tests: Vec<(&str, &str)> = vec![
+        ("puts(1, 2, 3)", "1 2 3\n"),
+        ("puts(\"hello\", [1, true])", "\"hello\" [1, true]\n"),
+        ("let f = fn(x) { puts(x) }; f(1); f(2);", "1\n2\n"),
+        ("puts()", "\n"),
+      ];
 
-      test_integer_object(eval(program, &mut env), 100);
+      for (input, expected) in tests.into_iter() {
+        let (evaluated, output) = test_eval_output(input);
+        test_null_object(evaluated);
+        assert_eq!(output, expected);
+      }
   }
 
   #[test]
@@ -851,7 +2753,7 @@ f(3)
       enum TestObject {
         Int(i64),
         Arr(Vec<TestObject>),
-      };
+      }
 
       impl TestObject {
         fn test(actual: object::Object, expected: Option<TestObject>) {
@@ -901,6 +2803,19 @@ f(3)
           vec![TestObject::Int(1)]
         ))),
         ("puts(1, 2, 3)", None),
+        ("assert(1 < 2)", None),
+        ("assert([], \"arrays are truthy\")", None),
+        ("map([1, 2, 3], fn(x) { x * 2 })", Some(TestObject::Arr(
+          vec![TestObject::Int(2), TestObject::Int(4), TestObject::Int(6)]
+        ))),
+        ("map([\"a\", \"bc\"], len)", Some(TestObject::Arr(
+          vec![TestObject::Int(1), TestObject::Int(2)]
+        ))),
+        ("filter(0..6, fn(x) { x % 2 == 0 })", Some(TestObject::Arr(
+          vec![TestObject::Int(0), TestObject::Int(2), TestObject::Int(4)]
+        ))),
+        ("reduce([1, 2, 3], 10, fn(acc, x) { acc + x })", Some(TestObject::Int(16))),
+        ("reduce([], 10, fn(acc, x) { acc + x })", Some(TestObject::Int(10))),
       ];
 
       for (input, expected) in tests.into_iter() {
@@ -909,6 +2824,184 @@ f(3)
       }
   }
 
+  #[test]
+  fn test_string_builtins() {
+      let tests = vec![
+        ("split(\"a,b,,c\", \",\")", "[\"a\", \"b\", \"\", \"c\"]"),
+        ("split(\"日本語\", \"\")", "[\"日\", \"本\", \"語\"]"),
+        ("split(\"\", \",\")", "[\"\"]"),
+        ("join([\"a\", \"b\", \"c\"], \"-\")", "\"a-b-c\""),
+        ("join([], \"-\")", "\"\""),
+        ("join(split(\"a b c\", \" \"), \"\")", "\"abc\""),
+        ("trim(\"  hello \\n\")", "\"hello\""),
+        ("upper(\"straße\")", "\"STRASSE\""),
+        ("lower(\"ÀB\")", "\"àb\""),
+        ("replace(\"a-b-c\", \"-\", \"+\")", "\"a+b+c\""),
+        ("replace(\"ああい\", \"あ\", \"う\")", "\"ううい\""),
+        ("contains(\"monkey\", \"key\")", "true"),
+        ("contains(\"monkey\", \"\")", "true"),
+        ("contains(\"monkey\", \"Key\")", "false"),
+        ("starts_with(\"monkey\", \"mon\")", "true"),
+        ("starts_with(\"monkey\", \"key\")", "false"),
+        ("ends_with(\"monkey\", \"key\")", "true"),
+        ("ends_with(\"猿\", \"\\u{733f}\")", "true"),
+        ("chars(\"héllo\")", "[\"h\", \"é\", \"l\", \"l\", \"o\"]"),
+        ("len(chars(\"日本語\"))", "3"),
+        ("chars(\"\")", "[]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_math_builtins() {
+      let tests = vec![
+        ("pow(2, 10)", "1024"),
+        ("pow(2, 0.5) == sqrt(2)", "true"),
+        ("pow(1.5, 2)", "2.25"),
+        ("sqrt(16)", "4.0"),
+        ("sqrt(2.25)", "1.5"),
+        ("floor(1.7)", "1"),
+        ("floor(-1.2)", "-2"),
+        ("ceil(1.2)", "2"),
+        ("ceil(-1.7)", "-1"),
+        ("floor(3)", "3"),
+        ("let x = random(); x >= 0 && x < 1", "true"),
+        ("let n = random(3); n >= 0 && n < 3", "true"),
+        ("random(1)", "0"),
+        ("[abs(-3), abs(2.5), abs(-0.5), abs(-0.0), abs(0)]", "[3, 2.5, 0.5, 0.0, 0]"),
+        ("[min(3, 1, 2), max(3, 1, 2), min(7), max(-1, -2)]", "[1, 3, 7, -1]"),
+        ("[min(1.5, 2), max(1, 2.5, -3), min(2, 2.0), max(2.0, 2)]", "[1.5, 2.5, 2, 2.0]"),
+        ("[min([3, 1, 2]), max(1..4), min([]), max([0.5])]", "[1, 3, null, 0.5]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_introspection_builtins() {
+      let tests = vec![
+        ("[type(1), type(1.5), type(true), type(\"a\"), type(null)]", "[\"INTEGER\", \"FLOAT\", \"BOOLEAN\", \"STRING\", \"NULL\"]"),
+        ("[type([]), type({}), type(0..1), type(fn() {}), type(len)]", "[\"ARRAY\", \"HASH\", \"RANGE\", \"FUNCTION\", \"BUILTIN\"]"),
+        ("keys({\"b\": 1, 10: 2, \"a\": 3, 2: 4, true: 5})", "[2, 10, true, \"a\", \"b\"]"),
+        ("values({\"b\": 1, 10: 2, \"a\": 3, 2: 4, true: 5})", "[4, 2, 5, 3, 1]"),
+        ("keys({})", "[]"),
+        ("[arity(fn() {}), arity(fn(a, b) { a }), arity(fn(a, b = 1) { a })]", "[0, 2, 2]"),
+        ("arity(len)", "null"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_conversion_builtins() {
+      let tests = vec![
+        ("to_string(\"a\") + to_string(1) + to_string(1.5)", "\"a11.5\""),
+        ("[to_string([1, \"a\"]), to_string(null), to_string(true)]", "[\"[1, \"a\"]\", \"null\", \"true\"]"),
+        ("to_int(\" -42 \") + 1", "-41"),
+        ("[to_int(1.9), to_int(-1.9), to_int(true), to_int(false), to_int(7)]", "[1, -1, 1, 0, 7]"),
+        ("to_int(to_string(123))", "123"),
+        ("[to_bool(0), to_bool(\"\"), to_bool([]), to_bool(null), to_bool(false)]", "[true, true, true, false, false]"),
+        ("try { to_int(\"abc\") } catch (e) { e[\"kind\"] }", "\"TypeError\""),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_hash_builtins() {
+      let tests = vec![
+        ("keys(delete({1: 1, 2: 2, 3: 3}, 1, 3, 4))", "[2]"),
+        ("let h = {1: 1}; delete(h, 1); h", "{1: 1}"),
+        ("keys(merge({1: \"a\", 2: \"b\"}, {2: \"c\"}, {3: \"d\"}))", "[1, 2, 3]"),
+        ("values(merge({1: \"a\", 2: \"b\"}, {2: \"c\"}, {3: \"d\"}))", "[\"a\", \"c\", \"d\"]"),
+        ("merge({})", "{}"),
+        ("[has_key({\"a\": null}, \"a\"), has_key({\"a\": 1}, \"b\"), has_key({1: 1}, true)]", "[true, false, false]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_array_builtins() {
+      let tests = vec![
+        ("sort([3, 1, 2.5, -1])", "[-1, 1, 2.5, 3]"),
+        ("sort([\"b\", \"a\", \"c\"])", "[\"a\", \"b\", \"c\"]"),
+        ("sort([3, 1, 2], fn(a, b) { a > b })", "[3, 2, 1]"),
+        ("sort([3, 1, 2], fn(a, b) { a - b })", "[1, 2, 3]"),
+        // 比較が等しい要素は元の順番のまま
+        ("sort([[1, \"a\"], [0, \"b\"], [1, \"c\"], [0, \"d\"]], fn(a, b) { a[0] < b[0] })", "[[0, \"b\"], [0, \"d\"], [1, \"a\"], [1, \"c\"]]"),
+        // 全順序でない比較関数でも止まらない
+        ("len(sort(0..50, fn(a, b) { true }))", "50"),
+        ("let arr = [2, 1]; sort(arr); arr", "[2, 1]"),
+        ("sort([])", "[]"),
+        ("[reverse([1, 2, 3]), reverse(\"日本語\"), reverse([])]", "[[3, 2, 1], \"語本日\", []]"),
+        ("[contains([1, \"a\", [2]], [2]), contains([1, 2], 3), contains([1], 1.0)]", "[true, false, true]"),
+        ("[index_of([1, 2, 1], 1), index_of([1, 2], 2), index_of([1, 2], 3)]", "[0, 1, null]"),
+        ("[slice([1, 2, 3, 4], 1, 3), slice([1, 2, 3, 4], -2), slice(\"héllo\", 1, 3)]", "[[2, 3], [3, 4], \"él\"]"),
+        ("concat([1], [], [2, 3])", "[1, 2, 3]"),
+        ("concat()", "[]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        let evaluated = test_eval(input);
+        assert_eq!(evaluated.to_string(), expected, "{}", input);
+      }
+  }
+
+  #[test]
+  fn test_method_call() {
+    let tests = vec![
+      ("[1, 2, 3].len()", "3"),
+      ("\"a,b\".split(\",\")", "[\"a\", \"b\"]"),
+      ("{\"a\": 1}.keys()", "[\"a\"]"),
+      ("[3, 1, 2].sort().map(fn(x) { x * 2 })", "[2, 4, 6]"),
+      ("let arr = [1, 2]; arr.push(3).reduce(0, fn(acc, x) { acc + x })", "6"),
+      ("\" Hi \".trim().lower().starts_with(\"h\")", "true"),
+      ("-[1, 2].first() + 3", "2"),
+      ("{\"a\": 1}.insert(\"b\", 2).has_key(\"b\")", "true"),
+      ("[[1, 2], [3]][0].len()", "2"),
+      ("let p = {\"f\": fn(x) { x * 2 }}; p.f(4)", "8"),
+      ("let p = {\"f\": len}; p.f([1, 2])", "2"),
+      ("let p = {\"len\": fn() { 10 }}; p.len()", "10"),
+      ("let p = {\"len\": 3}; p.len()", "1"),
+      ("struct Counter {n, next} let c = Counter {n: 1, next: fn(n) { n + 1 }}; c.next(c.n)", "2"),
+    ];
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    let errors = vec![
+      ("[1].foo()", "unknown method `foo` for array."),
+      ("\"a\".keys()", "unknown method `keys` for string."),
+      ("5.len()", "unknown method `len` for integer."),
+      ("{\"f\": 1}.f()", "unknown method `f` for hash."),
+      ("[1].len(2)", "wrong number of argument: got=2, want=1."),
+      ("undefined.len()", "identifier not found: undefined."),
+    ];
+    for (input, expected) in errors {
+      match test_eval(input) {
+        object::Object::Error(err) => assert_eq!(err.message, expected, "{}", input),
+        result => panic!("{}: expected an error, got {}", input, result),
+      }
+    }
+  }
+
   #[test]
   fn test_error_handling() {
       let tests: Vec<(&str, &str)> = vec![
@@ -916,9 +3009,91 @@ f(3)
         ("5 + true; 5;", "type mismatch: 5 + true."),
         ("-true", "unknown operator: -true."),
         ("true + false", "unknown operator: true + false."),
+        ("true && (1 + true)", "type mismatch: 1 + true."),
+        ("false || undefined", "identifier not found: undefined."),
+        ("true <= false", "unknown operator: true <= false."),
+        ("[1] < [2]", "unknown operator: [1] < [2]."),
+        ("\"a\" < 1", "type mismatch: \"a\" < 1."),
+        ("null < 1", "type mismatch: null < 1."),
         ("5; true + false; 5", "unknown operator: true + false."),
         ("if(10 > 1) { true + false }", "unknown operator: true + false."),
         ("foobar", "identifier not found: foobar."),
+        ("10 / 0", "division by zero: 10 / 0."),
+        ("10 % 0", "division by zero: 10 % 0."),
+        ("x = 1", "assignment to undeclared identifier: x."),
+        ("fn(x) { x }(1, 2)", "wrong number of argument: got=2, want=1."),
+        ("[1, ...2]", "spread operand must be ARRAY: got=2"),
+        ("len(...\"ab\")", "spread operand must be ARRAY: got=\"ab\""),
+        ("[...x]", "identifier not found: x."),
+        ("fn(x, y) { x }(1)", "missing argument: y."),
+        ("fn(x = y) { x }()", "identifier not found: y."),
+        ("[1, 2][\"a\":]", "specified index type is not supported: \"a\""),
+        ("5[1:2]", "slice operator not supported: 5"),
+        ("1..true", "range bounds must be INTEGER: got=1..true"),
+        ("0.5..2", "range bounds must be INTEGER: got=0.5..2"),
+        ("(0..3)[true]", "specified index type is not supported: true"),
+        ("let [a, b] = 1", "cannot destructure 1 as an array."),
+        ("let {a} = [1]", "cannot destructure [1] as a hash."),
+        ("match (1 + true) { x => x }", "type mismatch: 1 + true."),
+        ("match (1) { len => len }", "`len` is already used as a builtin function."),
+        ("let [a, len] = [1, 2]", "`len` is already used as a builtin function."),
+        ("(1 + true) ? 1 : 2", "type mismatch: 1 + true."),
+        ("null ?? (1 + true)", "type mismatch: 1 + true."),
+        ("null + 1", "type mismatch: null + 1."),
+        ("x++", "identifier not found: x."),
+        ("let x = true; x++", "unknown operator: true++."),
+        ("let x = \"a\"; --x", "unknown operator: --\"a\"."),
+        ("let a = [1]; a[1]++", "unknown operator: null++."),
+        ("let a = [1]; a[\"i\"]++", "specified index type is not supported: \"i\""),
+        ("let s = 1; s[0]++", "index operator not supported: 1"),
+        ("let a = [1]; a[1] = 2", "index out of range: 1."),
+        ("let a = [1]; a[-1] = 2", "index out of range: -1."),
+        ("let a = [1]; a[true] = 2", "specified index type is not supported: true"),
+        ("let h = {}; h[[1]] = 2", "unusable as hash key: [1]"),
+        ("let s = \"abc\"; s[0] = \"x\"", "index operator not supported: \"abc\""),
+        ("b[0] = 1", "identifier not found: b."),
+        ("x += 1", "identifier not found: x."),
+        ("let x = 1; x /= 0", "division by zero: 1 / 0."),
+        ("let x = true; x += 1", "type mismatch: true + 1."),
+        ("len = 1", "assignment to undeclared identifier: len."),
+        ("const x = 1; x = 2", "assignment to constant: x."),
+        ("const x = 1; x += 1", "assignment to constant: x."),
+        ("const x = 1; x++", "assignment to constant: x."),
+        ("const a = [1]; a[0] = 2", "assignment to constant: a."),
+        ("const [a, {b}] = [1, {\"b\": 2}]; b = 3", "assignment to constant: b."),
+        ("const x = 1; let f = fn() { x = 2 }; f()", "assignment to constant: x."),
+        ("const len = 1", "`len` is already used as a builtin function."),
+        ("if (true) { let y = 1 }; y", "identifier not found: y."),
+        ("let i = 0; while (i < 1) { let j = i; i += 1 }; j", "identifier not found: j."),
+        ("const x = 1; if (true) { x = 2 }", "assignment to constant: x."),
+        ("let x = 1; x = 1 + true", "type mismatch: 1 + true."),
+        ("for (x in 1) { x }", "1 is not iterable."),
+        ("for (x in [1, true]) { x + 1 }", "type mismatch: true + 1."),
+        ("for (x in [1]) { x }; x", "identifier not found: x."),
+        ("range(true)", "argument to `range` must be INTEGER: got=true"),
+        ("range(1, 2, 3)", "wrong number of argument: got=3, want=1 or 2."),
+        ("while (1 + true) { 1 }", "type mismatch: 1 + true."),
+        ("while (true) { 1 + true }", "type mismatch: 1 + true."),
+        ("1.5 / 0", "division by zero: 1.5 / 0.0."),
+        ("{1.5: 1}", "unusable as hash key: 1.5"),
+        ("1.5 + true", "type mismatch: 1.5 + true."),
+        ("2 ** -1", "negative exponent: 2 ** -1."),
+        ("2 ** 63", "integer overflow: 2 ** 63."),
+        ("9223372036854775807 + 1", "integer overflow: 9223372036854775807 + 1."),
+        ("-9223372036854775807 - 2", "integer overflow: -9223372036854775807 - 2."),
+        ("4611686018427387904 * 2", "integer overflow: 4611686018427387904 * 2."),
+        ("let lowest = -9223372036854775807 - 1; lowest / -1", "integer overflow: -9223372036854775808 / -1."),
+        ("let lowest = -9223372036854775807 - 1; lowest % -1", "integer overflow: -9223372036854775808 % -1."),
+        ("let lowest = -9223372036854775807 - 1; -lowest", "integer overflow: --9223372036854775808."),
+        ("let n = 9223372036854775807; n += 1", "integer overflow: 9223372036854775807 + 1."),
+        ("let n = 9223372036854775807; n++", "integer overflow: 9223372036854775807++."),
+        ("let n = -9223372036854775807 - 1; --n", "integer overflow: ---9223372036854775808."),
+        ("1 << 64", "invalid shift amount: 1 << 64."),
+        ("1 >> -1", "invalid shift amount: 1 >> -1."),
+        ("1.5 & 1", "unknown operator: 1.5 & 1.0."),
+        ("true | false", "unknown operator: true | false."),
+        ("~1.5", "unknown operator: ~1.5."),
+        ("let f = fn(x) { 1 / x }; f(0); 5", "division by zero: 1 / 0."),
         ("\"hello\" - \"world\"", "unknown operator: \"hello\" - \"world\"."),
         ("len(\"one\", \"two\")", "wrong number of argument: got=2, want=1."),
         ("len(1)", "argument to `len` not supported: got=1"),
@@ -930,8 +3105,64 @@ f(3)
         ("rest(1)", "argument to `rest` must be ARRAY: got=1"),
         ("push([], 3, 3)", "wrong number of argument: got=3, want=2."),
         ("push(1, 1)", "argument to `push` must be ARRAY: got=1"),
+        ("map([1], fn(x) { x }, 1)", "wrong number of argument: got=3, want=2."),
+        ("map(1, fn(x) { x })", "argument to `map` must be ARRAY: got=1"),
+        ("filter([1], 1)", "argument to `filter` must be FUNCTION: got=1"),
+        ("reduce([1], 0)", "wrong number of argument: got=2, want=3."),
+        ("map([1, 2], fn(x) { x + true })", "type mismatch: 1 + true."),
+        ("split(\"a\")", "wrong number of argument: got=1, want=2."),
+        ("upper(1)", "argument to `upper` must be STRING: got=1"),
+        ("replace(\"a\", \"a\", 1)", "argument to `replace` must be STRING: got=1"),
+        ("join(\"a\", \"\")", "argument to `join` must be ARRAY: got=\"a\""),
+        ("join([\"a\", 1], \"\")", "elements of `join` must be STRING: got=1"),
+        ("pow(2, -1)", "negative exponent: 2 ** -1."),
+        ("pow(\"a\", 2)", "argument to `pow` must be NUMBER: got=\"a\""),
+        ("sqrt(-1)", "square root of negative number: -1."),
+        ("floor(1e300)", "integer overflow: floor(1e300)."),
+        ("ceil(true)", "argument to `ceil` must be NUMBER: got=true"),
+        ("random(0)", "argument to `random` must be a positive INTEGER: got=0"),
+        ("abs(\"a\")", "argument to `abs` must be NUMBER: got=\"a\""),
+        ("abs(1, 2)", "wrong number of argument: got=2, want=1."),
+        ("abs(-9223372036854775807 - 1)", "integer overflow: abs(-9223372036854775808)."),
+        ("min()", "wrong number of argument: got=0, want=1 or more."),
+        ("max(1, \"a\")", "argument to `max` must be NUMBER: got=\"a\""),
+        ("min([1, null])", "argument to `min` must be NUMBER: got=null"),
+        ("max([[1]], 2)", "argument to `max` must be NUMBER: got=[[1]]"),
+        ("random(1, 2)", "wrong number of argument: got=2, want=0 or 1."),
+        ("type()", "wrong number of argument: got=0, want=1."),
+        ("keys([1])", "argument to `keys` must be HASH: got=[1]"),
+        ("values(1)", "argument to `values` must be HASH: got=1"),
+        ("arity(1)", "argument to `arity` must be FUNCTION: got=1"),
+        ("to_int(\"abc\")", "cannot convert \"abc\" to INTEGER."),
+        ("to_int(\"1.5\")", "cannot convert \"1.5\" to INTEGER."),
+        ("to_int(1e300)", "cannot convert 1e300 to INTEGER."),
+        ("to_int([1])", "cannot convert [1] to INTEGER."),
+        ("to_string(1, 2)", "wrong number of argument: got=2, want=1."),
         ("let len = 0", "`len` is already used as a builtin function."),
         ("{\"name\": \"Monkey\"}[fn(x) { x }]", "unusable as hash key: fn(x) { x }"),
+        ("{[1]: 2}", "unusable as hash key: [1]"),
+        ("insert({}, [1], 2)", "unusable as hash key: [1]"),
+        ("remove({1: 2}, {})", "unusable as hash key: {}"),
+        ("delete({1: 2})", "wrong number of argument: got=1, want=2 or more."),
+        ("delete([1], 1)", "argument to `delete` must be HASH: got=[1]"),
+        ("delete({1: 2}, 1, [1])", "unusable as hash key: [1]"),
+        ("merge()", "wrong number of argument: got=0, want=1 or more."),
+        ("sort([1, \"a\"])", "type mismatch: \"a\" < 1."),
+        ("sort([1, 2], 1)", "argument to `sort` must be FUNCTION: got=1"),
+        ("sort([1, 2], fn(a, b) { null })", "comparator of `sort` must return BOOLEAN or INTEGER: got=null"),
+        ("sort([1, 2], fn(a, b) { a + true })", "type mismatch: 2 + true."),
+        ("sort(1)", "argument to `sort` must be ARRAY: got=1"),
+        ("reverse(1)", "argument to `reverse` must be ARRAY or STRING: got=1"),
+        ("index_of(\"a\", \"a\")", "argument to `index_of` must be ARRAY: got=\"a\""),
+        ("slice([1], \"a\")", "specified index type is not supported: \"a\""),
+        ("slice([1])", "wrong number of argument: got=1, want=2 or 3."),
+        ("concat([1], 2)", "argument to `concat` must be ARRAY: got=2"),
+        ("contains(1, 1)", "argument to `contains` must be STRING: got=1"),
+        ("merge({}, 1)", "argument to `merge` must be HASH: got=1"),
+        ("has_key({}, [1])", "unusable as hash key: [1]"),
+        ("1[0]", "index operator not supported: 1"),
+        ("[1, 2][true]", "specified index type is not supported: true"),
+        ("[1, foobar, 3]", "identifier not found: foobar."),
         ("
 if(10 > 1) {
   if(10 > 1) {
@@ -946,8 +3177,8 @@ if(10 > 1) {
         let evaluated = test_eval(input);
         match evaluated {
           object::Object::Error(err) => {
-            if &err.value != expected {
-              panic!("wrong error message. actual={}, expected={}", &err.value, expected);
+            if err.message != expected {
+              panic!("wrong error message. actual={}, expected={}", &err.message, expected);
             }
           }
           _ => panic!("Object should has Error, but got {:?}", evaluated),
@@ -955,6 +3186,116 @@ if(10 > 1) {
       }
   }
 
+  #[test]
+  fn test_error_report() {
+      let tests: Vec<(&str, ErrorKind, &str)> = vec![
+        ("1 + true", ErrorKind::Type, "1:1: TypeError: type mismatch: 1 + true."),
+        ("let x = 1;\nx + y", ErrorKind::Name, "2:5: NameError: identifier not found: y."),
+        ("len(1)", ErrorKind::Type, "1:1: TypeError: argument to `len` not supported: got=1"),
+        ("let f = fn(x) { x }; f(1, 2)", ErrorKind::Argument, "1:22: ArgumentError: wrong number of argument: got=2, want=1."),
+        ("let len = 1", ErrorKind::Name, "1:1: NameError: `len` is already used as a builtin function."),
+        ("assert(1 > 2)", ErrorKind::Assertion, "1:1: AssertionError: assertion failed."),
+        ("let x = 1;\nassert(x == 2, \"x should be 2\")", ErrorKind::Assertion, "2:1: AssertionError: x should be 2"),
+        ("assert(null, [1])", ErrorKind::Assertion, "1:1: AssertionError: [1]"),
+        ("let f = fn() { panic(\"unreachable\") };\nf()", ErrorKind::Panic, "1:16: Panic: unreachable\n  in f (2:1)"),
+        ("try { 1 / 0 } catch (e) { panic(e[\"message\"]) }", ErrorKind::Panic, "1:27: Panic: division by zero: 1 / 0."),
+        ("assert()", ErrorKind::Argument, "1:1: ArgumentError: wrong number of argument: got=0, want=1 or 2."),
+        ("panic(1, 2)", ErrorKind::Argument, "1:1: ArgumentError: wrong number of argument: got=2, want=1."),
+        // 桁は文字の数で数える
+        ("let 名前 = \"あ\"; 名前 + 名", ErrorKind::Name, "1:20: NameError: identifier not found: 名."),
+        ("
+let inner = fn(x) {
+  x + true
+};
+let outer = fn() { inner(1) };
+outer()", ErrorKind::Type, "3:3: TypeError: type mismatch: 1 + true.
+  in inner (5:20)
+  in outer (6:1)"),
+        ("let f = fn(n) { n == 0 ? 1 / 0 : f(n - 1) }; [fn() { f(3) }][0]()", ErrorKind::Arithmetic, "1:26: ArithmeticError: division by zero: 1 / 0.
+  in f (1:34)
+  ... repeated 2 more times
+  in f (1:54)
+  in <anonymous> (1:46)"),
+      ];
+
+      for (input, kind, expected) in tests.into_iter() {
+        match test_eval(input) {
+          object::Object::Error(err) => {
+            assert_eq!(err.kind, kind, "input={}", input);
+            assert_eq!(err.report(None), expected, "input={}", input);
+          },
+          evaluated => panic!("Object should has Error, but got {:?}", evaluated),
+        }
+      }
+
+      let err = object::Error::new(ErrorKind::Io, "failed.".to_string());
+      assert_eq!(err.report(Some("main.mk")), "IOError: failed.");
+  }
+
+  #[test]
+  fn test_try_expression() {
+      let tests: Vec<(&str, &str)> = vec![
+        ("try { 1 } catch (e) { 2 }", "1"),
+        ("try { 1 / 0 } catch (e) { e[\"kind\"] }", "\"ArithmeticError\""),
+        ("try { undefined } catch (e) { e[\"message\"] }", "\"identifier not found: undefined.\""),
+        ("try { missing(1) } catch (e) { [e[\"line\"], e[\"column\"], e[\"trace\"]] }", "[1, 7, []]"),
+        ("
+let f = fn() {
+  1 + true
+};
+try { f() } catch (e) {
+  let frame = e[\"trace\"][0];
+  [e[\"line\"], e[\"column\"], frame[\"function\"], frame[\"line\"], frame[\"column\"], len(e[\"trace\"])]
+}", "[3, 3, \"f\", 5, 7, 1]"),
+        ("let r = try { 1 / 0 } catch (e) { -1 }; r + 1", "0"),
+        ("let f = fn() { try { 1 / 0 } catch (e) { return -1 }; 0 }; f()", "-1"),
+        ("let f = fn() { try { return 1 } catch (e) { 2 }; 3 }; f()", "1"),
+        ("try { try { 1 / 0 } catch (e) { e + 1 } } catch (e) { e[\"kind\"] }", "\"TypeError\""),
+        ("let x = 0; try { let x = 1; x + true } catch (e) { x }", "0"),
+        ("let e = 1; try { 1 / 0 } catch (e) { e }; e", "1"),
+        ("let f = fn(n) { f(n + 1) }; try { f(0) } catch (e) { e[\"kind\"] }", "\"RecursionError\""),
+        ("try { panic(\"oops\") } catch (e) { [e[\"kind\"], e[\"message\"]] }", "[\"Panic\", \"oops\"]"),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        assert_eq!(format!("{}", test_eval(input)), expected, "input={}", input);
+      }
+
+      let tests: Vec<(&str, &str)> = vec![
+        ("try { 1 / 0 } catch (e) { e + 1 }", "type mismatch: {"),
+        ("try { 1 / 0 } catch (e) { 1 }; e", "identifier not found: e."),
+      ];
+
+      for (input, expected) in tests.into_iter() {
+        match test_eval(input) {
+          object::Object::Error(err) => assert!(err.message.starts_with(expected), "actual={}, expected={}", err.message, expected),
+          evaluated => panic!("Object should has Error, but got {:?}", evaluated),
+        }
+      }
+  }
+
+  #[test]
+  fn test_environment_bindings() {
+      let env = Environment::new(builtins::new_builtins());
+      let program = Parser::new(Lexer::new("let b = [1]; let a = fn() { let c = 1; c }; a()".to_string())).parse_program();
+      eval(program, &env);
+
+      // 関数の中の束縛は含まない
+      let bindings: Vec<String> = env.borrow().bindings().iter()
+        .map(|(name, val)| format!("{} {}", name, val.type_name()))
+        .collect();
+      assert_eq!(bindings, vec!["a function", "b array"]);
+
+      env.borrow_mut().clear();
+      assert!(env.borrow().bindings().is_empty());
+      assert_eq!(test_eval_in("len(\"ab\")", &env).type_name(), "integer");
+      assert_eq!(test_eval_in("b", &env).type_name(), "error");
+  }
+
+  fn test_eval_in(input: &str, env: &Rc<RefCell<Environment>>) -> object::Object {
+    eval(Parser::new(Lexer::new(input.to_string())).parse_program(), env)
+  }
+
   fn test_eval(input: &str) -> object::Object {
     let l = Lexer::new(input.to_string());
     let mut p = Parser::new(l);
@@ -962,9 +3303,27 @@ if(10 > 1) {
     if !p.check_parse_errors() {
       panic!();
     }
-    let mut env = Rc::new(Environment::new(builtins::new_builtins()));
+    let env = Environment::new(builtins::new_builtins());
+
+    eval(program, &env)
+  }
+
+  // puts の出力も一緒に返す
+  fn test_eval_output(input: &str) -> (object::Object, String) {
+    let l = Lexer::new(input.into());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    let output = Rc::new(RefCell::new(vec![]));
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_output(output.clone());
 
-    return eval(program, &mut env);
+    let evaluated = eval(program, &env);
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    (evaluated, output)
   }
 
   fn test_integer_object(obj: object::Object, expected: i64) {
@@ -976,6 +3335,15 @@ if(10 > 1) {
     assert_eq!(int.value, expected, "actual={}, expected={}", int.value, expected);
   }
   
+  fn test_float_object(obj: object::Object, expected: f64) {
+    let float = match &obj {
+      object::Object::Float(float) => float,
+      _ => panic!("Object should has Float, but got {:?}", obj),
+    };
+
+    assert_eq!(float.value, expected, "actual={}, expected={}", float.value, expected);
+  }
+
   fn test_boolean_object(obj: object::Object, expected: bool) {
     let val = match &obj {
       object::Object::Boolean(val) => val,
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use super::object::{self, Object, ErrorKind};
+use super::environment::Environment;
+use super::macro_expansion;
+
+/// The extension added to an imported path which has none, so `import("math")` loads `math.mk`.
+pub const EXTENSION: &str = "mk";
+
+/// Loads the files imported with the `import` builtin. Each file is evaluated once in an environment of its own,
+/// and its module, a hash from the names of its top-level bindings to their values, is reused by later imports.
+/// It is shared by every environment created from the same outermost one.
+#[derive(Debug)]
+pub struct Modules {
+  // import した側のファイルがないときに、相対パスの基準にするディレクトリ
+  dir: RefCell<PathBuf>,
+  cache: RefCell<HashMap<PathBuf, Object>>,
+  // 読み込み中のファイル。循環した import を見つけるのに使う
+  loading: RefCell<Vec<PathBuf>>,
+}
+
+impl Default for Modules {
+  fn default() -> Modules {
+    Modules {
+      dir: RefCell::new(PathBuf::from(".")),
+      cache: RefCell::new(HashMap::new()),
+      loading: RefCell::new(vec![]),
+    }
+  }
+}
+
+impl Modules {
+  /// Sets the directory which paths imported by the main program are relative to. It is the current directory by default.
+  /// Paths imported by a module are relative to the directory of that module.
+  pub fn set_dir(&self, dir: impl Into<PathBuf>) {
+    *self.dir.borrow_mut() = dir.into();
+  }
+
+  /// Sets the script being run as the main program. Paths it imports are relative to its directory, and a module which
+  /// imports it back fails with an import cycle instead of evaluating it a second time.
+  pub fn set_main(&self, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    self.set_dir(path.parent().unwrap_or_else(|| Path::new(".")));
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    *self.loading.borrow_mut() = vec![path];
+  }
+
+  fn resolve(&self, path: &str) -> PathBuf {
+    let dir = match self.loading.borrow().last() {
+      Some(importer) => importer.parent().map(Path::to_path_buf).unwrap_or_default(),
+      None => self.dir.borrow().clone(),
+    };
+    let mut path = dir.join(path);
+    if path.extension().is_none() {
+      path.set_extension(EXTENSION);
+    }
+    // 同じファイルを別の書き方で import しても 1 回だけ評価する
+    fs::canonicalize(&path).unwrap_or(path)
+  }
+
+  /// Returns the module at `path`, evaluating the file first unless it has been imported before.
+  pub fn import(&self, path: &str, env: &Rc<RefCell<Environment>>) -> Object {
+    let path = self.resolve(path);
+    if let Some(module) = self.cache.borrow().get(&path) {
+      return module.clone();
+    }
+
+    if self.loading.borrow().contains(&path) {
+      let mut cycle: Vec<String> = self.loading.borrow().iter()
+        .skip_while(|loading| **loading != path)
+        .map(|loading| loading.display().to_string())
+        .collect();
+      cycle.push(path.display().to_string());
+      return new_error(format!("import cycle: {}.", cycle.join(" -> ")));
+    }
+
+    let source = match fs::read_to_string(&path) {
+      Ok(source) => source,
+      Err(err) => return new_error(format!("could not read {}: {}.", path.display(), err)),
+    };
+    let mut p = Parser::new(Lexer::new(source));
+    let mut program = p.parse_program();
+    if let Some(err) = p.errors.first() {
+      return new_error(format!("could not parse {}: {}: {}", path.display(), err.span(), err));
+    }
+
+    self.loading.borrow_mut().push(path.clone());
+    let module_env = Environment::new_module_env(env, &path.display().to_string());
+    macro_expansion::define_macros(&mut program, &module_env);
+    let result = match macro_expansion::expand_macros(program, &module_env) {
+      Ok(program) => super::eval(program, &module_env),
+      Err(err) => err,
+    };
+    self.loading.borrow_mut().pop();
+
+    // エラーの位置は module_env の file でこのファイルのものとして報告される
+    if let err @ Object::Error(_) = result {
+      return err;
+    }
+
+    let pairs = module_env.borrow().bindings().into_iter()
+      .map(|(name, val)| (Object::Str(object::Str::new(name.to_string())), val))
+      .collect();
+    let module = Object::Hash(object::Hash::new(pairs));
+    self.cache.borrow_mut().insert(path, module.clone());
+    module
+  }
+}
+
+fn new_error(msg: String) -> Object {
+  Object::Error(Box::new(object::Error::new(ErrorKind::Import, msg)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::evaluator::builtins;
+  use crate::evaluator::limits::Capabilities;
+
+  fn eval_in(dir: &Path, input: &str) -> (Object, String) {
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_capabilities(Capabilities { filesystem: true, ..Default::default() });
+    let output = Rc::new(RefCell::new(Vec::new()));
+    env.borrow_mut().set_output(output.clone());
+    env.borrow().modules().set_dir(dir);
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    let result = super::super::eval(program, &env);
+    let output = String::from_utf8(output.borrow().clone()).unwrap();
+    (result, output)
+  }
+
+  #[test]
+  fn test_import() {
+    let dir = std::env::temp_dir().join(format!("rsmonkey-import-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("lib/math.mk"), "puts(\"loading math\"); let count = 0; count += 1; let square = fn(x) { x * x };").unwrap();
+    fs::write(dir.join("main.mk"), "let math = import(\"lib/math\"); math").unwrap();
+    fs::write(dir.join("a.mk"), "let b = import(\"b.mk\");").unwrap();
+    fs::write(dir.join("b.mk"), "let a = import(\"a\");").unwrap();
+    fs::write(dir.join("c.mk"), "puts(\"loading c\"); let d = import(\"d\");").unwrap();
+    fs::write(dir.join("d.mk"), "let c = import(\"c\");").unwrap();
+    fs::write(dir.join("broken.mk"), "let x = ;").unwrap();
+    fs::write(dir.join("failing.mk"), "let x = 1;\nx + null").unwrap();
+    fs::write(dir.join("apply.mk"), "let apply = fn(f) {\n  f(1)\n};").unwrap();
+
+    let tests = vec![
+      ("let m = import(\"lib/math\"); m[\"square\"](3) + m[\"count\"]", "10"),
+      ("let math = import(\"lib/math\"); math.square(3) + math.count", "10"),
+      ("import(\"main\")[\"math\"][\"count\"]", "1"),
+      ("let square = fn(x) { x }; import(\"lib/math\")[\"square\"](3)", "9"),
+      ("import(\"missing\")", "ImportError"),
+      ("import(\"broken\")", "ImportError"),
+      ("import(1)", "TypeError"),
+    ];
+    for (input, expected) in tests {
+      let (result, _) = eval_in(&dir, input);
+      match result {
+        Object::Error(err) => assert_eq!(err.kind.to_string(), expected, "{}: {}", input, err.message),
+        result => assert_eq!(result.to_string(), expected, "{}", input),
+      }
+    }
+
+    // 2 回目の import は評価し直さない
+    let (result, output) = eval_in(&dir, "let m = import(\"lib/math\"); m[\"count\"] = 5; import(\"./lib/math.mk\")[\"count\"]");
+    assert_eq!(result.to_string(), "1");
+    assert_eq!(output, "\"loading math\"\n");
+
+    match eval_in(&dir, "import(\"a\")").0 {
+      Object::Error(err) => {
+        assert_eq!(err.kind, ErrorKind::Import);
+        assert!(err.message.ends_with("import cycle: {0}/a.mk -> {0}/b.mk -> {0}/a.mk.".replace("{0}", &fs::canonicalize(&dir).unwrap().display().to_string()).as_str()), "{}", err.message);
+      },
+      result => panic!("expected an import cycle, got {}", result),
+    }
+
+    // 実行中のスクリプトも読み込み中として扱い、import し返されても 2 回評価しない
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_capabilities(Capabilities { filesystem: true, ..Default::default() });
+    let output = Rc::new(RefCell::new(Vec::new()));
+    env.borrow_mut().set_output(output.clone());
+    env.borrow().modules().set_main(dir.join("c.mk"));
+    let program = Parser::new(Lexer::new(fs::read_to_string(dir.join("c.mk")).unwrap())).parse_program();
+    match super::super::eval(program, &env) {
+      Object::Error(err) => assert_eq!(err.message, "import cycle: {0}/c.mk -> {0}/d.mk -> {0}/c.mk.".replace("{0}", &fs::canonicalize(&dir).unwrap().display().to_string())),
+      result => panic!("expected an import cycle, got {}", result),
+    }
+    assert_eq!(String::from_utf8(output.borrow().clone()).unwrap(), "\"loading c\"\n");
+
+    // モジュールの中の位置は、そのモジュールのファイルの位置として報告する
+    let dir_name = fs::canonicalize(&dir).unwrap().display().to_string();
+    let tests = vec![
+      ("import(\"failing\")", "{0}/failing.mk:2:1: TypeError: type mismatch: 1 + null.\n  in import (main.mk:1:1)"),
+      ("let square = import(\"lib/math\")[\"square\"];\nsquare(true)", "{0}/lib/math.mk:1:71: TypeError: unknown operator: true * true.\n  in square (main.mk:2:1)"),
+      ("let apply = import(\"apply\")[\"apply\"];\napply(fn(x) { x + null })", "main.mk:2:15: TypeError: type mismatch: 1 + null.\n  in f ({0}/apply.mk:2:3)\n  in apply (main.mk:2:1)"),
+    ];
+    for (input, expected) in tests {
+      match eval_in(&dir, input).0 {
+        Object::Error(err) => assert_eq!(err.report(Some("main.mk")), expected.replace("{0}", &dir_name), "{}", input),
+        result => panic!("expected an error, got {}", result),
+      }
+    }
+    fs::remove_dir_all(&dir).unwrap();
+  }
+}
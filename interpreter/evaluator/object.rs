@@ -3,55 +3,167 @@ use std::cmp::PartialEq;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
 use std::hash::{self, Hasher};
 
 use crate::ast::ident::Identifier;
+use crate::ast::expr::Expression;
 use crate::ast::stmt::BlockStatement;
+use crate::code::Instructions;
+use crate::compiler::symbol_table::Symbol;
+use crate::intern::Name;
+use crate::span::Span;
 use crate::utils;
 use super::environment::Environment;
 
 #[derive(Debug, Clone)]
 pub enum Object {
   Integer(Integer),
+  /// An integer which does not fit in an `i64`. Only made in an environment with `set_bigint`.
+  #[cfg(feature = "bigint")]
+  BigInt(BigInt),
+  Float(Float),
   Boolean(Boolean),
   Str(Str),
   Array(Array),
   Hash(Hash),
+  Struct(Struct),
+  Record(Record),
+  Range(Range),
   Return(Return),
   Func(Func),
+  Closure(Closure),
+  Quote(Quote),
+  Macro(Macro),
   Builtin(Builtin),
   External(External),
-  Error(Error),
+  Error(Box<Error>), // エラーは滅多に作られないので、Object を大きくしないように Box で持つ
   Null,
 }
 
 impl Object {
-  pub fn is_primitive(&self) -> bool {
+  /// Returns the object as a `HashKey` if it can be used as a key of `Hash`.
+  pub fn as_hash_key(&self) -> Option<&dyn HashKey> {
     match self {
-      Object::Boolean(_)
-      | Object::Str(_)
-      | Object::Integer(_) => true,
-      _ => false,
+      Object::Boolean(val) => Some(val),
+      Object::Str(val) => Some(val),
+      Object::Integer(val) => Some(val),
+      #[cfg(feature = "bigint")]
+      Object::BigInt(val) => Some(val),
+      _ => None,
+    }
+  }
+
+  pub fn is_hashable(&self) -> bool {
+    self.as_hash_key().is_some()
+  }
+
+  /// Returns the name of the type of the object, e.g. `integer` or `function`.
+  pub fn type_name(&self) -> &'static str {
+    match self {
+      Object::Integer(_) => "integer",
+      #[cfg(feature = "bigint")]
+      Object::BigInt(_) => "integer",
+      Object::Float(_) => "float",
+      Object::Boolean(_) => "boolean",
+      Object::Str(_) => "string",
+      Object::Array(_) => "array",
+      Object::Hash(_) => "hash",
+      Object::Struct(_) => "struct",
+      Object::Record(_) => "record",
+      Object::Range(_) => "range",
+      Object::Return(rtn) => rtn.value.type_name(),
+      Object::Func(_) | Object::Closure(_) => "function",
+      Object::Quote(_) => "quote",
+      Object::Macro(_) => "macro",
+      Object::Builtin(_) | Object::External(_) => "builtin",
+      Object::Error(_) => "error",
+      Object::Null => "null",
     }
   }
 }
 
+/// Implemented by objects which can be used as a key of `Hash`.
+pub trait HashKey {
+  fn hash_key(&self) -> u64;
+}
+
+fn hash_of<T: hash::Hash>(val: &T) -> u64 {
+  let mut hasher = DefaultHasher::new();
+  val.hash(&mut hasher);
+  hasher.finish()
+}
+
+impl HashKey for Integer {
+  fn hash_key(&self) -> u64 {
+    hash_of(&self.value)
+  }
+}
+
+// i64 に収まる値は Integer になるので、同じ値の Integer とキーが重なることはない
+#[cfg(feature = "bigint")]
+impl HashKey for BigInt {
+  fn hash_key(&self) -> u64 {
+    hash_of(&self.value)
+  }
+}
+
+impl HashKey for Boolean {
+  fn hash_key(&self) -> u64 {
+    hash_of(&self.value)
+  }
+}
+
+impl HashKey for Str {
+  fn hash_key(&self) -> u64 {
+    hash_of(&self.value)
+  }
+}
+
+/// The equality of `==` and `!=`, which is defined between any two objects.
+///
+/// Numbers are equal when they have the same value, even if one is an integer and the other a float.
+/// Arrays are equal element-wise and hashes when they have the same keys with equal values.
+/// Functions and structs are only equal to themselves, that is to the same value passed around,
+/// records when they are of the same struct and have equal fields,
+/// and objects of different types are never equal.
 impl PartialEq for Object {
   fn eq(&self, other: &Self) -> bool {
     match (self, other) {
-      (
-        Object::Boolean(val),
-        Object::Boolean(other),
-      ) => val.value == other.value,
-      (
-        Object::Str(val),
-        Object::Str(other),
-      ) => val.value == other.value,
-      (
-        Object::Integer(val),
-        Object::Integer(other),
-      ) => val.value == other.value,
-      _ => panic!("PartialEq is not implemented for {}", self),
+      (Object::Integer(val), Object::Integer(other)) => val.value == other.value,
+      (Object::Float(val), Object::Float(other)) => val.value == other.value,
+      (Object::Integer(val), Object::Float(other)) => val.value as f64 == other.value,
+      (Object::Float(val), Object::Integer(other)) => val.value == other.value as f64,
+      // BigInt は i64 に収まらない値だけなので、Integer とは等しくならない
+      #[cfg(feature = "bigint")]
+      (Object::BigInt(val), Object::BigInt(other)) => val.value == other.value,
+      #[cfg(feature = "bigint")]
+      (Object::BigInt(val), Object::Float(other)) | (Object::Float(other), Object::BigInt(val)) => val.to_f64() == other.value,
+      (Object::Boolean(val), Object::Boolean(other)) => val.value == other.value,
+      (Object::Str(val), Object::Str(other)) => val.value == other.value,
+      (Object::Range(val), Object::Range(other)) => val == other,
+      (Object::Null, Object::Null) => true,
+      (Object::Array(val), Object::Array(other)) => val.elements == other.elements,
+      (Object::Hash(val), Object::Hash(other)) => {
+        val.pairs.len() == other.pairs.len()
+          && val.pairs.iter().all(|(key, val)| other.pairs.get(key) == Some(val))
+      },
+      (Object::Struct(val), Object::Struct(other)) => val.is(other),
+      (Object::Record(val), Object::Record(other)) => val.def.is(&other.def) && val.values == other.values,
+      // 関数は同じ値どうしのときだけ等しい
+      (Object::Func(val), Object::Func(other)) => {
+        Rc::ptr_eq(&val.body, &other.body) && Rc::ptr_eq(&val.env, &other.env)
+      },
+      (Object::Closure(val), Object::Closure(other)) => {
+        Rc::ptr_eq(&val.func, &other.func)
+          && Rc::ptr_eq(&val.env, &other.env)
+          && val.free.iter().zip(&other.free).all(|(val, other)| Rc::ptr_eq(val, other))
+      },
+      (Object::Quote(val), Object::Quote(other)) => Rc::ptr_eq(&val.node, &other.node),
+      (Object::Macro(val), Object::Macro(other)) => Rc::ptr_eq(&val.body, &other.body) && Rc::ptr_eq(&val.env, &other.env),
+      (Object::Builtin(val), Object::Builtin(other)) => val.func as usize == other.func as usize,
+      (Object::External(val), Object::External(other)) => Rc::ptr_eq(&val.func, &other.func),
+      _ => false,
     }
   }
 }
@@ -60,11 +172,9 @@ impl Eq for Object {}
 
 impl hash::Hash for Object {
   fn hash<H: Hasher>(&self, state: &mut H) {
-    match self {
-      Object::Boolean(val) => val.value.hash(state),
-      Object::Str(val) => val.value.hash(state),
-      Object::Integer(val) => val.value.hash(state),
-      _ => panic!("Hash is not implemented for {}", self),
+    match self.as_hash_key() {
+      Some(key) => key.hash_key().hash(state),
+      None => panic!("Hash is not implemented for {}", self),
     }
   }
 }
@@ -73,12 +183,21 @@ impl fmt::Display for Object {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
       Object::Integer(val) => write!(f, "{}", val),
+      #[cfg(feature = "bigint")]
+      Object::BigInt(val) => write!(f, "{}", val),
+      Object::Float(val) => write!(f, "{}", val),
       Object::Boolean(val) => write!(f, "{}", val),
       Object::Str(val) => write!(f, "{}", val),
       Object::Array(val) => write!(f, "{}", val),
       Object::Hash(val) => write!(f, "{}", val),
+      Object::Struct(val) => write!(f, "{}", val),
+      Object::Record(val) => write!(f, "{}", val),
+      Object::Range(val) => write!(f, "{}", val),
       Object::Return(val) => write!(f, "{}", val),
       Object::Func(val) => write!(f, "{}", val),
+      Object::Closure(val) => write!(f, "{}", val),
+      Object::Quote(val) => write!(f, "{}", val),
+      Object::Macro(val) => write!(f, "{}", val),
       Object::Builtin(val) => write!(f, "{}", val),
       Object::External(val) => write!(f, "{:?}", val),
       Object::Error(val) => write!(f, "{}", val),
@@ -104,6 +223,53 @@ impl fmt::Display for Integer {
   }
 }
 
+/// An integer of any size. Use `evaluator::bigint::normalize` to make an object from a value which may fit in an `i64`.
+#[cfg(feature = "bigint")]
+#[derive(Debug, PartialEq, Clone)]
+pub struct BigInt {
+  pub value: num_bigint::BigInt,
+}
+
+#[cfg(feature = "bigint")]
+impl BigInt {
+  pub fn new(value: num_bigint::BigInt) -> BigInt {
+    BigInt { value }
+  }
+
+  /// Returns the nearest float, or an infinity if the integer is too large for one.
+  pub fn to_f64(&self) -> f64 {
+    match num_traits::ToPrimitive::to_f64(&self.value) {
+      Some(value) => value,
+      None if self.value.sign() == num_bigint::Sign::Minus => f64::NEG_INFINITY,
+      None => f64::INFINITY,
+    }
+  }
+}
+
+#[cfg(feature = "bigint")]
+impl fmt::Display for BigInt {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.value)
+  }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Float {
+  pub value: f64,
+}
+
+impl Float {
+  pub fn new(value: f64) -> Float {
+    Float { value }
+  }
+}
+
+impl fmt::Display for Float {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{:?}", self.value)
+  }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Boolean {
   pub value: bool,
@@ -191,16 +357,139 @@ impl fmt::Display for Hash {
   }
 }
 
+/// The struct bound by `struct Point {x, y}`, which makes records with the fields.
+#[derive(Debug, Clone)]
+pub struct Struct {
+  pub name: Name,
+  pub fields: Rc<Vec<Name>>, // 同じ struct 文から作った値かどうかを、フィールドの Rc で見分ける
+}
+
+impl Struct {
+  pub fn new(name: Name, fields: Vec<Name>) -> Struct {
+    Struct { name, fields: Rc::new(fields) }
+  }
+
+  /// Returns the position of the field named `field`, which is also the position of its value in a record.
+  pub fn field_index(&self, field: &str) -> Option<usize> {
+    self.fields.iter().position(|name| name.as_str() == field)
+  }
+
+  /// Whether `other` is this struct, rather than another struct with the same name and fields.
+  pub fn is(&self, other: &Struct) -> bool {
+    Rc::ptr_eq(&self.fields, &other.fields)
+  }
+}
+
+impl fmt::Display for Struct {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "struct {} {{", self.name)?;
+    utils::write_object_list(&self.fields, f)?;
+    write!(f, "}}")
+  }
+}
+
+/// A value of a struct. `values` has the value of each field of `def`, in the same order.
+#[derive(Debug, Clone)]
+pub struct Record {
+  pub def: Struct,
+  pub values: Vec<Object>,
+}
+
+impl Record {
+  pub fn new(def: Struct, values: Vec<Object>) -> Record {
+    Record { def, values }
+  }
+
+  pub fn get(&self, field: &str) -> Option<&Object> {
+    self.def.field_index(field).map(|i| &self.values[i])
+  }
+}
+
+impl fmt::Display for Record {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{} {{", self.def.name)?;
+    let fields: Vec<String> = self.def.fields.iter().zip(&self.values)
+      .map(|(name, val)| format!("{}: {}", name, val))
+      .collect();
+    utils::write_object_list(&fields, f)?;
+    write!(f, "}}")
+  }
+}
+
+/// Integers from `start` up to, but not including, `end`, or up to and including `end` if the range is inclusive.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Range {
+  pub start: i64,
+  pub end: i64,
+  inclusive: bool,
+}
+
+impl Range {
+  pub fn new(start: i64, end: i64) -> Range {
+    Range { start, end, inclusive: false }
+  }
+
+  /// Makes a range which includes `end`. It is the same as `Range::new(start, end + 1)`,
+  /// unless `end` is `i64::MAX`.
+  pub fn new_inclusive(start: i64, end: i64) -> Range {
+    match end.checked_add(1) {
+      Some(end) => Range::new(start, end),
+      None => Range { start, end, inclusive: true },
+    }
+  }
+
+  /// Returns the number of the integers, or `None` if it does not fit in an `i64`.
+  pub fn len(&self) -> Option<i64> {
+    if self.is_empty() {
+      return Some(0);
+    }
+    let len = self.end.checked_sub(self.start)?;
+    if self.inclusive { len.checked_add(1) } else { Some(len) }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    if self.inclusive { self.start > self.end } else { self.start >= self.end }
+  }
+
+  /// Returns the `index`th integer, or `None` if the range is not that long.
+  pub fn get(&self, index: i64) -> Option<i64> {
+    if index < 0 {
+      return None;
+    }
+    let value = self.start.checked_add(index)?;
+    let contains = if self.inclusive { value <= self.end } else { value < self.end };
+    contains.then_some(value)
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = i64> {
+    // 終わりを含む範囲に直すと i64::MAX で終わる範囲も end + 1 を求めずに回せる
+    let end = if self.inclusive { Some(self.end) } else { self.end.checked_sub(1) };
+    let (start, end) = match end {
+      Some(end) => (self.start, end),
+      None => (1, 0),
+    };
+    start..=end
+  }
+}
+
+impl fmt::Display for Range {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let operator = if self.inclusive { "..=" } else { ".." };
+    write!(f, "{}{}{}", self.start, operator, self.end)
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct Func {
   pub args: Vec<Identifier>,
-  pub body: BlockStatement,
+  pub defaults: Vec<Option<Expression>>,
+  pub body: Rc<BlockStatement>, // Object のサイズを抑えるために Rc で持つ
   pub env: Rc<RefCell<Environment>>,
 }
 
 impl Func {
-  pub fn new(args: Vec<Identifier>, body: BlockStatement, env: Rc<RefCell<Environment>>) -> Func {
-    Func { args, body, env }
+  pub fn new(args: Vec<Identifier>, defaults: Vec<Option<Expression>>, body: Rc<BlockStatement>, env: Rc<RefCell<Environment>>) -> Func {
+    Func { args, defaults, body, env }
   }
 }
 
@@ -208,16 +497,97 @@ impl fmt::Display for Func {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     write!(f, "fn(")?;
 
-    utils::write_object_list(&self.args, f)?;
+    utils::write_param_list(&self.args, &self.defaults, f)?;
 
     write!(f, ") {}", &self.body)?;
     Ok(())
   }
 }
 
-pub type BuiltinFunc = fn(Vec<Object>) -> Object;
+/// A function literal compiled to bytecode. It owns the constants, names and inner functions
+/// its instructions refer to, so it can still be called after the program which defined it has finished.
+#[derive(Debug, PartialEq)]
+pub struct CompiledFunction {
+  pub instructions: Instructions,
+  pub constants: Vec<Object>,
+  pub names: Vec<Name>,
+  pub functions: Vec<Rc<CompiledFunction>>,
+  /// The names of the local slots, starting with the `num_params` parameters.
+  pub locals: Vec<Name>,
+  pub num_params: usize,
+  /// Where the closure takes each free variable from in the frame which creates it.
+  pub free: Vec<Symbol>,
+  pub source: String, // tree-walking の Func と同じ表示にするために元の関数リテラルを持つ
+}
 
-#[derive(Debug, PartialEq, Clone)]
+/// A local variable of a compiled function. Closures share it with the frame which defined it,
+/// so an assignment on either side is seen by the other. It is `None` until the variable is bound.
+pub type Slot = Rc<RefCell<Option<Object>>>;
+
+/// A compiled function together with the environment it was created in and the variables it captured.
+#[derive(Debug, Clone)]
+pub struct Closure {
+  pub func: Rc<CompiledFunction>,
+  pub env: Rc<RefCell<Environment>>,
+  pub free: Vec<Slot>,
+}
+
+impl Closure {
+  pub fn new(func: Rc<CompiledFunction>, env: Rc<RefCell<Environment>>, free: Vec<Slot>) -> Closure {
+    Closure { func, env, free }
+  }
+}
+
+impl fmt::Display for Closure {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.func.source)
+  }
+}
+
+/// An unevaluated expression made by `quote`, which a macro returns to replace its call.
+#[derive(Debug, Clone)]
+pub struct Quote {
+  pub node: Rc<Expression>,
+}
+
+impl Quote {
+  pub fn new(node: Expression) -> Quote {
+    Quote { node: Rc::new(node) }
+  }
+}
+
+impl fmt::Display for Quote {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "QUOTE({})", self.node)
+  }
+}
+
+/// A macro bound by `define_macros`. Like `Func`, it keeps the environment it was defined in.
+#[derive(Debug, Clone)]
+pub struct Macro {
+  pub args: Vec<Identifier>,
+  pub body: Rc<BlockStatement>,
+  pub env: Rc<RefCell<Environment>>,
+}
+
+impl Macro {
+  pub fn new(args: Vec<Identifier>, body: Rc<BlockStatement>, env: Rc<RefCell<Environment>>) -> Macro {
+    Macro { args, body, env }
+  }
+}
+
+impl fmt::Display for Macro {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "macro(")?;
+    utils::write_object_list(&self.args, f)?;
+    write!(f, ") {}", &self.body)
+  }
+}
+
+/// Builtins receive the environment of the caller so they can reach runtime state such as the output sink.
+pub type BuiltinFunc = fn(Vec<Object>, &Rc<RefCell<Environment>>) -> Object;
+
+#[derive(Debug, Clone)]
 pub struct Builtin {
   pub func: BuiltinFunc,
 }
@@ -259,19 +629,136 @@ impl PartialEq for External {
   }
 }
 
+/// What kind of problem stopped the program.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+  /// An operator or a builtin was applied to values of the wrong type.
+  Type,
+  /// An identifier was not bound, or could not be bound.
+  Name,
+  /// A function was called with the wrong number of arguments.
+  Argument,
+  /// Division by zero, integer overflow and the like.
+  Arithmetic,
+  /// An index was out of the range of an array.
+  Index,
+  /// Function calls or expressions were nested too deeply.
+  Recursion,
+  /// Writing output failed.
+  Io,
+  /// The compiler does not support the program.
+  Compile,
+  /// `assert` was called with a falsy condition.
+  Assertion,
+  /// `panic` was called.
+  Panic,
+  /// The program went over one of the `Limits` set on its environment. It can not be caught with `try`.
+  LimitExceeded,
+  /// The program was stopped with a `CancelToken`. It can not be caught with `try`.
+  Cancelled,
+  /// A file passed to `import` could not be read or parsed, or imports itself through other files.
+  Import,
+  /// A string passed to `json_parse` is not valid JSON.
+  Json,
+  /// A builtin needs a capability which the environment does not grant, such as access to the filesystem.
+  Permission,
+  /// A string passed to `eval` could not be parsed.
+  Syntax,
+}
+
+impl fmt::Display for ErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      ErrorKind::Type => "TypeError",
+      ErrorKind::Name => "NameError",
+      ErrorKind::Argument => "ArgumentError",
+      ErrorKind::Arithmetic => "ArithmeticError",
+      ErrorKind::Index => "IndexError",
+      ErrorKind::Recursion => "RecursionError",
+      ErrorKind::Io => "IOError",
+      ErrorKind::Compile => "CompileError",
+      ErrorKind::Assertion => "AssertionError",
+      ErrorKind::Panic => "Panic",
+      ErrorKind::LimitExceeded => "LimitExceeded",
+      ErrorKind::Cancelled => "Cancelled",
+      ErrorKind::Import => "ImportError",
+      ErrorKind::Json => "JSONError",
+      ErrorKind::Permission => "PermissionError",
+      ErrorKind::Syntax => "SyntaxError",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// A call to a function which was active when an error happened.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TraceFrame {
+  /// The name the function was called by, or `None` when it was not called through an identifier.
+  pub function: Option<Name>,
+  /// The call expression.
+  pub span: Span,
+  /// The file `span` is in, like `Error::file`.
+  pub file: Option<Name>,
+}
+
+impl fmt::Display for TraceFrame {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.function {
+      Some(name) => write!(f, "in {}", name),
+      None => write!(f, "in <anonymous>"),
+    }
+  }
+}
+
+/// A runtime error. It stops the evaluation and is passed up to the caller
+/// until `catch` or the host handles it.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Error {
-  pub value: String,
+  pub kind: ErrorKind,
+  pub message: String,
+  /// The innermost expression or statement which failed. The VM does not know the source locations.
+  pub span: Option<Span>,
+  /// The file `span` is in when it is not the program being run: an imported path, `<prelude>` or `<eval>`.
+  pub file: Option<Name>,
+  /// The active function calls, from the innermost one.
+  pub trace: Vec<TraceFrame>,
 }
 
 impl Error {
-  pub fn new(value: String) -> Error {
-    Error { value }
+  pub fn new(kind: ErrorKind, message: String) -> Error {
+    Error { kind, message, span: None, file: None, trace: vec![] }
+  }
+
+  /// Formats the error with its location and the stack trace, one call per line.
+  /// `file` is prepended to the locations in the program being run when it was read from a file.
+  /// The locations in other files, such as imported modules, are prepended with their own names.
+  pub fn report(&self, file: Option<&str>) -> String {
+    let location = |span: Span, other: Option<Name>| match other.map(Name::as_str).or(file) {
+      Some(file) => format!("{}:{}", file, span),
+      None => format!("{}", span),
+    };
+
+    let mut lines = vec![match self.span {
+      Some(span) => format!("{}: {}", location(span, self.file), self),
+      None => format!("{}", self),
+    }];
+    // 再帰で同じ呼び出しが続くところはまとめる
+    let mut i = 0;
+    while i < self.trace.len() {
+      let frame = &self.trace[i];
+      let repeated = self.trace[i..].iter().take_while(|other| *other == frame).count();
+      lines.push(format!("  {} ({})", frame, location(frame.span, frame.file)));
+      if repeated > 1 {
+        lines.push(format!("  ... repeated {} more times", repeated - 1));
+      }
+      i += repeated;
+    }
+    lines.join("\n")
   }
 }
 
 impl fmt::Display for Error {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "[Internal Error] {}", self.value)
+    write!(f, "{}: {}", self.kind, self.message)
   }
 }
@@ -0,0 +1,48 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::ast::stmt::Statement;
+use crate::ast::expr::{Expression, CallExpression};
+use crate::intern::Name;
+use super::object::Object;
+
+/// Callbacks from the evaluator, for tools such as profilers, coverage or tracers which watch a program run.
+/// Every method does nothing by default, so an observer implements only the ones it needs.
+///
+/// Each `enter_*` is followed by the matching `exit_*` with the result, which may be an error or, for a statement,
+/// a `return` value. An observer can not change the program; use a `Debugger` to pause or stop it.
+/// Code evaluated from inside a callback is not reported, and the VM does not call the observer.
+pub trait EvalObserver {
+  fn enter_statement(&mut self, _stmt: &Statement) {}
+
+  fn exit_statement(&mut self, _stmt: &Statement, _result: &Object) {}
+
+  fn enter_expression(&mut self, _expr: &Expression) {}
+
+  fn exit_expression(&mut self, _expr: &Expression, _result: &Object) {}
+
+  /// Called when a call expression has evaluated its function and arguments, before the function runs.
+  /// `function` is the callee's name when it is called through an identifier. Functions called by builtins,
+  /// e.g. the callback of `map`, are not reported here, but the expressions in their bodies are.
+  fn enter_call(&mut self, _call: &CallExpression, _function: Option<Name>, _args: &[Object]) {}
+
+  fn exit_call(&mut self, _call: &CallExpression, _function: Option<Name>, _result: &Object) {}
+
+  /// Called when a new binding is made by `let`, a parameter, a `for` loop or `catch`.
+  fn bind(&mut self, _name: Name, _value: &Object) {}
+
+  /// Called when an existing binding is changed with `=` or an update operator.
+  fn assign(&mut self, _name: Name, _value: &Object) {}
+}
+
+/// Where the observer of an environment is kept. It is shared by every environment created from the same outermost one.
+pub type ObserverSlot = Rc<RefCell<Option<Box<dyn EvalObserver>>>>;
+
+// コールバックの中から評価されたコードでは、借用済みなので呼ばない
+pub(crate) fn notify(slot: &ObserverSlot, f: impl FnOnce(&mut dyn EvalObserver)) {
+  if let Ok(mut observer) = slot.try_borrow_mut() {
+    if let Some(observer) = observer.as_mut() {
+      f(observer.as_mut());
+    }
+  }
+}
@@ -0,0 +1,23 @@
+// 起動時に読み込まれる、Monkey で書かれた関数。配列のほか範囲など for で回せるものなら渡せる
+
+// 空なら 0 を返す
+let sum = fn(arr) {
+  let total = 0;
+  for (x in arr) { total += x }
+  total
+};
+
+// f が真を返す要素が一つでもあれば true を返す
+let any = fn(arr, f) {
+  for (x in arr) {
+    if (f(x)) { return true }
+  }
+  false
+};
+
+let all = fn(arr, f) {
+  for (x in arr) {
+    if (!f(x)) { return false }
+  }
+  true
+};
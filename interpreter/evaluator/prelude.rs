@@ -0,0 +1,65 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use super::object::{self, Object};
+use super::environment::Environment;
+
+/// The source of the prelude: `sum`, `any` and `all`, written in Monkey.
+pub const SOURCE: &str = include_str!("prelude.mk");
+
+/// Evaluates the prelude and adds its functions to the builtins of the outermost environment of `env`.
+pub fn load(env: &Rc<RefCell<Environment>>) -> Result<(), object::Error> {
+  let mut p = Parser::new(Lexer::new(SOURCE.to_string()));
+  let program = p.parse_program();
+  // 同梱したソースなので、構文解析できることはテストで確かめている
+  assert!(p.errors.is_empty(), "the prelude does not parse: {:?}", p.errors);
+
+  // prelude の関数どうしは、スクリプトの束縛ではなく prelude の環境で名前を探す
+  let prelude_env = Environment::new_module_env(env, "<prelude>");
+  if let Object::Error(err) = super::eval(program, &prelude_env) {
+    return Err(*err);
+  }
+  for (name, val) in prelude_env.borrow().bindings() {
+    env.borrow_mut().insert_builtin(name, val);
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::evaluator::builtins;
+
+  fn test_eval(input: &str) -> Object {
+    let env = Environment::new(builtins::new_builtins());
+    load(&env).unwrap();
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    super::super::eval(program, &env)
+  }
+
+  #[test]
+  fn test_prelude() {
+    let tests = vec![
+      ("[sum([1, 2, 3]), sum(1..=4), sum([]), sum([0.5, 1])]", "[6, 10, 0, 1.5]"),
+      ("let even = fn(x) { x % 2 == 0 }; [any([1, 2], even), any([1, 3], even), any([], even)]", "[true, false, false]"),
+      ("let even = fn(x) { x % 2 == 0 }; [all([2, 4], even), all([2, 3], even), all([], even)]", "[true, false, true]"),
+      ("let sum = fn(arr) { 0 }; [sum([1, 2]), all(1..3, fn(x) { x > 0 })]", "[0, true]"),
+      ("sum([1, true])", "TypeError: type mismatch: 1 + true."),
+      ("let map = 1;", "NameError: `map` is already used as a builtin function."),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(test_eval(input).to_string(), expected, "{}", input);
+    }
+
+    // prelude の中の位置は、スクリプトのファイルではなく <prelude> の位置として報告する
+    match test_eval("let f = fn(x) { x };\nf(sum([1, true]))") {
+      Object::Error(err) => assert_eq!(err.report(Some("t.mk")), "<prelude>:6:20: TypeError: type mismatch: 1 + true.\n  in sum (t.mk:2:3)"),
+      obj => panic!("expected an error, got {}", obj),
+    }
+  }
+}
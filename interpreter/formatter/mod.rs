@@ -0,0 +1,570 @@
+use std::fmt;
+
+use crate::ast::Program;
+use crate::ast::stmt::{Statement, BlockStatement, Pattern};
+use crate::ast::expr::Expression;
+use crate::ast::ident::Identifier;
+use crate::ast::lit::{self, Literal};
+use crate::ast::operator::{Prefix, BinaryOperator};
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::parser::error::ParseError;
+use crate::span::Span;
+
+const INDENT: &str = "  ";
+
+/// The longest expression which is kept on the same line as the braces of the block it is the only statement of,
+/// e.g. `fn(x) { x * 2 }`.
+const INLINE_WIDTH: usize = 60;
+
+#[derive(Debug, PartialEq)]
+pub enum FormatError {
+  /// The source does not parse.
+  Parse(Vec<ParseError>),
+  /// The source has a `/* */` comment. The syntax tree does not keep them, so formatting would delete it.
+  BlockComment(Span),
+}
+
+impl fmt::Display for FormatError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FormatError::Parse(errors) => {
+        for (i, err) in errors.iter().enumerate() {
+          if i > 0 {
+            writeln!(f)?;
+          }
+          write!(f, "{}: {}", err.span(), err)?;
+        }
+        Ok(())
+      },
+      FormatError::BlockComment(span) => write!(f, "{}: block comments can not be formatted, use // comments instead", span),
+    }
+  }
+}
+
+/// Parses `source` and formats it with `format`.
+pub fn format_source(source: &str) -> Result<String, FormatError> {
+  let mut p = Parser::new(Lexer::new(source.to_string()));
+  let program = p.parse_program();
+  if !p.errors.is_empty() {
+    return Err(FormatError::Parse(p.errors));
+  }
+
+  let mut l = Lexer::new(source.to_string());
+  for _ in l.by_ref() {}
+  if let Some(span) = l.block_comments().first() {
+    return Err(FormatError::BlockComment(*span));
+  }
+
+  Ok(format(&program))
+}
+
+/// Formats `program` as canonical source code, which parses back to the same program.
+///
+/// Statements are put on their own lines, indented by two spaces in each block, and end with `;`
+/// except the last expression of a function, `if` or `try` block, and statements ending with a block.
+/// Blank lines between statements are kept, but not more than one in a row, and `//` comments stay
+/// on the line of the statement before them when they were on it.
+/// Operators are surrounded by spaces, and only the parentheses which the precedence of the operators needs are kept.
+pub fn format(program: &Program) -> String {
+  statements(&program.statements, 0, false)
+}
+
+/// Formats `stmts` as lines at the indentation `depth`.
+/// `is_value` tells whether the value of the last expression is the value of the block.
+fn statements(stmts: &[Statement], depth: usize, is_value: bool) -> String {
+  let texts: Vec<String> = stmts.iter().map(|stmt| statement(stmt, depth)).collect();
+  let last = stmts.iter().rposition(|stmt| !is_comment(stmt));
+
+  let mut out = String::new();
+  for (i, stmt) in stmts.iter().enumerate() {
+    if i > 0 {
+      let prev_end = stmts[i - 1].span().end.line;
+      let start = stmt.span().start.line;
+      // 前の文と同じ行にあったコメントは、その行末に残す
+      if is_comment(stmt) && start == prev_end {
+        out.pop();
+        out.push(' ');
+        out.push_str(&texts[i]);
+        out.push('\n');
+        continue;
+      }
+      if start > prev_end + 1 {
+        out.push('\n');
+      }
+    }
+
+    out.push_str(&INDENT.repeat(depth));
+    out.push_str(&texts[i]);
+
+    let next = (i + 1..stmts.len()).find(|j| !is_comment(&stmts[*j]));
+    if needs_semicolon(stmt, next.map(|j| (&stmts[j], texts[j].as_str())), is_value && Some(i) == last) {
+      out.push(';');
+    }
+    out.push('\n');
+  }
+  out
+}
+
+fn is_comment(stmt: &Statement) -> bool {
+  if let Statement::Comment(_) = stmt {
+    return true;
+  }
+  false
+}
+
+fn needs_semicolon(stmt: &Statement, next: Option<(&Statement, &str)>, is_value: bool) -> bool {
+  match stmt {
+    Statement::Let(_) | Statement::Return(_) => true,
+    Statement::Expr(_) if is_value => false,
+    Statement::Expr(expr) => match &expr.value {
+      // `}` で終わる式の後ろは、次の文が続きの演算子として読まれるときだけ区切る
+      Expression::If(_) | Expression::Try(_) => match next {
+        Some((Statement::Expr(_), text)) => text.starts_with(&['(', '[', '-', '+'][..]),
+        _ => false,
+      },
+      _ => true,
+    },
+    _ => false,
+  }
+}
+
+fn statement(stmt: &Statement, depth: usize) -> String {
+  match stmt {
+    Statement::Let(let_stmt) => format!("{} {} = {}", let_stmt.keyword(), pattern(&let_stmt.pattern, depth), expression(&let_stmt.value, depth)),
+    Statement::Func(func_stmt) => format!(
+      "fn {}({}) {}",
+      func_stmt.ident,
+      params(&func_stmt.func, depth),
+      block(&func_stmt.func.body, depth, true),
+    ),
+    Statement::Struct(struct_stmt) => {
+      let fields: Vec<String> = struct_stmt.fields.iter().map(|field| field.to_string()).collect();
+      format!("struct {} {{{}}}", struct_stmt.ident, fields.join(", "))
+    },
+    Statement::Return(rtn) => format!("return {}", expression(&rtn.value, depth)),
+    Statement::Expr(expr) => expression(&expr.value, depth),
+    Statement::Block(block_stmt) => block(block_stmt, depth, false),
+    Statement::While(while_stmt) => {
+      format!("while ({}) {}", expression(&while_stmt.condition, depth), block(&while_stmt.body, depth, false))
+    },
+    Statement::For(for_stmt) => format!(
+      "for ({} in {}) {}",
+      for_stmt.ident,
+      expression(&for_stmt.iterable, depth),
+      block(&for_stmt.body, depth, false),
+    ),
+    Statement::Comment(comment) => {
+      let value = comment.value.trim_end();
+      if value.is_empty() {
+        "//".to_string()
+      } else {
+        format!("// {}", value)
+      }
+    },
+  }
+}
+
+/// Formats `block` whose closing brace is at the indentation `depth`.
+/// `is_value` tells whether the block is the body of a function, `if` or `try`, which can be kept on one line.
+fn block(block: &BlockStatement, depth: usize, is_value: bool) -> String {
+  if block.statements.is_empty() {
+    return "{}".to_string();
+  }
+  if let (true, [Statement::Expr(expr)]) = (is_value, block.statements.as_slice()) {
+    let text = expression(&expr.value, depth);
+    if !text.contains('\n') && text.len() <= INLINE_WIDTH {
+      return format!("{{ {} }}", text);
+    }
+  }
+  format!("{{\n{}{}}}", statements(&block.statements, depth + 1, is_value), INDENT.repeat(depth))
+}
+
+fn expression(expr: &Expression, depth: usize) -> String {
+  match expr {
+    Expression::Identifier(ident) => ident.value.to_string(),
+    Expression::Literal(lit) => literal(lit, depth),
+    Expression::Prefix(pre) => {
+      let right = operand(&pre.right, BinaryOperator::Prefix, depth);
+      // `- -x` を `--x` にしない
+      if pre.operator == Prefix::Minus && right.starts_with('-') {
+        format!("{}({})", pre.operator, right)
+      } else {
+        format!("{}{}", pre.operator, right)
+      }
+    },
+    Expression::Infix(inf) => {
+      let precedence = inf.operator.precedence();
+      let left = left_operand(&inf.left, &precedence, depth);
+      format!("{} {} {}", left, inf.operator, operand(&inf.right, precedence, depth))
+    },
+    Expression::If(if_expr) => {
+      let mut s = format!("if ({}) {}", expression(&if_expr.condition, depth), block(&if_expr.consequence, depth, true));
+      if let Some(alt) = &if_expr.alternative {
+        s.push_str(&format!(" else {}", block(alt, depth, true)));
+      }
+      s
+    },
+    Expression::Try(try_expr) => format!(
+      "try {} catch ({}) {}",
+      block(&try_expr.block, depth, true),
+      try_expr.param,
+      block(&try_expr.handler, depth, true),
+    ),
+    // 腕は一行に一つずつ、最後の腕にもカンマを付けて並べる
+    Expression::Match(match_expr) => {
+      let subject = expression(&match_expr.subject, depth);
+      if match_expr.arms.is_empty() {
+        return format!("match ({}) {{}}", subject);
+      }
+      let indent = INDENT.repeat(depth + 1);
+      let arms: String = match_expr.arms.iter()
+        .map(|arm| format!("{}{} => {},\n", indent, pattern(&arm.pattern, depth + 1), expression(&arm.body, depth + 1)))
+        .collect();
+      format!("match ({}) {{\n{}{}}}", subject, arms, INDENT.repeat(depth))
+    },
+    // a.b(1) はメソッド呼び出しになるので、ハッシュの値を呼び出すときは括弧が要る
+    Expression::Call(call) => match &*call.func {
+      Expression::Member(_) => format!("({})({})", expression(&call.func, depth), list(&call.args, depth)),
+      func => format!("{}({})", left_operand(func, &BinaryOperator::Call, depth), list(&call.args, depth)),
+    },
+    Expression::MethodCall(method) => format!(
+      "{}.{}({})",
+      left_operand(&method.receiver, &BinaryOperator::Index, depth),
+      method.method,
+      list(&method.args, depth),
+    ),
+    Expression::Member(member) => format!(
+      "{}.{}",
+      left_operand(&member.object, &BinaryOperator::Index, depth),
+      member.property,
+    ),
+    Expression::Index(idx) => format!(
+      "{}[{}]",
+      left_operand(&idx.left, &BinaryOperator::Index, depth),
+      expression(&idx.index, depth),
+    ),
+    Expression::Slice(slice) => {
+      let bound = |bound: &Option<Box<Expression>>| match bound {
+        Some(expr) => expression(expr, depth),
+        None => String::new(),
+      };
+      format!("{}[{}:{}]", left_operand(&slice.left, &BinaryOperator::Index, depth), bound(&slice.start), bound(&slice.end))
+    },
+    Expression::Assign(assign) => {
+      let operator = match &assign.operator {
+        Some(operator) => format!("{}=", operator),
+        None => "=".to_string(),
+      };
+      let target = left_operand(&assign.target, &BinaryOperator::Assign, depth);
+      format!("{} {} {}", target, operator, operand(&assign.value, BinaryOperator::Assign, depth))
+    },
+    Expression::Update(update) => {
+      if update.prefix {
+        format!("{}{}", update.operator, operand(&update.target, BinaryOperator::Prefix, depth))
+      } else {
+        format!("{}{}", left_operand(&update.target, &BinaryOperator::Postfix, depth), update.operator)
+      }
+    },
+    Expression::Ternary(ternary) => format!(
+      "{} ? {} : {}",
+      left_operand(&ternary.condition, &BinaryOperator::Ternary, depth),
+      expression(&ternary.consequence, depth),
+      operand(&ternary.alternative, BinaryOperator::Ternary, depth),
+    ),
+    Expression::Range(range) => {
+      let operator = if range.inclusive { "..=" } else { ".." };
+      let start = left_operand(&range.start, &BinaryOperator::Range, depth);
+      format!("{}{}{}", start, operator, operand(&range.end, BinaryOperator::Range, depth))
+    },
+    Expression::Spread(spread) => format!("...{}", expression(&spread.value, depth)),
+  }
+}
+
+fn literal(lit: &Literal, depth: usize) -> String {
+  match lit {
+    Literal::Integer(int) => int.value.to_string(),
+    // 整数になる値も小数点か指数を残して浮動小数点数のまま読ませる。大きい値や小さい値は指数で書く
+    Literal::Float(float) => format!("{:?}", float.value),
+    Literal::Boolean(val) => val.value.to_string(),
+    Literal::Null(_) => "null".to_string(),
+    Literal::Str(s) => quote(&s.value),
+    Literal::Array(arr) => format!("[{}]", list(&arr.elements, depth)),
+    Literal::Hash(hash) => {
+      let pairs: Vec<String> = hash.pairs.iter()
+        .map(|(key, val)| format!("{}: {}", expression(key, depth), expression(val, depth)))
+        .collect();
+      format!("{{{}}}", pairs.join(", "))
+    },
+    Literal::Struct(lit) => {
+      let fields: Vec<String> = lit.fields.iter()
+        .map(|(key, val)| format!("{}: {}", key, expression(val, depth)))
+        .collect();
+      format!("{} {{{}}}", lit.name, fields.join(", "))
+    },
+    Literal::Func(func) => format!("fn({}) {}", params(func, depth), block(&func.body, depth, true)),
+    Literal::Macro(mac) => {
+      let params: Vec<String> = mac.args.iter().map(|arg| arg.to_string()).collect();
+      format!("macro({}) {}", params.join(", "), block(&mac.body, depth, true))
+    },
+  }
+}
+
+fn params(func: &lit::Func, depth: usize) -> String {
+  let params: Vec<String> = func.args.iter().zip(&func.defaults)
+    .map(|(arg, default)| match default {
+      Some(default) => format!("{} = {}", arg, expression(default, depth)),
+      None => arg.to_string(),
+    })
+    .collect();
+  params.join(", ")
+}
+
+fn pattern(pattern: &Pattern, depth: usize) -> String {
+  match pattern {
+    Pattern::Ident(ident) => ident.value.to_string(),
+    Pattern::Literal(lit) => literal(lit, depth),
+    Pattern::Array(arr) => {
+      let mut elements: Vec<String> = arr.elements.iter().map(|elm| self::pattern(elm, depth)).collect();
+      if let Some(rest) = &arr.rest {
+        elements.push(format!("...{}", rest));
+      }
+      format!("[{}]", elements.join(", "))
+    },
+    Pattern::Hash(hash) => format!("{{{}}}", field_patterns(&hash.pairs, depth)),
+    Pattern::Struct(pat) => format!("{} {{{}}}", pat.name, field_patterns(&pat.fields, depth)),
+  }
+}
+
+fn field_patterns(fields: &[(Identifier, Pattern)], depth: usize) -> String {
+  let fields: Vec<String> = fields.iter()
+    .map(|(key, val)| match val {
+      Pattern::Ident(ident) if ident.value == key.value => key.to_string(),
+      val => format!("{}: {}", key, pattern(val, depth)),
+    })
+    .collect();
+  fields.join(", ")
+}
+
+fn list(exprs: &[Expression], depth: usize) -> String {
+  exprs.iter().map(|expr| expression(expr, depth)).collect::<Vec<String>>().join(", ")
+}
+
+/// Writes `s` as a string literal which the lexer reads back to `s`.
+fn quote(s: &str) -> String {
+  let mut out = String::from("\"");
+  for ch in s.chars() {
+    match ch {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\t' => out.push_str("\\t"),
+      ch if ch.is_control() => out.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+      ch => out.push(ch),
+    }
+  }
+  out.push('"');
+  out
+}
+
+/// Formats `expr` which is parsed as the right operand of an operator of the precedence `context`,
+/// with parentheses if it would not be parsed as a whole there.
+fn operand(expr: &Expression, context: BinaryOperator, depth: usize) -> String {
+  let s = expression(expr, depth);
+  if needs_parens(expr, &context) {
+    format!("({})", s)
+  } else {
+    s
+  }
+}
+
+/// Formats `expr` which comes before an operator of the precedence `operator`,
+/// with parentheses if its right end would take `operator`, e.g. `(a + b) * c`.
+fn left_operand(expr: &Expression, operator: &BinaryOperator, depth: usize) -> String {
+  let s = expression(expr, depth);
+  match trailing_precedence(expr) {
+    Some(trailing) if trailing < *operator || (trailing == *operator && operator.is_right_assoc()) => format!("({})", s),
+    _ => s,
+  }
+}
+
+fn needs_parens(expr: &Expression, context: &BinaryOperator) -> bool {
+  let precedence = match expr {
+    Expression::Assign(_) => BinaryOperator::Assign,
+    Expression::Ternary(_) => BinaryOperator::Ternary,
+    Expression::Infix(inf) => inf.operator.precedence(),
+    Expression::Range(_) => BinaryOperator::Range,
+    // 前置の演算子は優先順位によらず読まれる
+    _ => return false,
+  };
+  !(*context < precedence || (*context == precedence && precedence.is_right_assoc()))
+}
+
+/// Returns the lowest precedence of the operators which the right end of `expr` would still take,
+/// or `None` when it ends with a closed form such as a literal, a call or a postfix operator.
+fn trailing_precedence(expr: &Expression) -> Option<BinaryOperator> {
+  let (precedence, right) = match expr {
+    Expression::Assign(assign) => (BinaryOperator::Assign, &assign.value),
+    Expression::Ternary(ternary) => (BinaryOperator::Ternary, &ternary.alternative),
+    Expression::Infix(inf) => (inf.operator.precedence(), &inf.right),
+    Expression::Range(range) => (BinaryOperator::Range, &range.end),
+    Expression::Prefix(pre) => (BinaryOperator::Prefix, &pre.right),
+    Expression::Update(update) if update.prefix => (BinaryOperator::Prefix, &update.target),
+    _ => return None,
+  };
+  if needs_parens(right, &precedence) {
+    return Some(precedence);
+  }
+  match trailing_precedence(right) {
+    Some(trailing) if trailing < precedence => Some(trailing),
+    _ => Some(precedence),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(input: &str) -> Program {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors(), "input={}", input);
+    program
+  }
+
+  #[test]
+  fn test_format() {
+    let input = "let add=fn(x,y=1){x+y};let [a,b]=[1,2.0];let {c}={\"c\":\"q\\\"\\n\"}
+
+
+// comment
+if(a<b){puts(a)}else{let d=a;d} // trailing
+-[1][0:]
+for(i in 0..=3){if(i%2==0){continue_at(i)}}
+let f=fn(){try{assert(false)}catch(e){e[\"kind\"]}}
+while(a>0){a-=1;}
+";
+    assert_eq!(format(&parse(input)), "let add = fn(x, y = 1) { x + y };
+let [a, b] = [1, 2.0];
+let {c} = {\"c\": \"q\\\"\\n\"};
+
+// comment
+if (a < b) { puts(a) } else {
+  let d = a;
+  d
+}; // trailing
+-[1][0:];
+for (i in 0..=3) {
+  if (i % 2 == 0) { continue_at(i) }
+}
+let f = fn() { try { assert(false) } catch (e) { e[\"kind\"] } };
+while (a > 0) {
+  a -= 1;
+}
+");
+  }
+
+  #[test]
+  fn test_format_parentheses() {
+    let tests = vec![
+      ("(1 + 2) * 3", "(1 + 2) * 3;\n"),
+      ("1 + (2 * 3)", "1 + 2 * 3;\n"),
+      ("a - (b - c)", "a - (b - c);\n"),
+      ("(a - b) - c", "a - b - c;\n"),
+      ("(a ** b) ** c", "(a ** b) ** c;\n"),
+      ("a ** (b ** c)", "a ** b ** c;\n"),
+      ("-(a ** b)", "-a ** b;\n"),
+      ("(-a) ** b", "(-a) ** b;\n"),
+      ("2 ** -1", "2 ** -1;\n"),
+      ("-(-a)", "-(-a);\n"),
+      ("-(--a)", "-(--a);\n"),
+      ("(++a) ** 2", "(++a) ** 2;\n"),
+      ("a * -b + c", "a * -b + c;\n"),
+      ("(a ? b : c) ? d : e", "(a ? b : c) ? d : e;\n"),
+      ("a ? b : (c ? d : e)", "a ? b : c ? d : e;\n"),
+      ("a = (b = c)", "a = b = c;\n"),
+      ("x = (a ?? b) || c", "x = (a ?? b) || c;\n"),
+      ("(a + b)[0]", "(a + b)[0];\n"),
+      ("(fn(x) { x })(1)", "fn(x) { x }(1);\n"),
+      ("(0..3)[1]", "(0..3)[1];\n"),
+      ("(a + 1)..(b + 1)", "a + 1..b + 1;\n"),
+      ("(a | b) & c", "(a | b) & c;\n"),
+      ("a | (b & c)", "a | b & c;\n"),
+      ("(a << 1) + 1", "(a << 1) + 1;\n"),
+      ("~(a ^ b)", "~(a ^ b);\n"),
+      ("(a & 1) == 0", "a & 1 == 0;\n"),
+      ("(-a).len()", "(-a).len();\n"),
+      ("-(a.len())", "-a.len();\n"),
+      ("(a + b).c(d + e)", "(a + b).c(d + e);\n"),
+      ("(-a).b", "(-a).b;\n"),
+      ("(a.b)(1)", "(a.b)(1);\n"),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(format(&parse(input)), expected, "input={}", input);
+    }
+  }
+
+  #[test]
+  fn test_format_semicolons() {
+    let tests = vec![
+      ("if (a) { 1 }; -1", "if (a) { 1 };\n-1;\n"),
+      ("if (a) { 1 } let b = 2", "if (a) { 1 }\nlet b = 2;\n"),
+      ("let f = fn() { 1; 2; }", "let f = fn() {\n  1;\n  2\n};\n"),
+      ("while (a) { 1; 2 }", "while (a) {\n  1;\n  2;\n}\n"),
+      ("fn() { return 1 }", "fn() {\n  return 1;\n};\n"),
+      ("match(x){[a,...b]=>a,{k:\"v\"}=>1,}", "match (x) {\n  [a, ...b] => a,\n  {k: \"v\"} => 1,\n};\n"),
+      ("let f = fn() { match (x) {} }", "let f = fn() { match (x) {} };\n"),
+      ("fn add(x,y){x+y} add(1, 2)", "fn add(x, y) { x + y }\nadd(1, 2);\n"),
+      ("fn f() { 1; 2; } -1", "fn f() {\n  1;\n  2\n}\n-1;\n"),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(format(&parse(input)), expected, "input={}", input);
+    }
+  }
+
+  // 整形した結果は同じ構文木に読めて、もう一度整形しても変わらない
+  #[test]
+  fn test_format_round_trip() {
+    let input = "
+let fib = fn(n) { if (n < 2) { return n; }; fib(n - 1) + fib(n - 2) };
+fn inc(x = 0, by = fib(1)) { let y = x + by; y }
+let xs = [1, -2.5, \"\\u{1}\\\\\", null, true, ...[3]];
+let h = {\"a\": fn(x) { let y = x * 2; y ** 2 }, true: [1, 2][:1]};
+xs[0] += h[\"a\"](3) ?? 0; xs[1]--; ++xs[0];
+for (x in xs) { if (x == null || !x) { puts(\"skip\") } else { puts(x > 0 ? x : -x) } }
+try { panic(\"p\") } catch (e) { e[\"message\"] + 1..2 }
+puts((-xs).len(), xs.map(fn(x) { x * 2 }).join(\",\"), (1 + 2).len());
+h.b = h.a; h.b.c += 1; (h.a)(h.b.c);
+let [first_, ...others] = match (h) { {a: [f, \"\\n\"]} => f, {} => -1.0, n => [n] };
+struct Point {x, y} struct Empty {}
+let Point {x, y: py} = Point {x: 1, y: Empty {}}; p.x = match (p) { Point {x: 0, y} => y, _ => x };";
+    let program = parse(input);
+    let formatted = format(&program);
+    let reparsed = parse(&formatted);
+
+    assert_eq!(format!("{}", reparsed), format!("{}", program));
+    assert_eq!(format(&reparsed), formatted);
+
+    // 大きい小数や小さい小数も、同じ値に読み戻せる
+    let program = parse("[1e300, 1.7976931348623157e308, 5e-324, 2.5e-10]");
+    assert_eq!(format!("{}", parse(&format(&program))), format!("{}", program));
+    assert_eq!(format_source("[1e300, 5e-324, 2.0, 1.5]").unwrap(), "[1e300, 5e-324, 2.0, 1.5];\n");
+  }
+
+  #[test]
+  fn test_format_source() {
+    assert_eq!(format_source("let a=1").unwrap(), "let a = 1;\n");
+    assert_eq!(format_source("").unwrap(), "");
+
+    match format_source("let a = /* one */ 1;") {
+      Err(FormatError::BlockComment(span)) => assert_eq!(format!("{}", span), "1:9"),
+      result => panic!("expected a block comment error: {:?}", result),
+    }
+    match format_source("let = 1;") {
+      Err(FormatError::Parse(errors)) => assert!(!errors.is_empty()),
+      result => panic!("expected parse errors: {:?}", result),
+    }
+  }
+}
@@ -0,0 +1,8 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use interpreter::lexer;
+
+fuzz_target!(|data: &[u8]| {
+  let _ = lexer::lex_bytes(data);
+});
@@ -0,0 +1,12 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use interpreter::parser;
+
+fuzz_target!(|data: &[u8]| {
+  // 構文木を書き出す処理も、解析できたプログラムなら何でも扱えるはず
+  if let Ok(program) = parser::parse_bytes(data) {
+    let _ = program.to_tree_string();
+    let _ = program.format();
+  }
+});
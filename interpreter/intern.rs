@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+/// An interned identifier. Equal strings are interned to the same `Name`,
+/// so comparing and hashing names does not look at their characters.
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Name(u32);
+
+// 名前は同じスレッドのプログラムで共有されるので、文字列は解放しない
+#[derive(Default)]
+struct Interner {
+  names: HashMap<&'static str, Name>,
+  strings: Vec<&'static str>,
+}
+
+thread_local! {
+  static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+impl Name {
+  pub fn new(s: &str) -> Name {
+    INTERNER.with(|interner| {
+      let mut interner = interner.borrow_mut();
+      if let Some(name) = interner.names.get(s) {
+        return *name;
+      }
+      let s: &'static str = Box::leak(s.to_string().into_boxed_str());
+      let name = Name(interner.strings.len() as u32);
+      interner.strings.push(s);
+      interner.names.insert(s, name);
+      name
+    })
+  }
+
+  pub fn as_str(self) -> &'static str {
+    INTERNER.with(|interner| interner.borrow().strings[self.0 as usize])
+  }
+}
+
+impl fmt::Display for Name {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+impl fmt::Debug for Name {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Name({:?})", self.as_str())
+  }
+}
+
+// 名前は文字列として書き出し、読み込むときに intern し直す
+#[cfg(feature = "serde")]
+impl serde::Serialize for Name {
+  fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(self.as_str())
+  }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Name {
+  fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Name, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    Ok(Name::new(&s))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_intern() {
+    let foo = Name::new("foo");
+    let owned = String::from("foo");
+    assert_eq!(foo, Name::new(&owned));
+    assert_ne!(foo, Name::new("bar"));
+    assert_eq!(foo.as_str(), "foo");
+    assert_eq!(format!("{} {:?}", foo, foo), "foo Name(\"foo\")");
+  }
+}
@@ -1,41 +1,166 @@
+use std::fmt;
+use std::io::{self, BufRead};
+use std::iter::FusedIterator;
+
+use unicode_xid::UnicodeXID;
+
 use crate::token;
+use crate::intern::Name;
+use crate::span::{Position, Span};
+
+/// The input of a lexer. When it is read from a reader, lines are read as the lexer reaches them,
+/// and the bytes before the token being read are dropped, so a long input is not kept in memory as a whole.
+struct Source {
+  buf: Vec<u8>,
+  /// The offset of `buf[0]` in the whole input.
+  base: usize,
+  reader: Option<Box<dyn BufRead>>,
+  error: Option<String>,
+}
+
+impl Source {
+  /// Returns the byte at `offset`, or 0 after the end of the input.
+  fn byte(&mut self, offset: usize) -> u8 {
+    while offset >= self.end() {
+      if !self.fill() {
+        return 0;
+      }
+    }
+    self.buf[offset - self.base]
+  }
+
+  // 次の行を読み足す。入力の終わりか読み込みに失敗したときは false を返す
+  fn fill(&mut self) -> bool {
+    let reader = match &mut self.reader {
+      Some(reader) => reader,
+      None => return false,
+    };
+    match reader.read_until(b'\n', &mut self.buf) {
+      Ok(0) => {
+        self.reader = None;
+        false
+      },
+      Ok(_) => true,
+      Err(err) => {
+        self.error = Some(format!("could not read the input: {}", err));
+        self.reader = None;
+        false
+      },
+    }
+  }
+
+  /// Returns the offset after the last byte read so far.
+  fn end(&self) -> usize {
+    self.base + self.buf.len()
+  }
+
+  fn slice(&self, start: usize, end: usize) -> &[u8] {
+    &self.buf[start - self.base..end - self.base]
+  }
+
+  /// Drops the bytes before `offset`, which are not read again.
+  fn discard(&mut self, offset: usize) {
+    let n = offset.min(self.end()) - self.base;
+    // 詰め直す量が読んだ量に比例するように、半分以上読み終えてから捨てる
+    if n > 0 && n * 2 >= self.buf.len() {
+      self.buf.drain(..n);
+      self.base += n;
+    }
+  }
+}
+
+impl fmt::Debug for Source {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Source")
+      .field("buf", &String::from_utf8_lossy(&self.buf))
+      .field("base", &self.base)
+      .field("streaming", &self.reader.is_some())
+      .finish()
+  }
+}
 
 #[derive(Debug)]
 pub struct Lexer {
-  input: String,
+  input: Source,
   position: usize,
   read_position: usize,
   ch: u8,
+  line: usize,
+  column: usize,
+  block_comments: Vec<Span>,
 }
 
 impl Lexer {
   pub fn new(input: String) -> Lexer {
+    Lexer::with_source(Source { buf: input.into_bytes(), base: 0, reader: None, error: None })
+  }
+
+  /// Creates a lexer which reads the input from `reader` while tokens are read.
+  /// An error while reading ends the input, and is returned as an `ILLEGAL` token.
+  pub fn from_reader<R: BufRead + 'static>(reader: R) -> Lexer {
+    Lexer::with_source(Source { buf: vec![], base: 0, reader: Some(Box::new(reader)), error: None })
+  }
+
+  fn with_source(input: Source) -> Lexer {
     let mut l = Lexer {
       input,
       position: 0,
       read_position: 0,
       ch: 0,
+      line: 1,
+      column: 1,
+      block_comments: vec![],
     };
-    &l.read_char();
+    l.read_char();
     l
   }
 
   fn read_char(&mut self) {
-    if self.read_position >= self.input.len() {
-      self.ch = 0;
-    } else {
-      self.ch = self.input.as_bytes()[self.read_position];
+    // 桁は文字の数で数えるので、UTF-8 の 2 バイト目以降では進めない
+    match self.ch {
+      b'\n' => {
+        self.line += 1;
+        self.column = 1;
+      },
+      0 => (),
+      ch if ch & 0xc0 != 0x80 => self.column += 1,
+      _ => (),
     }
+    self.ch = self.input.byte(self.read_position);
     self.position = self.read_position;
     self.read_position += 1;
   }
 
-  fn peek_char(&self) -> u8 {
-    if self.read_position >= self.input.len() {
-      0
-    } else {
-      self.input.as_bytes()[self.read_position]
+  fn peek_char(&mut self) -> u8 {
+    self.peek_nth_char(0)
+  }
+
+  /// Returns the character `n` characters after the next one without consuming anything.
+  fn peek_nth_char(&mut self, n: usize) -> u8 {
+    self.input.byte(self.read_position + n)
+  }
+
+  /// Returns the character which starts at the current byte and its length in bytes,
+  /// or `None` if the bytes are not valid UTF-8.
+  fn current_char(&mut self) -> Option<(char, usize)> {
+    let len = match self.ch {
+      0x00..=0x7f => 1,
+      0xc0..=0xdf => 2,
+      0xe0..=0xef => 3,
+      0xf0..=0xf7 => 4,
+      _ => return None,
+    };
+    let mut bytes = [self.ch, 0, 0, 0];
+    for (i, byte) in bytes.iter_mut().enumerate().take(len).skip(1) {
+      *byte = self.peek_nth_char(i - 1);
     }
+    let ch = std::str::from_utf8(&bytes[..len]).ok()?.chars().next()?;
+    Some((ch, len))
+  }
+
+  /// Returns the source from `start` up to the current character, which must be valid UTF-8.
+  fn str_from(&self, start: usize) -> &str {
+    std::str::from_utf8(self.input.slice(start, self.position)).unwrap()
   }
 
   fn skip_whitespace(&mut self) {
@@ -44,17 +169,95 @@ impl Lexer {
     }
   }
 
+  fn current_position(&self) -> Position {
+    let offset = self.position.min(self.input.end());
+    Position::new(offset, self.line, self.column)
+  }
+
+  /// Returns the spans of the `/* */` comments skipped so far. They are not kept as tokens.
+  pub fn block_comments(&self) -> &[Span] {
+    &self.block_comments
+  }
+
   pub fn next_token(&mut self) -> token::Token {
-    self.skip_whitespace();
+    self.next_spanned_token().0
+  }
+
+  /// Returns the next token together with the span of source code it was read from.
+  pub fn next_spanned_token(&mut self) -> (token::Token, Span) {
+    self.input.discard(self.position);
+    loop {
+      self.skip_whitespace();
+      if self.ch != b'/' || self.peek_char() != b'*' {
+        break;
+      }
+      let start = self.current_position();
+      if !self.skip_block_comment() {
+        let tok = token::Token::ILLEGAL("unterminated block comment".into());
+        return (tok, Span::new(start, self.current_position()));
+      }
+      self.block_comments.push(Span::new(start, self.current_position()));
+    }
+
+    let start = self.current_position();
+    if self.ch == 0 {
+      if let Some(msg) = self.input.error.take() {
+        return (token::Token::ILLEGAL(msg), Span::new(start, start));
+      }
+    }
+    let tok = self.read_token();
+    (tok, Span::new(start, self.current_position()))
+  }
 
+  // `/* ... */` を読み飛ばす。入れ子にすることができ、閉じられていない場合は false を返す
+  fn skip_block_comment(&mut self) -> bool {
+    let mut depth = 0;
+    loop {
+      match (self.ch, self.peek_char()) {
+        (b'/', b'*') => {
+          depth += 1;
+          self.read_char();
+        },
+        (b'*', b'/') => {
+          depth -= 1;
+          self.read_char();
+          if depth == 0 {
+            self.read_char();
+            return true;
+          }
+        },
+        (0, _) => return false,
+        _ => (),
+      }
+      self.read_char();
+    }
+  }
+
+  // 複数バイトの文字は最後のバイトまで読み進めるので、次のトークンは文字の区切りから始まる
+  fn illegal_char(&mut self) -> token::Token {
+    match self.current_char() {
+      Some((ch, len)) => {
+        for _ in 1..len {
+          self.read_char();
+        }
+        token::Token::ILLEGAL(format!("unexpected character {:?}", ch))
+      },
+      None => token::Token::ILLEGAL(format!("invalid UTF-8 byte 0x{:02x}", self.ch)),
+    }
+  }
+
+  fn read_token(&mut self) -> token::Token {
     let tok = match self.ch {
-      b'=' => {
-        if let b'=' = self.peek_char() {
+      b'=' => match self.peek_char() {
+        b'=' => {
           self.read_char();
           token::Token::EQ
-        } else {
-          token::Token::ASSIGN
-        }
+        },
+        b'>' => {
+          self.read_char();
+          token::Token::ARROW
+        },
+        _ => token::Token::ASSIGN,
       },
       b'!' => {
         if let b'=' = self.peek_char() {
@@ -64,6 +267,50 @@ impl Lexer {
           token::Token::BANG
         }
       },
+      b'&' => {
+        if let b'&' = self.peek_char() {
+          self.read_char();
+          token::Token::AND
+        } else {
+          token::Token::AMPERSAND
+        }
+      },
+      b'|' => {
+        if let b'|' = self.peek_char() {
+          self.read_char();
+          token::Token::OR
+        } else {
+          token::Token::PIPE
+        }
+      },
+      b'^' => token::Token::CARET,
+      b'~' => token::Token::TILDE,
+      b'?' => {
+        if let b'?' = self.peek_char() {
+          self.read_char();
+          token::Token::COALESCE
+        } else {
+          token::Token::QUESTION
+        }
+      },
+      b'.' => {
+        if let b'.' = self.peek_char() {
+          self.read_char();
+          match self.peek_char() {
+            b'=' => {
+              self.read_char();
+              token::Token::DotDotEq
+            },
+            b'.' => {
+              self.read_char();
+              token::Token::ELLIPSIS
+            },
+            _ => token::Token::DOTDOT,
+          }
+        } else {
+          token::Token::DOT
+        }
+      },
       b':' => token::Token::COLON,
       b';' => token::Token::SEMICOLON,
       b'(' => token::Token::LPAREN,
@@ -73,17 +320,79 @@ impl Lexer {
       b'[' => token::Token::LBRACKET,
       b']' => token::Token::RBRACKET,
       b',' => token::Token::COMMA,
-      b'+' => token::Token::PLUS,
-      b'-' => token::Token::MINUS,
-      b'*' => token::Token::ASTERISK,
-      b'/' => self.read_slash(),
-      b'<' => token::Token::LT,
-      b'>' => token::Token::GT,
+      b'+' => match self.peek_char() {
+        b'+' => {
+          self.read_char();
+          token::Token::INCREMENT
+        },
+        b'=' => {
+          self.read_char();
+          token::Token::PlusAssign
+        },
+        _ => token::Token::PLUS,
+      },
+      b'-' => match self.peek_char() {
+        b'-' => {
+          self.read_char();
+          token::Token::DECREMENT
+        },
+        b'=' => {
+          self.read_char();
+          token::Token::MinusAssign
+        },
+        _ => token::Token::MINUS,
+      },
+      b'*' => match self.peek_char() {
+        b'*' => {
+          self.read_char();
+          token::Token::POWER
+        },
+        b'=' => {
+          self.read_char();
+          token::Token::AsteriskAssign
+        },
+        _ => token::Token::ASTERISK,
+      },
+      b'%' => token::Token::PERCENT,
+      b'/' => match self.peek_char() {
+        b'/' => return self.read_comment(),
+        b'=' => {
+          self.read_char();
+          token::Token::SlashAssign
+        },
+        _ => token::Token::SLASH,
+      },
+      b'<' => match self.peek_char() {
+        b'=' => {
+          self.read_char();
+          token::Token::LtEq
+        },
+        b'<' => {
+          self.read_char();
+          token::Token::ShiftLeft
+        },
+        _ => token::Token::LT,
+      },
+      b'>' => match self.peek_char() {
+        b'=' => {
+          self.read_char();
+          token::Token::GtEq
+        },
+        b'>' => {
+          self.read_char();
+          token::Token::ShiftRight
+        },
+        _ => token::Token::GT,
+      },
       b'"' => self.read_string(),
-      b'0'..=b'9' => return self.read_int(),
+      b'0'..=b'9' => return self.read_number(),
       b'a'..=b'z' | b'A'..=b'Z' | b'_' => return self.read_ident(),
+      0x80..=0xff => match self.current_char() {
+        Some((ch, _)) if ch.is_xid_start() => return self.read_ident(),
+        _ => self.illegal_char(),
+      },
       0 => token::Token::EOF,
-      _ => token::Token::ILLEGAL,
+      _ => self.illegal_char(),
     };
     self.read_char();
     tok
@@ -91,57 +400,162 @@ impl Lexer {
 
   fn read_ident(&mut self) -> token::Token {
     let position = self.position;
-    while let b'a'..=b'z' | b'A'..=b'Z' | b'_' = self.ch {
-      self.read_char();
-    };
-    
-    let ident = &self.input[position..self.position];
+    // 識別子は Unicode の XID_Start か `_` で始まり、XID_Continue の文字が続く
+    while let Some((ch, len)) = self.current_char() {
+      if ch == '\0' || !ch.is_xid_continue() {
+        break;
+      }
+      for _ in 0..len {
+        self.read_char();
+      }
+    }
+
+    let ident = self.str_from(position);
 
     match ident {
       "let" => token::Token::LET,
+      "const" => token::Token::CONST,
       "fn" => token::Token::FUNCTION,
       "true" => token::Token::TRUE,
       "false" => token::Token::FALSE,
+      "null" => token::Token::NULL,
       "if" => token::Token::IF,
       "else" => token::Token::ELSE,
+      "while" => token::Token::WHILE,
+      "for" => token::Token::FOR,
+      "in" => token::Token::IN,
       "return" => token::Token::RETURN,
-      _ => token::Token::IDENT(ident.to_string()),
+      "try" => token::Token::TRY,
+      "catch" => token::Token::CATCH,
+      "match" => token::Token::MATCH,
+      "macro" => token::Token::MACRO,
+      "struct" => token::Token::STRUCT,
+      _ => token::Token::IDENT(Name::new(ident)),
     }
   }
 
-  fn read_int(&mut self) -> token::Token {
+  fn read_number(&mut self) -> token::Token {
     let position = self.position;
-    while let b'0'..=b'9' = self.ch {
+    let mut is_float = false;
+    self.read_digits();
+
+    // `.` の後に数字が続く場合のみ小数として扱う
+    if self.ch == b'.' && self.peek_char().is_ascii_digit() {
+      is_float = true;
       self.read_char();
+      self.read_digits();
     }
 
-    let int = &self.input[position..self.position];
+    if let b'e' | b'E' = self.ch {
+      let sign = self.peek_char();
+      let digit = if let b'+' | b'-' = sign { self.peek_nth_char(1) } else { sign };
+      if digit.is_ascii_digit() {
+        is_float = true;
+        self.read_char();
+        if let b'+' | b'-' = self.ch {
+          self.read_char();
+        }
+        self.read_digits();
+      }
+    }
 
-    token::Token::INT(int.parse().unwrap())
+    let num = self.str_from(position);
+
+    if is_float {
+      // 大きすぎる小数は inf になって書き戻せないので、整数と同じように受け付けない
+      match num.parse::<f64>() {
+        Ok(float) if float.is_finite() => token::Token::FLOAT(float),
+        _ => token::Token::ILLEGAL(format!("float literal is too large: {}", num)),
+      }
+    } else {
+      match num.parse() {
+        Ok(int) => token::Token::INT(int),
+        Err(_) => token::Token::ILLEGAL(format!("integer literal is too large: {}", num)),
+      }
+    }
+  }
+
+  fn read_digits(&mut self) {
+    while let b'0'..=b'9' = self.ch {
+      self.read_char();
+    }
   }
 
   fn read_string(&mut self) -> token::Token {
-    let position = self.position + 1;
+    let mut value = Vec::new();
+    let mut error = None;
     loop {
       self.read_char();
-      if let b'"' = self.ch {
-        break;
-      }
-      if let 0 = self.ch {
-        break;
+      match self.ch {
+        b'"' => break,
+        0 => return token::Token::ILLEGAL("unterminated string literal".into()),
+        b'\\' => {
+          self.read_char();
+          match self.read_escape() {
+            Ok(ch) => value.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+            // 最初のエラーを報告し、文字列の終わりまでは読み進める
+            Err(msg) => {
+              error.get_or_insert(msg);
+            },
+          }
+        },
+        ch => value.push(ch),
       }
     }
-    let str_lit = &self.input[position..self.position];
-    token::Token::STRING(str_lit.to_string())
+
+    match error {
+      Some(msg) => token::Token::ILLEGAL(msg),
+      // 読み込んだ入力は UTF-8 とは限らない
+      None => token::Token::STRING(String::from_utf8_lossy(&value).into_owned()),
+    }
   }
 
-  fn read_slash(&mut self) -> token::Token {
-    if let b'/' = self.peek_char() {
-      self.read_char();
+  /// Reads an escape sequence after `\`, leaving the lexer at its last character.
+  fn read_escape(&mut self) -> Result<char, String> {
+    match self.ch {
+      b'n' => Ok('\n'),
+      b't' => Ok('\t'),
+      b'"' => Ok('"'),
+      b'\\' => Ok('\\'),
+      b'u' => self.read_unicode_escape(),
+      0 => Err("unterminated string literal".into()),
+      _ => match self.current_char() {
+        Some((ch, _)) => Err(format!("invalid escape sequence '\\{}'", ch)),
+        None => Err(format!("invalid escape sequence '\\' followed by the byte 0x{:02x}", self.ch)),
+      },
+    }
+  }
+
+  fn read_unicode_escape(&mut self) -> Result<char, String> {
+    if self.peek_char() != b'{' {
+      return Err("invalid unicode escape: expected '{' after '\\u'".into());
+    }
+    self.read_char();
+
+    let start = self.read_position;
+    while self.peek_char().is_ascii_hexdigit() {
       self.read_char();
-    } else {
-      return token::Token::SLASH;
     }
+    let digits = String::from_utf8_lossy(self.input.slice(start, self.read_position)).into_owned();
+
+    if self.peek_char() != b'}' {
+      return Err(format!("invalid unicode escape '\\u{{{}': expected '}}'", digits));
+    }
+    self.read_char();
+
+    if digits.is_empty() || digits.len() > 6 {
+      return Err(format!("invalid unicode escape '\\u{{{}}}': expected 1 to 6 hex digits", digits));
+    }
+    match std::char::from_u32(u32::from_str_radix(&digits, 16).unwrap()) {
+      Some(ch) => Ok(ch),
+      None => Err(format!("invalid unicode code point '\\u{{{}}}'", digits)),
+    }
+  }
+
+  fn read_comment(&mut self) -> token::Token {
+    // skip `//`
+    self.read_char();
+    self.read_char();
 
     // skip whitespace
     if let b' ' | b'\t' = self.ch {
@@ -149,20 +563,95 @@ impl Lexer {
     }
 
     let position = self.position;
-    loop {
+    while self.ch != b'\n' && self.ch != 0 {
       self.read_char();
-      if let b'\n' = self.ch {
-        break;
+    }
+    let comment = String::from_utf8_lossy(self.input.slice(position, self.position)).into_owned();
+    token::Token::COMMENT(comment)
+  }
+}
+
+/// Reads the tokens up to, but not including, `EOF`, so `Lexer` can be used with iterator adapters
+/// such as `peekable` or `take_while`.
+impl Iterator for Lexer {
+  type Item = token::Token;
+
+  fn next(&mut self) -> Option<token::Token> {
+    self.spanned().next().map(|(tok, _)| tok)
+  }
+}
+
+impl FusedIterator for Lexer {}
+
+impl Lexer {
+  /// Returns an iterator over the following tokens together with their spans, which also ends before `EOF`.
+  /// The lexer is borrowed, so it can still be read from after the iterator is dropped.
+  pub fn spanned(&mut self) -> Spanned<'_> {
+    Spanned { l: self }
+  }
+}
+
+/// An iterator over the tokens of a `Lexer` and their spans. See `Lexer::spanned`.
+#[derive(Debug)]
+pub struct Spanned<'a> {
+  l: &'a mut Lexer,
+}
+
+impl<'a> Iterator for Spanned<'a> {
+  type Item = (token::Token, Span);
+
+  fn next(&mut self) -> Option<(token::Token, Span)> {
+    match self.l.next_spanned_token() {
+      (token::Token::EOF, _) => None,
+      item => Some(item),
+    }
+  }
+}
+
+impl<'a> FusedIterator for Spanned<'a> {}
+
+impl Lexer {
+  /// Reads the rest of the tokens, up to and including `EOF`, and writes each with its span on a line of `out`,
+  /// e.g. `1:5-1:8 IDENT(add)`. Each line is written as soon as its token is read.
+  /// Strings and comments are quoted with their escape sequences, so a token never takes more than one line.
+  pub fn write_tokens(&mut self, out: &mut dyn io::Write) -> io::Result<()> {
+    loop {
+      let (tok, span) = self.next_spanned_token();
+      match &tok {
+        token::Token::STRING(s) => writeln!(out, "{}-{} STRING({:?})", span.start, span.end, s)?,
+        token::Token::COMMENT(s) => writeln!(out, "{}-{} COMMENT({:?})", span.start, span.end, s)?,
+        tok => writeln!(out, "{}-{} {}", span.start, span.end, tok)?,
       }
-      if let 0 = self.ch {
-        break;
+      if tok == token::Token::EOF {
+        return Ok(());
       }
     }
-    let comment = &self.input[position..self.position];
-    token::Token::COMMENT(comment.into())
   }
 }
 
+/// Lexes `input` to the end and formats the tokens as `Lexer::write_tokens` does.
+pub fn to_token_string(input: &str) -> String {
+  let mut out = vec![];
+  Lexer::new(input.to_string()).write_tokens(&mut out).unwrap();
+  String::from_utf8(out).unwrap()
+}
+
+/// Lexes `input` and classifies its tokens for syntax highlighting, in the order they appear.
+/// Block comments, which the lexer skips, are included as comments.
+pub fn highlight(input: &str) -> Vec<(Span, token::TokenClass)> {
+  let mut l = Lexer::new(input.to_string());
+  let mut classes: Vec<(Span, token::TokenClass)> = l.spanned().map(|(tok, span)| (span, tok.class())).collect();
+  classes.extend(l.block_comments().iter().map(|span| (*span, token::TokenClass::Comment)));
+  classes.sort_by_key(|(span, _)| span.start.offset);
+  classes
+}
+
+/// Lexes any bytes, e.g. the input of a fuzzer, and returns the tokens with their spans up to `EOF`.
+/// Bytes which are not valid UTF-8 become `ILLEGAL` tokens, so this never panics.
+pub fn lex_bytes(input: &[u8]) -> Vec<(token::Token, Span)> {
+  Lexer::from_reader(io::Cursor::new(input.to_vec())).spanned().collect()
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -178,7 +667,7 @@ let add = fn(x, y) {
 };
 
 let result = add(five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -197,40 +686,40 @@ if (5 < 10) {
 
       let tests: Vec<token::Token> = vec![
         token::Token::LET,
-        token::Token::IDENT("five".to_string()),
+        token::Token::IDENT(Name::new("five")),
         token::Token::ASSIGN,
         token::Token::INT(5),
         token::Token::SEMICOLON,
         token::Token::LET,
-        token::Token::IDENT("ten".to_string()),
+        token::Token::IDENT(Name::new("ten")),
         token::Token::ASSIGN,
         token::Token::INT(10),
         token::Token::SEMICOLON,
         token::Token::COMMENT("function".into()),
         token::Token::LET,
-        token::Token::IDENT("add".to_string()),
+        token::Token::IDENT(Name::new("add")),
         token::Token::ASSIGN,
         token::Token::FUNCTION,
         token::Token::LPAREN,
-        token::Token::IDENT("x".to_string()),
+        token::Token::IDENT(Name::new("x")),
         token::Token::COMMA,
-        token::Token::IDENT("y".to_string()),
+        token::Token::IDENT(Name::new("y")),
         token::Token::RPAREN,
         token::Token::LBRACE,
-        token::Token::IDENT("x".to_string()),
+        token::Token::IDENT(Name::new("x")),
         token::Token::PLUS,
-        token::Token::IDENT("y".to_string()),
+        token::Token::IDENT(Name::new("y")),
         token::Token::SEMICOLON,
         token::Token::RBRACE,
         token::Token::SEMICOLON,
         token::Token::LET,
-        token::Token::IDENT("result".to_string()),
+        token::Token::IDENT(Name::new("result")),
         token::Token::ASSIGN,
-        token::Token::IDENT("add".to_string()),
+        token::Token::IDENT(Name::new("add")),
         token::Token::LPAREN,
-        token::Token::IDENT("five".to_string()),
+        token::Token::IDENT(Name::new("five")),
         token::Token::COMMA,
-        token::Token::IDENT("ten".to_string()),
+        token::Token::IDENT(Name::new("ten")),
         token::Token::RPAREN,
         token::Token::SEMICOLON,
         token::Token::BANG,
@@ -294,4 +783,386 @@ if (5 < 10) {
         assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
       }
   }
+
+  #[test]
+  fn get_comparison_tokens() {
+      let input = "a <= b >= c < d > e <=> f << g >> h ^ ~i <<< >>=";
+
+      let tests: Vec<token::Token> = vec![
+        token::Token::IDENT(Name::new("a")),
+        token::Token::LtEq,
+        token::Token::IDENT(Name::new("b")),
+        token::Token::GtEq,
+        token::Token::IDENT(Name::new("c")),
+        token::Token::LT,
+        token::Token::IDENT(Name::new("d")),
+        token::Token::GT,
+        token::Token::IDENT(Name::new("e")),
+        token::Token::LtEq,
+        token::Token::GT,
+        token::Token::IDENT(Name::new("f")),
+        token::Token::ShiftLeft,
+        token::Token::IDENT(Name::new("g")),
+        token::Token::ShiftRight,
+        token::Token::IDENT(Name::new("h")),
+        token::Token::CARET,
+        token::Token::TILDE,
+        token::Token::IDENT(Name::new("i")),
+        token::Token::ShiftLeft,
+        token::Token::LT,
+        token::Token::ShiftRight,
+        token::Token::ASSIGN,
+        token::Token::EOF,
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for tt in tests {
+        let tok = l.next_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+      }
+  }
+
+  #[test]
+  fn get_operator_tokens() {
+      let input = "a && b || !c & d | e % f ** g * h += -= *= /= + = ++ -- - - ?? null ? : match x => ==> macro struct";
+
+      let tests: Vec<token::Token> = vec![
+        token::Token::IDENT(Name::new("a")),
+        token::Token::AND,
+        token::Token::IDENT(Name::new("b")),
+        token::Token::OR,
+        token::Token::BANG,
+        token::Token::IDENT(Name::new("c")),
+        token::Token::AMPERSAND,
+        token::Token::IDENT(Name::new("d")),
+        token::Token::PIPE,
+        token::Token::IDENT(Name::new("e")),
+        token::Token::PERCENT,
+        token::Token::IDENT(Name::new("f")),
+        token::Token::POWER,
+        token::Token::IDENT(Name::new("g")),
+        token::Token::ASTERISK,
+        token::Token::IDENT(Name::new("h")),
+        token::Token::PlusAssign,
+        token::Token::MinusAssign,
+        token::Token::AsteriskAssign,
+        token::Token::SlashAssign,
+        token::Token::PLUS,
+        token::Token::ASSIGN,
+        token::Token::INCREMENT,
+        token::Token::DECREMENT,
+        token::Token::MINUS,
+        token::Token::MINUS,
+        token::Token::COALESCE,
+        token::Token::NULL,
+        token::Token::QUESTION,
+        token::Token::COLON,
+        token::Token::MATCH,
+        token::Token::IDENT(Name::new("x")),
+        token::Token::ARROW,
+        token::Token::EQ,
+        token::Token::GT,
+        token::Token::MACRO,
+        token::Token::STRUCT,
+        token::Token::EOF,
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for tt in tests {
+        let tok = l.next_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+      }
+  }
+
+  #[test]
+  fn get_number_tokens() {
+      let input = "1.5 0.25 3e2 1.5E-3 2e+1 7 1.foo 2e 1..2 3..=4.5 ...xs";
+
+      let tests: Vec<token::Token> = vec![
+        token::Token::FLOAT(1.5),
+        token::Token::FLOAT(0.25),
+        token::Token::FLOAT(300.0),
+        token::Token::FLOAT(0.0015),
+        token::Token::FLOAT(20.0),
+        token::Token::INT(7),
+        token::Token::INT(1),
+        token::Token::DOT,
+        token::Token::IDENT(Name::new("foo")),
+        token::Token::INT(2),
+        token::Token::IDENT(Name::new("e")),
+        token::Token::INT(1),
+        token::Token::DOTDOT,
+        token::Token::INT(2),
+        token::Token::INT(3),
+        token::Token::DotDotEq,
+        token::Token::FLOAT(4.5),
+        token::Token::ELLIPSIS,
+        token::Token::IDENT(Name::new("xs")),
+        token::Token::EOF,
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for tt in tests {
+        let tok = l.next_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+      }
+  }
+
+  #[test]
+  fn get_token_spans() {
+      let input = "let x = 10;
+// comment
+x + \"foo\"";
+
+      // (offset, line, column)
+      type Pos = (usize, usize, usize);
+      let tests: Vec<(token::Token, Pos, Pos)> = vec![
+        (token::Token::LET, (0, 1, 1), (3, 1, 4)),
+        (token::Token::IDENT(Name::new("x")), (4, 1, 5), (5, 1, 6)),
+        (token::Token::ASSIGN, (6, 1, 7), (7, 1, 8)),
+        (token::Token::INT(10), (8, 1, 9), (10, 1, 11)),
+        (token::Token::SEMICOLON, (10, 1, 11), (11, 1, 12)),
+        (token::Token::COMMENT("comment".into()), (12, 2, 1), (22, 2, 11)),
+        (token::Token::IDENT(Name::new("x")), (23, 3, 1), (24, 3, 2)),
+        (token::Token::PLUS, (25, 3, 3), (26, 3, 4)),
+        (token::Token::STRING("foo".to_string()), (27, 3, 5), (32, 3, 10)),
+        (token::Token::EOF, (32, 3, 10), (32, 3, 10)),
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for (tt, (start_offset, start_line, start_column), (end_offset, end_line, end_column)) in tests {
+        let (tok, span) = l.next_spanned_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+        assert_eq!(span.start, Position::new(start_offset, start_line, start_column), "start of {:?} is wrong", tok);
+        assert_eq!(span.end, Position::new(end_offset, end_line, end_column), "end of {:?} is wrong", tok);
+      }
+  }
+
+  #[test]
+  fn get_unterminated_string_token() {
+      let input = "let name = \"monkey;";
+
+      let tests: Vec<token::Token> = vec![
+        token::Token::LET,
+        token::Token::IDENT(Name::new("name")),
+        token::Token::ASSIGN,
+        token::Token::ILLEGAL("unterminated string literal".into()),
+        token::Token::EOF,
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for tt in tests {
+        let tok = l.next_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+      }
+  }
+
+  #[test]
+  fn skip_block_comments() {
+      let input = "let /* a */ x = /* outer /* inner */ still outer */ 1;
+/*
+ * multi-line
+ */ x / *y
+/* unterminated /* nested */";
+
+      let tests: Vec<token::Token> = vec![
+        token::Token::LET,
+        token::Token::IDENT(Name::new("x")),
+        token::Token::ASSIGN,
+        token::Token::INT(1),
+        token::Token::SEMICOLON,
+        token::Token::IDENT(Name::new("x")),
+        token::Token::SLASH,
+        token::Token::ASTERISK,
+        token::Token::IDENT(Name::new("y")),
+        token::Token::ILLEGAL("unterminated block comment".into()),
+        token::Token::EOF,
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+
+      for tt in tests {
+        let tok = l.next_token();
+        assert_eq!(tok, tt, "Token type is wrong: expect={:?}, actual={:?}", tt, tok);
+      }
+
+      // 閉じられていないものは含まない
+      let comments: Vec<&str> = l.block_comments().iter().map(|span| span.slice(input)).collect();
+      assert_eq!(comments, vec!["/* a */", "/* outer /* inner */ still outer */", "/*\n * multi-line\n */"]);
+  }
+
+  #[test]
+  fn iterate_tokens() {
+      let tokens: Vec<token::Token> = Lexer::new("let x = 1;".to_string()).collect();
+      assert_eq!(tokens, vec![
+        token::Token::LET,
+        token::Token::IDENT(Name::new("x")),
+        token::Token::ASSIGN,
+        token::Token::INT(1),
+        token::Token::SEMICOLON,
+      ]);
+
+      let mut l = Lexer::new("a + b /* c */".to_string());
+      let mut tokens = l.spanned().peekable();
+      assert_eq!(tokens.peek().map(|(_, span)| format!("{}", span.start)), Some("1:1".to_string()));
+      let spans: Vec<String> = tokens.map(|(tok, span)| format!("{} {}-{}", tok, span.start, span.end)).collect();
+      assert_eq!(spans, vec!["IDENT(a) 1:1-1:2", "PLUS 1:3-1:4", "IDENT(b) 1:5-1:6"]);
+      // 読み終えた後も lexer を使える
+      assert_eq!(l.block_comments().len(), 1);
+      assert_eq!(l.next(), None);
+  }
+
+  #[test]
+  fn highlight_tokens() {
+      use token::TokenClass::*;
+
+      let input = "let s = /* c */ \"a\"; // d\nif (s != null) { f(1.5) } $";
+      let classes: Vec<(&str, token::TokenClass)> = highlight(input).into_iter().map(|(span, class)| (span.slice(input), class)).collect();
+      assert_eq!(classes, vec![
+        ("let", Keyword), ("s", Identifier), ("=", Operator), ("/* c */", Comment), ("\"a\"", Literal), (";", Punctuation),
+        ("// d", Comment),
+        ("if", Keyword), ("(", Punctuation), ("s", Identifier), ("!=", Operator), ("null", Literal), (")", Punctuation),
+        ("{", Punctuation), ("f", Identifier), ("(", Punctuation), ("1.5", Literal), (")", Punctuation), ("}", Punctuation),
+        ("$", Invalid),
+      ]);
+  }
+
+  // 一度に数バイトずつしか返さない reader から読んでも、文字列から読むのと同じになる
+  #[test]
+  fn read_from_reader() {
+      let input = "let s = \"あい\\u{41}\"; // コメント
+let x = 12.5e3 /* multi
+line */ ..= [1];
+fn(a, b) { a != b }";
+
+      let mut expected = Lexer::new(input.to_string());
+      let mut l = Lexer::from_reader(io::BufReader::with_capacity(3, input.as_bytes()));
+      loop {
+        let tok = expected.next_spanned_token();
+        assert_eq!(l.next_spanned_token(), tok);
+        // 読み終えた行は捨てられている
+        assert!(l.input.buf.len() <= 2 * 40, "buffer={:?}", l.input);
+        if tok.0 == token::Token::EOF {
+          break;
+        }
+      }
+      assert_eq!(l.block_comments(), expected.block_comments());
+  }
+
+  #[test]
+  fn report_read_error() {
+      struct Failing(bool);
+      impl io::Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+          if self.0 {
+            return Err(io::Error::other("broken pipe"));
+          }
+          self.0 = true;
+          buf[..4].copy_from_slice(b"x y\n");
+          Ok(4)
+        }
+      }
+
+      let tokens: Vec<token::Token> = Lexer::from_reader(io::BufReader::new(Failing(false))).collect();
+      assert_eq!(tokens, vec![
+        token::Token::IDENT(Name::new("x")),
+        token::Token::IDENT(Name::new("y")),
+        token::Token::ILLEGAL("could not read the input: broken pipe".into()),
+      ]);
+  }
+
+  #[test]
+  fn get_unicode_ident_tokens() {
+      let input = "let 名前 = \"太郎\"; 名前2 + _x1 * café
+€ １";
+
+      let tests: Vec<(token::Token, &str)> = vec![
+        (token::Token::LET, "1:1-1:4"),
+        (token::Token::IDENT(Name::new("名前")), "1:5-1:7"),
+        (token::Token::ASSIGN, "1:8-1:9"),
+        (token::Token::STRING("太郎".into()), "1:10-1:14"),
+        (token::Token::SEMICOLON, "1:14-1:15"),
+        (token::Token::IDENT(Name::new("名前2")), "1:16-1:19"),
+        (token::Token::PLUS, "1:20-1:21"),
+        (token::Token::IDENT(Name::new("_x1")), "1:22-1:25"),
+        (token::Token::ASTERISK, "1:26-1:27"),
+        (token::Token::IDENT(Name::new("café")), "1:28-1:32"),
+        // 記号は識別子にならず、全角の数字は XID_Continue だが識別子の先頭には使えない
+        (token::Token::ILLEGAL("unexpected character '€'".into()), "2:1-2:2"),
+        (token::Token::ILLEGAL("unexpected character '１'".into()), "2:3-2:4"),
+        (token::Token::EOF, "2:4-2:4"),
+      ];
+
+      let mut l = Lexer::new(input.to_string());
+      for (expected, span) in tests {
+        let (tok, actual) = l.next_spanned_token();
+        assert_eq!(tok, expected);
+        assert_eq!(format!("{}-{}", actual.start, actual.end), span, "token={}", tok);
+        assert_eq!(actual.slice(input).chars().count(), actual.end.column - actual.start.column, "token={}", tok);
+      }
+
+      let mut l = Lexer::new("\"\\あ\"".to_string());
+      assert_eq!(l.next_token(), token::Token::ILLEGAL("invalid escape sequence '\\あ'".into()));
+  }
+
+  #[test]
+  fn dump_tokens() {
+      let input = "x += \"a\\n\"; // note
+y**=";
+
+      assert_eq!(to_token_string(input), "1:1-1:2 IDENT(x)
+1:3-1:5 PlusAssign
+1:6-1:11 STRING(\"a\\n\")
+1:11-1:12 SEMICOLON
+1:13-1:20 COMMENT(\"note\")
+2:1-2:2 IDENT(y)
+2:2-2:4 POWER
+2:4-2:5 ASSIGN
+2:5-2:5 EOF
+");
+  }
+
+  #[test]
+  fn get_string_escape_tokens() {
+      let tests: Vec<(&str, token::Token)> = vec![
+        (r#""a\nb""#, token::Token::STRING("a\nb".to_string())),
+        (r#""\t\"quoted\"\\""#, token::Token::STRING("\t\"quoted\"\\".to_string())),
+        (r#""\u{41}\u{3042}\u{1F600}""#, token::Token::STRING("A\u{3042}\u{1F600}".to_string())),
+        (r#""あ\nい""#, token::Token::STRING("あ\nい".to_string())),
+        (r#""\q""#, token::Token::ILLEGAL("invalid escape sequence '\\q'".into())),
+        (r#""\u41""#, token::Token::ILLEGAL("invalid unicode escape: expected '{' after '\\u'".into())),
+        (r#""\u{41""#, token::Token::ILLEGAL("invalid unicode escape '\\u{41': expected '}'".into())),
+        (r#""\u{}""#, token::Token::ILLEGAL("invalid unicode escape '\\u{}': expected 1 to 6 hex digits".into())),
+        (r#""\u{D800}""#, token::Token::ILLEGAL("invalid unicode code point '\\u{D800}'".into())),
+        (r#""abc\"#, token::Token::ILLEGAL("unterminated string literal".into())),
+      ];
+
+      for (input, expected) in tests {
+        let mut l = Lexer::new(input.to_string());
+        let tok = l.next_token();
+        assert_eq!(tok, expected, "Token type is wrong: input={}", input);
+        assert_eq!(l.next_token(), token::Token::EOF, "string should be read to the end: input={}", input);
+      }
+  }
+
+  #[test]
+  fn get_tokens_from_bytes() {
+      let tokens: Vec<token::Token> = lex_bytes(b"\xff 99999999999999999999 9223372036854775807").into_iter().map(|(tok, _)| tok).collect();
+      assert_eq!(tokens, vec![
+        token::Token::ILLEGAL("invalid UTF-8 byte 0xff".into()),
+        token::Token::ILLEGAL("integer literal is too large: 99999999999999999999".into()),
+        token::Token::INT(i64::MAX),
+      ]);
+
+      let tokens: Vec<token::Token> = lex_bytes(b"1e400 1.7976931348623157e308").into_iter().map(|(tok, _)| tok).collect();
+      assert_eq!(tokens, vec![
+        token::Token::ILLEGAL("float literal is too large: 1e400".into()),
+        token::Token::FLOAT(f64::MAX),
+      ]);
+  }
 }
@@ -1,6 +1,20 @@
 pub mod lexer;
 pub mod token;
+pub mod span;
+pub mod intern;
 pub mod ast;
 pub mod parser;
 pub mod evaluator;
+pub mod code;
+pub mod compiler;
+pub mod vm;
+pub mod optimizer;
+pub mod analysis;
+pub mod typecheck;
+pub mod formatter;
 pub mod utils;
+pub mod embed;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use embed::{Interpreter, EvalError};
@@ -0,0 +1,425 @@
+use std::cell::Cell;
+
+use crate::ast::Program;
+use crate::ast::stmt::{Statement, BlockStatement, ExpressionStatement, LetStatement, FuncStatement, ReturnStatement, WhileStatement, ForStatement};
+use crate::ast::expr::{
+  Expression, PrefixExpression, InfixExpression, IfExpression, TryExpression, TernaryExpression, CallExpression,
+  MethodCallExpression, MemberExpression, IndexExpression, SliceExpression, AssignExpression, RangeExpression,
+  SpreadExpression, MatchExpression, MatchArm,
+};
+use crate::ast::lit::{self, Literal};
+use crate::ast::operator::Infix;
+use crate::evaluator::{self, macro_expansion, object::{self, Object}};
+use crate::span::Span;
+use crate::utils;
+
+thread_local! {
+  // optimize を始めたときのスタックの位置
+  static STACK_BASE: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Folds the expressions of `program` whose operands are literals, and the `if` expressions
+/// and ternaries whose condition is a literal.
+/// An expression is left as it is when evaluating it would fail, so errors are still reported at runtime,
+/// and so are the expressions nested deeper than `utils::DEFAULT_MAX_STACK` of the host stack allows.
+pub fn optimize(program: Program) -> Program {
+  STACK_BASE.with(|base| base.set(utils::stack_address()));
+  Program { statements: fold_statements(program.statements) }
+}
+
+fn fold_statements(stmts: Vec<Statement>) -> Vec<Statement> {
+  let mut folded = vec![];
+  for stmt in stmts {
+    match stmt {
+      // 選ばれた方の文はそのまま展開できる。ただし let、fn と struct はブロックの外から見えないので、if (true) のまま残す
+      Statement::Expr(ExpressionStatement { value: Expression::If(if_expr), span }) => {
+        match fold_if_condition(if_expr) {
+          Branch::Chosen(Some(block)) if block.statements.iter().any(|stmt| {
+            matches!(stmt, Statement::Let(_) | Statement::Func(_) | Statement::Struct(_))
+          }) =>
+            folded.push(Statement::Expr(ExpressionStatement::new(fold_block_expression(fold_block(block)), span))),
+          Branch::Chosen(Some(block)) if block.statements.iter().any(|stmt| !is_comment(stmt)) =>
+            folded.extend(fold_statements(block.statements)),
+          Branch::Chosen(_) => folded.push(Statement::Expr(ExpressionStatement::new(null_literal(span), span))),
+          Branch::Unknown(if_expr) => folded.push(Statement::Expr(ExpressionStatement::new(fold_if_expression(if_expr), span))),
+        }
+      },
+      stmt => folded.push(fold_statement(stmt)),
+    }
+  }
+  folded
+}
+
+fn is_comment(stmt: &Statement) -> bool {
+  if let Statement::Comment(_) = stmt {
+    return true;
+  }
+  false
+}
+
+fn fold_statement(stmt: Statement) -> Statement {
+  match stmt {
+    Statement::Let(let_stmt) => Statement::Let(
+      LetStatement::new(let_stmt.pattern, fold_expression(let_stmt.value), let_stmt.constant, let_stmt.span),
+    ),
+    Statement::Func(func_stmt) => Statement::Func(
+      FuncStatement::new(func_stmt.ident, fold_func(func_stmt.func), func_stmt.span),
+    ),
+    Statement::Struct(_) => stmt,
+    Statement::Return(rtn) => Statement::Return(
+      ReturnStatement::new(fold_expression(rtn.value), rtn.span),
+    ),
+    Statement::Expr(expr) => Statement::Expr(
+      ExpressionStatement::new(fold_expression(expr.value), expr.span),
+    ),
+    Statement::Block(block) => Statement::Block(fold_block(block)),
+    Statement::While(while_stmt) => Statement::While(
+      WhileStatement::new(fold_expression(while_stmt.condition), fold_block(while_stmt.body), while_stmt.span),
+    ),
+    Statement::For(for_stmt) => Statement::For(
+      ForStatement::new(for_stmt.ident, fold_expression(for_stmt.iterable), fold_block(for_stmt.body), for_stmt.span),
+    ),
+    Statement::Comment(_) => stmt,
+  }
+}
+
+fn fold_block(block: BlockStatement) -> BlockStatement {
+  BlockStatement::new(fold_statements(block.statements), block.span)
+}
+
+fn fold_boxed(mut expr: Box<Expression>) -> Box<Expression> {
+  *expr = fold_expression(*expr);
+  expr
+}
+
+fn fold_expression(expr: Expression) -> Expression {
+  // 深すぎる式はホストのスタックが溢れる前に諦めて、そのまま実行時に任せる
+  if STACK_BASE.with(|base| utils::stack_address().abs_diff(base.get())) > utils::DEFAULT_MAX_STACK {
+    return expr;
+  }
+  match expr {
+    Expression::Identifier(_) => expr,
+    Expression::Literal(lit) => Expression::Literal(fold_literal(lit)),
+    Expression::Prefix(pre) => fold_prefix_expression(pre),
+    Expression::Infix(inf) => fold_infix_expression(inf),
+    Expression::If(if_expr) => {
+      let span = if_expr.span;
+      match fold_if_condition(if_expr) {
+        Branch::Chosen(Some(block)) => fold_block_expression(fold_block(block)),
+        Branch::Chosen(None) => null_literal(span),
+        Branch::Unknown(if_expr) => fold_if_expression(if_expr),
+      }
+    },
+    Expression::Try(try_expr) => Expression::Try(TryExpression::new(
+      fold_block(try_expr.block),
+      try_expr.param,
+      fold_block(try_expr.handler),
+      try_expr.span,
+    )),
+    // パターンはリテラルと名前だけなので、畳み込むのは対象と腕の本体だけ
+    Expression::Match(match_expr) => Expression::Match(MatchExpression::new(
+      fold_boxed(match_expr.subject),
+      match_expr.arms.into_iter()
+        .map(|arm| MatchArm::new(arm.pattern, fold_expression(arm.body), arm.span))
+        .collect(),
+      match_expr.span,
+    )),
+    Expression::Ternary(ternary) => {
+      let condition = fold_expression(*ternary.condition);
+      match to_object(&condition) {
+        Some(cond) if evaluator::is_truthy(cond.clone()) => fold_expression(*ternary.consequence),
+        Some(_) => fold_expression(*ternary.alternative),
+        None => Expression::Ternary(TernaryExpression::new(
+          Box::new(condition),
+          fold_boxed(ternary.consequence),
+          fold_boxed(ternary.alternative),
+          ternary.span,
+        )),
+      }
+    },
+    // quote した AST はそのままプログラムに渡す
+    Expression::Call(call) if macro_expansion::is_quote_call(&call) || macro_expansion::is_unquote_call(&call) =>
+      Expression::Call(call),
+    Expression::Call(call) => Expression::Call(CallExpression::new(
+      fold_boxed(call.func),
+      call.args.into_iter().map(fold_expression).collect(),
+      call.span,
+    )),
+    Expression::MethodCall(method) => Expression::MethodCall(MethodCallExpression::new(
+      fold_boxed(method.receiver),
+      method.method,
+      method.args.into_iter().map(fold_expression).collect(),
+      method.span,
+    )),
+    Expression::Member(member) => Expression::Member(
+      MemberExpression::new(fold_boxed(member.object), member.property, member.span),
+    ),
+    Expression::Index(idx) => Expression::Index(
+      IndexExpression::new(fold_boxed(idx.left), fold_boxed(idx.index), idx.span),
+    ),
+    Expression::Slice(slice) => Expression::Slice(SliceExpression::new(
+      fold_boxed(slice.left),
+      slice.start.map(fold_boxed),
+      slice.end.map(fold_boxed),
+      slice.span,
+    )),
+    // 代入先は書き込む場所なので畳み込まない
+    Expression::Assign(assign) => Expression::Assign(
+      AssignExpression::new(assign.target, assign.operator, fold_boxed(assign.value), assign.span),
+    ),
+    Expression::Update(_) => expr,
+    Expression::Range(range) => Expression::Range(
+      RangeExpression::new(fold_boxed(range.start), fold_boxed(range.end), range.inclusive, range.span),
+    ),
+    Expression::Spread(spread) => Expression::Spread(
+      SpreadExpression::new(fold_boxed(spread.value), spread.span),
+    ),
+  }
+}
+
+fn fold_literal(lit: Literal) -> Literal {
+  match lit {
+    Literal::Array(arr) => Literal::Array(
+      lit::Array::new(arr.elements.into_iter().map(fold_expression).collect(), arr.span),
+    ),
+    Literal::Hash(hash) => Literal::Hash(lit::Hash::new(
+      hash.pairs.into_iter().map(|(key, val)| (fold_expression(key), fold_expression(val))).collect(),
+      hash.span,
+    )),
+    Literal::Struct(lit) => Literal::Struct(lit::Struct::new(
+      lit.name,
+      lit.fields.into_iter().map(|(key, val)| (key, fold_expression(val))).collect(),
+      lit.span,
+    )),
+    Literal::Func(func) => Literal::Func(fold_func(func)),
+    _ => lit,
+  }
+}
+
+fn fold_func(func: lit::Func) -> lit::Func {
+  lit::Func::new(
+    func.args,
+    func.defaults.into_iter().map(|default| default.map(fold_expression)).collect(),
+    fold_block(func.body),
+    func.span,
+  )
+}
+
+fn fold_prefix_expression(pre: PrefixExpression) -> Expression {
+  let PrefixExpression { operator, right, span } = pre;
+  let right = fold_expression(*right);
+  let folded = match to_object(&right) {
+    Some(obj) => to_literal(evaluator::eval_prefix_expression(&operator, obj), span),
+    None => None,
+  };
+  folded.unwrap_or_else(|| Expression::Prefix(PrefixExpression::new(operator, Box::new(right), span)))
+}
+
+// 1 + 2 + ... のような長い式は左に深く入れ子になるので、再帰せずに左端から順に畳み込む
+fn fold_infix_expression(inf: InfixExpression) -> Expression {
+  let (left, operands) = inf.unchain();
+  let mut folded = fold_expression(left);
+  for (operator, right, span) in operands {
+    folded = fold_infix(folded, operator, fold_expression(*right), span);
+  }
+  folded
+}
+
+fn fold_infix(left: Expression, operator: Infix, right: Expression, span: Span) -> Expression {
+  let folded = match (&operator, to_object(&left)) {
+    (Infix::Coalesce, Some(Object::Null)) => Some(right.clone()),
+    (Infix::Coalesce, Some(_)) => Some(left.clone()),
+    (Infix::And, Some(obj)) if !evaluator::is_truthy(obj.clone()) => Some(bool_literal(false, span)),
+    (Infix::Or, Some(obj)) if evaluator::is_truthy(obj.clone()) => Some(bool_literal(true, span)),
+    // 左辺で決まらない && と || は右辺の真偽値になる
+    (Infix::And, Some(_)) | (Infix::Or, Some(_)) =>
+      to_object(&right).map(|obj| bool_literal(evaluator::is_truthy(obj), span)),
+    (operator, Some(l)) => match to_object(&right) {
+      Some(r) => to_literal(evaluator::eval_infix_expression(l, operator, r), span),
+      None => None,
+    },
+    _ => None,
+  };
+  folded.unwrap_or_else(|| Expression::Infix(InfixExpression::new(Box::new(left), operator, Box::new(right), span)))
+}
+
+enum Branch {
+  /// The condition is a literal, so the block (or the missing `else`) is always taken.
+  Chosen(Option<BlockStatement>),
+  /// The condition is only known at runtime. It has already been folded.
+  Unknown(IfExpression),
+}
+
+fn fold_if_condition(if_expr: IfExpression) -> Branch {
+  let condition = fold_expression(*if_expr.condition);
+  match to_object(&condition) {
+    Some(cond) if evaluator::is_truthy(cond.clone()) => Branch::Chosen(Some(if_expr.consequence)),
+    Some(_) => Branch::Chosen(if_expr.alternative),
+    None => Branch::Unknown(IfExpression::new(Box::new(condition), if_expr.consequence, if_expr.alternative, if_expr.span)),
+  }
+}
+
+fn fold_if_expression(if_expr: IfExpression) -> Expression {
+  Expression::If(IfExpression::new(
+    if_expr.condition,
+    fold_block(if_expr.consequence),
+    if_expr.alternative.map(fold_block),
+    if_expr.span,
+  ))
+}
+
+/// Turns a block chosen inside an expression back into an expression.
+/// Only a block of a single expression can be replaced by it; other blocks are kept as `if (true)`.
+fn fold_block_expression(block: BlockStatement) -> Expression {
+  let mut stmts = block.statements.iter().filter(|stmt| !is_comment(stmt));
+  match (stmts.next(), stmts.next()) {
+    (None, _) => null_literal(block.span),
+    (Some(Statement::Expr(expr)), None) => expr.value.clone(),
+    _ => {
+      let span = block.span;
+      Expression::If(IfExpression::new(Box::new(bool_literal(true, span)), block, None, span))
+    },
+  }
+}
+
+/// Returns the value of `expr` if it is a literal which can be evaluated without an environment.
+fn to_object(expr: &Expression) -> Option<Object> {
+  let lit = match expr {
+    Expression::Literal(lit) => lit,
+    _ => return None,
+  };
+  match lit {
+    Literal::Integer(int) => Some(Object::Integer(object::Integer::new(int.value))),
+    Literal::Float(float) => Some(Object::Float(object::Float::new(float.value))),
+    Literal::Boolean(b) => Some(evaluator::native_bool_to_boolean_object(b.value)),
+    Literal::Str(s) => Some(Object::Str(object::Str::new(s.value.clone()))),
+    Literal::Null(_) => Some(Object::Null),
+    _ => None,
+  }
+}
+
+fn to_literal(obj: Object, span: Span) -> Option<Expression> {
+  let lit = match obj {
+    Object::Integer(int) => Literal::Integer(lit::Integer::new(int.value, span)),
+    Object::Float(float) => Literal::Float(lit::Float::new(float.value, span)),
+    Object::Boolean(b) => Literal::Boolean(lit::Boolean::new(b.value, span)),
+    Object::Str(s) => Literal::Str(lit::Str::new(s.value, span)),
+    Object::Null => Literal::Null(lit::Null::new(span)),
+    _ => return None,
+  };
+  Some(Expression::Literal(lit))
+}
+
+fn bool_literal(value: bool, span: Span) -> Expression {
+  Expression::Literal(Literal::Boolean(lit::Boolean::new(value, span)))
+}
+
+fn null_literal(span: Span) -> Expression {
+  Expression::Literal(Literal::Null(lit::Null::new(span)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use crate::evaluator::{builtins, environment::Environment};
+
+  fn parse(input: &str) -> Program {
+    let l = Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+    program
+  }
+
+  #[test]
+  fn test_optimize() {
+    let tests = vec![
+      ("2 * 3 + 4", "10"),
+      ("x * (2 + 3)", "(x * 5)"),
+      ("1.5 * 2", "3.0"),
+      ("\"foo\" + \"bar\"", "\"foobar\""),
+      ("!true", "false"),
+      ("-(2 + 3)", "-5"),
+      ("1 < 2 == true", "true"),
+      ("true && false || true", "true"),
+      ("false && f()", "false"),
+      ("true || f()", "true"),
+      ("true && f()", "(true && f())"),
+      ("null ?? x", "x"),
+      ("1 ?? x", "1"),
+      ("true ? a : b", "a"),
+      ("if (1 > 2) { a } else { b + (1 + 1) }", "(b + 2)"),
+      ("let x = if (false) { 1 };", "let x = null;"),
+      ("if (true) { puts(1); 2 * 3 }", "puts(1)6"),
+      // let はブロックの外から見えないので、ブロックを展開しない
+      ("if (true) { let y = 1 + 1; y }", "if(true) { let y = 2; }{ y }"),
+      ("let f = fn(a = 1 + 1) { a * (2 * 3) };", "let f = fn(a = 2) { (a * 6) };"),
+      ("[1 + 1, {1 + 1: 2 * 2}]", "[2, {2: 4}]"),
+      ("x = 1 + 1", "(x = 2)"),
+      ("while (x < 2 * 5) { x += 1 }", "while((x < 10)) { (x += 1) }"),
+      // 実行時のエラーになる式は畳み込まない
+      ("1 / 0", "(1 / 0)"),
+      ("1 + true", "(1 + true)"),
+      ("9223372036854775807 + 1", "(9223372036854775807 + 1)"),
+      ("-(-9223372036854775807 - 1)", "(--9223372036854775808)"),
+      // quote の中は畳み込まない
+      ("quote(1 + 2)", "quote((1 + 2))"),
+      ("quote(!2.5)", "quote((!2.5))"),
+      ("quote(unquote(1 + 2) + 3 * 4)", "quote((unquote((1 + 2)) + (3 * 4)))"),
+      ("unquote(1 + 2)", "unquote((1 + 2))"),
+      ("puts(quote(1 + 2), 1 + 2)", "puts(quote((1 + 2)), 3)"),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let program = parse(input).optimize();
+      assert_eq!(program.to_string(), expected, "input: {}", input);
+    }
+  }
+
+  #[test]
+  fn test_optimize_keeps_result() {
+    let tests = vec![
+      "if (true) { 10 }",
+      "if (false) { 10 }",
+      "if (true) {}",
+      "let f = fn() { if (true) { return 1; }; 2 }; f()",
+      "let x = 1; if (1 < 2) { x = x + 1; x * 2 }",
+      "let f = fn(n) { if (false) { 0 } else { n * (2 + 3) } }; f(2)",
+      "let x = if (true) { let y = 1; y + 1 }; x",
+      "2 ** 3 - 1 / 0",
+      "null ?? 1 + 1",
+      "quote(1 + 2)",
+      "quote(!2.5)",
+    ];
+
+    for input in tests.into_iter() {
+      let expected = evaluator::eval(parse(input), &Environment::new(builtins::new_builtins()));
+      let evaluated = evaluator::eval(parse(input).optimize(), &Environment::new(builtins::new_builtins()));
+      assert_eq!(evaluated.to_string(), expected.to_string(), "input: {}", input);
+    }
+  }
+
+  // 長い式や深い式でもホストのスタックを溢れさせない
+  #[test]
+  fn test_optimize_deep_expressions() {
+    let input = vec!["1"; 10000].join(" + ");
+    assert_eq!(parse(&input).optimize().to_string(), "10000");
+
+    // 文字列にすると再帰するので、一番外側の式だけを確かめる
+    let input = format!("x{}", " + 1 * 2".repeat(10000));
+    match &parse(&input).optimize().statements[0] {
+      Statement::Expr(ExpressionStatement { value: Expression::Infix(inf), .. }) => assert_eq!(inf.right.to_string(), "2"),
+      stmt => panic!("expected an infix expression, got {:?}", stmt),
+    }
+
+    let input = format!("f{}", "(1 + 1)".repeat(10000));
+    match &parse(&input).optimize().statements[0] {
+      Statement::Expr(ExpressionStatement { value: Expression::Call(call), .. }) => assert_eq!(call.args[0].to_string(), "2"),
+      stmt => panic!("expected a call, got {:?}", stmt),
+    }
+  }
+}
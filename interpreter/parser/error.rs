@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::token;
+use crate::span::Span;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+  /// The next token is not the one required by the grammar.
+  UnexpectedToken {
+    expected: token::Token,
+    found: token::Token,
+    span: Span,
+  },
+  /// An identifier is required, e.g. after `let`.
+  ExpectedIdentifier {
+    found: token::Token,
+    span: Span,
+  },
+  /// The lexer could not read a token, e.g. an unterminated string.
+  IllegalToken {
+    found: token::Token,
+    span: Span,
+  },
+  /// No expression can start with the token.
+  NoPrefixParseFn {
+    found: token::Token,
+    span: Span,
+  },
+  /// The token can not follow an expression. The parser only tries tokens which can, so this is not expected.
+  NoInfixParseFn {
+    found: token::Token,
+    span: Span,
+  },
+  /// The left side of `=` is not something which can be assigned to.
+  InvalidAssignTarget {
+    target: String,
+    found: token::Token,
+    span: Span,
+  },
+  /// Expressions, blocks or patterns are nested deeper than the limit of the parser. See `Parser::set_max_depth`.
+  TooDeeplyNested {
+    found: token::Token,
+    span: Span,
+  },
+  /// The token can not be used in `place`, e.g. a literal in function arguments.
+  Unsupported {
+    found: token::Token,
+    place: String,
+    span: Span,
+  },
+}
+
+impl ParseError {
+  /// Returns the span of the token which caused the error.
+  pub fn span(&self) -> Span {
+    match self {
+      ParseError::UnexpectedToken { span, .. }
+      | ParseError::ExpectedIdentifier { span, .. }
+      | ParseError::IllegalToken { span, .. }
+      | ParseError::InvalidAssignTarget { span, .. }
+      | ParseError::NoPrefixParseFn { span, .. }
+      | ParseError::NoInfixParseFn { span, .. }
+      | ParseError::TooDeeplyNested { span, .. }
+      | ParseError::Unsupported { span, .. } => *span,
+    }
+  }
+
+  /// Returns the token which caused the error.
+  pub fn found(&self) -> &token::Token {
+    match self {
+      ParseError::UnexpectedToken { found, .. }
+      | ParseError::ExpectedIdentifier { found, .. }
+      | ParseError::IllegalToken { found, .. }
+      | ParseError::InvalidAssignTarget { found, .. }
+      | ParseError::NoPrefixParseFn { found, .. }
+      | ParseError::NoInfixParseFn { found, .. }
+      | ParseError::TooDeeplyNested { found, .. }
+      | ParseError::Unsupported { found, .. } => found,
+    }
+  }
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ParseError::UnexpectedToken { expected, found, .. } =>
+        write!(f, "expected next token to be {:?}, got {:?} instead.", expected, found),
+      ParseError::ExpectedIdentifier { found, .. } =>
+        write!(f, "expected next token to be IDENT, got {:?} instead.", found),
+      ParseError::IllegalToken { found: token::Token::ILLEGAL(msg), .. } =>
+        write!(f, "{}.", msg),
+      ParseError::IllegalToken { found, .. } =>
+        write!(f, "illegal token {:?}.", found),
+      ParseError::NoPrefixParseFn { found, .. } =>
+        write!(f, "no prefix parse function for {:?}.", found),
+      ParseError::NoInfixParseFn { found, .. } =>
+        write!(f, "no infix parse function for {:?}.", found),
+      ParseError::InvalidAssignTarget { target, .. } =>
+        write!(f, "invalid assignment target: {}.", target),
+      ParseError::TooDeeplyNested { .. } =>
+        write!(f, "nested too deeply."),
+      ParseError::Unsupported { found, place, .. } =>
+        write!(f, "{:?} is not supported in {:?}.", found, place),
+    }
+  }
+}
@@ -1,242 +1,426 @@
 use super::{Parser};
+use super::error::ParseError;
 use crate::{token};
 use crate::ast::expr::*;
 use crate::ast::lit::*;
 use crate::ast::ident::{Identifier};
-use crate::ast::operator::{Prefix, Infix, BinaryOperator};
+use crate::intern::Name;
+use crate::span::Span;
+use crate::ast::operator::{Prefix, Infix, Update, BinaryOperator};
 
 impl token::Token {
   fn to_binary_operator(&self) -> BinaryOperator {
     match self {
+      token::Token::ASSIGN |
+      token::Token::PlusAssign |
+      token::Token::MinusAssign |
+      token::Token::AsteriskAssign |
+      token::Token::SlashAssign => BinaryOperator::Assign,
+      token::Token::QUESTION => BinaryOperator::Ternary,
+      token::Token::COALESCE => BinaryOperator::Coalesce,
+      token::Token::OR => BinaryOperator::LogicalOr,
+      token::Token::AND => BinaryOperator::LogicalAnd,
       token::Token::EQ | token::Token::NotEq => BinaryOperator::Equals,
-      token::Token::LT | token::Token::GT => BinaryOperator::LtGt,
+      token::Token::LT | token::Token::GT | token::Token::LtEq | token::Token::GtEq => BinaryOperator::LtGt,
+      token::Token::DOTDOT | token::Token::DotDotEq => BinaryOperator::Range,
+      token::Token::PIPE => BinaryOperator::BitOr,
+      token::Token::CARET => BinaryOperator::BitXor,
+      token::Token::AMPERSAND => BinaryOperator::BitAnd,
+      token::Token::ShiftLeft | token::Token::ShiftRight => BinaryOperator::Shift,
       token::Token::PLUS | token::Token::MINUS => BinaryOperator::Sum,
-      token::Token::ASTERISK | token::Token::SLASH => BinaryOperator::Product,
+      token::Token::ASTERISK | token::Token::SLASH | token::Token::PERCENT => BinaryOperator::Product,
+      token::Token::POWER => BinaryOperator::Power,
+      token::Token::INCREMENT | token::Token::DECREMENT => BinaryOperator::Postfix,
       token::Token::LPAREN => BinaryOperator::Call,
-      token::Token::LBRACKET => BinaryOperator::Index,
+      token::Token::LBRACKET | token::Token::DOT => BinaryOperator::Index,
       _ => BinaryOperator::Lowest,
     }
   }
 }
 
 impl Parser {
-  pub(super) fn parse_expression(&mut self, op: BinaryOperator) -> Option<Expression> {
-    let mut left = match self.parse_prefix() {
-      Some(expr) => expr,
-      None => return None,
-    };
+  pub(super) fn parse_expression(&mut self, op: BinaryOperator) -> Result<Expression, ParseError> {
+    self.check_depth()?;
+
+    self.depth += 1;
+    let expr = self.parse_nested_expression(op);
+    self.depth -= 1;
+    expr
+  }
+
+  fn parse_nested_expression(&mut self, op: BinaryOperator) -> Result<Expression, ParseError> {
+    let mut left = self.parse_prefix()?;
     
-    while !self.peek_token.is(token::Token::SEMICOLON) && op < self.peek_token.to_binary_operator() {
+    while !self.peek_token.is(token::Token::SEMICOLON) && self.binds_tighter(&op) {
       self.next_token();
-      left = match self.parse_infix(left) {
-        Some(expr) => expr,
-        None => return None,
-      };
+      left = self.parse_infix(left)?;
     }
 
-    Some(left)
+    Ok(left)
+  }
+
+  // 右結合の演算子は同じ優先順位でも右側に結合させる
+  fn binds_tighter(&self, op: &BinaryOperator) -> bool {
+    let peek = self.peek_token.to_binary_operator();
+    *op < peek || (*op == peek && peek.is_right_assoc())
   }
 
-  fn parse_prefix(&mut self) -> Option<Expression> {
+  fn parse_prefix(&mut self) -> Result<Expression, ParseError> {
     match &self.current_token {
-      token::Token::IDENT(s) => self.parse_identifier(s.to_string()),
+      token::Token::IDENT(s) if self.peek_token.is(token::Token::LBRACE) => self.parse_struct_literal(*s),
+      token::Token::IDENT(s) => self.parse_identifier(*s),
       token::Token::INT(int) => self.parse_integer_literal(*int),
+      token::Token::FLOAT(float) => self.parse_float_literal(*float),
       token::Token::STRING(s) => self.parse_string_literal(s.clone()),
       token::Token::TRUE | token::Token::FALSE => self.parse_boolean_literal(),
-      token::Token::BANG | token::Token::MINUS => self.parse_prefix_expression(),
+      token::Token::NULL => self.parse_null_literal(),
+      token::Token::BANG | token::Token::MINUS | token::Token::TILDE => self.parse_prefix_expression(),
+      token::Token::INCREMENT | token::Token::DECREMENT => self.parse_prefix_update_expression(),
       token::Token::LPAREN => self.parse_grouped_expression(),
       token::Token::LBRACKET => self.parse_array_literal(),
       token::Token::LBRACE => self.parse_hash_literal(),
       token::Token::IF => self.parse_if_expression(),
+      token::Token::TRY => self.parse_try_expression(),
+      token::Token::MATCH => self.parse_match_expression(),
+      token::Token::MACRO => self.parse_macro_literal(),
       token::Token::FUNCTION => self.parse_func_literal(),
-      _ => {
-        self.no_prefix_parse_error();
-        return None;
-      }
+      token::Token::ILLEGAL(_) => Err(ParseError::IllegalToken {
+        found: self.current_token.clone(),
+        span: self.current_span,
+      }),
+      _ => Err(self.no_prefix_parse_error()),
     }
   }
 
-  fn parse_infix(&mut self, left: Expression) -> Option<Expression> {
+  fn parse_infix(&mut self, left: Expression) -> Result<Expression, ParseError> {
     match &self.current_token {
       token::Token::PLUS |
       token::Token::MINUS |
       token::Token::SLASH |
       token::Token::ASTERISK |
+      token::Token::PERCENT |
+      token::Token::POWER |
+      token::Token::AMPERSAND |
+      token::Token::PIPE |
+      token::Token::CARET |
+      token::Token::ShiftLeft |
+      token::Token::ShiftRight |
       token::Token::GT |
       token::Token::LT |
+      token::Token::GtEq |
+      token::Token::LtEq |
       token::Token::EQ |
-      token::Token::NotEq => self.parse_infix_expression(left),
+      token::Token::NotEq |
+      token::Token::AND |
+      token::Token::OR |
+      token::Token::COALESCE => self.parse_infix_expression(left),
+      token::Token::ASSIGN |
+      token::Token::PlusAssign |
+      token::Token::MinusAssign |
+      token::Token::AsteriskAssign |
+      token::Token::SlashAssign => self.parse_assign_expression(left),
+      token::Token::QUESTION => self.parse_ternary_expression(left),
+      token::Token::DOTDOT |
+      token::Token::DotDotEq => self.parse_range_expression(left),
+      token::Token::INCREMENT |
+      token::Token::DECREMENT => self.parse_postfix_update_expression(left),
       token::Token::LPAREN => self.parse_call_expression(left),
       token::Token::LBRACKET => self.parse_index_expression(left),
-      _ => return None,
+      token::Token::DOT => self.parse_dot_expression(left),
+      _ => Err(self.no_infix_parse_error()),
     }
   }
 
-  fn parse_identifier(&self, value: String) -> Option<Expression> {
-    Some(Expression::Identifier(Identifier::new(value)))
+  fn parse_identifier(&self, value: Name) -> Result<Expression, ParseError> {
+    Ok(Expression::Identifier(Identifier::new(value, self.current_span)))
   }
 
-  fn parse_integer_literal(&self, int: i64) -> Option<Expression> {
-    Some(
+  fn parse_integer_literal(&self, int: i64) -> Result<Expression, ParseError> {
+    Ok(
       Expression::Literal(
         Literal::Integer(
-          Integer::new(int),
+          Integer::new(int, self.current_span),
+        ),
+      )
+    )
+  }
+
+  fn parse_float_literal(&self, float: f64) -> Result<Expression, ParseError> {
+    Ok(
+      Expression::Literal(
+        Literal::Float(
+          Float::new(float, self.current_span),
         ),
       )
     )
   }
 
-  fn parse_boolean_literal(&self) -> Option<Expression> {
-    Some(
+  fn parse_boolean_literal(&self) -> Result<Expression, ParseError> {
+    Ok(
       Expression::Literal(
         Literal::Boolean(
-          Boolean::new(self.current_token == token::Token::TRUE),
+          Boolean::new(self.current_token == token::Token::TRUE, self.current_span),
         ),
       ),
     )
   }
 
-  fn parse_string_literal(&self, lit: String) -> Option<Expression> {
-    Some(
+  fn parse_null_literal(&self) -> Result<Expression, ParseError> {
+    Ok(Expression::Literal(Literal::Null(Null::new(self.current_span))))
+  }
+
+  fn parse_string_literal(&self, lit: String) -> Result<Expression, ParseError> {
+    Ok(
       Expression::Literal(
         Literal::Str(
-          Str::new(lit),
+          Str::new(lit, self.current_span),
         ),
       ),
     )
   }
 
-  fn parse_array_literal(&mut self) -> Option<Expression> {
-    if let Some(elements) = self.parse_expression_list(token::Token::RBRACKET) {
-      Some(
-        Expression::Literal(
-          Literal::Array(Array::new(elements)),
-        ),
-      )
-    } else {
-      None
-    }
+  fn parse_array_literal(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+    let elements = self.parse_expression_list(token::Token::RBRACKET)?;
+    Ok(
+      Expression::Literal(
+        Literal::Array(Array::new(elements, start.to(self.current_span))),
+      ),
+    )
   }
 
-  fn parse_hash_literal(&mut self) -> Option<Expression> {
+  fn parse_hash_literal(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
     let mut pairs = vec![];
 
     while !self.peek_token.is(token::Token::RBRACE) {
       self.next_token();
 
-      let key = match self.parse_expression(BinaryOperator::Lowest) {
-        Some(val) => val,
-        None => return None,
-      };
+      let key = self.parse_expression(BinaryOperator::Lowest)?;
 
-      if !self.expect_peek(token::Token::COLON) {
-        return None;
-      }
+      self.expect_peek(token::Token::COLON)?;
 
       self.next_token();
 
-      let value = match self.parse_expression(BinaryOperator::Lowest) {
-        Some(val) => val,
-        None => return None,
-      };
+      let value = self.parse_expression(BinaryOperator::Lowest)?;
 
-      if !self.peek_token.is(token::Token::RBRACE) && !self.expect_peek(token::Token::COMMA) {
-        return None;
+      if !self.peek_token.is(token::Token::RBRACE) {
+        self.expect_peek(token::Token::COMMA)?;
       }
 
       pairs.push((key, value));
     }
 
-    if !self.expect_peek(token::Token::RBRACE) {
-      return None;
-    }
+    self.expect_peek(token::Token::RBRACE)?;
 
-    Some(
+    Ok(
       Expression::Literal(
         Literal::Hash(
-          Hash::new(pairs),
+          Hash::new(pairs, start.to(self.current_span)),
         ),
       ),
     )
   }
 
-  fn parse_prefix_expression(&mut self) -> Option<Expression> {
+  // 名前の直後の { はレコードを作る。if や while の条件は括弧で閉じるので、本体のブロックとは紛れない
+  fn parse_struct_literal(&mut self, name: Name) -> Result<Expression, ParseError> {
+    let name = Identifier::new(name, self.current_span);
+    let mut fields = vec![];
+
+    self.next_token();
+
+    while !self.peek_token.is(token::Token::RBRACE) {
+      let key = self.expect_ident_peek()?;
+
+      self.expect_peek(token::Token::COLON)?;
+
+      self.next_token();
+
+      let value = self.parse_expression(BinaryOperator::Lowest)?;
+
+      if !self.peek_token.is(token::Token::RBRACE) {
+        self.expect_peek(token::Token::COMMA)?;
+      }
+
+      fields.push((key, value));
+    }
+
+    self.expect_peek(token::Token::RBRACE)?;
+
+    let span = name.span.to(self.current_span);
+    Ok(Expression::Literal(Literal::Struct(Struct::new(name, fields, span))))
+  }
+
+  fn parse_prefix_expression(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
     let operator = match self.current_token {
       token::Token::MINUS => Prefix::Minus,
       token::Token::BANG => Prefix::Bang,
-      _ => {
-        self.no_prefix_parse_error();
-        return None;
-      }
+      token::Token::TILDE => Prefix::BitNot,
+      _ => return Err(self.no_prefix_parse_error()),
     };
     
     self.next_token();
 
-    let right = match self.parse_expression(BinaryOperator::Prefix) {
-      Some(expr) => expr,
-      None => return None,
-    };
+    let right = self.parse_expression(BinaryOperator::Prefix)?;
+
+    let span = start.to(right.span());
+    Ok(Expression::Prefix(PrefixExpression::new(operator, Box::new(right), span)))
+  }
+
+  fn parse_prefix_update_expression(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+    let operator = self.current_update_operator();
+
+    self.next_token();
+
+    let target = self.parse_expression(BinaryOperator::Prefix)?;
+
+    self.check_assign_target(&target)?;
+
+    let span = start.to(target.span());
+    Ok(Expression::Update(UpdateExpression::new(Box::new(target), operator, true, span)))
+  }
+
+  fn parse_postfix_update_expression(&mut self, target: Expression) -> Result<Expression, ParseError> {
+    self.check_assign_target(&target)?;
+
+    let operator = self.current_update_operator();
+    let span = target.span().to(self.current_span);
+    Ok(Expression::Update(UpdateExpression::new(Box::new(target), operator, false, span)))
+  }
+
+  fn current_update_operator(&self) -> Update {
+    match self.current_token {
+      token::Token::DECREMENT => Update::Decrement,
+      _ => Update::Increment,
+    }
+  }
+
+  /// Reports an error unless `target` is an identifier or an index or member expression on one, e.g. `a[0].b`.
+  fn check_assign_target(&self, target: &Expression) -> Result<(), ParseError> {
+    fn is_assignable(expr: &Expression) -> bool {
+      match expr {
+        Expression::Identifier(_) => true,
+        Expression::Index(idx) => is_assignable(&idx.left),
+        Expression::Member(member) => is_assignable(&member.object),
+        _ => false,
+      }
+    }
 
-    Some(Expression::Prefix(PrefixExpression::new(operator, Box::new(right))))
+    if is_assignable(target) {
+      return Ok(());
+    }
+    Err(ParseError::InvalidAssignTarget {
+      target: format!("{}", target),
+      found: self.current_token.clone(),
+      span: target.span(),
+    })
   }
 
-  fn parse_infix_expression(&mut self, left: Expression) -> Option<Expression> {
+  fn parse_infix_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
     let operator = match self.current_token {
       token::Token::PLUS => Infix::Plus,
       token::Token::MINUS => Infix::Minus,
       token::Token::SLASH => Infix::Slash,
       token::Token::ASTERISK => Infix::Asterisk,
+      token::Token::PERCENT => Infix::Percent,
+      token::Token::POWER => Infix::Power,
+      token::Token::AMPERSAND => Infix::BitAnd,
+      token::Token::PIPE => Infix::BitOr,
+      token::Token::CARET => Infix::BitXor,
+      token::Token::ShiftLeft => Infix::ShiftLeft,
+      token::Token::ShiftRight => Infix::ShiftRight,
       token::Token::GT => Infix::Gt,
       token::Token::LT => Infix::Lt,
+      token::Token::GtEq => Infix::GtEq,
+      token::Token::LtEq => Infix::LtEq,
       token::Token::EQ => Infix::Equal,
       token::Token::NotEq => Infix::NotEq,
-      _ => return None,
+      token::Token::AND => Infix::And,
+      token::Token::OR => Infix::Or,
+      token::Token::COALESCE => Infix::Coalesce,
+      _ => return Err(self.no_infix_parse_error()),
     };
 
     let precedence = self.current_token.to_binary_operator();
 
     self.next_token();
 
-    let right = match self.parse_expression(precedence) {
-      Some(expr) => expr,
-      None => return None,
+    let right = self.parse_expression(precedence)?;
+
+    let span = left.span().to(right.span());
+    let expr = Expression::Infix(InfixExpression::new(Box::new(left), operator, Box::new(right), span));
+    Ok(expr)
+  }
+
+  fn parse_assign_expression(&mut self, target: Expression) -> Result<Expression, ParseError> {
+    self.check_assign_target(&target)?;
+
+    let operator = match self.current_token {
+      token::Token::PlusAssign => Some(Infix::Plus),
+      token::Token::MinusAssign => Some(Infix::Minus),
+      token::Token::AsteriskAssign => Some(Infix::Asterisk),
+      token::Token::SlashAssign => Some(Infix::Slash),
+      _ => None,
     };
 
-    let expr = Expression::Infix(InfixExpression::new(Box::new(left), operator, Box::new(right)));
-    Some(expr)
+    self.next_token();
+
+    let value = self.parse_expression(BinaryOperator::Assign)?;
+
+    let span = target.span().to(value.span());
+    Ok(Expression::Assign(AssignExpression::new(Box::new(target), operator, Box::new(value), span)))
   }
 
-  fn parse_grouped_expression(&mut self) -> Option<Expression> {
+  fn parse_ternary_expression(&mut self, condition: Expression) -> Result<Expression, ParseError> {
     self.next_token();
 
-    let left = self.parse_expression(BinaryOperator::Lowest);
+    let consequence = self.parse_expression(BinaryOperator::Lowest)?;
 
-    if !self.expect_peek(token::Token::RPAREN) {
-      return None;
-    }
+    self.expect_peek(token::Token::COLON)?;
+
+    self.next_token();
+
+    let alternative = self.parse_expression(BinaryOperator::Ternary)?;
 
-    left
+    let span = condition.span().to(alternative.span());
+    Ok(Expression::Ternary(TernaryExpression::new(Box::new(condition), Box::new(consequence), Box::new(alternative), span)))
   }
 
-  fn parse_if_expression(&mut self) -> Option<Expression> {
-    if !self.expect_peek(token::Token::LPAREN) {
-      return None;
-    }
+  fn parse_range_expression(&mut self, start: Expression) -> Result<Expression, ParseError> {
+    let inclusive = self.current_token.is(token::Token::DotDotEq);
 
     self.next_token();
 
-    let condition = match self.parse_expression(BinaryOperator::Lowest) {
-      Some(expr) => expr,
-      None => return None,
-    };
+    let end = self.parse_expression(BinaryOperator::Range)?;
 
-    if !self.expect_peek(token::Token::RPAREN) {
-      return None;
-    }
+    let span = start.span().to(end.span());
+    Ok(Expression::Range(RangeExpression::new(Box::new(start), Box::new(end), inclusive, span)))
+  }
 
-    if !self.expect_peek(token::Token::LBRACE) {
-      return None;
-    }
+  fn parse_grouped_expression(&mut self) -> Result<Expression, ParseError> {
+    self.next_token();
+
+    let left = self.parse_expression(BinaryOperator::Lowest)?;
+
+    self.expect_peek(token::Token::RPAREN)?;
+
+    Ok(left)
+  }
+
+  fn parse_if_expression(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LPAREN)?;
+
+    self.next_token();
+
+    let condition = self.parse_expression(BinaryOperator::Lowest)?;
+
+    self.expect_peek(token::Token::RPAREN)?;
+
+    self.expect_peek(token::Token::LBRACE)?;
 
     let consequence = self.parse_block_statement();
 
@@ -244,164 +428,298 @@ impl Parser {
     // self.expect_peek() is not used
     let alternative = if self.peek_token.is(token::Token::ELSE) {
       self.next_token();
-      if !self.expect_peek(token::Token::LBRACE) {
-        return None;
-      }
+      self.expect_peek(token::Token::LBRACE)?;
       Some(self.parse_block_statement())
     } else {
       None
     };
 
-    Some(
+    Ok(
       Expression::If(
-        IfExpression::new(Box::new(condition), consequence, alternative),
+        IfExpression::new(Box::new(condition), consequence, alternative, start.to(self.current_span)),
       ),
     )
   }
 
-  fn parse_func_literal(&mut self) -> Option<Expression> {
-    if !self.expect_peek(token::Token::LPAREN) {
-      return None;
+  fn parse_try_expression(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let block = self.parse_block_statement();
+
+    self.expect_peek(token::Token::CATCH)?;
+    self.expect_peek(token::Token::LPAREN)?;
+    let param = self.expect_ident_peek()?;
+
+    self.expect_peek(token::Token::RPAREN)?;
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let handler = self.parse_block_statement();
+
+    Ok(
+      Expression::Try(
+        TryExpression::new(block, param, handler, start.to(self.current_span)),
+      ),
+    )
+  }
+
+  // 腕はカンマで区切り、最後の腕の後ろにもカンマを書ける
+  fn parse_match_expression(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LPAREN)?;
+    self.next_token();
+    let subject = self.parse_expression(BinaryOperator::Lowest)?;
+    self.expect_peek(token::Token::RPAREN)?;
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let mut arms = vec![];
+    while !self.peek_token.is(token::Token::RBRACE) {
+      self.next_token();
+      let arm_start = self.current_span;
+      let pattern = self.parse_pattern(true)?;
+      self.expect_peek(token::Token::ARROW)?;
+      self.next_token();
+      let body = self.parse_expression(BinaryOperator::Lowest)?;
+      arms.push(MatchArm::new(pattern, body, arm_start.to(self.current_span)));
+      if !self.peek_token.is(token::Token::COMMA) {
+        break;
+      }
+      self.next_token();
     }
+    self.expect_peek(token::Token::RBRACE)?;
 
-    let args = match self.parse_func_args() {
-      Some(args) => args,
-      None => return None,
-    };
+    Ok(
+      Expression::Match(
+        MatchExpression::new(Box::new(subject), arms, start.to(self.current_span)),
+      ),
+    )
+  }
+
+  fn parse_func_literal(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+    let func = self.parse_func(start)?;
+    Ok(Expression::Literal(Literal::Func(func)))
+  }
 
-    if !self.expect_peek(token::Token::LBRACE) {
-      return None;
+  // fn 文では、名前の次の引数から読む
+  pub(super) fn parse_func(&mut self, start: Span) -> Result<Func, ParseError> {
+    self.expect_peek(token::Token::LPAREN)?;
+
+    let (args, defaults) = self.parse_func_args()?;
+
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let body = self.parse_block_statement();
+
+    Ok(Func::new(args, defaults, body, start.to(self.current_span)))
+  }
+
+  // マクロの引数にはデフォルト値を書けない
+  fn parse_macro_literal(&mut self) -> Result<Expression, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LPAREN)?;
+
+    let mut args = vec![];
+    if !self.peek_token.is(token::Token::RPAREN) {
+      loop {
+        self.next_token();
+        args.push(self.only_parse_identifier()?);
+        if !self.peek_token.is(token::Token::COMMA) {
+          break;
+        }
+        self.next_token();
+      }
     }
+    self.expect_peek(token::Token::RPAREN)?;
+
+    self.expect_peek(token::Token::LBRACE)?;
 
     let body = self.parse_block_statement();
 
-    Some(
+    Ok(
       Expression::Literal(
-        Literal::Func(
-          Func::new(args, body),
+        Literal::Macro(
+          Macro::new(args, body, start.to(self.current_span)),
         )
       ),
     )
   }
 
-  fn parse_func_args(&mut self) -> Option<Vec<Identifier>> {
+  fn parse_func_args(&mut self) -> Result<(Vec<Identifier>, Vec<Option<Expression>>), ParseError> {
     let mut args: Vec<Identifier> = vec![];
+    let mut defaults: Vec<Option<Expression>> = vec![];
 
     if self.peek_token.is(token::Token::RPAREN) {
       self.next_token();
-      return Some(args);
+      return Ok((args, defaults));
     }
 
-    self.next_token();
+    loop {
+      self.next_token();
 
-    let ident = match self.only_parse_identifier() {
-      Some(ident) => ident,
-      None => return None,
-    };
-    args.push(ident);
+      let ident = self.only_parse_identifier()?;
+      args.push(ident);
 
-    while self.peek_token.is(token::Token::COMMA) {
-      self.next_token();
-      self.next_token();
-  
-      let ident = match self.only_parse_identifier() {
-        Some(ident) => ident,
-        None => return None,
+      // fn(x, y = 10)
+      let default = if self.peek_token.is(token::Token::ASSIGN) {
+        self.next_token();
+        self.next_token();
+        Some(self.parse_expression(BinaryOperator::Lowest)?)
+      } else {
+        None
       };
-      args.push(ident);
-    }
+      defaults.push(default);
 
-    if !self.expect_peek(token::Token::RPAREN) {
-      return None;
+      if !self.peek_token.is(token::Token::COMMA) {
+        break;
+      }
+      self.next_token();
     }
 
-    Some(args)
+    self.expect_peek(token::Token::RPAREN)?;
+
+    Ok((args, defaults))
   }
 
-  fn only_parse_identifier(&mut self) -> Option<Identifier> {
-    let ident_str = match &self.current_token {
-      token::Token::IDENT(s) => s,
-      _ => {
-        self.not_support_literal_error("args");
-        return None;
-      }
+  fn only_parse_identifier(&mut self) -> Result<Identifier, ParseError> {
+    let ident = match &self.current_token {
+      token::Token::IDENT(s) => *s,
+      _ => return Err(self.not_support_literal_error("args")),
     };
 
-    Some(Identifier::new(ident_str.to_string()))
+    Ok(Identifier::new(ident, self.current_span))
   }
 
-  fn parse_call_expression(&mut self, func: Expression) -> Option<Expression> {
-    let args = match self.parse_expression_list(token::Token::RPAREN) {
-      Some(args) => args,
-      None => return None,
-    };
-    Some(
+  fn parse_call_expression(&mut self, func: Expression) -> Result<Expression, ParseError> {
+    let args = self.parse_expression_list(token::Token::RPAREN)?;
+    let span = func.span().to(self.current_span);
+    Ok(
       Expression::Call(
-        CallExpression::new(Box::new(func), args),
+        CallExpression::new(Box::new(func), args, span),
       ),
     )
   }
 
-  fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+  // 名前の後に引数が続けばメソッド呼び出し、続かなければハッシュの値の参照になる
+  fn parse_dot_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+    let name = self.expect_ident_peek()?;
+    if !self.peek_token.is(token::Token::LPAREN) {
+      let span = left.span().to(name.span);
+      return Ok(Expression::Member(MemberExpression::new(Box::new(left), name, span)));
+    }
     self.next_token();
-    let idx = if let Some(idx) = self.parse_expression(BinaryOperator::Lowest) {
-      idx
-    } else {
-      return None;
-    };
+    let args = self.parse_expression_list(token::Token::RPAREN)?;
+    let span = left.span().to(self.current_span);
+    Ok(
+      Expression::MethodCall(
+        MethodCallExpression::new(Box::new(left), name, args, span),
+      ),
+    )
+  }
+
+  fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+    // arr[:end]
+    if self.peek_token.is(token::Token::COLON) {
+      self.next_token();
+      return self.parse_slice_expression(left, None);
+    }
+
+    self.next_token();
+    let idx = self.parse_expression(BinaryOperator::Lowest)?;
 
-    if !self.expect_peek(token::Token::RBRACKET) {
-      return None;
+    if self.peek_token.is(token::Token::COLON) {
+      self.next_token();
+      return self.parse_slice_expression(left, Some(Box::new(idx)));
     }
 
-    Some(
+    self.expect_peek(token::Token::RBRACKET)?;
+
+    let span = left.span().to(self.current_span);
+    Ok(
       Expression::Index(
-        IndexExpression::new(Box::new(left), Box::new(idx)),
+        IndexExpression::new(Box::new(left), Box::new(idx), span),
       ),
     )
   }
 
-  fn parse_expression_list(&mut self, end_token: token::Token) -> Option<Vec<Expression>> {
+  // current_token は `:` を指している
+  fn parse_slice_expression(&mut self, left: Expression, start: Option<Box<Expression>>) -> Result<Expression, ParseError> {
+    let end = if self.peek_token.is(token::Token::RBRACKET) {
+      None
+    } else {
+      self.next_token();
+      Some(Box::new(self.parse_expression(BinaryOperator::Lowest)?))
+    };
+
+    self.expect_peek(token::Token::RBRACKET)?;
+
+    let span = left.span().to(self.current_span);
+    Ok(Expression::Slice(SliceExpression::new(Box::new(left), start, end, span)))
+  }
+
+  fn parse_expression_list(&mut self, end_token: token::Token) -> Result<Vec<Expression>, ParseError> {
     let mut list = vec![];
     if self.peek_token.is(end_token.clone()) {
       self.next_token();
-      return Some(list);
+      return Ok(list);
     }
 
     self.next_token();
     
-    let item = match self.parse_expression(BinaryOperator::Lowest) {
-      Some(expr) => expr,
-      None => return None,
-    };
+    let item = self.parse_list_item()?;
     list.push(item);
 
     while self.peek_token.is(token::Token::COMMA) {
       self.next_token();
       self.next_token();
 
-      let item = match self.parse_expression(BinaryOperator::Lowest) {
-        Some(expr) => expr,
-        None => return None,
-      };
+      let item = self.parse_list_item()?;
       list.push(item);
     }
 
-    if !self.expect_peek(end_token) {
-      return None;
+    self.expect_peek(end_token)?;
+
+    Ok(list)
+  }
+
+  // 配列リテラルと関数呼び出しの要素では ...xs で配列を展開できる
+  fn parse_list_item(&mut self) -> Result<Expression, ParseError> {
+    if !self.current_token.is(token::Token::ELLIPSIS) {
+      return self.parse_expression(BinaryOperator::Lowest);
     }
 
-    Some(list)
+    let start = self.current_span;
+    self.next_token();
+
+    let value = self.parse_expression(BinaryOperator::Lowest)?;
+
+    let span = start.to(value.span());
+    Ok(Expression::Spread(SpreadExpression::new(Box::new(value), span)))
+  }
+
+  fn no_prefix_parse_error(&self) -> ParseError {
+    ParseError::NoPrefixParseFn {
+      found: self.current_token.clone(),
+      span: self.current_span,
+    }
   }
 
-  fn no_prefix_parse_error(&mut self) {
-    let msg = format!("no prefix parse function for {:?}.", self.current_token);
-    self.errors.push(msg);
+  fn no_infix_parse_error(&self) -> ParseError {
+    ParseError::NoInfixParseFn {
+      found: self.current_token.clone(),
+      span: self.current_span,
+    }
   }
 
-  fn not_support_literal_error(&mut self, place: &str) {
-    let msg = format!("{:?} is not supported in {:?}.", self.current_token, place);
-    self.errors.push(msg);
+  fn not_support_literal_error(&self, place: &str) -> ParseError {
+    ParseError::Unsupported {
+      found: self.current_token.clone(),
+      place: place.to_string(),
+      span: self.current_span,
+    }
   }
 }
 
@@ -428,7 +746,7 @@ pub fn test_identifier(expr: &Expression, value: &str) {
     _ => panic!("Expression should has Identifier, got {}", &expr)
   };
 
-  if &ident.value != value {
+  if ident.value.as_str() != value {
     panic!("Identifier should has {}, but got {}", value, &ident.value);
   }
 }
@@ -462,7 +780,7 @@ pub fn test_string_literal(expr: &Expression, comp: &str) {
     _ => panic!("Literal should has Integer, got {}", &lit)
   };
 
-  if &s.value != comp {
+  if s.value != comp {
     panic!("Expression should has {}, but got {}", comp, s.value);
   }
 }
@@ -486,8 +804,9 @@ pub fn test_boolean(expr: &Expression, comp: &bool) {
 
 #[cfg(test)]
 mod tests {
-  use crate::ast::stmt::{Statement};
+  use crate::ast::stmt::{Statement, ExpressionStatement};
   use crate::lexer;
+  use crate::span::{Position, Span};
   use super::*;
 
   #[test]
@@ -538,6 +857,35 @@ mod tests {
     test_integer_literal(&expr.value, &5);
   }
   
+  #[test]
+  fn test_parse_float_literal_expression() {
+    let tests = vec![("1.5;", 1.5), ("0.25;", 0.25), ("2e3;", 2000.0)];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      let expr = match &program.statements[0] {
+        Statement::Expr(expr) => expr,
+        _ => panic!("program.statements should has ExpressionStatement, but got {:?}", program.statements[0]),
+      };
+
+      let float = match &expr.value {
+        Expression::Literal(Literal::Float(float)) => float,
+        _ => panic!("Expression should has Float, but got {}", &expr.value),
+      };
+
+      if float.value != expected {
+        panic!("Float should has {}, but got {}", expected, float.value);
+      }
+    }
+  }
+
   #[test]
   fn test_parse_boolean_expression() {
     let input = "
@@ -545,7 +893,7 @@ true;
 false;
 ";
 
-    let tests = vec![true, false];
+    let tests = [true, false];
 
     let l = lexer::Lexer::new(input.to_string());
     let mut p = Parser::new(l);
@@ -669,6 +1017,89 @@ false;
     );
   }
 
+  #[test]
+  fn test_parse_nesting_limit() {
+    let nested = |n: usize| format!("{}1{}", "(".repeat(n), ")".repeat(n));
+
+    let l = lexer::Lexer::new(nested(crate::parser::MAX_NESTING_DEPTH - 1));
+    let mut p = Parser::new(l);
+    p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    for input in [nested(100_000), "-".repeat(100_000) + "1"].iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      p.parse_program();
+
+      assert_eq!(p.errors.len(), 1, "errors={:?}", p.errors);
+      assert_eq!(format!("{}", p.errors[0]), "nested too deeply.");
+    }
+
+    let parse = |input: &str| {
+      let mut p = Parser::new(lexer::Lexer::new(input.to_string()));
+      p.set_max_depth(3);
+      p.parse_program();
+      p.errors
+    };
+    assert!(parse(&nested(2)).is_empty());
+    assert_eq!(parse(&nested(3)).iter().map(|err| err.span().start.column).collect::<Vec<usize>>(), vec![4]);
+  }
+
+  #[test]
+  fn test_parse_spread_expression() {
+    let tests = vec![
+      ("[1, ...xs, 9]", "[1, ...xs, 9]"),
+      ("f(...args)", "f(...args)"),
+      ("f(a, ...b + c)", "f(a, ...(b + c))"),
+      ("[...f(...xs)]", "[...f(...xs)]"),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+      assert_eq!(format!("{}", program), expected);
+    }
+  }
+
+  #[test]
+  fn test_parse_slice_expression() {
+    let tests = vec![
+      ("arr[1:3]", "(arr[1:3])"),
+      ("str[2:]", "(str[2:])"),
+      ("arr[:-1]", "(arr[:(-1)])"),
+      ("arr[:]", "(arr[:])"),
+      ("arr[a ? 1 : 2:n + 1]", "(arr[(a ? 1 : 2):(n + 1)])"),
+      ("f()[1:][0]", "((f()[1:])[0])"),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      match &program.statements[0] {
+        Statement::Expr(ExpressionStatement { value: Expression::Slice(_), .. }) => {},
+        Statement::Expr(ExpressionStatement { value: Expression::Index(index), .. }) => match &*index.left {
+          Expression::Slice(_) => {},
+          _ => panic!("index.left should has SliceExpression, but got {:?}", index.left),
+        },
+        _ => panic!("program.statements[0] should has SliceExpression, but got {:?}", program.statements[0]),
+      };
+      assert_eq!(format!("{}", program), expected);
+    }
+  }
+
   #[test]
   fn test_parse_hash_string_keys() {
     let input = "{\"one\": 1, \"two\": 2, \"three\": 3}";
@@ -833,7 +1264,8 @@ false;
       panic!("Hash literal should has 3 pairs, but got {}", hash_lit.pairs.len());
     }
 
-    let expected: Vec<(String, fn(expr: &Expression))> = vec![
+    type TestExpression = fn(expr: &Expression);
+    let expected: Vec<(String, TestExpression)> = vec![
       ("one".into(), |expr| {
         test_infix_expression(
           expr,
@@ -895,7 +1327,7 @@ false;
       _ => panic!("Expression should has Str literal, but got {:?}", expr.value),
     };
 
-    if hash_lit.pairs.len() != 0 {
+    if !hash_lit.pairs.is_empty() {
       panic!("Hash literal should has 3 pairs, but got {}", hash_lit.pairs.len());
     }
   }
@@ -953,7 +1385,7 @@ false;
         _ => panic!("Expression should has PrefixExpression, got {:?}", &expr.value)
       };
 
-      if &pre.operator != &tt.operator {
+      if pre.operator != tt.operator {
         panic!("Prefix should has '{:?}', but got '{:?}'", &tt.operator, &pre.operator);
       }
 
@@ -1007,28 +1439,52 @@ false;
         right: ExpressionLiteral::Int(5),
       },
       InfixExpressionTest { 
-        input: "5 == 5;".to_string(),
+        input: "5 % 5;".to_string(),
         left: ExpressionLiteral::Int(5),
-        operator: Infix::Equal,
+        operator: Infix::Percent,
         right: ExpressionLiteral::Int(5),
       },
       InfixExpressionTest { 
-        input: "5 != 5;".to_string(),
+        input: "5 ** 5;".to_string(),
         left: ExpressionLiteral::Int(5),
-        operator: Infix::NotEq,
+        operator: Infix::Power,
         right: ExpressionLiteral::Int(5),
       },
       InfixExpressionTest { 
-        input: "true == true;".to_string(),
-        left: ExpressionLiteral::Bool(true),
-        operator: Infix::Equal,
-        right: ExpressionLiteral::Bool(true),
+        input: "5 >= 5;".to_string(),
+        left: ExpressionLiteral::Int(5),
+        operator: Infix::GtEq,
+        right: ExpressionLiteral::Int(5),
       },
       InfixExpressionTest { 
-        input: "true != false;".to_string(),
-        left: ExpressionLiteral::Bool(true),
-        operator: Infix::NotEq,
-        right: ExpressionLiteral::Bool(false),
+        input: "5 <= 5;".to_string(),
+        left: ExpressionLiteral::Int(5),
+        operator: Infix::LtEq,
+        right: ExpressionLiteral::Int(5),
+      },
+      InfixExpressionTest { 
+        input: "5 == 5;".to_string(),
+        left: ExpressionLiteral::Int(5),
+        operator: Infix::Equal,
+        right: ExpressionLiteral::Int(5),
+      },
+      InfixExpressionTest { 
+        input: "5 != 5;".to_string(),
+        left: ExpressionLiteral::Int(5),
+        operator: Infix::NotEq,
+        right: ExpressionLiteral::Int(5),
+      },
+      InfixExpressionTest { 
+        input: "true == true;".to_string(),
+        left: ExpressionLiteral::Bool(true),
+        operator: Infix::Equal,
+        right: ExpressionLiteral::Bool(true),
+      },
+      InfixExpressionTest { 
+        input: "true != false;".to_string(),
+        left: ExpressionLiteral::Bool(true),
+        operator: Infix::NotEq,
+        right: ExpressionLiteral::Bool(false),
       },
       InfixExpressionTest { 
         input: "false == false;".to_string(),
@@ -1036,6 +1492,24 @@ false;
         operator: Infix::Equal,
         right: ExpressionLiteral::Bool(false),
       },
+      InfixExpressionTest { 
+        input: "true && false;".to_string(),
+        left: ExpressionLiteral::Bool(true),
+        operator: Infix::And,
+        right: ExpressionLiteral::Bool(false),
+      },
+      InfixExpressionTest { 
+        input: "true || false;".to_string(),
+        left: ExpressionLiteral::Bool(true),
+        operator: Infix::Or,
+        right: ExpressionLiteral::Bool(false),
+      },
+      InfixExpressionTest { 
+        input: "a ?? 5;".to_string(),
+        left: ExpressionLiteral::Str("a".to_string()),
+        operator: Infix::Coalesce,
+        right: ExpressionLiteral::Int(5),
+      },
     ];
 
     for tt in infix_expression_tests.into_iter() {
@@ -1068,7 +1542,7 @@ false;
     
     test_literal_expression(&inf.left, left);
     
-    if &inf.operator != &operator {
+    if inf.operator != operator {
       panic!("Infix should has '{:?}', but got '{:?}'", &operator, &inf.operator);
     }
 
@@ -1086,6 +1560,10 @@ false;
         input: "true".to_string(),
         expected: "true".to_string(),
       },
+      PrecedenceTest {
+        input: "try { a + b } catch (err) { err }".to_string(),
+        expected: "try { (a + b) } catch (err) { err }".to_string(),
+      },
       PrecedenceTest { 
         input: "false".to_string(),
         expected: "false".to_string(),
@@ -1142,6 +1620,10 @@ false;
         input: "5 < 4 != 3 > 4".to_string(),
         expected: "((5 < 4) != (3 > 4))".to_string(),
       },
+      PrecedenceTest { 
+        input: "a + 1 <= b * 2 == c >= d - 1".to_string(),
+        expected: "(((a + 1) <= (b * 2)) == (c >= (d - 1)))".to_string(),
+      },
       PrecedenceTest { 
         input: "3 + 4 * 5 == 3 * 1 + 4 * 5".to_string(),
         expected: "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))".to_string(),
@@ -1174,6 +1656,182 @@ false;
         input: "add(a * b[2], b[1], 2 * [1, 2][1])".to_string(),
         expected: "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))".to_string(),
       },
+      PrecedenceTest { 
+        input: "a[1][2] + f()[0]".to_string(),
+        expected: "(((a[1])[2]) + (f()[0]))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a + add(b * c) + d".to_string(),
+        expected: "((a + add((b * c))) + d)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "add(a, b, 1, 2 * 3, 4 + 5, add(6, 7 * 8))".to_string(),
+        expected: "add(a, b, 1, (2 * 3), (4 + 5), add(6, (7 * 8)))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "add(a + b + c * d / f + g)".to_string(),
+        expected: "add((((a + b) + ((c * d) / f)) + g))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "adder(1)(2)".to_string(),
+        expected: "adder(1)(2)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a + b % c * d".to_string(),
+        expected: "(a + ((b % c) * d))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ** b ** c".to_string(),
+        expected: "(a ** (b ** c))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a * b ** c * d".to_string(),
+        expected: "((a * (b ** c)) * d)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "-a ** b".to_string(),
+        expected: "(-(a ** b))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ** -b ** c".to_string(),
+        expected: "(a ** (-(b ** c)))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a[0] ** f(b)".to_string(),
+        expected: "((a[0]) ** f(b))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x = y = 1".to_string(),
+        expected: "(x = (y = 1))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x = a || b && c == d + 1".to_string(),
+        expected: "(x = (a || (b && (c == (d + 1)))))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x += y *= 2 + 1".to_string(),
+        expected: "(x += (y *= (2 + 1)))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "-x++".to_string(),
+        expected: "(-(x++))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "++a[0] + b--".to_string(),
+        expected: "((++(a[0])) + (b--))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x++ * 2".to_string(),
+        expected: "((x++) * 2)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "f(x = 1)".to_string(),
+        expected: "f((x = 1))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "1..n + 1".to_string(),
+        expected: "(1..(n + 1))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a * 2..=b".to_string(),
+        expected: "((a * 2)..=b)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x == 0..1".to_string(),
+        expected: "(x == (0..1))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "(0..10)[3]".to_string(),
+        expected: "((0..10)[3])".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ? b : c".to_string(),
+        expected: "(a ? b : c)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ? b : c ? d : e".to_string(),
+        expected: "(a ? b : (c ? d : e))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ? b ? 1 : 2 : 3".to_string(),
+        expected: "(a ? (b ? 1 : 2) : 3)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x = a < b || c ? d + 1 : e ?? f".to_string(),
+        expected: "(x = (((a < b) || c) ? (d + 1) : (e ?? f)))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ? x = 1 : y".to_string(),
+        expected: "(a ? (x = 1) : y)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "{a ? b : c: d}".to_string(),
+        expected: "{(a ? b : c): d}".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a ?? b || c ?? d".to_string(),
+        expected: "((a ?? (b || c)) ?? d)".to_string(),
+      },
+      PrecedenceTest { 
+        input: "x = a ?? null".to_string(),
+        expected: "(x = (a ?? null))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a || b && c".to_string(),
+        expected: "(a || (b && c))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a && b || c && d".to_string(),
+        expected: "((a && b) || (c && d))".to_string(),
+      },
+      PrecedenceTest { 
+        input: "a < b && !c == d || e".to_string(),
+        expected: "(((a < b) && ((!c) == d)) || e)".to_string(),
+      },
+      PrecedenceTest {
+        input: "a | b ^ c & d".to_string(),
+        expected: "(a | (b ^ (c & d)))".to_string(),
+      },
+      PrecedenceTest {
+        input: "a & b << c + d".to_string(),
+        expected: "(a & (b << (c + d)))".to_string(),
+      },
+      PrecedenceTest {
+        input: "a >> 1 == b | c & 1".to_string(),
+        expected: "((a >> 1) == (b | (c & 1)))".to_string(),
+      },
+      PrecedenceTest {
+        input: "~a & -b ** 2".to_string(),
+        expected: "((~a) & (-(b ** 2)))".to_string(),
+      },
+      PrecedenceTest {
+        input: "0..1 << n".to_string(),
+        expected: "(0..(1 << n))".to_string(),
+      },
+      PrecedenceTest {
+        input: "-a.b(1)[0] + c.d().e(f.g())".to_string(),
+        expected: "((-(a.b(1)[0])) + c.d().e(f.g()))".to_string(),
+      },
+      PrecedenceTest {
+        input: "[1, 2].map(fn(x) { x * 2 }).len()".to_string(),
+        expected: "[1, 2].map(fn(x) { (x * 2) }).len()".to_string(),
+      },
+      PrecedenceTest {
+        input: "-a.b.c + d.e[0].f".to_string(),
+        expected: "((-a.b.c) + (d.e[0]).f)".to_string(),
+      },
+      PrecedenceTest {
+        input: "-Point { x: 1, y: f(2) }.x * 3".to_string(),
+        expected: "((-Point {x: 1, y: f(2)}.x) * 3)".to_string(),
+      },
+      PrecedenceTest {
+        input: "if (a) { b } else { c }; while (a) { b }".to_string(),
+        expected: "if(a) { b } else { c }while(a) { b }".to_string(),
+      },
+      PrecedenceTest {
+        input: "a.b.c = d.e += 1".to_string(),
+        expected: "(a.b.c = (d.e += 1))".to_string(),
+      },
     ];
 
     for tt in precedence_tests.iter() {
@@ -1192,6 +1850,34 @@ false;
     }
   }
 
+  #[test]
+  fn test_parse_match_expression() {
+    let tests = vec![
+      ("match (x) { 0 => a, -1.5 => b, \"s\" => c, null => d, other => e }", "match (x) { 0 => a, -1.5 => b, \"s\" => c, null => d, other => e }"),
+      ("match (xs) { [] => 0, [head, ...tail] => head + 1, }", "match (xs) { [] => 0, [head, ...tail] => (head + 1) }"),
+      ("match (p) { {kind: \"circle\", r} => r, {} => true }", "match (p) { {kind: \"circle\", r} => r, {} => true }"),
+      ("match (x) {}", "match (x) {  }"),
+    ];
+    for (input, expected) in tests {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!("input={}", input);
+      }
+      assert_eq!(format!("{}", program), expected);
+    }
+
+    let l = lexer::Lexer::new("match (x) { 1 => 2 3 => 4 }".to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert_eq!(p.errors.first(), Some(&ParseError::UnexpectedToken {
+      expected: token::Token::RBRACE,
+      found: token::Token::INT(3),
+      span: Span::new(Position::new(19, 1, 20), Position::new(20, 1, 21)),
+    }));
+  }
+
   #[test]
   fn test_parse_if_expression() {
     let input = "if (x < y) { x };";
@@ -1242,7 +1928,7 @@ false;
 
     test_identifier(&con_expr.value, "x");
 
-    if let Some(_) = if_expr.alternative {
+    if if_expr.alternative.is_some() {
       panic!("if_expr.alternative should be None, but {:?}", if_expr.alternative);
     }
   }
@@ -1310,6 +1996,44 @@ false;
     test_identifier(&alt_expr.value, "y");
   }
 
+  #[test]
+  fn test_parse_macro_literal() {
+    let input = "macro(x, y) { x + y; }";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    let mac = match &program.statements[0] {
+      Statement::Expr(ExpressionStatement { value: Expression::Literal(Literal::Macro(mac)), .. }) => mac,
+      stmt => panic!("program.statements should has a macro literal, but got {:?}", stmt),
+    };
+    assert_eq!(mac.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(), vec!["x", "y"]);
+    assert_eq!(mac.span, Span::new(Position::new(0, 1, 1), Position::new(22, 1, 23)));
+    match &mac.body.statements[..] {
+      [Statement::Expr(expr)] => test_infix_expression(
+        &expr.value,
+        ExpressionLiteral::Str("x".to_string()),
+        Infix::Plus,
+        ExpressionLiteral::Str("y".to_string()),
+      ),
+      stmts => panic!("mac.body.statements should has only 1 statement, but got {:?}", stmts),
+    }
+
+    let l = lexer::Lexer::new("macro(x = 1) { x }".to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert_eq!(p.errors.first(), Some(&ParseError::UnexpectedToken {
+      expected: token::Token::RPAREN,
+      found: token::Token::ASSIGN,
+      span: Span::new(Position::new(8, 1, 9), Position::new(9, 1, 10)),
+    }));
+  }
+
   #[test]
   fn test_parse_func_expression() {
     let input = "fn(x, y) { x + y; }";
@@ -1427,12 +2151,288 @@ false;
     );
   }
 
+  #[test]
+  fn test_parse_assign_expression() {
+    let input = "x = 10;";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    let expr = match &program.statements[0] {
+      Statement::Expr(expr) => expr,
+      _ => panic!("program.statements should has ExpressionStatement, but got {:?}", program.statements[0]),
+    };
+
+    let assign = match &expr.value {
+      Expression::Assign(assign) => assign,
+      _ => panic!("Expression should has AssignExpression, but got {}", &expr.value),
+    };
+
+    test_identifier(&assign.target, "x");
+    assert_eq!(assign.operator, None);
+    test_integer_literal(&assign.value, &10);
+    assert_eq!(assign.span.slice(input), "x = 10");
+
+    let input = "a[0][\"k\"] = 1 + 2";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+    assert_eq!(format!("{}", program), "(((a[0])[\"k\"]) = (1 + 2))");
+
+    let tests = vec![
+      ("x += 1", Infix::Plus),
+      ("x -= 1", Infix::Minus),
+      ("x *= 1", Infix::Asterisk),
+      ("x /= 1", Infix::Slash),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      match &program.statements[0] {
+        Statement::Expr(ExpressionStatement { value: Expression::Assign(assign), .. }) => {
+          test_identifier(&assign.target, "x");
+          assert_eq!(assign.operator, Some(expected));
+          test_integer_literal(&assign.value, &1);
+        },
+        stmt => panic!("program.statements should has AssignExpression, but got {:?}", stmt),
+      }
+      assert_eq!(format!("{}", program), format!("({})", input));
+    }
+
+    let tests = vec![
+      ("1 = 2", "1"),
+      ("a + b = 2", "(a + b)"),
+      ("f() = 2", "f()"),
+      ("f()[0] = 2", "(f()[0])"),
+      ("1 += 2", "1"),
+    ];
+
+    for (input, target) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      p.parse_program();
+
+      match &p.errors[..] {
+        [ParseError::InvalidAssignTarget { target: actual, .. }] =>
+          assert_eq!(actual, target, "input={}", input),
+        _ => panic!("parser should has InvalidAssignTarget error, but got {:?}", p.errors),
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_update_expression() {
+    let tests = vec![
+      ("++x", Update::Increment, true, "x"),
+      ("--x", Update::Decrement, true, "x"),
+      ("x++", Update::Increment, false, "x"),
+      ("x--", Update::Decrement, false, "x"),
+      ("a[1]++", Update::Increment, false, "(a[1])"),
+    ];
+
+    for (input, operator, prefix, target) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      let update = match &program.statements[0] {
+        Statement::Expr(ExpressionStatement { value: Expression::Update(update), .. }) => update,
+        stmt => panic!("program.statements should has UpdateExpression, but got {:?}", stmt),
+      };
+
+      assert_eq!(update.operator, operator, "input={}", input);
+      assert_eq!(update.prefix, prefix, "input={}", input);
+      assert_eq!(format!("{}", update.target), target, "input={}", input);
+      assert_eq!(update.span.slice(input), input);
+    }
+
+    for input in vec!["1++", "++f()", "++x++", "(a + b)--"].into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      p.parse_program();
+
+      match &p.errors[..] {
+        [ParseError::InvalidAssignTarget { .. }, ..] => (),
+        _ => panic!("parser should has InvalidAssignTarget error, but got {:?}: input={}", p.errors, input),
+      }
+    }
+  }
+
+  #[test]
+  fn test_parse_nested_call_expression() {
+    let input = "add(1, mul(2, 3))(fn(x) { x })";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 1 {
+      panic!("program.statements should has only 1 statement, but got {}", program.statements.len());
+    }
+
+    let expr = match &program.statements[0] {
+      Statement::Expr(expr) => expr,
+      _ => panic!("program.statements should has ExpressionStatement, but got {:?}", program.statements[0]),
+    };
+
+    let outer = match &expr.value {
+      Expression::Call(call) => call,
+      _ => panic!("Expression should has Call, but got {:?}", &expr.value),
+    };
+
+    if outer.args.len() != 1 {
+      panic!("outer.args should has only 1 argument, but got {}", outer.args.len());
+    }
+
+    match &outer.args[0] {
+      Expression::Literal(Literal::Func(_)) => (),
+      _ => panic!("outer.args[0] should be Func literal, but got {:?}", &outer.args[0]),
+    };
+
+    let inner = match &*outer.func {
+      Expression::Call(call) => call,
+      _ => panic!("outer.func should has Call, but got {:?}", &outer.func),
+    };
+
+    test_identifier(&inner.func, "add");
+
+    if inner.args.len() != 2 {
+      panic!("inner.args should has only 2 arguments, but got {}", inner.args.len());
+    }
+
+    test_literal_expression(&inner.args[0], ExpressionLiteral::Int(1));
+
+    let nested = match &inner.args[1] {
+      Expression::Call(call) => call,
+      _ => panic!("inner.args[1] should has Call, but got {:?}", &inner.args[1]),
+    };
+
+    test_identifier(&nested.func, "mul");
+    test_literal_expression(&nested.args[0], ExpressionLiteral::Int(2));
+    test_literal_expression(&nested.args[1], ExpressionLiteral::Int(3));
+  }
+
+  #[test]
+  fn test_expression_spans() {
+    let input = "let add = fn(x, y) { x + y; };
+add(1, -2 * 3)[0];
+if (a) { [1, {\"b\": 2}] } else { !c }";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 3 {
+      panic!("program.statements should has 3 statements, but got {}", program.statements.len());
+    }
+
+    let slice = |span: crate::span::Span| span.slice(input).to_string();
+
+    assert_eq!(slice(program.statements[0].span()), "let add = fn(x, y) { x + y; };");
+    assert_eq!(slice(program.statements[1].span()), "add(1, -2 * 3)[0];");
+    assert_eq!(slice(program.statements[2].span()), "if (a) { [1, {\"b\": 2}] } else { !c }");
+
+    let func = match &program.statements[0] {
+      Statement::Let(let_stmt) => {
+        assert_eq!(slice(let_stmt.pattern.span()), "add");
+        match &let_stmt.value {
+          Expression::Literal(Literal::Func(func)) => func,
+          _ => panic!("let_stmt.value should be Func literal, but got {:?}", let_stmt.value),
+        }
+      },
+      _ => panic!("program.statements[0] should has LetStatement, but got {:?}", program.statements[0]),
+    };
+    assert_eq!(slice(func.span), "fn(x, y) { x + y; }");
+    assert_eq!(slice(func.args[1].span), "y");
+    assert_eq!(slice(func.body.span), "{ x + y; }");
+    assert_eq!(slice(func.body.statements[0].span()), "x + y;");
+
+    let index = match &program.statements[1] {
+      Statement::Expr(ExpressionStatement { value: Expression::Index(index), .. }) => index,
+      _ => panic!("program.statements[1] should has IndexExpression, but got {:?}", program.statements[1]),
+    };
+    assert_eq!(slice(index.span), "add(1, -2 * 3)[0]");
+    let call = match &*index.left {
+      Expression::Call(call) => call,
+      _ => panic!("index.left should has Call, but got {:?}", index.left),
+    };
+    assert_eq!(slice(call.span), "add(1, -2 * 3)");
+    assert_eq!(slice(call.args[0].span()), "1");
+    assert_eq!(slice(call.args[1].span()), "-2 * 3");
+
+    let if_expr = match &program.statements[2] {
+      Statement::Expr(ExpressionStatement { value: Expression::If(if_expr), .. }) => if_expr,
+      _ => panic!("program.statements[2] should has IfExpression, but got {:?}", program.statements[2]),
+    };
+    assert_eq!(slice(if_expr.consequence.statements[0].span()), "[1, {\"b\": 2}]");
+    assert_eq!(slice(if_expr.alternative.as_ref().unwrap().span), "{ !c }");
+    assert_eq!(if_expr.span.start.line, 3);
+  }
+
+  #[test]
+  fn test_func_default_args() {
+    let tests = vec![
+      ("fn(x, y = 10) { x + y }", "fn(x, y = 10) { (x + y) }", vec![false, true]),
+      ("fn(a = 1, b = a * 2) {}", "fn(a = 1, b = (a * 2)) ", vec![true, true]),
+      ("fn(f = fn(x = 0) { x }) {}", "fn(f = fn(x = 0) { x }) ", vec![true]),
+    ];
+
+    for (input, expected, has_default) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      let func = match &program.statements[0] {
+        Statement::Expr(ExpressionStatement { value: Expression::Literal(Literal::Func(func)), .. }) => func,
+        _ => panic!("program.statements[0] should has Func literal, but got {:?}", program.statements[0]),
+      };
+
+      let actual: Vec<bool> = func.defaults.iter().map(|d| d.is_some()).collect();
+      assert_eq!(actual, has_default);
+      assert_eq!(format!("{}", program), expected);
+    }
+  }
+
   #[test]
   fn test_func_args_expression() {
     let tests: Vec<(&str, Vec<&str>)> = vec![
       ("fn() {}", vec![]),
       ("fn(x) {}", vec!["x"]),
       ("fn(x, y, z) {}", vec!["x", "y", "z"]),
+      ("fn(x, y = 10) {}", vec!["x", "y"]),
     ];
 
     for (input, args) in tests.into_iter() {
@@ -2,9 +2,17 @@ use std::mem;
 
 use crate::{token, lexer};
 use crate::ast::{Program};
+use crate::span::Span;
 
 pub mod stmt;
 pub mod expr;
+pub mod error;
+
+use error::ParseError;
+
+/// How deeply expressions, blocks and patterns may nest by default before parsing stops with an error
+/// instead of overflowing the host stack. See `Parser::set_max_depth`.
+pub const MAX_NESTING_DEPTH: usize = 256;
 
 impl token::Token {
   pub(super) fn is(&self, token: token::Token) -> bool {
@@ -12,30 +20,51 @@ impl token::Token {
   }
 }
 
+/// Parses any bytes, e.g. the input of a fuzzer, into a program. Bytes which are not valid UTF-8 and input nested
+/// deeper than `MAX_NESTING_DEPTH` are reported as errors, so this never panics nor overflows the stack.
+pub fn parse_bytes(input: &[u8]) -> Result<Program, Vec<ParseError>> {
+  let mut p = Parser::new(lexer::Lexer::from_reader(std::io::Cursor::new(input.to_vec())));
+  let program = p.parse_program();
+  if !p.errors.is_empty() {
+    return Err(p.errors);
+  }
+  Ok(program)
+}
+
 pub struct Parser {
   l: lexer::Lexer,
   current_token: token::Token,
+  current_span: Span,
   peek_token: token::Token,
-  pub errors: Vec<String>,
+  peek_span: Span,
+  depth: usize,
+  max_depth: usize,
+  pub errors: Vec<ParseError>,
 }
 
 impl Parser {
   pub fn new(mut l: lexer::Lexer) -> Parser {
-    let current_token = l.next_token();
-    let peek_token = l.next_token();
-    Parser { l, current_token, peek_token, errors: vec![] }
+    let (current_token, current_span) = l.next_spanned_token();
+    let (peek_token, peek_span) = l.next_spanned_token();
+    Parser { l, current_token, current_span, peek_token, peek_span, depth: 0, max_depth: MAX_NESTING_DEPTH, errors: vec![] }
   }
 
   pub(super) fn next_token(&mut self) {
-    self.current_token = mem::replace(&mut self.peek_token, self.l.next_token());
+    let (tok, span) = self.l.next_spanned_token();
+    self.current_token = mem::replace(&mut self.peek_token, tok);
+    self.current_span = mem::replace(&mut self.peek_span, span);
   }
 
   pub fn parse_program(&mut self) -> Program {
     let mut program = Program::new();
     
     while !self.current_token.is(token::Token::EOF) {
-      if let Some(stmt) = self.parse_statement() {
-        program.statements.push(stmt);
+      match self.parse_statement() {
+        Ok(stmt) => program.statements.push(stmt),
+        Err(err) => {
+          self.errors.push(err);
+          self.synchronize();
+        },
       }
       self.next_token();
     }
@@ -43,33 +72,83 @@ impl Parser {
     program
   }
 
-  pub(super) fn peek_error(&mut self, t: token::Token) {
-    let msg = format!("expected next token to be {:?}, got {:?} instead.", t, self.peek_token);
-    self.errors.push(msg);
-  }
+  /// Skips the rest of a statement which failed to parse, so that one mistake
+  /// is reported once instead of cascading into errors for the following tokens.
+  /// Stops at the `;` ending the statement, before the `}` closing the enclosing block,
+  /// or after a block opened inside the statement is closed.
+  pub(super) fn synchronize(&mut self) {
+    let mut depth = 0;
+    loop {
+      match self.current_token {
+        token::Token::EOF => return,
+        token::Token::SEMICOLON if depth == 0 => return,
+        token::Token::LBRACE => depth += 1,
+        token::Token::RBRACE => {
+          if depth <= 1 {
+            if depth == 1 && self.peek_token.is(token::Token::SEMICOLON) {
+              self.next_token();
+            }
+            return;
+          }
+          depth -= 1;
+        },
+        _ => (),
+      }
+
+      if depth == 0 && (self.peek_token.is(token::Token::RBRACE) || self.peek_token.is(token::Token::EOF)) {
+        return;
+      }
 
-  pub(super) fn expect_peek(&mut self, t: token::Token) -> bool {
-    if self.peek_token == t {
       self.next_token();
-      true
-    } else {
-      self.peek_error(t);
-      false
     }
   }
 
+  /// Sets how deeply expressions, blocks and patterns may nest, which is `MAX_NESTING_DEPTH` by default.
+  /// Each level uses some of the host stack, so a much larger limit may overflow it on a deeply nested input.
+  pub fn set_max_depth(&mut self, max: usize) {
+    self.max_depth = max;
+  }
+
+  /// Fails with `TooDeeplyNested` at the current token if the nesting has reached the limit.
+  pub(super) fn check_depth(&self) -> Result<(), ParseError> {
+    if self.depth < self.max_depth {
+      return Ok(());
+    }
+    Err(ParseError::TooDeeplyNested {
+      found: self.current_token.clone(),
+      span: self.current_span,
+    })
+  }
+
+  pub(super) fn peek_error(&self, t: token::Token) -> ParseError {
+    ParseError::UnexpectedToken {
+      expected: t,
+      found: self.peek_token.clone(),
+      span: self.peek_span,
+    }
+  }
+
+  /// Moves to the next token if it is `t`, or fails without moving.
+  pub(super) fn expect_peek(&mut self, t: token::Token) -> Result<(), ParseError> {
+    if self.peek_token != t {
+      return Err(self.peek_error(t));
+    }
+    self.next_token();
+    Ok(())
+  }
+
   pub fn check_parse_errors(&self) -> bool {
     let errs = &self.errors;
-    if errs.len() == 0 {
+    if errs.is_empty() {
       return true;
     }
 
     eprintln!("error: Parser has {} errors", errs.len());
-    for err in errs.into_iter() {
-      eprintln!("error: Parser Error: {}", err);
+    for err in errs.iter() {
+      eprintln!("error: Parser Error: {}: {}", err.span(), err);
     }
     eprintln!("error: {} parser errors occurred.", errs.len());
 
-    return false;
+    false
   }
 }
@@ -1,42 +1,42 @@
 use super::{Parser};
+use super::error::ParseError;
 use crate::{token};
 use crate::ast::stmt::*;
 use crate::ast::ident::{Identifier};
+use crate::ast::lit::{Literal, Integer, Float, Str, Boolean, Null};
 use crate::ast::operator::{BinaryOperator};
 
 impl Parser {
-  pub(super) fn parse_statement(&mut self) -> Option<Statement> {
+  pub(super) fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+    // ブロックの中の文は式を通らずに入れ子になるので、ここでも深さを確かめる
+    self.check_depth()?;
     match &self.current_token {
-      token::Token::LET => self.parse_let_statement(),
+      token::Token::LET | token::Token::CONST => self.parse_let_statement(),
+      // 名前のない fn は関数のリテラルの式
+      token::Token::FUNCTION if matches!(self.peek_token, token::Token::IDENT(_)) => self.parse_func_statement(),
+      token::Token::STRUCT => self.parse_struct_statement(),
       token::Token::RETURN => self.parse_return_statement(),
+      token::Token::WHILE => self.parse_while_statement(),
+      token::Token::FOR => self.parse_for_statement(),
       token::Token::COMMENT(s) => self.parse_comment_statement(s.to_string()),
       _ => self.parse_expression_statement(),
     }
   }
 
-  fn parse_let_statement(&mut self) -> Option<Statement> {
-    if !self.expect_ident_peek() {
-      return None;
-    }
+  // const も let と同じ形で、束縛した名前に代入できないことだけが違う
+  fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
+    let constant = self.current_token.is(token::Token::CONST);
 
-    let val = if let token::Token::IDENT(s) = &self.current_token {
-      s.to_string()
-    } else {
-      return None;
-    };
+    self.next_token();
 
-    let ident = Identifier::new(val);
+    let pattern = self.parse_pattern(false)?;
 
-    if !self.expect_peek(token::Token::ASSIGN) {
-      return None;
-    }
+    self.expect_peek(token::Token::ASSIGN)?;
 
     self.next_token();
 
-    let value = match self.parse_expression(BinaryOperator::Lowest) {
-      Some(expr) => expr,
-      None => return None,
-    };
+    let value = self.parse_expression(BinaryOperator::Lowest)?;
 
     if self.peek_token.is(token::Token::SEMICOLON) {
       self.next_token();
@@ -44,72 +44,277 @@ impl Parser {
 
     let stmt = Statement::Let(
       LetStatement::new(
-        ident,
+        pattern,
         value,
+        constant,
+        start.to(self.current_span),
       ),
     );
 
-    Some(stmt)
+    Ok(stmt)
   }
 
-  fn parse_return_statement(&mut self) -> Option<Statement> {
-    self.next_token();
+  fn parse_func_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
 
-    let value = match self.parse_expression(BinaryOperator::Lowest) {
-      Some(expr) => expr,
-      None => return None,
-    };
+    let ident = self.expect_ident_peek()?;
+
+    let func = self.parse_func(start)?;
 
     if self.peek_token.is(token::Token::SEMICOLON) {
       self.next_token();
     }
 
-    let stmt = Statement::Return(ReturnStatement::new(value));
+    Ok(Statement::Func(FuncStatement::new(ident, func, start.to(self.current_span))))
+  }
+
+  fn parse_struct_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
+
+    let ident = self.expect_ident_peek()?;
+
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let mut fields = vec![];
+    while !self.peek_token.is(token::Token::RBRACE) {
+      fields.push(self.expect_ident_peek()?);
+      if !self.peek_token.is(token::Token::RBRACE) {
+        self.expect_peek(token::Token::COMMA)?;
+      }
+    }
+
+    self.expect_peek(token::Token::RBRACE)?;
 
-    Some(stmt)
+    if self.peek_token.is(token::Token::SEMICOLON) {
+      self.next_token();
+    }
+
+    Ok(Statement::Struct(StructStatement::new(ident, fields, start.to(self.current_span))))
   }
 
-  fn parse_expression_statement(&mut self) -> Option<Statement> {
-    match self.parse_expression(BinaryOperator::Lowest) {
-      Some(expr) => {
-        if self.peek_token.is(token::Token::SEMICOLON) {
+  // リテラルのパターンは、一致しないことがある match の腕でだけ書ける
+  pub(super) fn parse_pattern(&mut self, literals: bool) -> Result<Pattern, ParseError> {
+    self.check_depth()?;
+    let span = self.current_span;
+    let lit = match &self.current_token {
+      token::Token::IDENT(s) if self.peek_token.is(token::Token::LBRACE) => {
+        let name = Identifier::new(*s, span);
+        self.next_token();
+        let fields = self.parse_field_patterns(literals)?;
+        return Ok(Pattern::Struct(StructPattern::new(name, fields, span.to(self.current_span))));
+      },
+      token::Token::IDENT(s) => return Ok(Pattern::Ident(Identifier::new(*s, span))),
+      token::Token::LBRACKET => return self.parse_array_pattern(literals),
+      token::Token::LBRACE => return self.parse_hash_pattern(literals),
+      token::Token::INT(int) if literals => Literal::Integer(Integer::new(*int, span)),
+      token::Token::FLOAT(float) if literals => Literal::Float(Float::new(*float, span)),
+      token::Token::STRING(s) if literals => Literal::Str(Str::new(s.clone(), span)),
+      token::Token::TRUE | token::Token::FALSE if literals => Literal::Boolean(Boolean::new(self.current_token.is(token::Token::TRUE), span)),
+      token::Token::NULL if literals => Literal::Null(Null::new(span)),
+      token::Token::MINUS if literals => match self.peek_token {
+        token::Token::INT(int) => {
+          self.next_token();
+          Literal::Integer(Integer::new(int.wrapping_neg(), span.to(self.current_span)))
+        },
+        token::Token::FLOAT(float) => {
           self.next_token();
-        }
-        Some(Statement::Expr(ExpressionStatement::new(expr)))
+          Literal::Float(Float::new(-float, span.to(self.current_span)))
+        },
+        _ => return Err(ParseError::ExpectedIdentifier { found: self.peek_token.clone(), span: self.peek_span }),
       },
-      None => None,
+      _ => return Err(ParseError::ExpectedIdentifier {
+        found: self.current_token.clone(),
+        span,
+      }),
+    };
+    Ok(Pattern::Literal(lit))
+  }
+
+  fn parse_nested_pattern(&mut self, literals: bool) -> Result<Pattern, ParseError> {
+    self.depth += 1;
+    let pattern = self.parse_pattern(literals);
+    self.depth -= 1;
+    pattern
+  }
+
+  // ...rest は最後の要素にだけ書ける
+  fn parse_array_pattern(&mut self, literals: bool) -> Result<Pattern, ParseError> {
+    let start = self.current_span;
+    let mut elements = vec![];
+    let mut rest = None;
+
+    if self.peek_token.is(token::Token::RBRACKET) {
+      self.next_token();
+      return Ok(Pattern::Array(ArrayPattern::new(elements, rest, start.to(self.current_span))));
+    }
+
+    loop {
+      self.next_token();
+      if self.current_token.is(token::Token::ELLIPSIS) {
+        rest = Some(self.expect_ident_peek()?);
+        break;
+      }
+      elements.push(self.parse_nested_pattern(literals)?);
+      if !self.peek_token.is(token::Token::COMMA) {
+        break;
+      }
+      self.next_token();
+    }
+
+    self.expect_peek(token::Token::RBRACKET)?;
+
+    Ok(Pattern::Array(ArrayPattern::new(elements, rest, start.to(self.current_span))))
+  }
+
+  fn parse_hash_pattern(&mut self, literals: bool) -> Result<Pattern, ParseError> {
+    let start = self.current_span;
+    let pairs = self.parse_field_patterns(literals)?;
+    Ok(Pattern::Hash(HashPattern::new(pairs, start.to(self.current_span))))
+  }
+
+  // {x, y: pattern} の中身を、閉じ括弧まで読む
+  fn parse_field_patterns(&mut self, literals: bool) -> Result<Vec<(Identifier, Pattern)>, ParseError> {
+    let mut pairs = vec![];
+
+    if self.peek_token.is(token::Token::RBRACE) {
+      self.next_token();
+      return Ok(pairs);
+    }
+
+    loop {
+      let key = self.expect_ident_peek()?;
+      let pattern = if self.peek_token.is(token::Token::COLON) {
+        self.next_token();
+        self.next_token();
+        self.parse_nested_pattern(literals)?
+      } else {
+        Pattern::Ident(key.clone())
+      };
+      pairs.push((key, pattern));
+      if !self.peek_token.is(token::Token::COMMA) {
+        break;
+      }
+      self.next_token();
+    }
+
+    self.expect_peek(token::Token::RBRACE)?;
+
+    Ok(pairs)
+  }
+
+  fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
+
+    self.next_token();
+
+    let value = self.parse_expression(BinaryOperator::Lowest)?;
+
+    if self.peek_token.is(token::Token::SEMICOLON) {
+      self.next_token();
+    }
+
+    let stmt = Statement::Return(ReturnStatement::new(value, start.to(self.current_span)));
+
+    Ok(stmt)
+  }
+
+  fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LPAREN)?;
+
+    self.next_token();
+
+    let condition = self.parse_expression(BinaryOperator::Lowest)?;
+
+    self.expect_peek(token::Token::RPAREN)?;
+
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let body = self.parse_block_statement();
+    let span = start.to(self.current_span);
+
+    if self.peek_token.is(token::Token::SEMICOLON) {
+      self.next_token();
     }
+
+    Ok(Statement::While(WhileStatement::new(condition, body, span)))
+  }
+
+  fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+    let start = self.current_span;
+
+    self.expect_peek(token::Token::LPAREN)?;
+
+    let ident = self.expect_ident_peek()?;
+
+    self.expect_peek(token::Token::IN)?;
+
+    self.next_token();
+
+    let iterable = self.parse_expression(BinaryOperator::Lowest)?;
+
+    self.expect_peek(token::Token::RPAREN)?;
+
+    self.expect_peek(token::Token::LBRACE)?;
+
+    let body = self.parse_block_statement();
+    let span = start.to(self.current_span);
+
+    if self.peek_token.is(token::Token::SEMICOLON) {
+      self.next_token();
+    }
+
+    Ok(Statement::For(ForStatement::new(ident, iterable, body, span)))
+  }
+
+  fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
+    let expr = self.parse_expression(BinaryOperator::Lowest)?;
+    if self.peek_token.is(token::Token::SEMICOLON) {
+      self.next_token();
+    }
+    let span = expr.span().to(self.current_span);
+    Ok(Statement::Expr(ExpressionStatement::new(expr, span)))
   }
 
   pub(super) fn parse_block_statement(&mut self) -> BlockStatement {
+    let start = self.current_span;
+
     self.next_token();
 
     let mut statements = vec![];
 
+    self.depth += 1;
     while !self.current_token.is(token::Token::RBRACE) && !self.current_token.is(token::Token::EOF) {
-      if let Some(stmt) = self.parse_statement() {
-        statements.push(stmt);
+      match self.parse_statement() {
+        Ok(stmt) => statements.push(stmt),
+        Err(err) => {
+          self.errors.push(err);
+          self.synchronize();
+        },
       }
       self.next_token();
     }
+    self.depth -= 1;
 
-    BlockStatement::new(statements)
+    BlockStatement::new(statements, start.to(self.current_span))
   }
 
-  fn parse_comment_statement(&self, s: String) -> Option<Statement> {
-    Some(Statement::Comment(CommentStatement::new(s)))
+  fn parse_comment_statement(&self, s: String) -> Result<Statement, ParseError> {
+    Ok(Statement::Comment(CommentStatement::new(s, self.current_span)))
   }
 
-  fn expect_ident_peek(&mut self) -> bool {
+  /// Moves to the next token if it is an identifier and returns it, or fails without moving.
+  pub(super) fn expect_ident_peek(&mut self) -> Result<Identifier, ParseError> {
     match self.peek_token {
-      token::Token::IDENT(_) => {
+      token::Token::IDENT(s) => {
         self.next_token();
-        true
+        Ok(Identifier::new(s, self.current_span))
       },
-      _ => {
-        self.peek_error(token::Token::IDENT("something".to_string()));
-        false
-      }
+      _ => Err(ParseError::ExpectedIdentifier {
+        found: self.peek_token.clone(),
+        span: self.peek_span,
+      }),
     }
   }
 }
@@ -117,7 +322,10 @@ impl Parser {
 #[cfg(test)]
 mod tests {
   use crate::lexer;
+  use crate::intern::Name;
   use crate::parser::expr::*;
+  use crate::span::{Position, Span};
+  use crate::ast::expr::Expression;
   use super::*;
 
   #[test]
@@ -147,18 +355,102 @@ mod tests {
         _ => panic!("stmt.token_literal() not 'let'. get={:?}", stmt),
       };
   
+      let ident = match &let_stmt.pattern {
+        Pattern::Ident(ident) => ident,
+        _ => panic!("let_stmt.pattern should be Identifier, but got {}", let_stmt.pattern),
+      };
       assert_eq!(
-        &let_stmt.ident.value,
+        ident.value.as_str(),
         expected_ident,
         "expect={}, actual={}",
         expected_ident,
-        &let_stmt.ident.value,
+        &ident.value,
       );
 
       test_literal_expression(&let_stmt.value, expected_expr);
     }
   }
   
+  #[test]
+  fn test_parse_let_pattern() {
+    let tests = vec![
+      ("let [a, b] = pair;", "let [a, b] = pair;"),
+      ("let [] = xs;", "let [] = xs;"),
+      ("let [a, [b, c], {d}] = xs;", "let [a, [b, c], {d}] = xs;"),
+      ("let {x, y} = point;", "let {x, y} = point;"),
+      ("let {} = h;", "let {} = h;"),
+      ("let [a, ...others] = xs;", "let [a, ...others] = xs;"),
+      ("let [...all] = xs;", "let [...all] = xs;"),
+      ("let {name: n, age, pos: [x, y]} = p;", "let {name: n, age, pos: [x, y]} = p;"),
+      ("let Point {x, y: [a, b]} = p;", "let Point {x, y: [a, b]} = p;"),
+      ("let [Point {}, q] = ps;", "let [Point {}, q] = ps;"),
+      ("const [a, b] = pair;", "const [a, b] = pair;"),
+      ("const x = 1", "const x = 1;"),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+
+      let program = p.parse_program();
+      if !p.check_parse_errors() {
+        panic!();
+      }
+
+      if program.statements.len() != 1 {
+        panic!("program.statements does not contain 1 statements. got={}", program.statements.len());
+      }
+      assert_eq!(format!("{}", program), expected);
+    }
+
+    let l = lexer::Lexer::new("const [a, b] = pair;".to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    match &program.statements[0] {
+      Statement::Let(let_stmt) => assert!(let_stmt.constant),
+      stmt => panic!("LetStatement is not included, got {:?}", stmt),
+    }
+
+    let l = lexer::Lexer::new("let [a, b] = pair;".to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    match &program.statements[0] {
+      Statement::Let(LetStatement { pattern: Pattern::Array(arr), .. }) => {
+        assert_eq!(arr.elements.len(), 2);
+        assert_eq!(arr.span, Span::new(Position::new(4, 1, 5), Position::new(10, 1, 11)));
+      },
+      stmt => panic!("LetStatement with ArrayPattern is not included, got {:?}", stmt),
+    }
+
+    let tests = vec![
+      ("let [1] = xs;", ParseError::ExpectedIdentifier {
+        found: token::Token::INT(1),
+        span: Span::new(Position::new(5, 1, 6), Position::new(6, 1, 7)),
+      }),
+      ("let {x: 1} = h;", ParseError::ExpectedIdentifier {
+        found: token::Token::INT(1),
+        span: Span::new(Position::new(8, 1, 9), Position::new(9, 1, 10)),
+      }),
+      ("let [...a, b] = xs;", ParseError::UnexpectedToken {
+        expected: token::Token::RBRACKET,
+        found: token::Token::COMMA,
+        span: Span::new(Position::new(9, 1, 10), Position::new(10, 1, 11)),
+      }),
+      ("let [a b] = xs;", ParseError::UnexpectedToken {
+        expected: token::Token::RBRACKET,
+        found: token::Token::IDENT(Name::new("b")),
+        span: Span::new(Position::new(7, 1, 8), Position::new(8, 1, 9)),
+      }),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      p.parse_program();
+      assert_eq!(p.errors.first(), Some(&expected), "input={}", input);
+    }
+  }
+
   #[test]
   fn test_parse_return_statement() {
     let tests = vec![
@@ -189,6 +481,287 @@ mod tests {
     }
   }
 
+  #[test]
+  fn test_parse_errors() {
+    let tests = vec![
+      ("let = 5;", vec![
+        ParseError::ExpectedIdentifier {
+          found: token::Token::ASSIGN,
+          span: Span::new(Position::new(4, 1, 5), Position::new(5, 1, 6)),
+        },
+      ]),
+      ("let x 5;", vec![
+        ParseError::UnexpectedToken {
+          expected: token::Token::ASSIGN,
+          found: token::Token::INT(5),
+          span: Span::new(Position::new(6, 1, 7), Position::new(7, 1, 8)),
+        },
+      ]),
+      ("\n  fn(1) {}", vec![
+        ParseError::Unsupported {
+          found: token::Token::INT(1),
+          place: "args".to_string(),
+          span: Span::new(Position::new(6, 2, 6), Position::new(7, 2, 7)),
+        },
+      ]),
+      ("return ;", vec![
+        ParseError::NoPrefixParseFn {
+          found: token::Token::SEMICOLON,
+          span: Span::new(Position::new(7, 1, 8), Position::new(8, 1, 9)),
+        },
+      ]),
+      ("try { 1 } (e) {}", vec![
+        ParseError::UnexpectedToken {
+          expected: token::Token::CATCH,
+          found: token::Token::LPAREN,
+          span: Span::new(Position::new(10, 1, 11), Position::new(11, 1, 12)),
+        },
+      ]),
+      ("1 + /* open", vec![
+        ParseError::IllegalToken {
+          found: token::Token::ILLEGAL("unterminated block comment".into()),
+          span: Span::new(Position::new(4, 1, 5), Position::new(11, 1, 12)),
+        },
+      ]),
+      ("a.;", vec![
+        ParseError::ExpectedIdentifier {
+          found: token::Token::SEMICOLON,
+          span: Span::new(Position::new(2, 1, 3), Position::new(3, 1, 4)),
+        },
+      ]),
+      ("a.1()", vec![
+        ParseError::ExpectedIdentifier {
+          found: token::Token::INT(1),
+          span: Span::new(Position::new(2, 1, 3), Position::new(3, 1, 4)),
+        },
+      ]),
+    ];
+
+    for (input, expected) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      p.parse_program();
+
+      if p.errors.len() < expected.len() {
+        panic!("parser should has at least {} errors, but got {:?}", expected.len(), p.errors);
+      }
+
+      for (i, err) in expected.into_iter().enumerate() {
+        assert_eq!(p.errors[i], err, "input={}", input);
+      }
+    }
+
+    let err = ParseError::UnexpectedToken {
+      expected: token::Token::RPAREN,
+      found: token::Token::EOF,
+      span: Span::default(),
+    };
+    assert_eq!(format!("{}", err), "expected next token to be RPAREN, got EOF instead.");
+    assert_eq!(err.found(), &token::Token::EOF);
+
+    let err = ParseError::IllegalToken {
+      found: token::Token::ILLEGAL("unterminated string literal".into()),
+      span: Span::default(),
+    };
+    assert_eq!(format!("{}", err), "unterminated string literal.");
+  }
+
+  #[test]
+  fn test_parse_bytes() {
+    match crate::parser::parse_bytes(b"let x = 1; x") {
+      Ok(program) => assert_eq!(program.to_string(), "let x = 1;x"),
+      Err(errors) => panic!("errors={:?}", errors),
+    }
+
+    let nested = [
+      "while (x) { ".repeat(100_000),
+      "fn() { ".repeat(100_000),
+      format!("let {}x = 1", "[".repeat(100_000)),
+    ];
+    for input in nested.iter() {
+      let errors = crate::parser::parse_bytes(input.as_bytes()).err().unwrap();
+      assert_eq!(errors.len(), 1, "errors={:?}", errors);
+      assert_eq!(format!("{}", errors[0]), "nested too deeply.");
+    }
+
+    let errors = crate::parser::parse_bytes(b"let x = \xc3;").err().unwrap();
+    assert_eq!(format!("{}", errors[0]), "invalid UTF-8 byte 0xc3.");
+  }
+
+  #[test]
+  fn test_parse_error_recovery() {
+    let tests = vec![
+      ("let = 5; let y = 10; y;", 1, vec!["let y = 10;", "y"]),
+      ("let = 5; let y 10; let z = 1;", 2, vec!["let z = 1;"]),
+      ("let x = ; x;", 1, vec!["x"]),
+      ("let x = fn() { let = 1; 2 }; x;", 1, vec!["let x = fn() { 2 };", "x"]),
+      ("let = fn() { 1; 2 }; 3;", 1, vec!["3"]),
+      ("if (1 +) { a } let b = 2;", 1, vec!["let b = 2;"]),
+      ("let a = [1, 2; let b = 3;", 1, vec!["let b = 3;"]),
+      ("fn() { let = 1 }; fn() { let y 2 };", 2, vec!["fn() ", "fn() "]),
+      ("let", 1, vec![]),
+    ];
+
+    for (input, expected_errors, expected_stmts) in tests.into_iter() {
+      let l = lexer::Lexer::new(input.to_string());
+      let mut p = Parser::new(l);
+      let program = p.parse_program();
+
+      assert_eq!(p.errors.len(), expected_errors, "input={}, errors={:?}", input, p.errors);
+
+      let stmts: Vec<String> = program.statements.iter().map(|stmt| format!("{}", stmt)).collect();
+      assert_eq!(stmts, expected_stmts, "input={}", input);
+    }
+  }
+
+  #[test]
+  fn test_parse_while_statement() {
+    let input = "while (x < 10) { x; y }; z";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 2 {
+      panic!("program.statements does not contain 2 statements. got={}", program.statements.len());
+    }
+
+    let while_stmt = match &program.statements[0] {
+      Statement::While(while_stmt) => while_stmt,
+      _ => panic!("WhileStatement is not included, got {:?}", &program.statements[0]),
+    };
+
+    assert_eq!(format!("{}", while_stmt.condition), "(x < 10)");
+    assert_eq!(while_stmt.body.statements.len(), 2);
+    test_identifier(&expr_value(&while_stmt.body.statements[0]), "x");
+    test_identifier(&expr_value(&while_stmt.body.statements[1]), "y");
+    assert_eq!(while_stmt.span.slice(input), "while (x < 10) { x; y }");
+  }
+
+  #[test]
+  fn test_parse_for_statement() {
+    let input = "for (x in [1, 2]) { x }";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 1 {
+      panic!("program.statements does not contain 1 statements. got={}", program.statements.len());
+    }
+
+    let for_stmt = match &program.statements[0] {
+      Statement::For(for_stmt) => for_stmt,
+      _ => panic!("ForStatement is not included, got {:?}", &program.statements[0]),
+    };
+
+    assert_eq!(for_stmt.ident.value.as_str(), "x");
+    assert_eq!(format!("{}", for_stmt.iterable), "[1, 2]");
+    assert_eq!(for_stmt.body.statements.len(), 1);
+    test_identifier(&expr_value(&for_stmt.body.statements[0]), "x");
+    assert_eq!(format!("{}", program), "for(x in [1, 2]) { x }");
+
+    let l = lexer::Lexer::new("for (1 in xs) {}".to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert_eq!(p.errors[0], ParseError::ExpectedIdentifier {
+      found: token::Token::INT(1),
+      span: Span::new(Position::new(5, 1, 6), Position::new(6, 1, 7)),
+    });
+  }
+
+  #[test]
+  fn test_parse_func_statement() {
+    let input = "fn add(x, y = 1) { x + y }; fn(x) { x }";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 2 {
+      panic!("program.statements does not contain 2 statements. got={}", program.statements.len());
+    }
+
+    let func_stmt = match &program.statements[0] {
+      Statement::Func(func_stmt) => func_stmt,
+      _ => panic!("FuncStatement is not included, got {:?}", &program.statements[0]),
+    };
+
+    assert_eq!(func_stmt.ident.value.as_str(), "add");
+    assert_eq!(func_stmt.func.args.len(), 2);
+    assert!(func_stmt.func.defaults[1].is_some());
+    assert_eq!(func_stmt.span.slice(input), "fn add(x, y = 1) { x + y };");
+    assert_eq!(func_stmt.func.span.slice(input), "fn add(x, y = 1) { x + y }");
+    // 名前のない fn は今までどおり式になる
+    assert_eq!(format!("{}", expr_value(&program.statements[1])), "fn(x) { x }");
+    assert_eq!(format!("{}", program), "fn add(x, y = 1) { (x + y) }fn(x) { x }");
+
+    let l = lexer::Lexer::new("fn add x, y { x }".to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert_eq!(p.errors[0], ParseError::UnexpectedToken {
+      expected: token::Token::LPAREN,
+      found: token::Token::IDENT(crate::intern::Name::new("x")),
+      span: Span::new(Position::new(7, 1, 8), Position::new(8, 1, 9)),
+    });
+  }
+
+  #[test]
+  fn test_parse_struct_statement() {
+    let input = "struct Point { x, y, } Point { x: 1, y: 2 + 3 }";
+
+    let l = lexer::Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+
+    if program.statements.len() != 2 {
+      panic!("program.statements does not contain 2 statements. got={}", program.statements.len());
+    }
+
+    let struct_stmt = match &program.statements[0] {
+      Statement::Struct(struct_stmt) => struct_stmt,
+      _ => panic!("StructStatement is not included, got {:?}", &program.statements[0]),
+    };
+
+    assert_eq!(struct_stmt.ident.value.as_str(), "Point");
+    assert_eq!(struct_stmt.fields.iter().map(|field| field.value.as_str()).collect::<Vec<_>>(), vec!["x", "y"]);
+    assert_eq!(struct_stmt.span.slice(input), "struct Point { x, y, }");
+    assert_eq!(expr_value(&program.statements[1]).span().slice(input), "Point { x: 1, y: 2 + 3 }");
+    assert_eq!(format!("{}", program), "struct Point {x, y}Point {x: 1, y: (2 + 3)}");
+
+    let l = lexer::Lexer::new("struct Point { x y }".to_string());
+    let mut p = Parser::new(l);
+    p.parse_program();
+    assert_eq!(p.errors[0], ParseError::UnexpectedToken {
+      expected: token::Token::COMMA,
+      found: token::Token::IDENT(crate::intern::Name::new("y")),
+      span: Span::new(Position::new(17, 1, 18), Position::new(18, 1, 19)),
+    });
+  }
+
+  fn expr_value(stmt: &Statement) -> Expression {
+    match stmt {
+      Statement::Expr(expr) => expr.value.clone(),
+      _ => panic!("ExpressionStatement is not included, got {:?}", stmt),
+    }
+  }
+
   #[test]
   fn test_parse_comment_statement() {
     let tests = vec![
@@ -0,0 +1,60 @@
+use std::fmt;
+
+/// A location in source code.
+/// `offset` is a byte offset, and `line` and `column` start from 1.
+/// `column` counts characters, so a multi-byte character before it on the line moves it by one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+  pub offset: usize,
+  pub line: usize,
+  pub column: usize,
+}
+
+impl Position {
+  pub fn new(offset: usize, line: usize, column: usize) -> Position {
+    Position { offset, line, column }
+  }
+}
+
+impl Default for Position {
+  fn default() -> Position {
+    Position::new(0, 1, 1)
+  }
+}
+
+impl fmt::Display for Position {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}:{}", self.line, self.column)
+  }
+}
+
+/// A range of source code from `start` up to, but not including, `end`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+  pub start: Position,
+  pub end: Position,
+}
+
+impl Span {
+  pub fn new(start: Position, end: Position) -> Span {
+    Span { start, end }
+  }
+
+  /// Returns the span which covers from the start of `self` to the end of `other`.
+  pub fn to(&self, other: Span) -> Span {
+    Span::new(self.start, other.end)
+  }
+
+  /// Returns the source text covered by the span.
+  pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+    &source[self.start.offset..self.end.offset]
+  }
+}
+
+impl fmt::Display for Span {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}", self.start)
+  }
+}
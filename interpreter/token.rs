@@ -1,33 +1,61 @@
 use std::cmp::PartialEq;
 use std::fmt;
 
+use crate::intern::Name;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
-  ILLEGAL,
+  ILLEGAL(String),
   EOF,
   
   // 識別子 + リテラル
-  IDENT(String),
+  IDENT(Name),
   INT(i64),
+  FLOAT(f64),
   STRING(String),
   
   // 演算子
   ASSIGN,
+  INCREMENT,
+  DECREMENT,
+  PlusAssign,
+  MinusAssign,
+  AsteriskAssign,
+  SlashAssign,
   PLUS,
   MINUS,
   BANG,
   ASTERISK,
+  POWER,
   SLASH,
+  PERCENT,
+  AMPERSAND,
+  PIPE,
+  CARET,
+  TILDE,
+  ShiftLeft,
+  ShiftRight,
 
   LT,
   GT,
+  LtEq,
+  GtEq,
   EQ,
   NotEq,
+  AND,
+  OR,
+  COALESCE,
+  QUESTION,
+  DOTDOT,
+  DotDotEq,
+  ELLIPSIS,
+  ARROW,
   
   // デリミタ
   COMMA,
   COLON,
   SEMICOLON,
+  DOT,
   
   LPAREN,
   RPAREN,
@@ -39,42 +67,78 @@ pub enum Token {
   // キーワード
   FUNCTION,
   LET,
+  CONST,
   TRUE,
   FALSE,
+  NULL,
   IF,
   ELSE,
+  WHILE,
+  FOR,
+  IN,
   RETURN,
+  TRY,
+  CATCH,
+  MATCH,
+  MACRO,
+  STRUCT,
   COMMENT(String),
 }
 
 impl fmt::Display for Token {
   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      Token::ILLEGAL => write!(f, "ILLEGAL"),
+      Token::ILLEGAL(msg) => write!(f, "ILLEGAL({})", msg),
       Token::EOF => write!(f, "EOF"),
 
       // 識別子 + リテラル
       Token::IDENT(s) => write!(f, "IDENT({})", s),
       Token::INT(i) => write!(f, "INT({})", i),
+      Token::FLOAT(v) => write!(f, "FLOAT({:?})", v),
       Token::STRING(s) => write!(f, "STRING({})", s),
       
       // 演算子
       Token::ASSIGN => write!(f, "ASSIGN"),
+      Token::INCREMENT => write!(f, "INCREMENT"),
+      Token::DECREMENT => write!(f, "DECREMENT"),
+      Token::PlusAssign => write!(f, "PlusAssign"),
+      Token::MinusAssign => write!(f, "MinusAssign"),
+      Token::AsteriskAssign => write!(f, "AsteriskAssign"),
+      Token::SlashAssign => write!(f, "SlashAssign"),
       Token::PLUS => write!(f, "PLUS"),
       Token::MINUS => write!(f, "MINUS"),
       Token::BANG => write!(f, "BANG"),
       Token::ASTERISK => write!(f, "ASTERISK"),
+      Token::POWER => write!(f, "POWER"),
       Token::SLASH => write!(f, "SLASH"),
+      Token::PERCENT => write!(f, "PERCENT"),
+      Token::AMPERSAND => write!(f, "AMPERSAND"),
+      Token::PIPE => write!(f, "PIPE"),
+      Token::CARET => write!(f, "CARET"),
+      Token::TILDE => write!(f, "TILDE"),
+      Token::ShiftLeft => write!(f, "ShiftLeft"),
+      Token::ShiftRight => write!(f, "ShiftRight"),
     
       Token::LT => write!(f, "LT"),
       Token::GT => write!(f, "GT"),
+      Token::LtEq => write!(f, "LtEq"),
+      Token::GtEq => write!(f, "GtEq"),
       Token::EQ => write!(f, "EQ"),
       Token::NotEq => write!(f, "NotEq"),
+      Token::AND => write!(f, "AND"),
+      Token::OR => write!(f, "OR"),
+      Token::COALESCE => write!(f, "COALESCE"),
+      Token::QUESTION => write!(f, "QUESTION"),
+      Token::DOTDOT => write!(f, "DOTDOT"),
+      Token::DotDotEq => write!(f, "DotDotEq"),
+      Token::ELLIPSIS => write!(f, "ELLIPSIS"),
+      Token::ARROW => write!(f, "ARROW"),
       
       // デリミタ
       Token::COMMA => write!(f, "COMMA"),
       Token::COLON => write!(f, "COLON"),
       Token::SEMICOLON => write!(f, "SEMICOLON"),
+      Token::DOT => write!(f, "DOT"),
       
       Token::LPAREN => write!(f, "LPAREN"),
       Token::RPAREN => write!(f, "RPAREN"),
@@ -86,12 +150,53 @@ impl fmt::Display for Token {
       // キーワード
       Token::FUNCTION => write!(f, "FUNCTION"),
       Token::LET => write!(f, "LET"),
+      Token::CONST => write!(f, "CONST"),
       Token::TRUE => write!(f, "TRUE"),
       Token::FALSE => write!(f, "FALSE"),
+      Token::NULL => write!(f, "NULL"),
       Token::IF => write!(f, "IF"),
       Token::ELSE => write!(f, "ELSE"),
+      Token::WHILE => write!(f, "WHILE"),
+      Token::FOR => write!(f, "FOR"),
+      Token::IN => write!(f, "IN"),
       Token::RETURN => write!(f, "RETURN"),
+      Token::TRY => write!(f, "TRY"),
+      Token::CATCH => write!(f, "CATCH"),
+      Token::MATCH => write!(f, "MATCH"),
+      Token::MACRO => write!(f, "MACRO"),
+      Token::STRUCT => write!(f, "STRUCT"),
       Token::COMMENT(s) => write!(f, "COMMENT({})", s),
     }
   }
 }
+
+/// What kind of syntax a token is, for syntax highlighting.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TokenClass {
+  Keyword,
+  Identifier,
+  /// Numbers, strings, `true`, `false` and `null`.
+  Literal,
+  Operator,
+  /// Commas, colons, semicolons, dots and brackets.
+  Punctuation,
+  Comment,
+  /// Characters which are not a token, and unterminated strings or comments.
+  Invalid,
+}
+
+impl Token {
+  pub fn class(&self) -> TokenClass {
+    match self {
+      Token::ILLEGAL(_) | Token::EOF => TokenClass::Invalid,
+      Token::IDENT(_) => TokenClass::Identifier,
+      Token::INT(_) | Token::FLOAT(_) | Token::STRING(_) | Token::TRUE | Token::FALSE | Token::NULL => TokenClass::Literal,
+      Token::COMMA | Token::COLON | Token::SEMICOLON | Token::DOT
+      | Token::LPAREN | Token::RPAREN | Token::LBRACE | Token::RBRACE | Token::LBRACKET | Token::RBRACKET => TokenClass::Punctuation,
+      Token::FUNCTION | Token::LET | Token::CONST | Token::IF | Token::ELSE | Token::WHILE | Token::FOR
+      | Token::IN | Token::RETURN | Token::TRY | Token::CATCH | Token::MATCH | Token::MACRO | Token::STRUCT => TokenClass::Keyword,
+      Token::COMMENT(_) => TokenClass::Comment,
+      _ => TokenClass::Operator,
+    }
+  }
+}
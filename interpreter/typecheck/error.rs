@@ -0,0 +1,73 @@
+use std::fmt;
+
+use crate::span::Span;
+use super::types::Type;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypeError {
+  /// A value has another type than where it is used requires, e.g. an argument, an assigned value or a returned value.
+  Mismatch {
+    expected: Type,
+    found: Type,
+    span: Span,
+  },
+  /// The operator is not defined for the operands, e.g. `1 + true`. `right` is `None` for a prefix operator.
+  Operator {
+    operator: String,
+    left: Type,
+    right: Option<Type>,
+    span: Span,
+  },
+  /// A value which is not a function is called.
+  NotCallable {
+    found: Type,
+    span: Span,
+  },
+  /// A function is called with fewer arguments than its parameters without default values, or more than all of them.
+  Arity {
+    min: usize,
+    max: usize,
+    found: usize,
+    span: Span,
+  },
+  /// The value can not be used in `operation`, e.g. an integer which is indexed or iterated over.
+  Unsupported {
+    operation: String,
+    found: Type,
+    span: Span,
+  },
+}
+
+impl TypeError {
+  /// Returns the span of the expression which has the wrong type.
+  pub fn span(&self) -> Span {
+    match self {
+      TypeError::Mismatch { span, .. }
+      | TypeError::Operator { span, .. }
+      | TypeError::NotCallable { span, .. }
+      | TypeError::Arity { span, .. }
+      | TypeError::Unsupported { span, .. } => *span,
+    }
+  }
+}
+
+impl fmt::Display for TypeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TypeError::Mismatch { expected, found, .. } =>
+        write!(f, "type mismatch: expected {}, found {}.", expected, found),
+      TypeError::Operator { operator, left, right: Some(right), .. } =>
+        write!(f, "unsupported operand types for {}: {} and {}.", operator, left, right),
+      TypeError::Operator { operator, left, right: None, .. } =>
+        write!(f, "unsupported operand type for {}: {}.", operator, left),
+      TypeError::NotCallable { found, .. } =>
+        write!(f, "{} is not a function.", found),
+      TypeError::Arity { min, max, found, .. } if min == max =>
+        write!(f, "wrong number of arguments: got={}, want={}.", found, min),
+      TypeError::Arity { min, max, found, .. } =>
+        write!(f, "wrong number of arguments: got={}, want={}..={}.", found, min, max),
+      TypeError::Unsupported { operation, found, .. } =>
+        write!(f, "cannot {} {}.", operation, found),
+    }
+  }
+}
@@ -0,0 +1,839 @@
+use std::collections::HashMap;
+
+use crate::ast::Program;
+use crate::ast::stmt::{Statement, BlockStatement, Pattern};
+use crate::ast::expr::{Expression, CallExpression, MethodCallExpression};
+use crate::ast::lit::{self, Literal};
+use crate::ast::operator::{Prefix, Infix};
+use crate::evaluator::macro_expansion;
+use crate::intern::Name;
+use crate::span::Span;
+
+pub mod error;
+pub mod types;
+
+use error::TypeError;
+use types::Type;
+
+/// Infers the types in `program` without running it, and returns the type of its value or the errors found.
+///
+/// Integers, floats, booleans, strings, arrays and functions are checked. A `let` binding of a function literal
+/// is generic, so `let id = fn(x) { x }` can be called with any type, while other bindings keep one type and
+/// can only be assigned a value of it. Everything else, and a name which is not bound before it is used,
+/// is `Type::Any` and accepted anywhere, so a program which passes may still fail at runtime.
+pub fn check(program: &Program) -> Result<Type, Vec<TypeError>> {
+  let mut checker = Checker {
+    substitution: vec![],
+    scopes: vec![HashMap::new()],
+    returns: vec![],
+    errors: vec![],
+  };
+  let ty = checker.check_statements(&program.statements);
+  if checker.errors.is_empty() {
+    return Ok(rename_vars(&[checker.resolve(&ty)]).remove(0));
+  }
+  Err(checker.errors.into_iter().map(rename_error_vars).collect())
+}
+
+// 型変数 vars を呼び出すごとに新しい変数にする型
+#[derive(Clone)]
+struct Scheme {
+  vars: Vec<usize>,
+  ty: Type,
+}
+
+struct Checker {
+  // 型変数に決まった型
+  substitution: Vec<Option<Type>>,
+  // 関数、for、catch ごとのスコープ
+  scopes: Vec<HashMap<Name, Scheme>>,
+  // 中にいる関数の戻り値の型
+  returns: Vec<Type>,
+  errors: Vec<TypeError>,
+}
+
+impl Checker {
+  fn fresh(&mut self) -> Type {
+    self.substitution.push(None);
+    Type::Var(self.substitution.len() - 1)
+  }
+
+  // 型変数が決まっていれば、決まった型をたどる
+  fn prune(&self, ty: &Type) -> Type {
+    let mut ty = ty.clone();
+    while let Type::Var(id) = ty {
+      match &self.substitution[id] {
+        Some(bound) => ty = bound.clone(),
+        None => break,
+      }
+    }
+    ty
+  }
+
+  fn resolve(&self, ty: &Type) -> Type {
+    match self.prune(ty) {
+      Type::Array(elm) => Type::Array(Box::new(self.resolve(&elm))),
+      Type::Fn { params, required, ret } => Type::Fn {
+        params: params.iter().map(|param| self.resolve(param)).collect(),
+        required,
+        ret: Box::new(self.resolve(&ret)),
+      },
+      ty => ty,
+    }
+  }
+
+  fn unify(&mut self, a: &Type, b: &Type) -> Result<(), ()> {
+    match (self.prune(a), self.prune(b)) {
+      (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+      // Any を渡された引数なども Any にして、後から他の型に決めない
+      (Type::Var(id), ty) | (ty, Type::Var(id)) => {
+        // 'a = ['a] のような無限の型は作らない
+        let mut vars = vec![];
+        self.resolve(&ty).vars(&mut vars);
+        if vars.contains(&id) {
+          return Err(());
+        }
+        self.substitution[id] = Some(ty);
+        Ok(())
+      },
+      (Type::Any, _) | (_, Type::Any) => Ok(()),
+      (Type::Array(a), Type::Array(b)) => self.unify(&a, &b),
+      (Type::Fn { params: a_params, ret: a_ret, .. }, Type::Fn { params: b_params, ret: b_ret, .. }) => {
+        if a_params.len() != b_params.len() {
+          return Err(());
+        }
+        for (a, b) in a_params.iter().zip(b_params.iter()) {
+          self.unify(a, b)?;
+        }
+        self.unify(&a_ret, &b_ret)
+      },
+      (a, b) if a == b => Ok(()),
+      _ => Err(()),
+    }
+  }
+
+  fn expect(&mut self, expected: &Type, found: &Type, span: Span) {
+    if self.unify(expected, found).is_err() {
+      let (expected, found) = (self.resolve(expected), self.resolve(found));
+      self.errors.push(TypeError::Mismatch { expected, found, span });
+    }
+  }
+
+  // 分岐の値などは、型が揃わなければ Any にする
+  fn join(&mut self, a: &Type, b: &Type) -> Type {
+    match self.unify(a, b) {
+      Ok(()) => self.prune(a),
+      Err(()) => Type::Any,
+    }
+  }
+
+  fn instantiate(&mut self, scheme: &Scheme) -> Type {
+    let mut mapping = HashMap::new();
+    for var in &scheme.vars {
+      let fresh = self.fresh();
+      mapping.insert(*var, fresh);
+    }
+    replace_vars(&self.resolve(&scheme.ty), &mapping)
+  }
+
+  // スコープの束縛から参照されていない型変数だけを、呼び出しごとに変えられる
+  fn generalize(&self, ty: &Type) -> Scheme {
+    let mut bound = vec![];
+    for scope in &self.scopes {
+      for scheme in scope.values() {
+        let mut vars = vec![];
+        self.resolve(&scheme.ty).vars(&mut vars);
+        bound.extend(vars.into_iter().filter(|var| !scheme.vars.contains(var)));
+      }
+    }
+    for ret in &self.returns {
+      self.resolve(ret).vars(&mut bound);
+    }
+
+    let ty = self.resolve(ty);
+    let mut vars = vec![];
+    ty.vars(&mut vars);
+    vars.retain(|var| !bound.contains(var));
+    Scheme { vars, ty }
+  }
+
+  fn bind(&mut self, name: Name, ty: Type) {
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(name, Scheme { vars: vec![], ty });
+    }
+  }
+
+  fn lookup(&mut self, name: Name) -> Type {
+    let scheme = self.scopes.iter().rev().find_map(|scope| scope.get(&name)).cloned();
+    match scheme {
+      Some(scheme) => self.instantiate(&scheme),
+      None => self.builtin(name),
+    }
+  }
+
+  // 引数の数や型が決まっている組み込み関数と prelude の関数だけ型を付ける
+  fn builtin(&mut self, name: Name) -> Type {
+    let (elm, other) = (self.fresh(), self.fresh());
+    let array = |elm: &Type| Type::Array(Box::new(elm.clone()));
+    let func = |params: Vec<Type>, ret: Type| Type::Fn { required: params.len(), params, ret: Box::new(ret) };
+    match name.as_str() {
+      "len" => func(vec![Type::Any], Type::Int),
+      "first" | "last" | "sum" => func(vec![array(&elm)], elm),
+      "rest" => func(vec![array(&elm)], array(&elm)),
+      "push" => func(vec![array(&elm), elm.clone()], array(&elm)),
+      "abs" => func(vec![elm.clone()], elm),
+      "split" => func(vec![Type::Str, Type::Str], array(&Type::Str)),
+      "join" => func(vec![array(&Type::Str), Type::Str], Type::Str),
+      "trim" | "upper" | "lower" => func(vec![Type::Str], Type::Str),
+      "replace" => func(vec![Type::Str, Type::Str, Type::Str], Type::Str),
+      "starts_with" | "ends_with" => func(vec![Type::Str, Type::Str], Type::Bool),
+      "contains" => func(vec![Type::Any, Type::Any], Type::Bool),
+      "chars" => func(vec![Type::Str], array(&Type::Str)),
+      "pow" => func(vec![elm.clone(), elm.clone()], elm),
+      "sqrt" => func(vec![Type::Any], Type::Float),
+      "floor" | "ceil" => func(vec![Type::Any], Type::Int),
+      "json_stringify" => func(vec![Type::Any], Type::Str),
+      "read_file" => func(vec![Type::Str], Type::Str),
+      "read_lines" => func(vec![Type::Str], array(&Type::Str)),
+      "write_file" => func(vec![Type::Str, Type::Str], Type::Any),
+      "now_ms" => func(vec![], Type::Int),
+      "clock" => func(vec![], Type::Float),
+      "sleep" => func(vec![Type::Int], Type::Any),
+      "getenv" => func(vec![Type::Str], Type::Any),
+      "type" | "to_string" => func(vec![Type::Any], Type::Str),
+      "to_int" => func(vec![Type::Any], Type::Int),
+      "to_bool" => func(vec![Type::Any], Type::Bool),
+      "has_key" => func(vec![Type::Any, Type::Any], Type::Bool),
+      "map" => func(vec![array(&elm), func(vec![elm.clone()], other.clone())], array(&other)),
+      "filter" => func(vec![array(&elm), func(vec![elm.clone()], Type::Any)], array(&elm)),
+      "any" | "all" => func(vec![array(&elm), func(vec![elm.clone()], Type::Any)], Type::Bool),
+      "sort" => Type::Fn {
+        params: vec![array(&elm), func(vec![elm.clone(), elm.clone()], Type::Any)],
+        required: 1,
+        ret: Box::new(array(&elm)),
+      },
+      "eval" => Type::Fn { params: vec![Type::Str, Type::Bool], required: 1, ret: Box::new(Type::Any) },
+      "index_of" => func(vec![array(&elm), elm], Type::Any),
+      "reduce" => func(vec![array(&elm), other.clone(), func(vec![other.clone(), elm], other.clone())], other),
+      _ => Type::Any,
+    }
+  }
+
+  fn check_statements(&mut self, stmts: &[Statement]) -> Type {
+    let mut ty = Type::Any;
+    for stmt in stmts {
+      if let Statement::Comment(_) = stmt {
+        continue;
+      }
+      ty = self.check_statement(stmt);
+    }
+    ty
+  }
+
+  fn check_block(&mut self, block: &BlockStatement) -> Type {
+    self.check_statements(&block.statements)
+  }
+
+  // 関数の本体以外のブロックは、evaluator と同じくスコープを作る
+  fn check_scoped_block(&mut self, block: &BlockStatement) -> Type {
+    self.scopes.push(HashMap::new());
+    let ty = self.check_block(block);
+    self.scopes.pop();
+    ty
+  }
+
+  fn check_statement(&mut self, stmt: &Statement) -> Type {
+    match stmt {
+      Statement::Let(let_stmt) => {
+        match (&let_stmt.pattern, &let_stmt.value) {
+          (Pattern::Ident(ident), Expression::Literal(Literal::Func(func))) => self.check_let_function(ident.value, func),
+          (pattern, value) => {
+            let ty = self.check_expression(value);
+            self.bind_pattern(pattern, ty);
+          },
+        }
+        Type::Any
+      },
+      Statement::Func(func_stmt) => {
+        self.check_let_function(func_stmt.ident.value, &func_stmt.func);
+        Type::Any
+      },
+      Statement::Struct(struct_stmt) => {
+        self.bind(struct_stmt.ident.value, Type::Any);
+        Type::Any
+      },
+      Statement::Return(rtn) => {
+        let ty = self.check_expression(&rtn.value);
+        if let Some(ret) = self.returns.last().cloned() {
+          self.expect(&ret, &ty, rtn.value.span());
+        }
+        Type::Any
+      },
+      Statement::Expr(expr) => self.check_expression(&expr.value),
+      Statement::Block(block) => self.check_scoped_block(block),
+      Statement::While(while_stmt) => {
+        self.check_expression(&while_stmt.condition);
+        self.check_scoped_block(&while_stmt.body);
+        Type::Any
+      },
+      Statement::For(for_stmt) => {
+        let iterable = self.check_expression(&for_stmt.iterable);
+        let elm = match self.prune(&iterable) {
+          Type::Array(elm) => *elm,
+          // ハッシュや範囲は Any なので、配列に決めても渡せる
+          Type::Var(_) => {
+            let elm = self.fresh();
+            let _ = self.unify(&iterable, &Type::Array(Box::new(elm.clone())));
+            elm
+          },
+          Type::Any => Type::Any,
+          found => {
+            let found = self.resolve(&found);
+            self.errors.push(TypeError::Unsupported { operation: "iterate over".to_string(), found, span: for_stmt.iterable.span() });
+            Type::Any
+          },
+        };
+        self.scopes.push(HashMap::new());
+        self.bind(for_stmt.ident.value, elm);
+        self.check_block(&for_stmt.body);
+        self.scopes.pop();
+        Type::Any
+      },
+      Statement::Comment(_) => Type::Any,
+    }
+  }
+
+  // 再帰呼び出しの中では同じ型で、束縛した後は呼び出しごとに型変数を変えられる
+  fn check_let_function(&mut self, name: Name, func: &lit::Func) {
+    let ty = self.fresh();
+    self.bind(name, ty.clone());
+    let func_ty = self.check_function(func);
+    let _ = self.unify(&ty, &func_ty);
+
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.remove(&name);
+    }
+    let scheme = self.generalize(&func_ty);
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(name, scheme);
+    }
+  }
+
+  fn bind_pattern(&mut self, pattern: &Pattern, ty: Type) {
+    match pattern {
+      Pattern::Ident(ident) => self.bind(ident.value, ty),
+      Pattern::Array(arr) => {
+        let elm = match self.prune(&ty) {
+          Type::Array(elm) => *elm,
+          _ => Type::Any,
+        };
+        for pattern in &arr.elements {
+          self.bind_pattern(pattern, elm.clone());
+        }
+        if let Some(rest) = &arr.rest {
+          self.bind(rest.value, Type::Array(Box::new(elm)));
+        }
+      },
+      Pattern::Hash(hash) => {
+        for (_, pattern) in &hash.pairs {
+          self.bind_pattern(pattern, Type::Any);
+        }
+      },
+      Pattern::Struct(pat) => {
+        for (_, pattern) in &pat.fields {
+          self.bind_pattern(pattern, Type::Any);
+        }
+      },
+      Pattern::Literal(_) => (),
+    }
+  }
+
+  fn check_function(&mut self, func: &lit::Func) -> Type {
+    self.scopes.push(HashMap::new());
+    // デフォルト値はそれより前の引数が束縛されたスコープで評価される
+    let mut params = vec![];
+    for (param, default) in func.args.iter().zip(func.defaults.iter()) {
+      let ty = self.fresh();
+      if let Some(default) = default {
+        let default = self.check_expression(default);
+        let _ = self.unify(&ty, &default);
+      }
+      self.bind(param.value, ty.clone());
+      params.push(ty);
+    }
+    // 省略できるのは、後ろのデフォルト値がない引数より後ろだけ
+    let required = func.defaults.iter().rposition(|default| default.is_none()).map_or(0, |i| i + 1);
+
+    let ret = self.fresh();
+    self.returns.push(ret.clone());
+    let body = self.check_block(&func.body);
+    match func.body.statements.iter().rev().find(|stmt| !matches!(stmt, Statement::Comment(_))) {
+      Some(Statement::Return(_)) => (),
+      Some(stmt) => self.expect(&ret, &body, stmt.span()),
+      None => (),
+    }
+    self.returns.pop();
+    self.scopes.pop();
+
+    Type::Fn { params, required, ret: Box::new(ret) }
+  }
+
+  fn check_expression(&mut self, expr: &Expression) -> Type {
+    match expr {
+      Expression::Identifier(ident) => self.lookup(ident.value),
+      Expression::Literal(lit) => self.check_literal(lit),
+      Expression::Prefix(pre) => {
+        let right = self.check_expression(&pre.right);
+        match pre.operator {
+          Prefix::Bang => Type::Bool,
+          Prefix::Minus => self.check_numeric(&pre.operator.to_string(), right, pre.span),
+          Prefix::BitNot => match self.unify(&right, &Type::Int) {
+            Ok(()) => Type::Int,
+            Err(()) => {
+              let right = self.resolve(&right);
+              self.errors.push(TypeError::Operator { operator: pre.operator.to_string(), left: right, right: None, span: pre.span });
+              Type::Any
+            },
+          },
+        }
+      },
+      Expression::Infix(inf) => {
+        let left = self.check_expression(&inf.left);
+        let right = self.check_expression(&inf.right);
+        self.check_infix(&inf.operator, left, right, inf.span)
+      },
+      Expression::If(if_expr) => {
+        self.check_expression(&if_expr.condition);
+        let consequence = self.check_scoped_block(&if_expr.consequence);
+        match &if_expr.alternative {
+          Some(alt) => {
+            let alternative = self.check_scoped_block(alt);
+            self.join(&consequence, &alternative)
+          },
+          None => Type::Any,
+        }
+      },
+      Expression::Try(try_expr) => {
+        let block = self.check_scoped_block(&try_expr.block);
+        self.scopes.push(HashMap::new());
+        self.bind(try_expr.param.value, Type::Any);
+        let handler = self.check_block(&try_expr.handler);
+        self.scopes.pop();
+        self.join(&block, &handler)
+      },
+      // 最後の腕が名前だけのパターンでなければ、どの腕にも当てはまらずに null になることがある
+      Expression::Match(match_expr) => {
+        let subject = self.check_expression(&match_expr.subject);
+        let mut result: Option<Type> = None;
+        for arm in &match_expr.arms {
+          self.scopes.push(HashMap::new());
+          self.bind_pattern(&arm.pattern, subject.clone());
+          let body = self.check_expression(&arm.body);
+          self.scopes.pop();
+          result = Some(match result {
+            Some(ty) => self.join(&ty, &body),
+            None => body,
+          });
+        }
+        match (result, match_expr.arms.last().map(|arm| &arm.pattern)) {
+          (Some(ty), Some(Pattern::Ident(_))) => ty,
+          _ => Type::Any,
+        }
+      },
+      Expression::Ternary(ternary) => {
+        self.check_expression(&ternary.condition);
+        let consequence = self.check_expression(&ternary.consequence);
+        let alternative = self.check_expression(&ternary.alternative);
+        self.join(&consequence, &alternative)
+      },
+      Expression::Call(call) => self.check_call(call),
+      Expression::MethodCall(method) => self.check_method_call(method),
+      Expression::Index(idx) => {
+        let left = self.check_expression(&idx.left);
+        let index = self.check_expression(&idx.index);
+        match self.prune(&left) {
+          Type::Array(elm) => {
+            self.expect(&Type::Int, &index, idx.index.span());
+            *elm
+          },
+          Type::Var(_) | Type::Any => Type::Any,
+          found => self.unsupported("index", &found, idx.left.span()),
+        }
+      },
+      // ハッシュの型は区別しないので、値の型は分からない
+      Expression::Member(member) => {
+        let object = self.check_expression(&member.object);
+        match self.prune(&object) {
+          Type::Var(_) | Type::Any => Type::Any,
+          found => self.unsupported("index", &found, member.object.span()),
+        }
+      },
+      Expression::Slice(slice) => {
+        let left = self.check_expression(&slice.left);
+        for bound in slice.start.iter().chain(slice.end.iter()) {
+          let ty = self.check_expression(bound);
+          self.expect(&Type::Int, &ty, bound.span());
+        }
+        match self.prune(&left) {
+          Type::Array(_) | Type::Str => left,
+          Type::Var(_) | Type::Any => Type::Any,
+          found => self.unsupported("slice", &found, slice.left.span()),
+        }
+      },
+      Expression::Assign(assign) => {
+        let current = self.check_expression(&assign.target);
+        let mut value = self.check_expression(&assign.value);
+        if let Some(operator) = &assign.operator {
+          value = self.check_infix(operator, current.clone(), value, assign.span);
+        }
+        self.expect(&current, &value, assign.value.span());
+        value
+      },
+      Expression::Update(update) => {
+        let target = self.check_expression(&update.target);
+        self.check_numeric(&update.operator.to_string(), target, update.span)
+      },
+      Expression::Range(range) => {
+        for bound in [&range.start, &range.end].iter() {
+          let ty = self.check_expression(bound);
+          self.expect(&Type::Int, &ty, bound.span());
+        }
+        Type::Any
+      },
+      Expression::Spread(spread) => {
+        self.check_expression(&spread.value);
+        Type::Any
+      },
+    }
+  }
+
+  fn unsupported(&mut self, operation: &str, found: &Type, span: Span) -> Type {
+    let found = self.resolve(found);
+    self.errors.push(TypeError::Unsupported { operation: operation.to_string(), found, span });
+    Type::Any
+  }
+
+  fn check_literal(&mut self, lit: &Literal) -> Type {
+    match lit {
+      Literal::Integer(_) => Type::Int,
+      Literal::Float(_) => Type::Float,
+      Literal::Boolean(_) => Type::Bool,
+      Literal::Str(_) => Type::Str,
+      Literal::Null(_) => Type::Any,
+      Literal::Array(arr) => {
+        let elm = self.fresh();
+        let mut mixed = false;
+        for element in &arr.elements {
+          let ty = match element {
+            // 展開した配列の要素が、この配列の要素になる
+            Expression::Spread(spread) => {
+              let ty = self.check_expression(&spread.value);
+              match self.prune(&ty) {
+                Type::Array(elm) => *elm,
+                _ => Type::Any,
+              }
+            },
+            element => self.check_expression(element),
+          };
+          mixed |= self.unify(&elm, &ty).is_err();
+        }
+        if mixed {
+          return Type::Array(Box::new(Type::Any));
+        }
+        Type::Array(Box::new(elm))
+      },
+      Literal::Hash(hash) => {
+        for (key, val) in &hash.pairs {
+          self.check_expression(key);
+          self.check_expression(val);
+        }
+        Type::Any
+      },
+      // レコードもハッシュと同じく、フィールドの型を持たない
+      Literal::Struct(lit) => {
+        for (_, val) in &lit.fields {
+          self.check_expression(val);
+        }
+        Type::Any
+      },
+      Literal::Func(func) => self.check_function(func),
+      // マクロの本体は構文木を組み立てるので調べない
+      Literal::Macro(_) => Type::Any,
+    }
+  }
+
+  // - と ++、-- は数にだけ使える
+  fn check_numeric(&mut self, operator: &str, ty: Type, span: Span) -> Type {
+    match self.prune(&ty) {
+      Type::Int | Type::Float | Type::Var(_) | Type::Any => ty,
+      left => {
+        let left = self.resolve(&left);
+        self.errors.push(TypeError::Operator { operator: operator.to_string(), left, right: None, span });
+        Type::Any
+      },
+    }
+  }
+
+  fn check_infix(&mut self, operator: &Infix, left: Type, right: Type, span: Span) -> Type {
+    let (l, r) = (self.prune(&left), self.prune(&right));
+    let result = match operator {
+      // どんな値どうしでも比べられ、&& と || は真偽値に変換する
+      Infix::Equal | Infix::NotEq | Infix::And | Infix::Or => return Type::Bool,
+      Infix::Coalesce => return self.join(&left, &right),
+      Infix::Lt | Infix::Gt | Infix::LtEq | Infix::GtEq => self.check_operands(&l, &r, true).map(|_| Type::Bool),
+      Infix::Plus => self.check_operands(&l, &r, true),
+      Infix::Minus | Infix::Asterisk | Infix::Slash | Infix::Percent | Infix::Power => self.check_operands(&l, &r, false),
+      // ビット演算は整数にだけ使える
+      Infix::BitAnd | Infix::BitOr | Infix::BitXor | Infix::ShiftLeft | Infix::ShiftRight => {
+        match (self.unify(&l, &Type::Int), self.unify(&r, &Type::Int)) {
+          (Ok(()), Ok(())) => Some(Type::Int),
+          _ => None,
+        }
+      },
+      Infix::Call => Some(Type::Any),
+    };
+
+    match result {
+      Some(ty) => ty,
+      None => {
+        let (left, right) = (self.resolve(&l), self.resolve(&r));
+        self.errors.push(TypeError::Operator { operator: operator.to_string(), left, right: Some(right), span });
+        Type::Any
+      },
+    }
+  }
+
+  // 算術演算の結果の型。整数と小数を混ぜると小数になる。片方しか決まっていなければ、もう片方も同じ型にする
+  fn check_operands(&mut self, l: &Type, r: &Type, strings: bool) -> Option<Type> {
+    match (l, r) {
+      (Type::Int, Type::Int) => Some(Type::Int),
+      (Type::Int, Type::Float) | (Type::Float, Type::Int) | (Type::Float, Type::Float) => Some(Type::Float),
+      (Type::Str, Type::Str) if strings => Some(Type::Str),
+      (Type::Any, _) | (_, Type::Any) => Some(Type::Any),
+      (Type::Var(_), Type::Float) | (Type::Float, Type::Var(_)) => Some(Type::Float),
+      (Type::Var(_), ty) | (ty, Type::Var(_)) if *ty == Type::Int || (strings && *ty == Type::Str) || matches!(ty, Type::Var(_)) => {
+        let _ = self.unify(l, r);
+        Some(self.prune(l))
+      },
+      _ => None,
+    }
+  }
+
+  fn check_call(&mut self, call: &CallExpression) -> Type {
+    // quote の引数は評価されない構文木なので調べない
+    if macro_expansion::is_quote_call(call) {
+      return Type::Any;
+    }
+    let func = self.check_expression(&call.func);
+    let mut args = vec![];
+    let mut spread = false;
+    for arg in &call.args {
+      match arg {
+        Expression::Spread(s) => {
+          self.check_expression(&s.value);
+          spread = true;
+        },
+        arg => {
+          let ty = self.check_expression(arg);
+          args.push((ty, arg.span()));
+        },
+      }
+    }
+
+    // 展開した引数の数は分からないので調べない
+    if spread {
+      return Type::Any;
+    }
+    self.apply(func, args, call.func.span(), call.span)
+  }
+
+  // メソッドは、レシーバを最初の引数として同じ名前の組み込み関数を呼び出す
+  fn check_method_call(&mut self, method: &MethodCallExpression) -> Type {
+    let receiver = self.check_expression(&method.receiver);
+    // ハッシュやレコードは、組み込みのメソッドではなく値に持つ関数を呼ぶかもしれない
+    let field = matches!(self.prune(&receiver), Type::Any);
+    let mut args = vec![(receiver, method.receiver.span())];
+    let mut spread = false;
+    for arg in &method.args {
+      match arg {
+        Expression::Spread(s) => {
+          self.check_expression(&s.value);
+          spread = true;
+        },
+        arg => {
+          let ty = self.check_expression(arg);
+          args.push((ty, arg.span()));
+        },
+      }
+    }
+    if spread || field {
+      return Type::Any;
+    }
+    let func = self.builtin(method.method.value);
+    self.apply(func, args, method.method.span, method.span)
+  }
+
+  fn apply(&mut self, func: Type, args: Vec<(Type, Span)>, func_span: Span, span: Span) -> Type {
+    match self.prune(&func) {
+      Type::Fn { params, required, ret } => {
+        if args.len() < required || args.len() > params.len() {
+          self.errors.push(TypeError::Arity { min: required, max: params.len(), found: args.len(), span });
+          return *ret;
+        }
+        for (param, (arg, span)) in params.iter().zip(args.iter()) {
+          self.expect(param, arg, *span);
+        }
+        *ret
+      },
+      // 引数の関数などは、呼び出し方から型を決める
+      Type::Var(_) => {
+        let ret = self.fresh();
+        let params: Vec<Type> = args.into_iter().map(|(ty, _)| ty).collect();
+        let called = Type::Fn { required: params.len(), params, ret: Box::new(ret.clone()) };
+        self.expect(&func, &called, func_span);
+        ret
+      },
+      Type::Any => Type::Any,
+      found => {
+        let found = self.resolve(&found);
+        self.errors.push(TypeError::NotCallable { found, span: func_span });
+        Type::Any
+      },
+    }
+  }
+}
+
+// 表示する型変数を 'a から順に付け直す
+fn rename_vars(types: &[Type]) -> Vec<Type> {
+  let mut vars = vec![];
+  for ty in types {
+    ty.vars(&mut vars);
+  }
+  let mapping = vars.into_iter().enumerate().map(|(i, var)| (var, Type::Var(i))).collect();
+  types.iter().map(|ty| replace_vars(ty, &mapping)).collect()
+}
+
+fn rename_error_vars(err: TypeError) -> TypeError {
+  match err {
+    TypeError::Mismatch { expected, found, span } => {
+      let mut types = rename_vars(&[expected, found]);
+      let found = types.pop().unwrap();
+      TypeError::Mismatch { expected: types.pop().unwrap(), found, span }
+    },
+    TypeError::Operator { operator, left, right: Some(right), span } => {
+      let mut types = rename_vars(&[left, right]);
+      let right = types.pop();
+      TypeError::Operator { operator, left: types.pop().unwrap(), right, span }
+    },
+    TypeError::Operator { operator, left, right: None, span } =>
+      TypeError::Operator { operator, left: rename_vars(&[left]).remove(0), right: None, span },
+    TypeError::NotCallable { found, span } =>
+      TypeError::NotCallable { found: rename_vars(&[found]).remove(0), span },
+    TypeError::Unsupported { operation, found, span } =>
+      TypeError::Unsupported { operation, found: rename_vars(&[found]).remove(0), span },
+    err => err,
+  }
+}
+
+fn replace_vars(ty: &Type, mapping: &HashMap<usize, Type>) -> Type {
+  match ty {
+    Type::Var(id) => mapping.get(id).cloned().unwrap_or(Type::Var(*id)),
+    Type::Array(elm) => Type::Array(Box::new(replace_vars(elm, mapping))),
+    Type::Fn { params, required, ret } => Type::Fn {
+      params: params.iter().map(|param| replace_vars(param, mapping)).collect(),
+      required: *required,
+      ret: Box::new(replace_vars(ret, mapping)),
+    },
+    ty => ty.clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+
+  fn test_check(input: &str) -> Result<String, Vec<String>> {
+    let mut p = Parser::new(Lexer::new(input.to_string()));
+    let program = p.parse_program();
+    assert!(p.check_parse_errors());
+    check(&program)
+      .map(|ty| ty.to_string())
+      .map_err(|errors| errors.iter().map(|err| format!("{}: {}", err.span(), err)).collect())
+  }
+
+  #[test]
+  fn test_infer() {
+    let tests = vec![
+      ("1 + 2 * 3", "int"),
+      ("1 + 2.5", "float"),
+      ("\"a\" + \"b\"", "string"),
+      ("!5", "bool"),
+      ("fn(a, b) { a & b << 2 }", "fn(int, int) -> int"),
+      ("~[1][0]", "int"),
+      ("[1, 2][0] < 3", "bool"),
+      ("[1, \"a\"]", "[any]"),
+      ("let xs = [1, 2]; [...xs, 3]", "[int]"),
+      ("fn(x) { x }", "fn('a) -> 'a"),
+      ("fn(a, b = 1) { a + b }", "fn(int, int?) -> int"),
+      ("let id = fn(x) { x }; [id(1), len(id(\"a\"))]", "[int]"),
+      ("let fib = fn(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) }; fib", "fn(int) -> int"),
+      ("fn fib(n) { if (n < 2) { return n; } fib(n - 1) + fib(n - 2) } fib", "fn(int) -> int"),
+      ("fn id(x) { x } [id(1), len(id(\"a\"))]", "[int]"),
+      ("let map = fn(arr, f) { let out = []; for (x in arr) { out = push(out, f(x)) }; out }; map", "fn(['a], fn('a) -> 'b) -> ['b]"),
+      ("let x = if (true) { 1 } else { \"a\" }; x", "any"),
+      ("let {a} = {\"a\": 1}; a + 1", "any"),
+      ("let [a, ...rest] = [1, 2]; rest", "[int]"),
+      ("match (2) { 0 => 1, n => n * 10 }", "int"),
+      ("match (2) { 0 => 1, 1 => 2 }", "any"),
+      ("match ([1]) { [] => 0, [x, ...xs] => len(xs) }", "any"),
+      ("let f = fn() { g() }; let g = fn() { 1 }; f()", "any"),
+      ("reduce(map([1, 2], fn(x) { x * 1.5 }), 0.0, fn(acc, x) { acc + x })", "float"),
+      ("join(map(split(\"a b\", \" \"), upper), \",\")", "string"),
+      ("contains(trim(\" a \"), \"a\")", "bool"),
+      ("[floor(sqrt(pow(3, 2))), ceil(1.5)]", "[int]"),
+      ("\"a b\".split(\" \").map(upper).join(\",\")", "string"),
+      ("[1, 2].map(fn(x) { x * 1.5 })", "[float]"),
+      ("[1].foo()", "any"),
+      ("let p = {\"a\": 1}; p.a", "any"),
+      ("let p = {\"len\": fn(x, y) { x + y }}; p.len(1, 2)", "any"),
+      ("struct P {x} let P {x} = P {x: 1}; [P {x: 2}.x, x]", "[any]"),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(test_check(input), Ok(expected.to_string()), "{}", input);
+    }
+  }
+
+  #[test]
+  fn test_type_errors() {
+    let tests = vec![
+      ("1 + true", vec!["1:1: unsupported operand types for +: int and bool."]),
+      ("-\"a\"", vec!["1:1: unsupported operand type for -: string."]),
+      ("1.5 | 1", vec!["1:1: unsupported operand types for |: float and int."]),
+      ("~true", vec!["1:1: unsupported operand type for ~: bool."]),
+      ("let x = 1; x = \"a\"", vec!["1:16: type mismatch: expected int, found string."]),
+      ("let f = fn(n) { n * 2 }; f(\"a\")", vec!["1:28: type mismatch: expected int, found string."]),
+      ("let f = fn(n) { n * 2 }; f(true, 1)", vec!["1:26: wrong number of arguments: got=2, want=1."]),
+      ("let f = fn(a, b = 1) { a }; f()", vec!["1:29: wrong number of arguments: got=0, want=1..=2."]),
+      ("let f = fn(g) { g(1) + 1 }; f(fn(s) { s + \"!\" })", vec!["1:31: type mismatch: expected fn(int) -> int, found fn(string) -> string."]),
+      ("5(1)", vec!["1:1: int is not a function."]),
+      ("upper(split(\"a\", \"\"))", vec!["1:7: type mismatch: expected string, found [string]."]),
+      ("for (i in 10) { i }; true[0]", vec!["1:11: cannot iterate over int.", "1:22: cannot index bool."]),
+      ("[1, 2][\"a\"]", vec!["1:8: type mismatch: expected int, found string."]),
+      ("let f = fn(n) { if (n) { return 1; } \"a\" }", vec!["1:38: type mismatch: expected int, found string."]),
+      ("let x = 1; x += \"a\"", vec!["1:12: unsupported operand types for +: int and string."]),
+      ("let x = 1; if (true) { let x = \"a\"; x }; x + \"b\"", vec!["1:42: unsupported operand types for +: int and string."]),
+      ("filter([1, 2], fn(s) { s + \"!\" })", vec!["1:16: type mismatch: expected fn(int) -> any, found fn(string) -> string."]),
+      ("[\"a\"].upper()", vec!["1:1: type mismatch: expected string, found [string]."]),
+      ("\"a\".split()", vec!["1:1: wrong number of arguments: got=1, want=2."]),
+      ("let xs = [1]; xs.a", vec!["1:15: cannot index [int]."]),
+    ];
+
+    for (input, expected) in tests {
+      let expected: Vec<String> = expected.iter().map(|err| err.to_string()).collect();
+      assert_eq!(test_check(input), Err(expected), "{}", input);
+    }
+  }
+}
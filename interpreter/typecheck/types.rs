@@ -0,0 +1,67 @@
+use std::fmt;
+
+/// The type of a value as `typecheck::check` infers it.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+  Int,
+  Float,
+  Bool,
+  Str,
+  /// An array whose elements all have the same type.
+  Array(Box<Type>),
+  /// A function which needs at least `required` arguments, since the parameters after them have default values.
+  Fn {
+    params: Vec<Type>,
+    required: usize,
+    ret: Box<Type>,
+  },
+  /// A type which is not known yet, e.g. of a parameter which is never used. It is shown as `'a`, `'b`, ....
+  Var(usize),
+  /// A value whose type is not followed, e.g. `null`, a hash, a range or an array of mixed types.
+  /// It is accepted wherever a type is expected.
+  Any,
+}
+
+impl Type {
+  // 出てくる型変数を、重複させずに出てきた順に集める
+  pub(crate) fn vars(&self, vars: &mut Vec<usize>) {
+    match self {
+      Type::Var(id) if !vars.contains(id) => vars.push(*id),
+      Type::Array(elm) => elm.vars(vars),
+      Type::Fn { params, ret, .. } => {
+        for param in params {
+          param.vars(vars);
+        }
+        ret.vars(vars);
+      },
+      _ => (),
+    }
+  }
+}
+
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Type::Int => write!(f, "int"),
+      Type::Float => write!(f, "float"),
+      Type::Bool => write!(f, "bool"),
+      Type::Str => write!(f, "string"),
+      Type::Array(elm) => write!(f, "[{}]", elm),
+      Type::Fn { params, required, ret } => {
+        // デフォルト値のある引数には ? を付ける
+        let params: Vec<String> = params.iter().enumerate()
+          .map(|(i, param)| if i < *required { param.to_string() } else { format!("{}?", param) })
+          .collect();
+        write!(f, "fn({}) -> {}", params.join(", "), ret)
+      },
+      Type::Var(id) => {
+        let letter = (b'a' + (id % 26) as u8) as char;
+        match id / 26 {
+          0 => write!(f, "'{}", letter),
+          n => write!(f, "'{}{}", letter, n),
+        }
+      },
+      Type::Any => write!(f, "any"),
+    }
+  }
+}
@@ -7,7 +7,7 @@ pub fn write_object_list<T: fmt::Display>(list: &[T], f: &mut fmt::Formatter<'_>
   while let Some(val) = next {
     next = iter.next();
     write!(f, "{}", val)?;
-    if let Some(_) = next {
+    if next.is_some() {
       write!(f, ", ")?;
     }
   }
@@ -15,6 +15,21 @@ pub fn write_object_list<T: fmt::Display>(list: &[T], f: &mut fmt::Formatter<'_>
   Ok(())
 }
 
+/// Writes `x, y = 10` style parameter lists, where `defaults` runs parallel to `params`.
+pub fn write_param_list<T: fmt::Display, U: fmt::Display>(params: &[T], defaults: &[Option<U>], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+  for (i, param) in params.iter().enumerate() {
+    if i > 0 {
+      write!(f, ", ")?;
+    }
+    write!(f, "{}", param)?;
+    if let Some(Some(default)) = defaults.get(i) {
+      write!(f, " = {}", default)?;
+    }
+  }
+
+  Ok(())
+}
+
 pub fn format_object_list<T: fmt::Display>(list: &[T], sep: &str) -> String {
   let mut output = String::new();
   
@@ -24,10 +39,22 @@ pub fn format_object_list<T: fmt::Display>(list: &[T], sep: &str) -> String {
   while let Some(val) = next {
     next = iter.next();
     output.push_str(&format!("{}", val));
-    if let Some(_) = next {
-      output.push_str(&format!("{}", sep));
+    if next.is_some() {
+      output.push_str(sep);
     }
   }
 
   output
 }
+
+/// How much of the host stack a pass which recurses over a syntax tree, like the optimizer or the evaluator,
+/// may use by default before it stops. It leaves half of the 2MiB stack of a spawned thread to the host.
+pub const DEFAULT_MAX_STACK: usize = 1 << 20;
+
+/// Returns an address in the stack frame of the caller. The distance between two of them is about how much
+/// of the host stack the frames between them use, whichever way the stack grows.
+#[inline(never)]
+pub fn stack_address() -> usize {
+  let marker = 0u8;
+  std::hint::black_box(&marker) as *const u8 as usize
+}
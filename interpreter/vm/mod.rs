@@ -0,0 +1,601 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use crate::code::{self, Opcode};
+use crate::compiler::{self, Bytecode, symbol_table::SymbolScope};
+use crate::evaluator::{self, object::{self, Object, ErrorKind, CompiledFunction, Closure, Slot}};
+use crate::evaluator::environment::Environment;
+use crate::evaluator::limits::Budget;
+use crate::intern::Name;
+
+const NULL: Object = Object::Null;
+
+/// Runs `bytecode` in `env` and returns the value of the last statement, like `evaluator::eval`.
+/// Runtime errors are returned as `Object::Error` with the same kinds and messages as the evaluator,
+/// but without their locations and stack traces.
+pub fn run(bytecode: Bytecode, env: &Rc<RefCell<Environment>>) -> Object {
+  let main = CompiledFunction {
+    instructions: bytecode.instructions,
+    constants: bytecode.constants,
+    names: bytecode.names,
+    functions: bytecode.functions,
+    locals: bytecode.locals,
+    num_params: 0,
+    free: vec![],
+    source: String::new(),
+  };
+  let locals = (0..main.locals.len()).map(|_| Rc::new(RefCell::new(None))).collect();
+  let mut vm = Vm {
+    stack: vec![],
+    budget: env.borrow().budget(),
+    main: 1,
+    frames: vec![Frame { func: Rc::new(main), ip: 0, env: Rc::clone(env), base: 0, locals, free: vec![] }],
+  };
+  match vm.run() {
+    Ok(obj) => obj,
+    Err(err) => err,
+  }
+}
+
+/// Calls `closure` from outside of the VM, e.g. when the evaluator calls a function defined by compiled code.
+pub(crate) fn apply_closure(closure: &Closure, args: Vec<Object>) -> Object {
+  let mut vm = Vm { stack: vec![], frames: vec![], budget: closure.env.borrow().budget(), main: 0 };
+  if let Err(err) = vm.push_frame(closure, args) {
+    return err;
+  }
+  match vm.run() {
+    Ok(obj) => obj,
+    Err(err) => err,
+  }
+}
+
+struct Frame {
+  func: Rc<CompiledFunction>,
+  ip: usize,
+  env: Rc<RefCell<Environment>>,
+  // 呼び出し時のスタックの高さ。戻るときにここまで戻す
+  base: usize,
+  locals: Vec<Slot>,
+  free: Vec<Slot>,
+}
+
+struct Vm {
+  stack: Vec<Object>,
+  frames: Vec<Frame>,
+  budget: Rc<Budget>,
+  // プログラム本体のフレームの数。呼び出しの深さには数えない
+  main: usize,
+}
+
+impl Vm {
+  fn frame(&self) -> &Frame {
+    self.frames.last().unwrap()
+  }
+
+  fn pop(&mut self) -> Object {
+    self.stack.pop().unwrap()
+  }
+
+  fn push(&mut self, obj: Object) -> Result<(), Object> {
+    if let Object::Error(_) = obj {
+      return Err(obj);
+    }
+    self.budget.allocate(&obj)?;
+    self.stack.push(obj);
+    Ok(())
+  }
+
+  fn run(&mut self) -> Result<Object, Object> {
+    loop {
+      let frame = self.frames.last_mut().unwrap();
+      let ins = &frame.func.instructions.0;
+      // 関数は必ず ReturnValue で終わるので、ここに来るのはプログラムの終わりだけ
+      if frame.ip >= ins.len() {
+        return Ok(self.stack.pop().unwrap_or(NULL));
+      }
+
+      let op = match Opcode::from_byte(ins[frame.ip]) {
+        Some(op) => op,
+        None => unreachable!("undefined opcode {}", ins[frame.ip]),
+      };
+      self.budget.step()?;
+      let (operands, read) = code::read_operands(op, &ins[frame.ip + 1..]);
+      frame.ip += 1 + read;
+      let operand = operands.first().copied().unwrap_or(0);
+
+      match op {
+        Opcode::Constant => {
+          let constant = self.frame().func.constants[operand].clone();
+          self.push(constant)?;
+        },
+        Opcode::Pop => {
+          self.pop();
+        },
+        Opcode::True => self.push(evaluator::native_bool_to_boolean_object(true))?,
+        Opcode::False => self.push(evaluator::native_bool_to_boolean_object(false))?,
+        Opcode::Null => self.push(NULL)?,
+        Opcode::Add
+        | Opcode::Sub
+        | Opcode::Mul
+        | Opcode::Div
+        | Opcode::Mod
+        | Opcode::Pow
+        | Opcode::BitAnd
+        | Opcode::BitOr
+        | Opcode::BitXor
+        | Opcode::ShiftLeft
+        | Opcode::ShiftRight
+        | Opcode::Equal
+        | Opcode::NotEqual
+        | Opcode::GreaterThan
+        | Opcode::LessThan
+        | Opcode::GreaterEqual
+        | Opcode::LessEqual => {
+          let right = self.pop();
+          let left = self.pop();
+          let operator = compiler::to_infix(op).unwrap();
+          let env = Rc::clone(&self.frame().env);
+          self.push(evaluator::eval_infix_in_env(left, &operator, right, &env))?;
+        },
+        Opcode::Minus | Opcode::Bang | Opcode::BitNot => {
+          let right = self.pop();
+          let operator = match op {
+            Opcode::Minus => crate::ast::operator::Prefix::Minus,
+            Opcode::BitNot => crate::ast::operator::Prefix::BitNot,
+            _ => crate::ast::operator::Prefix::Bang,
+          };
+          let env = Rc::clone(&self.frame().env);
+          self.push(evaluator::eval_prefix_in_env(&operator, right, &env))?;
+        },
+        Opcode::Jump => self.jump(operand),
+        Opcode::JumpNotTruthy => {
+          if !evaluator::is_truthy(self.pop()) {
+            self.jump(operand);
+          }
+        },
+        Opcode::JumpTruthy => {
+          if evaluator::is_truthy(self.pop()) {
+            self.jump(operand);
+          }
+        },
+        // null でなければ値を残したまま飛ぶ
+        Opcode::JumpNotNull => match self.stack.last() {
+          Some(Object::Null) => {
+            self.pop();
+          },
+          _ => self.jump(operand),
+        },
+        Opcode::GetName => {
+          let frame = self.frame();
+          let val = evaluator::lookup(frame.func.names[operand], &frame.env);
+          self.push(val)?;
+        },
+        Opcode::DefineName => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          evaluator::bind(frame.func.names[operand], val, &frame.env)?;
+        },
+        Opcode::DefineConstName => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          evaluator::bind_constant(frame.func.names[operand], val, &frame.env)?;
+        },
+        Opcode::AssignName => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          let name = frame.func.names[operand];
+          if frame.env.borrow().is_constant(name) {
+            return Err(evaluator::new_error(ErrorKind::Name, format!("assignment to constant: {}.", name)));
+          }
+          if !frame.env.borrow_mut().assign(name, val) {
+            return Err(evaluator::new_error(ErrorKind::Name, format!("assignment to undeclared identifier: {}.", name)));
+          }
+        },
+        Opcode::GetLocal => {
+          let frame = self.frame();
+          let val = read_slot(&frame.locals[operand], frame.func.locals[operand])?;
+          self.push(val)?;
+        },
+        Opcode::GetFree => {
+          let frame = self.frame();
+          let val = read_slot(&frame.free[operand], frame.func.free[operand].name)?;
+          self.push(val)?;
+        },
+        Opcode::DefineLocal => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          evaluator::check_bindable(frame.func.locals[operand], &frame.env)?;
+          *frame.locals[operand].borrow_mut() = Some(val);
+        },
+        Opcode::AssignLocal => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          write_slot(&frame.locals[operand], frame.func.locals[operand], val)?;
+        },
+        // 取り込んだクロージャには前のスロットが残る
+        Opcode::ResetLocal => {
+          let frame = self.frames.last_mut().unwrap();
+          frame.locals[operand] = Rc::new(RefCell::new(None));
+        },
+        Opcode::AssignFree => {
+          let val = self.stack.last().unwrap().clone();
+          let frame = self.frame();
+          write_slot(&frame.free[operand], frame.func.free[operand].name, val)?;
+        },
+        Opcode::Array => {
+          let elements = self.stack.split_off(self.stack.len() - operand);
+          self.push(Object::Array(object::Array::new(elements)))?;
+        },
+        Opcode::Hash => {
+          let elements = self.stack.split_off(self.stack.len() - operand * 2);
+          let mut pairs = HashMap::new();
+          let mut elements = elements.into_iter();
+          while let (Some(key), Some(val)) = (elements.next(), elements.next()) {
+            if !key.is_hashable() {
+              return Err(evaluator::new_error(ErrorKind::Type, format!("unusable as hash key: {}", key)));
+            }
+            pairs.insert(key, val);
+          }
+          self.push(Object::Hash(object::Hash::new(pairs)))?;
+        },
+        Opcode::Index => {
+          let index = self.pop();
+          let left = self.pop();
+          self.push(evaluator::eval_index_expression(left, index))?;
+        },
+        Opcode::Slice => {
+          let end = self.pop();
+          let start = self.pop();
+          let left = self.pop();
+          self.push(evaluator::slice_object(left, start, end))?;
+        },
+        Opcode::Range => {
+          let end = self.pop();
+          let start = self.pop();
+          self.push(evaluator::new_range(start, end, operand == 1))?;
+        },
+        Opcode::Call => {
+          let args = self.stack.split_off(self.stack.len() - operand);
+          let callee = self.pop();
+          self.call(callee, args)?;
+        },
+        // 引数の下にある受け取る値からメソッドを引いて呼ぶ
+        Opcode::CallMethod => {
+          let args = self.stack.split_off(self.stack.len() - operands[1]);
+          let receiver = self.pop();
+          let frame = self.frame();
+          let (callee, args) = evaluator::builtins::lookup_method(receiver, frame.func.names[operand], args, &frame.env)?;
+          self.call(callee, args)?;
+        },
+        Opcode::ReturnValue => {
+          let val = self.pop();
+          let frame = self.frames.pop().unwrap();
+          // トップレベルの return はプログラムを終わらせる
+          if self.frames.is_empty() {
+            return Ok(val);
+          }
+          self.stack.truncate(frame.base);
+          self.push(val)?;
+        },
+        Opcode::Closure => {
+          let frame = self.frame();
+          let func = Rc::clone(&frame.func.functions[operand]);
+          // 自由変数は値ではなくスロットを共有するので、後からの代入も見える
+          let free = func.free.iter()
+            .map(|symbol| match symbol.scope {
+              SymbolScope::Local => Rc::clone(&frame.locals[symbol.index]),
+              _ => Rc::clone(&frame.free[symbol.index]),
+            })
+            .collect();
+          let closure = Closure::new(func, Rc::clone(&frame.env), free);
+          self.push(Object::Closure(closure))?;
+        },
+      }
+    }
+  }
+
+  fn jump(&mut self, target: usize) {
+    self.frames.last_mut().unwrap().ip = target;
+  }
+
+  fn call(&mut self, mut callee: Object, args: Vec<Object>) -> Result<(), Object> {
+    match callee {
+      Object::Closure(closure) => self.push_frame(&closure, args),
+      // 組み込み関数や tree-walking で作られた関数は evaluator に任せる
+      _ => {
+        let env = Rc::clone(&self.frame().env);
+        self.push(evaluator::apply_func(&mut callee, args, &env))
+      },
+    }
+  }
+
+  fn push_frame(&mut self, closure: &Closure, args: Vec<Object>) -> Result<(), Object> {
+    let func = &closure.func;
+    if args.len() > func.num_params {
+      return Err(evaluator::new_error(ErrorKind::Argument, format!("wrong number of argument: got={}, want={}.", args.len(), func.num_params)));
+    }
+    if args.len() < func.num_params {
+      return Err(evaluator::new_error(ErrorKind::Argument, format!("missing argument: {}.", func.locals[args.len()])));
+    }
+
+    let max = closure.env.borrow().depth().max();
+    if self.frames.len() > max {
+      return Err(evaluator::new_error(ErrorKind::Recursion, format!("maximum recursion depth exceeded: {}.", max)));
+    }
+    self.budget.check_calls(self.frames.len() - self.main)?;
+
+    let mut args = args.into_iter();
+    let locals = (0..func.locals.len()).map(|_| Rc::new(RefCell::new(args.next()))).collect();
+
+    self.frames.push(Frame {
+      func: Rc::clone(func),
+      ip: 0,
+      env: Rc::clone(&closure.env),
+      base: self.stack.len(),
+      locals,
+      free: closure.free.clone(),
+    });
+    Ok(())
+  }
+}
+
+fn read_slot(slot: &Slot, name: Name) -> Result<Object, Object> {
+  match &*slot.borrow() {
+    Some(val) => Ok(val.clone()),
+    // 条件によって実行されなかった let の変数は、まだ束縛されていない
+    None => Err(evaluator::new_error(ErrorKind::Name, format!("identifier not found: {}.", name))),
+  }
+}
+
+fn write_slot(slot: &Slot, name: Name, val: Object) -> Result<(), Object> {
+  let mut slot = slot.borrow_mut();
+  if slot.is_none() {
+    return Err(evaluator::new_error(ErrorKind::Name, format!("assignment to undeclared identifier: {}.", name)));
+  }
+  *slot = Some(val);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::lexer::Lexer;
+  use crate::parser::Parser;
+  use crate::evaluator::builtins;
+  use crate::evaluator::limits::Limits;
+
+  fn parse(input: &str) -> crate::ast::Program {
+    let l = Lexer::new(input.to_string());
+    let mut p = Parser::new(l);
+    let program = p.parse_program();
+    if !p.check_parse_errors() {
+      panic!();
+    }
+    program
+  }
+
+  fn test_run(input: &str) -> Object {
+    let bytecode = match compiler::compile(&parse(input)) {
+      Ok(bytecode) => bytecode,
+      Err(err) => panic!("failed to compile {}: {}", input, err),
+    };
+    let env = Environment::new(builtins::new_builtins());
+    run(bytecode, &env)
+  }
+
+  // VM の結果は evaluator の結果と一致する
+  fn test_same_as_evaluator(input: &str) {
+    let env = Environment::new(builtins::new_builtins());
+    let expected = evaluator::eval(parse(input), &env);
+    assert_eq!(format!("{}", test_run(input)), format!("{}", expected), "input={}", input);
+  }
+
+  #[test]
+  fn test_arithmetic() {
+    let tests = vec![
+      "1", "1 + 2", "2 * (3 - 1) / 4", "7 % 3", "2 ** 10", "-5 + 10", "1.5 * 2", "\"a\" + \"b\"",
+      "1 < 2", "1 >= 2", "1 == 1", "true != false", "!true", "!!5", "null == null", "5 / 0",
+      "12 & 10", "12 | 10", "12 ^ 10", "~5", "1 << 10", "-16 >> 2", "1 << 64", "1.5 & 1",
+      "9223372036854775807 + 1", "let min = -9223372036854775807 - 1; [min / -1, -min]",
+    ];
+
+    for input in tests.into_iter() {
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_conditionals_and_logic() {
+    let tests = vec![
+      "if (true) { 10 }", "if (false) { 10 }", "if (1 > 2) { 10 } else { 20 }", "if (1) { }",
+      "true ? 1 : 2", "null ? 1 : 2", "true && false", "1 && 2", "false || 0", "null || false",
+      "false && x", "true || x", "null ?? 3", "4 ?? x", "let a = null; a ?? a ?? 5",
+    ];
+
+    for input in tests.into_iter() {
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_bindings_and_loops() {
+    let tests = vec![
+      "let a = 1; a", "let a = 1; let b = a + 1; a + b", "let a = 1;", "", "// comment",
+      "let a = 1; a = 5; a", "let a = 1; a += 2; a *= 3; a",
+      "let i = 0; let sum = 0; while (i < 5) { sum += i; i += 1; }; sum",
+      "let i = 0; while (i < 3) { i += 1 }",
+      "let f = fn() { let i = 0; while (true) { i += 1; if (i == 3) { return i; } } }; f()",
+      "if (true) { return 1; }; 2",
+      "const a = 1; a", "const a = 1; let a = 2; a = 3; a",
+      "let f = fn() { const a = 1; let a = 2; a = 5; a }; f()",
+      "let x = 1; if (true) { let x = 2; x = 3 }; x", "let x = 1; if (true) { x = 2; let y = x }; x",
+      "let f = fn(x) { if (x > 0) { let x = x * 10; x } else { x } }; f(1) + f(-1)",
+      "let fs = []; let i = 0; while (i < 3) { let j = i; fs = push(fs, fn() { j }); i += 1 }; fs[0]() * 10 + fs[2]()",
+      "let i = 0; while (i < 2) { if (i == 1) { y } else { let y = 1 }; i += 1 }",
+    ];
+
+    for input in tests.into_iter() {
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_collections() {
+    let tests = vec![
+      "[]", "[1, 2 + 3, \"x\"]", "[1, 2, 3][1]", "[1, 2, 3][5]", "{\"a\": 1}[\"a\"]", "{1: 2, true: 3}[true]",
+      "[1, 2, 3, 4][1:3]", "\"hello\"[:-1]", "[1, 2][:]", "len(0..=4)", "(2..8)[3]", "len([1, 2, 3])",
+      "first(rest([1, 2, 3]))", "[1, 2, 3].len()", "\"a,b\".split(\",\").join(\"-\")", "{\"a\": 1}.keys()",
+      "[3, 1, 2].sort().map(fn(x) { x * 2 })", "-[1, 2].first() + 3", "let f = fn(xs) { xs.rest().len() }; f([1, 2])",
+      "{\"a\": {\"b\": 1}}.a.b", "let f = fn(p) { p.name }; [f({\"name\": \"x\"}), f({})]",
+      "let p = {\"f\": fn(x) { x * 2 }}; p.f(4)", "let p = {\"len\": fn() { 10 }, \"n\": 1}; [p.len(), p.keys().len()]",
+    ];
+
+    for input in tests.into_iter() {
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_functions() {
+    let tests = vec![
+      "let f = fn(x, y) { x + y }; f(1, 2)",
+      "let f = fn() { return 5; 10 }; f()",
+      "let f = fn() { }; f()",
+      "fn(x) { x * 2 }(4)",
+      "let f = fn(x) { x }; f",
+      "let adder = fn(x) { fn(y) { x + y } }; let addTwo = adder(2); addTwo(3)",
+      "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+      "let counter = fn() { let c = 0; fn() { c += 1; c } }; let next = counter(); next(); next(); next()",
+      "let x = 1; let f = fn() { x = 10 }; f(); x",
+      "let apply = fn(f, x) { f(x) }; apply(fn(x) { x + 1 }, 1)",
+      "let f = fn() { let g = fn(n) { if (n == 0) { 0 } else { n + g(n - 1) } }; g(4) }; f()",
+      "let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3)",
+      "let f = fn() { let x = 1; let get = fn() { x }; x = 5; get() }; f()",
+      "let f = fn() { let c = 0; let inc = fn() { c += 1 }; inc(); inc(); c }; f()",
+      "let f = fn(x) { let x = x + 1; x }; f(1)",
+      "let f = fn(x, x) { x }; f(1, 2)",
+      "let f = fn(len) { len }; f(3)",
+      "let x = 1; let f = fn() { let y = x; x = 2; let g = fn() { x + y }; g() }; f()",
+      "let f = fn() { let i = 0; let fs = []; while (i < 3) { let j = i; fs = push(fs, fn() { j }); i += 1 }; fs[0]() }; f()",
+      "fn fib(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } } fib(15)",
+      "let wrap = fn(h) { fn(n) { h(n) } }; let f = fn() { let sum = wrap(fn(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }); sum(4) }; f()",
+      "let f = fn() { let g = 1; let g = [fn() { g }]; len(g[0]()) }; f()",
+      "fn f() { fn g(n) { if (n == 0) { 0 } else { n + g(n - 1) } } g(4) } f()",
+      "fn add(x, y) { x + y }",
+    ];
+
+    for input in tests.into_iter() {
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_immediately_called_closure() {
+    // その場で呼ぶ関数の中の c は、ブロックの c を束縛する前の外側の c を読む
+    let tests = vec![
+      "let c = 1; if (true) { let c = fn() { c }(); puts(c); }",
+      "let f = fn() { let c = 1; if (true) { let c = fn() { c }(); puts(c); } }; f()",
+    ];
+
+    for input in tests.into_iter() {
+      let run_with = |run: &dyn Fn(&Rc<RefCell<Environment>>) -> Object| {
+        let output = Rc::new(RefCell::new(vec![]));
+        let env = Environment::new(builtins::new_builtins());
+        env.borrow_mut().set_output(output.clone());
+        let result = run(&env);
+        let output = String::from_utf8(output.borrow().clone()).unwrap();
+        (format!("{}", result), output)
+      };
+      let expected = run_with(&|env| evaluator::eval(parse(input), env));
+      let actual = run_with(&|env| run(compiler::compile(&parse(input)).unwrap(), env));
+      assert_eq!(expected.1, "1\n", "input={}", input);
+      assert_eq!(actual, expected, "input={}", input);
+    }
+  }
+
+  #[test]
+  fn test_errors() {
+    let tests = vec![
+      "5 + true", "-true", "x", "x = 1", "let len = 1;", "[1][true]", "{[1]: 2}", "5()",
+      "fn(x) { x }(1, 2)", "fn(x, y) { x }(1)", "1..true", "1 + 2; foo; 3", "len(1, 2)",
+      "let f = fn() { 1 + true; 2 }; f()",
+      "let f = fn() { let len = 1; }; f()",
+      "let f = fn() { if (false) { let y = 1 }; y }; f()",
+      "let f = fn() { let g = fn() { y }; g() }; f()",
+      "const x = 1; x = 2",
+      "const x = 1; let f = fn() { x += 1 }; f()",
+      "if (true) { let y = 1 }; y",
+      "fn len(x) { x }",
+      "if (true) { fn g() { 1 } }; g()",
+      "let f = fn() { let g = fn() { g }(); g }; f()",
+      "let f = fn() { while (true) { let y = 1; return g() }; let g = fn() { y } }; f()",
+      "[1].foo()", "5.len()", "[1].len(2)", "x.len()", "[1].foo", "x.foo",
+    ];
+
+    for input in tests.into_iter() {
+      match test_run(input) {
+        Object::Error(_) => (),
+        obj => panic!("Object should has Error, but got {:?} for {}", obj, input),
+      }
+      test_same_as_evaluator(input);
+    }
+  }
+
+  #[test]
+  fn test_recursion_limit() {
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_max_depth(100);
+    let bytecode = compiler::compile(&parse("let f = fn(n) { f(n + 1) }; f(0)")).unwrap();
+    match run(bytecode, &env) {
+      Object::Error(err) => assert_eq!(err.message, "maximum recursion depth exceeded: 100."),
+      obj => panic!("Object should has Error, but got {:?}", obj),
+    }
+  }
+
+  #[test]
+  fn test_limits() {
+    let tests = vec![
+      ("while (true) {}", Limits { max_steps: Some(1000), ..Default::default() }, "step limit exceeded: 1000."),
+      ("let f = fn(n) { f(n + 1) }; f(0)", Limits { max_calls: Some(10), ..Default::default() }, "call depth limit exceeded: 10."),
+      ("let a = []; while (true) { a = push(a, \"x\") }", Limits { max_objects: Some(100), ..Default::default() }, "object limit exceeded: 100."),
+      ("let s = \"x\"; while (true) { s = s + s }", Limits { max_bytes: Some(1 << 20), ..Default::default() }, "memory limit exceeded: 1048576 bytes."),
+    ];
+
+    for (input, limits, expected) in tests {
+      let env = Environment::new(builtins::new_builtins());
+      env.borrow_mut().set_limits(limits);
+      let bytecode = compiler::compile(&parse(input)).unwrap();
+      match run(bytecode, &env) {
+        Object::Error(err) => {
+          assert_eq!(err.kind, ErrorKind::LimitExceeded);
+          assert_eq!(err.message, expected);
+        },
+        obj => panic!("Object should has Error, but got {:?} for {}", obj, input),
+      }
+    }
+
+    let env = Environment::new(builtins::new_builtins());
+    env.borrow_mut().set_limits(Limits { max_calls: Some(10), ..Default::default() });
+    let bytecode = compiler::compile(&parse("let g = fn(n) { if (n == 0) { 0 } else { g(n - 1) } }; g(9)")).unwrap();
+    assert_eq!(run(bytecode, &env).to_string(), "0");
+  }
+
+  #[test]
+  fn test_shares_environment_with_evaluator() {
+    let env = Environment::new(builtins::new_builtins());
+    evaluator::eval(parse("let double = fn(x) { x * 2 }; let n = 4;"), &env);
+
+    let bytecode = compiler::compile(&parse("let sq = fn(x) { x * x }; double(n) + sq(3)")).unwrap();
+    assert_eq!(format!("{}", run(bytecode, &env)), "17");
+
+    assert_eq!(format!("{}", evaluator::eval(parse("sq(5) + double(1)"), &env)), "27");
+
+    // 関数は定義したプログラムの定数を持ち続ける
+    let bytecode = compiler::compile(&parse("let greet = fn(name) { \"hi \" + name };")).unwrap();
+    run(bytecode, &env);
+    let bytecode = compiler::compile(&parse("let x = 100; greet(\"vm\")")).unwrap();
+    assert_eq!(format!("{}", run(bytecode, &env)), "\"hi vm\"");
+  }
+
+}
@@ -0,0 +1,129 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use wasm_bindgen::prelude::*;
+
+use crate::embed::{Interpreter, EvalError};
+use crate::lexer::{self, Lexer};
+use crate::parser::Parser;
+
+/// Parses `source` and returns its syntax tree as `Program::to_tree_string` formats it.
+/// Parse errors are thrown as a string with one error per line.
+#[wasm_bindgen]
+pub fn parse(source: &str) -> Result<String, JsValue> {
+  let mut p = Parser::new(Lexer::new(source.to_string()));
+  let program = p.parse_program();
+  if !p.errors.is_empty() {
+    return Err(JsValue::from_str(&EvalError::Parse(p.errors).to_string()));
+  }
+  Ok(program.to_tree_string())
+}
+
+/// Classifies the tokens of `source` for syntax highlighting. Each token is three numbers: where it starts and ends
+/// in UTF-16 code units, as JavaScript strings are indexed, and its `TokenClass` in the order the variants are declared,
+/// from 0 for `Keyword` to 6 for `Invalid`.
+#[wasm_bindgen]
+pub fn highlight(source: &str) -> Vec<u32> {
+  // トークンは前から順に並んでいるので、UTF-16 での位置は数えた続きから数える
+  let mut bytes = 0;
+  let mut units = 0;
+  let mut to_units = |offset: usize| {
+    units += source[bytes..offset].encode_utf16().count();
+    bytes = offset;
+    units as u32
+  };
+  let mut numbers = vec![];
+  for (span, class) in lexer::highlight(source) {
+    numbers.push(to_units(span.start.offset));
+    numbers.push(to_units(span.end.offset));
+    numbers.push(class as u32);
+  }
+  numbers
+}
+
+/// Evaluates `source` in a new session. Use `Session` to keep bindings between evaluations.
+#[wasm_bindgen]
+pub fn eval(source: &str) -> Evaluation {
+  Session::new().eval(source)
+}
+
+/// What an evaluation printed with `puts`, and its value or error.
+#[wasm_bindgen]
+pub struct Evaluation {
+  output: String,
+  value: String,
+  error: bool,
+}
+
+#[wasm_bindgen]
+impl Evaluation {
+  /// The text written by `puts`.
+  #[wasm_bindgen(getter)]
+  pub fn output(&self) -> String {
+    self.output.clone()
+  }
+
+  /// The value of the last statement, or the error report if `error` is true.
+  #[wasm_bindgen(getter)]
+  pub fn value(&self) -> String {
+    self.value.clone()
+  }
+
+  #[wasm_bindgen(getter)]
+  pub fn error(&self) -> bool {
+    self.error
+  }
+}
+
+/// An interpreter which keeps its bindings between evaluations, like the REPL.
+#[wasm_bindgen]
+pub struct Session {
+  interpreter: Interpreter,
+  output: Rc<RefCell<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl Session {
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> Session {
+    let mut interpreter = Interpreter::new();
+    // ブラウザには stdout がないので、puts の出力は溜めて返す
+    let output = Rc::new(RefCell::new(vec![]));
+    interpreter.set_output(output.clone());
+    Session { interpreter, output }
+  }
+
+  pub fn eval(&mut self, source: &str) -> Evaluation {
+    let result = self.interpreter.eval_str(source);
+    let output = String::from_utf8_lossy(&self.output.borrow()).into_owned();
+    self.output.borrow_mut().clear();
+    match result {
+      Ok(value) => Evaluation { output, value: value.to_string(), error: false },
+      Err(err) => Evaluation { output, value: err.to_string(), error: true },
+    }
+  }
+}
+
+impl Default for Session {
+  fn default() -> Session {
+    Session::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_session() {
+    let mut session = Session::new();
+    let evaluation = session.eval("let x = 2; puts(x); x * 3");
+    assert_eq!((evaluation.output(), evaluation.value(), evaluation.error()), ("2\n".to_string(), "6".to_string(), false));
+
+    let evaluation = session.eval("x + y");
+    assert_eq!((evaluation.output(), evaluation.value(), evaluation.error()), ("".to_string(), "1:5: NameError: identifier not found: y.".to_string(), true));
+
+    assert_eq!(highlight("\"𝑎\" + x"), vec![0, 4, 2, 5, 6, 3, 7, 8, 1]);
+    assert_eq!(parse("1").unwrap(), "Program\n  ExpressionStatement 1:1-1:2\n    Integer 1 1:1-1:2\n");
+  }
+}
@@ -0,0 +1,115 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+
+use rustyline::Context;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::Helper;
+
+use interpreter::evaluator::environment::Environment;
+
+const KEYWORDS: [&str; 16] = ["catch", "const", "else", "false", "fn", "for", "if", "in", "let", "macro", "match", "null", "return", "true", "try", "while"];
+
+/// The REPL commands, completed when the line starts with `:`.
+const COMMANDS: [&str; 6] = [":help", ":env", ":type", ":ast", ":reset", ":load"];
+
+/// Completes the word before the cursor with a keyword, a builtin or a name bound in the session, when Tab is pressed.
+pub struct MonkeyHelper {
+  env: Rc<RefCell<Environment>>,
+}
+
+impl MonkeyHelper {
+  pub fn new(env: &Rc<RefCell<Environment>>) -> MonkeyHelper {
+    MonkeyHelper { env: Rc::clone(env) }
+  }
+
+  /// Returns where the word before `pos` starts in `line`, and the sorted names which start with it.
+  pub fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+    let start = before
+      .char_indices()
+      .rev()
+      .take_while(|(_, ch)| ch.is_alphanumeric() || *ch == '_')
+      .last()
+      .map_or(pos, |(i, _)| i);
+    let word = &line[start..pos];
+
+    // 行頭の `:` はコマンドの補完
+    if start == 1 && before.starts_with(':') {
+      return (0, matching(COMMANDS.iter().map(|command| command.to_string()), before));
+    }
+    if word.is_empty() || word.starts_with(|ch: char| ch.is_ascii_digit()) {
+      return (pos, vec![]);
+    }
+
+    let env = self.env.borrow();
+    let mut names: Vec<String> = KEYWORDS.iter().map(|keyword| keyword.to_string()).collect();
+    if let Some(builtins) = &env.builtins {
+      names.extend(builtins.keys().map(|name| name.to_string()));
+    }
+    names.extend(env.bindings().into_iter().map(|(name, _)| name.to_string()));
+    (start, matching(names.into_iter(), word))
+  }
+}
+
+fn matching(names: impl Iterator<Item = String>, prefix: &str) -> Vec<String> {
+  let mut names: Vec<String> = names.filter(|name| name.starts_with(prefix)).collect();
+  names.sort();
+  names.dedup();
+  names
+}
+
+impl Completer for MonkeyHelper {
+  type Candidate = String;
+
+  fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+    Ok(self.candidates(line, pos))
+  }
+}
+
+impl Hinter for MonkeyHelper {
+  type Hint = String;
+}
+
+impl Highlighter for MonkeyHelper {}
+
+impl Validator for MonkeyHelper {}
+
+impl Helper for MonkeyHelper {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use interpreter::evaluator::{self, builtins};
+  use interpreter::{lexer, parser};
+
+  #[test]
+  fn test_candidates() {
+    let env = Environment::new(builtins::new_builtins());
+    let program = parser::Parser::new(lexer::Lexer::new("let length = 1; let 長さ = 2;".to_string())).parse_program();
+    evaluator::eval(program, &env);
+    let helper = MonkeyHelper::new(&env);
+
+    let tests = vec![
+      ("le", 2, (0, vec!["len", "length", "let"])),
+      ("puts(le", 7, (5, vec!["len", "length", "let"])),
+      ("puts(le)", 7, (5, vec!["len", "length", "let"])),
+      ("1 + 長", 5, (4, vec!["長さ"])),
+      ("whi", 3, (0, vec!["while"])),
+      ("xyz", 3, (0, vec![])),
+      ("1 + ", 4, (4, vec![])),
+      (":t", 2, (0, vec![":type"])),
+      (":", 1, (0, COMMANDS.to_vec())),
+    ];
+
+    for (line, pos, (start, expected)) in tests {
+      let pos = line.char_indices().nth(pos).map_or(line.len(), |(i, _)| i);
+      let start = line.char_indices().nth(start).map_or(line.len(), |(i, _)| i);
+      let mut expected: Vec<String> = expected.into_iter().map(|name| name.to_string()).collect();
+      expected.sort();
+      assert_eq!(helper.candidates(line, pos), (start, expected), "line={:?}", line);
+    }
+  }
+}
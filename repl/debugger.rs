@@ -0,0 +1,155 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::io::{BufRead, Write};
+
+use interpreter::{evaluator, lexer, parser};
+use interpreter::ast::stmt::Statement;
+use evaluator::debugger::{Action, Debugger};
+use evaluator::environment::Environment;
+use evaluator::object::Object;
+
+const PROMPT: &str = "(debug) ";
+
+const HELP: &str = "\
+step, s           run to the next statement (also an empty line)
+continue, c       run to the next breakpoint
+break, b <line>   stop before the statements on <line>
+delete, d <line>  remove the breakpoint on <line>
+env, e            list the bindings visible here
+print, p <expr>   evaluate <expr> here and show its value
+quit, q           stop the program
+help, h           show this message";
+
+/// Pauses a script started with `--debug` and reads commands from `input`, which are listed by `help`.
+/// It starts paused before the first statement.
+pub struct CliDebugger {
+  lines: Vec<String>,
+  input: Box<dyn BufRead>,
+  out: Rc<RefCell<dyn Write>>,
+  stepping: bool,
+  breakpoints: BTreeSet<usize>,
+}
+
+impl CliDebugger {
+  /// `source` is the script being debugged, whose lines are shown where the program stops.
+  pub fn new(source: &str, input: Box<dyn BufRead>, out: Rc<RefCell<dyn Write>>) -> CliDebugger {
+    CliDebugger {
+      lines: source.lines().map(|line| line.to_string()).collect(),
+      input,
+      out,
+      stepping: true,
+      breakpoints: BTreeSet::new(),
+    }
+  }
+
+  fn print(&self, env: &Rc<RefCell<Environment>>, source: &str) -> String {
+    let mut p = parser::Parser::new(lexer::Lexer::new(source.to_string()));
+    let program = p.parse_program();
+    if let Some(err) = p.errors.first() {
+      return format!("error: {}: {}", err.span(), err);
+    }
+    match evaluator::eval(program, env) {
+      Object::Error(err) => format!("error: {}", err.report(None)),
+      evaluated => evaluated.to_string(),
+    }
+  }
+
+  // 次に進むときの Action を返すまでコマンドを読む
+  fn prompt(&mut self, env: &Rc<RefCell<Environment>>) -> Action {
+    let out = Rc::clone(&self.out);
+    let mut out = out.borrow_mut();
+    loop {
+      let _ = write!(out, "{}", PROMPT);
+      let _ = out.flush();
+      let mut line = String::new();
+      match self.input.read_line(&mut line) {
+        Ok(0) | Err(_) => return Action::Abort,
+        Ok(_) => (),
+      }
+
+      let line = line.trim();
+      let (command, arg) = match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+      };
+      let reply = match command {
+        "" | "s" | "step" => {
+          self.stepping = true;
+          return Action::Continue;
+        },
+        "c" | "continue" => {
+          self.stepping = false;
+          return Action::Continue;
+        },
+        "q" | "quit" => return Action::Abort,
+        "b" | "break" | "d" | "delete" => match arg.parse::<usize>() {
+          Ok(n) if command.starts_with('b') => {
+            self.breakpoints.insert(n);
+            format!("breakpoint on line {}", n)
+          },
+          Ok(n) if self.breakpoints.remove(&n) => format!("removed the breakpoint on line {}", n),
+          Ok(n) => format!("error: no breakpoint on line {}", n),
+          Err(_) => format!("error: {} needs a line number", command),
+        },
+        "e" | "env" => env.borrow()
+          .visible_bindings()
+          .iter()
+          .map(|(name, val)| format!("{} = {}", name, val))
+          .collect::<Vec<String>>()
+          .join("\n"),
+        "p" | "print" => self.print(env, arg),
+        "h" | "help" => HELP.to_string(),
+        _ => format!("error: unknown command {}, see help", command),
+      };
+      if !reply.is_empty() {
+        let _ = writeln!(out, "{}", reply);
+      }
+    }
+  }
+}
+
+impl Debugger for CliDebugger {
+  fn on_statement(&mut self, stmt: &Statement, env: &Rc<RefCell<Environment>>) -> Action {
+    let line = stmt.span().start.line;
+    if !self.stepping && !self.breakpoints.contains(&line) {
+      return Action::Continue;
+    }
+    let source = self.lines.get(line - 1).map_or("", |line| line.as_str());
+    let _ = writeln!(self.out.borrow_mut(), "{:>4} | {}", line, source);
+    self.prompt(env)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+  use evaluator::builtins;
+
+  #[test]
+  fn test_cli_debugger() {
+    let source = "let x = 1;
+let f = fn(n) {
+  n * 2
+};
+let y = f(x);
+y + 1";
+    let commands = "env\nb 3\nc\np n + 10\ns\nd 3\nd 3\nc\n";
+    let out = Rc::new(RefCell::new(vec![]));
+    let env = Environment::new(builtins::new_builtins());
+    let debugger = CliDebugger::new(source, Box::new(Cursor::new(commands)), out.clone());
+    env.borrow_mut().set_debugger(Some(Box::new(debugger)));
+
+    let program = parser::Parser::new(lexer::Lexer::new(source.to_string())).parse_program();
+    assert_eq!(evaluator::eval(program, &env).to_string(), "3");
+    assert_eq!(String::from_utf8(out.borrow().clone()).unwrap(), "   1 | let x = 1;
+(debug) (debug) breakpoint on line 3
+(debug)    3 |   n * 2
+(debug) 11
+(debug)    6 | y + 1
+(debug) removed the breakpoint on line 3
+(debug) error: no breakpoint on line 3
+(debug) ");
+  }
+}
@@ -1,58 +1,440 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::{env, fs, io, process, thread};
 
-use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use interpreter::{analysis, ast, evaluator, formatter, lexer, parser, typecheck};
+use interpreter::intern::Name;
+use evaluator::{builtins, macro_expansion, prelude};
+use evaluator::coverage::Coverage;
+use evaluator::limits::Capabilities;
+use evaluator::object::Object;
+use evaluator::environment::{Environment};
 
-use std::{env, fs};
+mod repl;
+mod completion;
+mod debugger;
+#[cfg(feature = "playground")]
+mod playground;
 
-use interpreter::{evaluator, lexer, parser};
-use evaluator::builtins;
-use evaluator::environment::{Environment};
+use repl::Engine;
+
+/// The size of the stack of the thread which runs everything, so that deep recursions fit in it.
+const STACK_SIZE: usize = 256 << 20;
+
+/// Parses the script at `filename`, or reports why it could not be read or parsed to stderr.
+fn parse_file(filename: &str) -> Option<ast::Program> {
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", filename, err);
+            return None;
+        }
+    };
+
+    let l = lexer::Lexer::new(contents);
+    let mut p = parser::Parser::new(l);
+    let program = p.parse_program();
+
+    if !p.errors.is_empty() {
+        for err in &p.errors {
+            eprintln!("error: {}:{}: {}", filename, err.span(), err);
+        }
+        return None;
+    }
+    Some(program)
+}
+
+// import するパスはスクリプトのあるディレクトリから探す
+fn set_module_dir(filename: &str, env: &Rc<RefCell<Environment>>) {
+    env.borrow().modules().set_main(filename);
+}
+
+/// Runs the script at `filename` and returns the exit code of the process.
+fn run_file(filename: &str, env: &Rc<RefCell<Environment>>, engine: Engine) -> i32 {
+    let program = match parse_file(filename) {
+        Some(program) => program,
+        None => return 1,
+    };
+
+    set_module_dir(filename, env);
+
+    // 警告があっても実行はする
+    let mut globals: Vec<_> = env.borrow().builtins.iter().flat_map(|builtins| builtins.keys().copied()).collect();
+    globals.extend(env.borrow().bindings().into_iter().map(|(name, _)| name));
+    for warning in analysis::analyze(&program, &globals) {
+        eprintln!("warning: {}:{}: {}", filename, warning.span(), warning);
+    }
+
+    match repl::run(program, env, engine) {
+        Object::Error(err) => {
+            eprintln!("error: {}", err.report(Some(filename)));
+            1
+        },
+        _ => 0,
+    }
+}
+
+/// Binds `args` to the arguments which follow the script, then to the ones after `--`.
+fn bind_args(env: &Rc<RefCell<Environment>>, args: &[String], script_args: Vec<String>) {
+    let args: Vec<String> = args.iter().cloned().chain(script_args).collect();
+    env.borrow_mut().set(Name::new("args"), Object::from(args));
+}
 
-fn exec(buf: String, env: &mut Rc<RefCell<evaluator::environment::Environment>>) {
-  let l = lexer::Lexer::new(buf);
-  let mut p = parser::Parser::new(l);
-  let program = p.parse_program();
-  
-  if !p.check_parse_errors() {
-    return;
-  }
-  
-  println!("{}", evaluator::eval(program, env));
-}
-  
-fn start(env: &mut Rc<RefCell<evaluator::environment::Environment>>) {
-  let mut rl = Editor::<()>::new();
-  loop {
-    let readline = rl.readline(">> ");
-    match readline {
-      Ok(line) => exec(line, env),
-      Err(ReadlineError::Interrupted) => {
-        println!("CTRL-C");
-        break
-      },
-      Err(ReadlineError::Eof) => {
-        println!("CTRL-D");
-        break
-      },
-      Err(err) => {
-        println!("Error: {:?}", err);
-        break
-      }
-    }
-  }
+/// Runs the script at `filename` under `debugger::CliDebugger`, which reads its commands from stdin.
+fn debug_file(filename: &str, env: &Rc<RefCell<Environment>>) -> i32 {
+    let source = match fs::read_to_string(filename) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", filename, err);
+            return 1;
+        }
+    };
+    println!("Debugging {}. Type help for the commands.", filename);
+    let debugger = debugger::CliDebugger::new(&source, Box::new(io::BufReader::new(io::stdin())), Rc::new(RefCell::new(io::stdout())));
+    env.borrow_mut().set_debugger(Some(Box::new(debugger)));
+    run_file(filename, env, Engine::Eval)
+}
+
+/// Runs the script at `filename` while counting the statements it executes, then prints the source annotated
+/// with the counts when `annotate` is set, and writes an lcov tracefile to `lcov` if it is given.
+/// Returns the exit code of the process, which is 1 when the script fails even though the reports are written.
+fn cover_file(filename: &str, env: &Rc<RefCell<Environment>>, annotate: bool, lcov: Option<&str>) -> i32 {
+    let source = match fs::read_to_string(filename) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("error: could not read {}: {}", filename, err);
+            return 1;
+        }
+    };
+    let program = match parse_file(filename) {
+        Some(program) => program,
+        None => return 1,
+    };
+
+    set_module_dir(filename, env);
+    let coverage = Coverage::new();
+    env.borrow_mut().set_observer(Some(Box::new(coverage.clone())));
+    // 畳み込まれた分岐が実行されなかったことにならないように、最適化せずに実行する
+    let mut expanded = program.clone();
+    macro_expansion::define_macros(&mut expanded, env);
+    let result = match macro_expansion::expand_macros(expanded, env) {
+        Ok(expanded) => evaluator::eval(expanded, env),
+        Err(err) => err,
+    };
+    let mut status = match result {
+        Object::Error(err) => {
+            eprintln!("error: {}", err.report(Some(filename)));
+            1
+        },
+        _ => 0,
+    };
+
+    if annotate {
+        print!("{}", coverage.annotate(&program, &source));
+    }
+    if let Some(path) = lcov {
+        if let Err(err) = fs::write(path, coverage.lcov(&program, filename)) {
+            eprintln!("error: could not write {}: {}", path, err);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Prints the syntax tree of the script at `filename` without running it, and returns the exit code of the process.
+fn dump_ast(filename: &str) -> i32 {
+    match parse_file(filename) {
+        Some(program) => {
+            print!("{}", program.to_tree_string());
+            0
+        },
+        None => 1,
+    }
+}
+
+/// Type checks the scripts in `filenames` without running them, and returns the exit code of the process.
+fn check_files(filenames: &[String]) -> i32 {
+    let mut status = 0;
+    for filename in filenames {
+        let program = match parse_file(filename) {
+            Some(program) => program,
+            None => {
+                status = 1;
+                continue;
+            },
+        };
+
+        if let Err(errors) = typecheck::check(&program) {
+            for err in &errors {
+                eprintln!("error: {}:{}: {}", filename, err.span(), err);
+            }
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Formats the scripts in `filenames` in place, or with `check` only lists the ones which are not formatted,
+/// and returns the exit code of the process.
+fn format_files(filenames: &[String], check: bool) -> i32 {
+    let mut status = 0;
+    for filename in filenames {
+        let contents = match fs::read_to_string(filename) {
+            Ok(contents) => contents,
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", filename, err);
+                status = 1;
+                continue;
+            }
+        };
+
+        let formatted = match formatter::format_source(&contents) {
+            Ok(formatted) => formatted,
+            Err(formatter::FormatError::Parse(errors)) => {
+                for err in &errors {
+                    eprintln!("error: {}:{}: {}", filename, err.span(), err);
+                }
+                status = 1;
+                continue;
+            },
+            Err(err) => {
+                eprintln!("error: {}:{}", filename, err);
+                status = 1;
+                continue;
+            },
+        };
+        if formatted == contents {
+            continue;
+        }
+
+        if check {
+            println!("{}", filename);
+            status = 1;
+        } else if let Err(err) = fs::write(filename, formatted) {
+            eprintln!("error: could not write {}: {}", filename, err);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Prints the tokens of the script at `filename`, or of stdin for `-`, as the lexer reads them,
+/// and returns the exit code of the process. The input is read as it is tokenized.
+fn dump_tokens(filename: &str) -> i32 {
+    let mut l = if filename == "-" {
+        lexer::Lexer::from_reader(io::BufReader::new(io::stdin()))
+    } else {
+        match fs::File::open(filename) {
+            Ok(file) => lexer::Lexer::from_reader(io::BufReader::new(file)),
+            Err(err) => {
+                eprintln!("error: could not read {}: {}", filename, err);
+                return 1;
+            }
+        }
+    };
+
+    let stdout = io::stdout();
+    match l.write_tokens(&mut stdout.lock()) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: could not write the tokens: {}", err);
+            1
+        },
+    }
+}
+
+#[cfg(feature = "playground")]
+fn serve(port: &str) -> i32 {
+    match playground::serve(&format!("127.0.0.1:{}", port)) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: could not serve the playground: {}", err);
+            1
+        },
+    }
+}
+
+#[cfg(not(feature = "playground"))]
+fn serve(_port: &str) -> i32 {
+    eprintln!("error: rsmonkey was built without the playground feature");
+    1
 }
 
 fn main() {
-    let mut environment = Environment::new(builtins::new_builtins());
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 1 {
-        let filename = &args[1];
-        let contents = fs::read_to_string(filename)
-            .expect("Something went wrong reading the file");
-        exec(contents, &mut environment);
+    // スタックは使った分しか確保されないので、メインスレッドの 8MiB より大きく取る
+    let main = thread::Builder::new().stack_size(STACK_SIZE).spawn(start).expect("could not spawn the main thread");
+    if main.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn start() {
+    let environment = Environment::new(builtins::new_builtins());
+    // 評価のほかに使う分を残して、スタックのほとんどを再帰に使えるようにする
+    environment.borrow_mut().set_max_stack(STACK_SIZE - (16 << 20));
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // -- より後ろはオプションとして読まずに、そのまま args としてスクリプトに渡す
+    let script_args = match args.iter().position(|arg| arg == "--") {
+        Some(i) => {
+            let rest = args.split_off(i + 1);
+            args.pop();
+            rest
+        },
+        None => vec![],
+    };
+
+    // --sandbox を付けると read_file や import などでファイルに触れさせず、時計や環境変数、eval も使わせない
+    match args.iter().position(|arg| arg == "--sandbox") {
+        Some(i) => {
+            args.remove(i);
+        },
+        None => environment.borrow_mut().set_capabilities(Capabilities { filesystem: true, clock: true, env: true, eval: true }),
+    }
+
+    // --bigint を付けると i64 からあふれる整数の演算をエラーにせず、多倍長整数で続ける
+    #[cfg(feature = "bigint")]
+    if let Some(i) = args.iter().position(|arg| arg == "--bigint") {
+        args.remove(i);
+        environment.borrow_mut().set_bigint(true);
+    }
+
+    // --no-prelude を付けると Monkey で書かれた sum などを読み込まない
+    match args.iter().position(|arg| arg == "--no-prelude") {
+        Some(i) => {
+            args.remove(i);
+        },
+        None => {
+            if let Err(err) = prelude::load(&environment) {
+                eprintln!("error: could not load the prelude: {}", err.report(None));
+                process::exit(1);
+            }
+        },
+    }
+
+    // fmt [--check] <files> でファイルを整形する
+    if args.first().map(|arg| arg.as_str()) == Some("fmt") {
+        let mut files: Vec<String> = args.into_iter().skip(1).collect();
+        let check = match files.iter().position(|arg| arg == "--check") {
+            Some(i) => {
+                files.remove(i);
+                true
+            },
+            None => false,
+        };
+        if files.is_empty() {
+            eprintln!("error: fmt needs files to format");
+            process::exit(1);
+        }
+        process::exit(format_files(&files, check));
+    }
+
+    // check <files> で実行せずに型を調べる
+    if args.first().map(|arg| arg.as_str()) == Some("check") {
+        let files: Vec<String> = args.into_iter().skip(1).collect();
+        if files.is_empty() {
+            eprintln!("error: check needs files to check");
+            process::exit(1);
+        }
+        process::exit(check_files(&files));
+    }
+
+    // serve [--port <port>] でブラウザから使える REPL を提供する
+    if args.first().map(|arg| arg.as_str()) == Some("serve") {
+        let port = match args.iter().position(|arg| arg == "--port") {
+            Some(i) => match args.get(i + 1) {
+                Some(port) => port.clone(),
+                None => {
+                    eprintln!("error: --port needs a port number");
+                    process::exit(1);
+                },
+            },
+            None => "8080".to_string(),
+        };
+        process::exit(serve(&port));
+    }
+
+    // --vm を付けると bytecode にコンパイルして VM で実行する。
+    // コンパイラはまだ言語の一部にしか対応しておらず、対応していない構文は実行する前に CompileError になる
+    let engine = match args.iter().position(|arg| arg == "--vm") {
+        Some(i) => {
+            args.remove(i);
+            Engine::Vm
+        },
+        None => Engine::Eval,
+    };
+
+    // --debug を付けると文ごとに止めながら実行する
+    if let Some(i) = args.iter().position(|arg| arg == "--debug") {
+        args.remove(i);
+        if args.is_empty() {
+            eprintln!("error: --debug needs a file to run");
+            process::exit(1);
+        }
+        if engine == Engine::Vm {
+            eprintln!("error: --debug can not be used with --vm");
+            process::exit(1);
+        }
+        bind_args(&environment, &args[1..], script_args);
+        process::exit(debug_file(&args[0], &environment));
+    }
+
+    // --coverage を付けると実行した回数を添えたソースを、--lcov <path> を付けると lcov のレポートを出力する
+    let annotate = match args.iter().position(|arg| arg == "--coverage") {
+        Some(i) => {
+            args.remove(i);
+            true
+        },
+        None => false,
+    };
+    let lcov = match args.iter().position(|arg| arg == "--lcov") {
+        Some(i) if i + 1 < args.len() => {
+            args.remove(i);
+            Some(args.remove(i))
+        },
+        Some(_) => {
+            eprintln!("error: --lcov needs a path to write");
+            process::exit(1);
+        },
+        None => None,
+    };
+    if annotate || lcov.is_some() {
+        if args.is_empty() {
+            eprintln!("error: --coverage and --lcov need a file to run");
+            process::exit(1);
+        }
+        if engine == Engine::Vm {
+            eprintln!("error: --coverage and --lcov can not be used with --vm");
+            process::exit(1);
+        }
+        bind_args(&environment, &args[1..], script_args);
+        process::exit(cover_file(&args[0], &environment, annotate, lcov.as_deref()));
+    }
+
+    // --tokens を付けると実行せずにトークン列を表示する
+    if let Some(i) = args.iter().position(|arg| arg == "--tokens") {
+        args.remove(i);
+        if args.is_empty() {
+            eprintln!("error: --tokens needs a file to read");
+            process::exit(1);
+        }
+        process::exit(dump_tokens(&args[0]));
+    }
+
+    // --ast を付けると実行せずに構文木を表示する
+    if let Some(i) = args.iter().position(|arg| arg == "--ast") {
+        args.remove(i);
+        if args.is_empty() {
+            eprintln!("error: --ast needs a file to parse");
+            process::exit(1);
+        }
+        process::exit(dump_ast(&args[0]));
+    }
+
+    if !args.is_empty() {
+        bind_args(&environment, &args[1..], script_args);
+        process::exit(run_file(&args[0], &environment, engine));
     } else {
-        start(&mut environment);
+        repl::start(&environment, engine);
     }
 }
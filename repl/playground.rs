@@ -0,0 +1,308 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use interpreter::Interpreter;
+use interpreter::evaluator::limits::Limits;
+
+const PAGE: &str = include_str!("playground.html");
+
+/// Sessions beyond this many drop the one used least recently.
+const MAX_SESSIONS: usize = 64;
+
+/// Requests with a larger body are refused.
+const MAX_BODY: usize = 64 * 1024;
+
+/// Requests whose request line and headers are longer than this are refused.
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Connections beyond this many at once are closed without a response.
+const MAX_CONNECTIONS: usize = 64;
+
+/// How long a connection may wait for the next bytes of a request, or for the client to take the response.
+const IO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The limits of each evaluation, so that a script sent by a visitor can not hang the server.
+const LIMITS: Limits = Limits {
+  max_steps: Some(10_000_000),
+  max_calls: Some(200),
+  max_objects: None,
+  max_bytes: Some(64 << 20),
+  timeout: Some(Duration::from_secs(5)),
+};
+
+type Job = (Request, Sender<Response>);
+
+/// Serves the playground on `addr` until the process is stopped. Each connection is read and written on a thread
+/// of its own, while the requests are evaluated one by one on this thread, where every session lives.
+pub fn serve(addr: &str) -> io::Result<()> {
+  let listener = TcpListener::bind(addr)?;
+  println!("Serving the playground on http://{}", listener.local_addr()?);
+
+  let (jobs, received) = mpsc::channel::<Job>();
+  thread::spawn(move || accept(listener, jobs));
+
+  let mut sessions = Sessions { sessions: vec![] };
+  for (request, reply) in received {
+    // 応答を待たずに切れた接続には何も返さない
+    let _ = reply.send(sessions.respond(&request));
+  }
+  Ok(())
+}
+
+// 遅いクライアントがほかの接続を待たせないように、接続ごとにスレッドを立てる
+fn accept(listener: TcpListener, jobs: Sender<Job>) {
+  let connections = Arc::new(AtomicUsize::new(0));
+  for stream in listener.incoming() {
+    let stream = match stream {
+      Ok(stream) => stream,
+      Err(_) => continue,
+    };
+    if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+      connections.fetch_sub(1, Ordering::SeqCst);
+      continue;
+    }
+
+    let (jobs, connections) = (jobs.clone(), Arc::clone(&connections));
+    thread::spawn(move || {
+      if let Err(err) = handle(stream, &jobs) {
+        eprintln!("error: could not write a response: {}", err);
+      }
+      connections.fetch_sub(1, Ordering::SeqCst);
+    });
+  }
+}
+
+fn handle(mut stream: TcpStream, jobs: &Sender<Job>) -> io::Result<()> {
+  stream.set_read_timeout(Some(IO_TIMEOUT))?;
+  stream.set_write_timeout(Some(IO_TIMEOUT))?;
+  let response = match read_request(&mut BufReader::new(&mut stream)) {
+    Ok(request) => {
+      let (reply, response) = mpsc::channel();
+      let stopped = || io::Error::other("the server has stopped");
+      jobs.send((request, reply)).map_err(|_| stopped())?;
+      response.recv().map_err(|_| stopped())?
+    },
+    Err(err) => Response::text(400, &format!("{}\n", err)),
+  };
+  response.write_to(&mut stream)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Request {
+  pub method: String,
+  pub path: String,
+  pub query: String,
+  pub body: String,
+}
+
+pub struct Response {
+  status: u16,
+  content_type: &'static str,
+  body: String,
+}
+
+impl Response {
+  fn text(status: u16, body: &str) -> Response {
+    Response { status, content_type: "text/plain; charset=utf-8", body: body.to_string() }
+  }
+
+  fn write_to(&self, out: &mut dyn Write) -> io::Result<()> {
+    let reason = match self.status {
+      200 => "OK",
+      400 => "Bad Request",
+      _ => "Not Found",
+    };
+    write!(
+      out,
+      "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+      self.status, reason, self.content_type, self.body.len(), self.body,
+    )?;
+    out.flush()
+  }
+}
+
+/// Reads an HTTP/1.1 request with its body. The request line and the headers are at most `MAX_HEAD` bytes,
+/// and the body at most `MAX_BODY` bytes of UTF-8.
+pub fn read_request(reader: &mut dyn BufRead) -> io::Result<Request> {
+  let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+  let mut head = (&mut *reader).take(MAX_HEAD as u64);
+  let mut line = String::new();
+  read_head_line(&mut head, &mut line)?;
+  let mut parts = line.split_whitespace();
+  let (method, target) = match (parts.next(), parts.next()) {
+    (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+    _ => return Err(invalid("malformed request line")),
+  };
+  let (path, query) = match target.find('?') {
+    Some(i) => (target[..i].to_string(), target[i + 1..].to_string()),
+    None => (target, String::new()),
+  };
+
+  let mut length = 0;
+  loop {
+    if read_head_line(&mut head, &mut line)? == 0 {
+      break;
+    }
+    let header = line.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      if name.eq_ignore_ascii_case("content-length") {
+        length = value.trim().parse().map_err(|_| invalid("malformed Content-Length"))?;
+      }
+    }
+  }
+  if length > MAX_BODY {
+    return Err(invalid("the body is too large"));
+  }
+
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body)?;
+  let body = String::from_utf8(body).map_err(|_| invalid("the body is not UTF-8"))?;
+  Ok(Request { method, path, query, body })
+}
+
+// 改行が来ないまま読み続けないように、MAX_HEAD に届いても行が終わらなければエラーにする
+fn read_head_line<R: BufRead>(head: &mut io::Take<R>, line: &mut String) -> io::Result<usize> {
+  line.clear();
+  let read = head.read_line(line)?;
+  if head.limit() == 0 && !line.ends_with('\n') {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "the headers are too large"));
+  }
+  Ok(read)
+}
+
+struct Session {
+  id: String,
+  interpreter: Interpreter,
+  output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl Session {
+  fn new(id: &str) -> Session {
+    let mut interpreter = Interpreter::new();
+    interpreter.set_limits(LIMITS);
+    let output = Rc::new(RefCell::new(vec![]));
+    interpreter.set_output(output.clone());
+    Session { id: id.to_string(), interpreter, output }
+  }
+
+  fn eval(&mut self, source: &str) -> String {
+    let result = self.interpreter.eval_str(source);
+    let output = String::from_utf8_lossy(&self.output.borrow()).into_owned();
+    self.output.borrow_mut().clear();
+    let (value, error) = match result {
+      Ok(value) => (value.to_string(), false),
+      Err(err) => (err.to_string(), true),
+    };
+    format!("{{\"output\":{},\"value\":{},\"error\":{}}}", json_string(&output), json_string(&value), error)
+  }
+}
+
+// 最後に使ったセッションを末尾に置く
+struct Sessions {
+  sessions: Vec<Session>,
+}
+
+impl Sessions {
+  fn get(&mut self, id: &str) -> &mut Session {
+    let session = match self.sessions.iter().position(|session| session.id == id) {
+      Some(i) => self.sessions.remove(i),
+      None => Session::new(id),
+    };
+    self.sessions.push(session);
+    if self.sessions.len() > MAX_SESSIONS {
+      self.sessions.remove(0);
+    }
+    self.sessions.last_mut().unwrap()
+  }
+
+  fn respond(&mut self, request: &Request) -> Response {
+    let session = query_param(&request.query, "session").unwrap_or_default();
+    match (request.method.as_str(), request.path.as_str()) {
+      ("GET", "/") => Response { status: 200, content_type: "text/html; charset=utf-8", body: PAGE.to_string() },
+      ("POST", "/eval") => {
+        let body = self.get(&session).eval(&request.body);
+        Response { status: 200, content_type: "application/json", body }
+      },
+      ("POST", "/reset") => {
+        self.sessions.retain(|other| other.id != session);
+        Response::text(200, "")
+      },
+      _ => Response::text(404, "not found\n"),
+    }
+  }
+}
+
+fn query_param(query: &str, name: &str) -> Option<String> {
+  query.split('&').find_map(|pair| match pair.split_once('=') {
+    Some((key, value)) if key == name => Some(value.to_string()),
+    _ => None,
+  })
+}
+
+fn json_string(s: &str) -> String {
+  let mut out = String::from("\"");
+  for ch in s.chars() {
+    match ch {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+      ch => out.push(ch),
+    }
+  }
+  out.push('"');
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_respond() {
+    let input = "POST /eval?session=a HTTP/1.1\r\nHost: localhost\r\nContent-Length: 20\r\n\r\nlet x = 1; puts(\"x\")";
+    let request = read_request(&mut input.as_bytes()).unwrap();
+    assert_eq!(request, Request {
+      method: "POST".to_string(),
+      path: "/eval".to_string(),
+      query: "session=a".to_string(),
+      body: "let x = 1; puts(\"x\")".to_string(),
+    });
+
+    let mut sessions = Sessions { sessions: vec![] };
+    assert_eq!(sessions.respond(&request).body, r#"{"output":"\"x\"\n","value":"null","error":false}"#);
+
+    let eval = |sessions: &mut Sessions, session: &str, body: &str| {
+      let request = Request { method: "POST".to_string(), path: "/eval".to_string(), query: format!("session={}", session), body: body.to_string() };
+      sessions.respond(&request).body
+    };
+    assert_eq!(eval(&mut sessions, "a", "x + 1"), r#"{"output":"","value":"2","error":false}"#);
+    assert_eq!(
+      eval(&mut sessions, "b", "x + 1"),
+      r#"{"output":"","value":"1:1: NameError: identifier not found: x.","error":true}"#,
+    );
+    assert_eq!(eval(&mut sessions, "a", "let = 1"), r#"{"output":"","value":"1:5: expected next token to be IDENT, got ASSIGN instead.","error":true}"#);
+
+    let page = Request { method: "GET".to_string(), path: "/".to_string(), query: String::new(), body: String::new() };
+    assert_eq!(sessions.respond(&page).status, 200);
+    assert!(read_request(&mut "POST / HTTP/1.1\r\nContent-Length: 100000\r\n\r\n".as_bytes()).is_err());
+    let long = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_HEAD));
+    match read_request(&mut long.as_bytes()) {
+      Err(err) => assert_eq!(err.to_string(), "the headers are too large"),
+      Ok(request) => panic!("expected an error, got {:?}", request),
+    }
+  }
+}
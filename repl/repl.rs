@@ -0,0 +1,250 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::{env, fs};
+use std::path::PathBuf;
+
+use rustyline::error::ReadlineError;
+use rustyline::Editor;
+
+use crate::completion::MonkeyHelper;
+use interpreter::{ast, compiler, evaluator, lexer, parser, token, vm};
+use evaluator::object::{self, Object};
+use evaluator::environment::Environment;
+use evaluator::macro_expansion;
+
+const PROMPT: &str = ">> ";
+/// Shown while the input so far has unclosed brackets, strings or comments.
+const CONTINUATION_PROMPT: &str = ".. ";
+const HISTORY_FILE: &str = ".rsmonkey_history";
+
+const HELP: &str = "\
+:help         show this message
+:env          list the bindings defined in the session
+:type <expr>  evaluate <expr> and show the type of its value
+:ast <expr>   show the syntax tree of <expr> without evaluating it
+:reset        remove the bindings defined in the session
+:load <file>  run <file> in the session";
+
+/// How programs are executed.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Engine {
+  /// Walks the AST with `evaluator::eval`.
+  Eval,
+  /// Compiles to bytecode and runs it with `vm::run`. The compiler supports only part of the language,
+  /// and the errors raised on the VM have no location.
+  Vm,
+}
+
+/// Runs `program` in `env` with `engine`, after expanding its macros and folding its constant expressions.
+/// Syntax which the compiler does not support is reported as an error object.
+pub fn run(mut program: ast::Program, env: &Rc<RefCell<Environment>>, engine: Engine) -> Object {
+  macro_expansion::define_macros(&mut program, env);
+  let program = match macro_expansion::expand_macros(program, env) {
+    Ok(program) => program.optimize(),
+    Err(err) => return err,
+  };
+  match engine {
+    Engine::Eval => evaluator::eval(program, env),
+    Engine::Vm => match compiler::compile(&program) {
+      Ok(bytecode) => vm::run(bytecode, env),
+      Err(err) => {
+        let mut error = object::Error::new(object::ErrorKind::Compile, err.to_string());
+        error.span = Some(err.span());
+        Object::Error(Box::new(error))
+      },
+    },
+  }
+}
+
+/// Evaluates `buf` in `env` and returns the result, or `None` when `buf` could not be parsed.
+/// Parse errors are reported to stderr.
+pub fn exec(buf: String, env: &Rc<RefCell<Environment>>, engine: Engine) -> Option<Object> {
+  let l = lexer::Lexer::new(buf);
+  let mut p = parser::Parser::new(l);
+  let program = p.parse_program();
+
+  if !p.check_parse_errors() {
+    return None;
+  }
+
+  Some(run(program, env, engine))
+}
+
+/// Returns true if `input` ends inside a bracket, a string literal or a block comment,
+/// so more lines are needed before it can be parsed.
+pub fn is_incomplete(input: &str) -> bool {
+  let mut depth = 0;
+  for tok in lexer::Lexer::new(input.to_string()) {
+    match tok {
+      token::Token::LPAREN | token::Token::LBRACE | token::Token::LBRACKET => depth += 1,
+      token::Token::RPAREN | token::Token::RBRACE | token::Token::RBRACKET => depth -= 1,
+      token::Token::ILLEGAL(msg) if msg.starts_with("unterminated") => return true,
+      _ => (),
+    }
+  }
+  // 閉じ括弧が多すぎる場合は、続きを待たずにパースエラーとして報告する
+  depth > 0
+}
+
+/// Runs a REPL command such as `:env`. `line` starts with `:`.
+fn run_command(line: &str, env: &Rc<RefCell<Environment>>, engine: Engine) {
+  let line = line.trim();
+  let (command, arg) = match line.find(char::is_whitespace) {
+    Some(i) => (&line[..i], line[i..].trim()),
+    None => (line, ""),
+  };
+
+  match command {
+    ":help" => println!("{}", HELP),
+    ":env" => {
+      for (name, val) in env.borrow().bindings() {
+        println!("{} = {}", name, val);
+      }
+    },
+    ":type" => match exec(arg.to_string(), env, engine) {
+      Some(Object::Error(err)) => eprintln!("error: {}", err.report(None)),
+      // type(x) と同じ大文字の型名を表示する
+      Some(evaluated) => println!("{}", evaluated.type_name().to_uppercase()),
+      None => (),
+    },
+    ":ast" => {
+      let mut p = parser::Parser::new(lexer::Lexer::new(arg.to_string()));
+      let program = p.parse_program();
+      if p.check_parse_errors() {
+        print!("{}", program.to_tree_string());
+      }
+    },
+    ":reset" => env.borrow_mut().clear(),
+    ":load" => match fs::read_to_string(arg) {
+      Ok(contents) => match exec(contents, env, engine) {
+        Some(Object::Error(err)) => eprintln!("error: {}", err.report(Some(arg))),
+        Some(evaluated) => println!("{}", evaluated),
+        None => (),
+      },
+      Err(err) => eprintln!("error: could not read {}: {}", arg, err),
+    },
+    _ => eprintln!("error: unknown command {}, see :help", command),
+  }
+}
+
+fn history_path() -> Option<PathBuf> {
+  env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE))
+}
+
+/// Starts an interactive session. Bindings persist in `env` between lines,
+/// and errors are reported without leaving the session.
+/// Lines are read until brackets are balanced, so a function can be written over several lines,
+/// and the inputs are kept in `~/.rsmonkey_history` across sessions.
+/// Lines starting with `:` are commands for the session, which are listed by `:help`.
+/// Tab completes keywords, builtins and the names bound in the session.
+pub fn start(env: &Rc<RefCell<Environment>>, engine: Engine) {
+  let mut rl = Editor::<MonkeyHelper>::new();
+  rl.set_helper(Some(MonkeyHelper::new(env)));
+  let history = history_path();
+  if let Some(path) = &history {
+    // 初回は履歴ファイルがないので、読み込めなくても無視する
+    let _ = rl.load_history(path);
+  }
+
+  let mut buf = String::new();
+  loop {
+    let prompt = if buf.is_empty() { PROMPT } else { CONTINUATION_PROMPT };
+    let readline = rl.readline(prompt);
+    match readline {
+      Ok(line) => {
+        if buf.is_empty() && line.trim_start().starts_with(':') {
+          rl.add_history_entry(line.trim_end());
+          run_command(&line, env, engine);
+          continue;
+        }
+        if !buf.is_empty() {
+          buf.push('\n');
+        }
+        // 端末でない入力から読むと改行が残る
+        buf.push_str(line.trim_end_matches(&['\n', '\r'][..]));
+        if is_incomplete(&buf) {
+          continue;
+        }
+
+        let input = std::mem::take(&mut buf);
+        if input.trim().is_empty() {
+          continue;
+        }
+        rl.add_history_entry(input.as_str());
+        match exec(input, env, engine) {
+          Some(Object::Error(err)) => eprintln!("error: {}", err.report(None)),
+          Some(evaluated) => println!("{}", evaluated),
+          None => (),
+        }
+      },
+      // 書きかけの入力があるときは、それを捨てて続ける
+      Err(ReadlineError::Interrupted) if !buf.is_empty() => buf.clear(),
+      Err(ReadlineError::Interrupted) => {
+        println!("CTRL-C");
+        break
+      },
+      Err(ReadlineError::Eof) => {
+        println!("CTRL-D");
+        break
+      },
+      Err(err) => {
+        println!("Error: {:?}", err);
+        break
+      }
+    }
+  }
+
+  if let Some(path) = &history {
+    if let Err(err) = rl.save_history(path) {
+      eprintln!("error: could not save the history to {}: {}", path.display(), err);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_is_incomplete() {
+    let tests = vec![
+      ("let a = 1;", false),
+      ("let f = fn(x) {", true),
+      ("let f = fn(x) {\n  x * 2\n}", false),
+      ("puts([1,", true),
+      ("let s = \"abc", true),
+      ("1 /* comment", true),
+      ("}", false),
+      ("", false),
+    ];
+
+    for (input, expected) in tests {
+      assert_eq!(is_incomplete(input), expected, "input={:?}", input);
+    }
+  }
+
+  #[test]
+  fn test_run_unsupported_on_vm() {
+    let tests = vec![
+      ("let [a, b] = [1, 2];", "destructuring let", 0),
+      ("let xs = [1];\nfor (x in xs) { x }", "for statement", 14),
+      ("let f = fn(a, b) { a + b };\nf(...[1, 2])", "spread argument", 30),
+      ("let xs = [1];\nxs[0] = 2", "assignment to an index", 14),
+      ("let x = 1;\nx++", "++ operator", 11),
+      ("fn(x = 1) { x }", "default parameter", 0),
+    ];
+
+    for (input, node, offset) in tests {
+      let env = Environment::new(evaluator::builtins::new_builtins());
+      let program = parser::Parser::new(lexer::Lexer::new(input.to_string())).parse_program();
+      match run(program, &env, Engine::Vm) {
+        Object::Error(err) => {
+          assert_eq!(err.kind, object::ErrorKind::Compile, "input={:?}", input);
+          assert_eq!(err.message, format!("{} is not supported by the compiler yet.", node), "input={:?}", input);
+          assert_eq!(err.span.map(|span| span.start.offset), Some(offset), "input={:?}", input);
+        },
+        obj => panic!("{:?} should not run on the VM, but got {:?}", input, obj),
+      }
+    }
+  }
+}
@@ -2,41 +2,14 @@ use std::rc::Rc;
 use std::cell::RefCell;
 use yew::prelude::*;
 use yew::web_sys::HtmlTextAreaElement;
-use interpreter::{lexer, parser, evaluator, utils};
-use evaluator::environment::Environment;
-use evaluator::{builtins, object};
+use interpreter::wasm;
 
 use super::header::Header;
 
-fn exec(buf: String, env: &mut Rc<RefCell<Environment>>) -> Rc<RefCell<Vec<String>>> {
-    let l = lexer::Lexer::new(buf);
-    let mut p = parser::Parser::new(l);
-    let program = p.parse_program();
-
-    if p.errors.len() > 0 {
-      return Rc::new(RefCell::new(p.errors));
-    }
-
-    let outputs = Rc::new(RefCell::new(vec![]));
-    
-    let f = Rc::new(
-      RefCell::new({
-        let outputs = Rc::clone(&outputs);
-        move |args: Vec<object::Object>| {
-          let mut outputs = outputs.borrow_mut();
-          outputs.push(format!("{}", utils::format_object_list(&args, " ")));
-          return object::Object::Null;
-        }
-      })
-    );
-
-    env.borrow_mut().set("puts", object::Object::External(object::External::new(f)));
-
-    let evaluated = evaluator::eval(program, env);
-
-    outputs.borrow_mut().push(format!("{}", evaluated));
-    
-    outputs
+fn exec(buf: String) -> Rc<RefCell<Vec<String>>> {
+    let evaluation = wasm::eval(&buf);
+    let outputs = evaluation.output().lines().chain(evaluation.value().lines()).map(|line| line.to_string()).collect();
+    Rc::new(RefCell::new(outputs))
 }
 
 fn count_lines(s: &str) -> usize {
@@ -96,8 +69,7 @@ map([1, 2, 3], fn(v) { v + 1 });
                     Some(elm) => elm,
                     None => return false,
                 };
-                let mut env = Environment::new(builtins::new_builtins());
-                self.state.result = exec(elm.value(), &mut env);
+                self.state.result = exec(elm.value());
             },
             Msg::NewLine(val) => {
               self.state.lines = count_lines(&val);